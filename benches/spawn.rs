@@ -0,0 +1,69 @@
+//! Compare process spawn latency between `posix_spawn` and `fork`, in a
+//! process with a large resident set.
+//!
+//! `std::process::Command` uses `posix_spawn` when it can, and falls back to
+//! `fork` and `exec` when the command has a `pre_exec` closure. `fork` has to
+//! copy the parent's page tables, so its latency grows with the parent's
+//! memory size, while mustang's `posix_spawn`, which uses `clone` with
+//! `CLONE_VM | CLONE_VFORK`, doesn't copy them.
+//!
+//! ```console
+//! $ cargo bench -Z build-std --target=x86_64-mustang-linux-gnu --bench spawn
+//! ```
+//!
+//! Set `MUSTANG_BENCH_RSS_MIB` to change the resident set size, which
+//! defaults to 1024 MiB.
+
+mustang::can_run_this!();
+
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 200;
+
+fn main() {
+    let mib = std::env::var("MUSTANG_BENCH_RSS_MIB")
+        .map(|mib| mib.parse().expect("invalid MUSTANG_BENCH_RSS_MIB"))
+        .unwrap_or(1024_usize);
+
+    println!("{:>10} {:>16} {:>16}", "RSS", "posix_spawn", "fork");
+    for rss in [0, mib / 4, mib] {
+        // Touch every page, so that they're all mapped.
+        let ballast = vec![1_u8; rss << 20];
+        let spawn = measure(|| Command::new("true"));
+        let fork = measure(|| {
+            let mut command = Command::new("true");
+            // SAFETY: The closure doesn't do anything.
+            unsafe {
+                command.pre_exec(|| Ok(()));
+            }
+            command
+        });
+        drop(std::hint::black_box(ballast));
+
+        println!(
+            "{:>6} MiB {:>13.1} µs {:>13.1} µs",
+            rss,
+            micros(spawn),
+            micros(fork)
+        );
+    }
+}
+
+/// Return the mean time to spawn the command and wait for it to exit.
+fn measure(command: impl Fn() -> Command) -> Duration {
+    // Warm up.
+    command().status().unwrap();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let status = command().status().unwrap();
+        assert!(status.success());
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e6
+}
@@ -0,0 +1,53 @@
+//! Measure thread spawn/join throughput, for a few stack sizes.
+//!
+//! Each spawn maps a fresh stack, guard page and TLS block, which origin
+//! unmaps again when the thread exits, so short-lived threads pay for a few
+//! `mmap`, `mprotect` and `munmap` calls each, plus the page faults to touch
+//! the new stack.
+//!
+//! ```console
+//! $ cargo bench -Z build-std --target=x86_64-mustang-linux-gnu --bench threads
+//! ```
+
+mustang::can_run_this!();
+
+use std::hint::black_box;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 2000;
+
+fn main() {
+    println!("{:>10} {:>16} {:>16}", "stack", "spawn+join", "threads/s");
+    for kib in [64, 2048, 8192] {
+        let mean = measure(kib << 10);
+        println!(
+            "{:>6} KiB {:>13.1} µs {:>16.0}",
+            kib,
+            mean.as_secs_f64() * 1e6,
+            1.0 / mean.as_secs_f64()
+        );
+    }
+}
+
+/// Return the mean time to spawn a thread with the given stack size, and
+/// join it.
+fn measure(stack_size: usize) -> Duration {
+    let spawn = || {
+        thread::Builder::new()
+            .stack_size(stack_size)
+            .spawn(|| black_box(0_u8))
+            .unwrap()
+            .join()
+            .unwrap()
+    };
+
+    // Warm up.
+    spawn();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        spawn();
+    }
+    start.elapsed() / ITERATIONS
+}
@@ -0,0 +1,71 @@
+//! Print the CPU features detected from `AT_HWCAP`/`AT_HWCAP2`, and check
+//! that `getauxval`, which `std`'s feature detection reads them through, and
+//! `std`'s feature detection itself agree.
+
+#![cfg_attr(target_arch = "arm", feature(stdarch_arm_feature_detection))]
+#![cfg_attr(target_arch = "riscv64", feature(stdarch_riscv_feature_detection))]
+
+mustang::can_run_this!();
+
+fn main() {
+    #[cfg(target_vendor = "mustang")]
+    {
+        use mustang::hwcap;
+
+        let caps = hwcap::get();
+        println!("AT_HWCAP:  {:#x}", caps.hwcap());
+        println!("AT_HWCAP2: {:#x}", caps.hwcap2());
+        for (feature, detected) in caps.features() {
+            println!("{:>12}: {}", feature.name, detected);
+        }
+
+        unsafe {
+            assert_eq!(libc::getauxval(libc::AT_HWCAP) as usize, caps.hwcap());
+            assert_eq!(libc::getauxval(libc::AT_HWCAP2) as usize, caps.hwcap2());
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        {
+            assert_eq!(
+                caps.has(hwcap::ASIMD),
+                std::arch::is_aarch64_feature_detected!("neon")
+            );
+            assert_eq!(
+                caps.has(hwcap::ATOMICS),
+                std::arch::is_aarch64_feature_detected!("lse")
+            );
+            assert_eq!(
+                caps.has(hwcap::CRC32),
+                std::arch::is_aarch64_feature_detected!("crc")
+            );
+        }
+
+        #[cfg(target_arch = "arm")]
+        {
+            assert_eq!(
+                caps.has(hwcap::NEON),
+                std::arch::is_arm_feature_detected!("neon")
+            );
+            assert_eq!(
+                caps.has(hwcap::CRC32),
+                std::arch::is_arm_feature_detected!("crc")
+            );
+        }
+
+        #[cfg(target_arch = "riscv64")]
+        {
+            assert_eq!(
+                caps.has(hwcap::M),
+                std::arch::is_riscv_feature_detected!("m")
+            );
+        }
+
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            assert_eq!(
+                caps.has(hwcap::SSE2),
+                std::arch::is_x86_feature_detected!("sse2")
+            );
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! Launch a program, passing it its arguments and environment variables in a
+//! memfd instead of on the initial stack. The launched program must be built
+//! with mustang's "memfd-args" feature.
+//!
+//! Usage: memfd-launch <program> [args...]
+
+mustang::can_run_this!();
+
+fn main() {
+    #[cfg(target_vendor = "mustang")]
+    {
+        use std::ffi::CString;
+
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        let Some(program) = args.first() else {
+            eprintln!("usage: memfd-launch <program> [args...]");
+            std::process::exit(2);
+        };
+        let vars = std::env::vars().collect::<Vec<_>>();
+
+        let path = CString::new(program.as_str()).unwrap();
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        let vars = vars
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+
+        let err = mustang::args::exec(&path, &path, &args, &vars);
+        eprintln!("memfd-launch: {}: {}", program, err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,13 @@
+/* Copy a string into a buffer that's too small, in C code compiled with
+   `-D_FORTIFY_SOURCE=2` against mustang's headers, whose `strcpy` calls
+   `__strcpy_chk` when it can't prove the copy fits. This is linked into
+   examples/test-fortify-c.rs by `test_fortify_c` in tests/examples.rs. */
+
+#include <string.h>
+
+void fortify_strcpy(const char *src) {
+    char buf[8];
+    strcpy(buf, src);
+    /* Keep the copy from being optimized away. */
+    __asm__ volatile("" : : "r"(buf) : "memory");
+}
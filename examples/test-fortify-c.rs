@@ -0,0 +1,23 @@
+//! Call C code compiled with `-D_FORTIFY_SOURCE=2`, which copies a string
+//! into a buffer that's too small. This aborts with a "buffer overflow
+//! detected" message. examples/test-fortify-c.c must be linked in;
+//! `test_fortify_c` in tests/examples.rs does this.
+
+#![feature(linkage)]
+
+mustang::can_run_this!();
+
+use std::ffi::c_char;
+use std::hint::black_box;
+
+// This is weak, so that the example still links when it's built without
+// the C code, as `cargo test` does.
+extern "C" {
+    #[linkage = "extern_weak"]
+    static fortify_strcpy: Option<unsafe extern "C" fn(*const c_char)>;
+}
+
+fn main() {
+    let strcpy = unsafe { fortify_strcpy.expect("test-fortify-c.c isn't linked in") };
+    unsafe { strcpy(black_box(c"this doesn't fit").as_ptr()) }
+}
@@ -0,0 +1,24 @@
+//! Call `__strcpy_chk` the way `_FORTIFY_SOURCE` code does, with a
+//! destination that's too small. This aborts with a "buffer overflow
+//! detected" message.
+
+mustang::can_run_this!();
+
+use std::ffi::c_char;
+use std::hint::black_box;
+
+extern "C" {
+    fn __strcpy_chk(dst: *mut c_char, src: *const c_char, dstlen: usize) -> *mut c_char;
+}
+
+fn main() {
+    let mut buf = [0 as c_char; 8];
+    unsafe {
+        __strcpy_chk(
+            buf.as_mut_ptr(),
+            black_box(c"this doesn't fit").as_ptr(),
+            buf.len(),
+        );
+    }
+    black_box(&buf);
+}
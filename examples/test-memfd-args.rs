@@ -0,0 +1,17 @@
+//! Print the structured arguments passed by `memfd-launch`, one per line,
+//! followed by the value of the `MUSTANG_TEST_VAR` environment variable.
+
+mustang::can_run_this!();
+
+fn main() {
+    #[cfg(target_vendor = "mustang")]
+    {
+        for arg in mustang::args::args() {
+            println!("{}", arg);
+        }
+        if let Ok(value) = std::env::var("MUSTANG_TEST_VAR") {
+            println!("MUSTANG_TEST_VAR={}", value);
+        }
+        assert!(std::env::var_os(mustang::args::FD_ENV).is_none());
+    }
+}
@@ -0,0 +1,50 @@
+//! Print the preopens passed in `MUSTANG_PREOPENS`, and the contents of
+//! `/data/hello.txt` opened through them.
+
+mustang::can_run_this!();
+
+fn main() {
+    #[cfg(target_vendor = "mustang")]
+    {
+        use mustang::caps;
+        use rustix::fs::{Mode, OFlags};
+        use std::io::Read;
+
+        for preopen in caps::preopens() {
+            println!("{}", preopen.name());
+        }
+
+        let fd = caps::open("/data/hello.txt", OFlags::RDONLY, Mode::empty()).unwrap();
+        let mut contents = String::new();
+        std::fs::File::from(fd)
+            .read_to_string(&mut contents)
+            .unwrap();
+        print!("{}", contents);
+
+        assert_eq!(
+            caps::open("/data/../outside", OFlags::RDONLY, Mode::empty()).unwrap_err(),
+            rustix::io::Errno::XDEV
+        );
+        assert_eq!(
+            caps::open("/elsewhere", OFlags::RDONLY, Mode::empty()).unwrap_err(),
+            rustix::io::Errno::ACCESS
+        );
+
+        #[cfg(feature = "preopens-enforce")]
+        {
+            // Paths outside the preopens can't be opened.
+            assert_eq!(
+                std::fs::read_dir("/").unwrap_err().kind(),
+                std::io::ErrorKind::PermissionDenied
+            );
+
+            // The restriction was applied after the main thread's stack
+            // bounds were found, which may need `/proc/self/maps`.
+            let stack = mustang::stack::main_stack();
+            let local = 0_u8;
+            let local = std::hint::black_box(&local) as *const u8 as usize;
+            assert!(stack.addr() as usize > stack.guard_size());
+            assert!((stack.addr() as usize..stack.addr() as usize + stack.size()).contains(&local));
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Declare a sandbox which only allows reading `/proc`, and check that it's
+//! in effect by the time constructors run.
+
+mustang::can_run_this!();
+
+mustang::sandbox! {
+    read: ["/proc"],
+    bind_tcp: [],
+}
+
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static CTOR_SANDBOXED: AtomicBool = AtomicBool::new(false);
+
+#[ctor::ctor]
+fn ctor() {
+    let denied =
+        std::fs::read_dir("/").map(|_| ()).unwrap_err().kind() == ErrorKind::PermissionDenied;
+    CTOR_SANDBOXED.store(denied, Ordering::Relaxed);
+}
+
+fn main() {
+    assert!(CTOR_SANDBOXED.load(Ordering::Relaxed));
+
+    std::fs::read_to_string("/proc/self/status").unwrap();
+    assert_eq!(
+        std::fs::read_dir("/").map(|_| ()).unwrap_err().kind(),
+        ErrorKind::PermissionDenied
+    );
+    assert_eq!(
+        std::fs::write("/proc/self/comm", "sandboxed")
+            .unwrap_err()
+            .kind(),
+        ErrorKind::PermissionDenied
+    );
+    println!("sandboxed");
+}
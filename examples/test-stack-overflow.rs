@@ -0,0 +1,17 @@
+//! Overflow the main thread's stack. std's `SIGSEGV` handler recognizes the
+//! fault as an overflow, using the stack bounds `pthread_getattr_np`
+//! reports, and aborts with a "has overflowed its stack" message.
+
+mustang::can_run_this!();
+
+use std::hint::black_box;
+
+#[allow(unconditional_recursion)]
+fn recurse(depth: u64) -> u64 {
+    let frame = black_box([depth; 64]);
+    recurse(depth + 1) + frame[0]
+}
+
+fn main() {
+    black_box(recurse(0));
+}
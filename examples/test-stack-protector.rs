@@ -0,0 +1,21 @@
+//! Overflow a stack buffer. When compiled with `-Z stack-protector=strong`,
+//! this aborts with a "stack smashing detected" message.
+
+mustang::can_run_this!();
+
+use std::hint::black_box;
+
+#[inline(never)]
+fn overflow(len: usize) {
+    let mut buf = [0_u8; 16];
+    let ptr = black_box(buf.as_mut_ptr());
+    for i in 0..len {
+        // SAFETY: It isn't; that's the point.
+        unsafe { ptr.add(i).write_volatile(0x41) };
+    }
+    black_box(&buf);
+}
+
+fn main() {
+    overflow(black_box(64));
+}
@@ -0,0 +1,26 @@
+/* Thread-local variables accessed from C code compiled with `-fPIC`, which
+   uses the general-dynamic model for `extern` variables and the
+   local-dynamic model for `static` ones. Linkers rewrite some of these
+   accesses into fixed offsets from the thread pointer in executables, but on
+   some architectures they're left as calls to `__tls_get_addr`. This is
+   linked into examples/test-tls-dynamic.rs by `test_tls_dynamic`, and
+   compiled to use TLS descriptors by `test_tls_descriptors`, in
+   tests/examples.rs. */
+
+/* Defined in examples/test-tls-dynamic.rs. */
+extern __thread unsigned long long tls_dynamic_rust;
+
+static __thread int tls_dynamic_count = 1;
+static __thread char tls_dynamic_buf[256] __attribute__((aligned(64)));
+
+unsigned long long *tls_dynamic_rust_addr(void) {
+    return &tls_dynamic_rust;
+}
+
+int tls_dynamic_bump(void) {
+    return tls_dynamic_count++;
+}
+
+char *tls_dynamic_buf_addr(void) {
+    return tls_dynamic_buf;
+}
@@ -0,0 +1,70 @@
+//! Access thread-local variables from C code compiled with `-fPIC`, which
+//! uses the dynamic TLS models. examples/test-tls-dynamic.c must be linked
+//! in; `test_tls_dynamic` in tests/examples.rs does this.
+
+#![feature(linkage, thread_local)]
+
+mustang::can_run_this!();
+
+use std::cell::Cell;
+use std::thread;
+
+#[allow(non_upper_case_globals)]
+#[no_mangle]
+#[thread_local]
+static tls_dynamic_rust: Cell<u64> = Cell::new(INIT);
+
+const INIT: u64 = 0x0123_4567_89ab_cdef;
+
+// These are weak, so that the example still links when it's built without
+// the C code, as `cargo test` does.
+extern "C" {
+    #[linkage = "extern_weak"]
+    static tls_dynamic_rust_addr: Option<unsafe extern "C" fn() -> *mut u64>;
+    #[linkage = "extern_weak"]
+    static tls_dynamic_bump: Option<unsafe extern "C" fn() -> i32>;
+    #[linkage = "extern_weak"]
+    static tls_dynamic_buf_addr: Option<unsafe extern "C" fn() -> *mut u8>;
+}
+
+/// Check the calling thread's variables, and fill its buffer.
+fn check() {
+    let (rust_addr, bump, buf_addr) = unsafe {
+        (
+            tls_dynamic_rust_addr.expect("test-tls-dynamic.c isn't linked in"),
+            tls_dynamic_bump.unwrap(),
+            tls_dynamic_buf_addr.unwrap(),
+        )
+    };
+    unsafe {
+        assert_eq!(rust_addr(), tls_dynamic_rust.as_ptr());
+        assert_eq!(*rust_addr(), INIT);
+        tls_dynamic_rust.set(INIT + 1);
+        assert_eq!(*rust_addr(), INIT + 1);
+
+        assert_eq!(bump(), 1);
+        assert_eq!(bump(), 2);
+
+        let buf = buf_addr();
+        assert_eq!(buf as usize % 64, 0);
+        let buf = std::slice::from_raw_parts_mut(buf, 256);
+        assert!(buf.iter().all(|b| *b == 0));
+        buf.fill(0xa5);
+    }
+}
+
+fn main() {
+    check();
+    let threads = (0..4).map(|_| thread::spawn(check)).collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // The other threads didn't touch the main thread's variables.
+    unsafe {
+        assert_eq!(tls_dynamic_rust.get(), INIT + 1);
+        assert_eq!(tls_dynamic_bump.unwrap()(), 3);
+        let buf = std::slice::from_raw_parts(tls_dynamic_buf_addr.unwrap()(), 256);
+        assert!(buf.iter().all(|b| *b == 0xa5));
+    }
+}
@@ -0,0 +1,1281 @@
+//! The headers, and what goes in them.
+
+use crate::{c_int_type, Field, Header, Libc};
+use core::mem::{offset_of, size_of};
+use libc::*;
+
+/// Describe the fields of `$ty`, with their C types.
+macro_rules! fields {
+    ($ty:ty { $($field:ident: $cty:literal),* $(,)? }) => {
+        vec![$(Field::new::<$ty, _>(
+            stringify!($field),
+            $cty,
+            offset_of!($ty, $field),
+            |s: &$ty| &s.$field,
+        )),*]
+    };
+}
+
+/// The end offset of field `$field` of `$ty`.
+macro_rules! end_of {
+    ($ty:ty, $field:ident) => {{
+        fn size<S, T>(_: fn(&S) -> &T) -> usize {
+            size_of::<T>()
+        }
+        offset_of!($ty, $field) + size(|s: &$ty| &s.$field)
+    }};
+}
+
+/// Define each of the named `libc` crate constants.
+macro_rules! defines {
+    ($h:ident, $($name:ident),* $(,)?) => {
+        $($h.define(stringify!($name), $name);)*
+    };
+}
+
+pub(crate) fn all(libc: &Libc) -> Vec<Header<'_>> {
+    vec![
+        stdint(libc),
+        limits(libc),
+        sys_types(libc),
+        errno(libc),
+        fcntl(libc),
+        unistd(libc),
+        string(libc),
+        strings(libc),
+        stdlib(libc),
+        stdio(libc),
+        time(libc),
+        sys_time(libc),
+        sys_stat(libc),
+        dirent(libc),
+        signal(libc),
+        sched(libc),
+        pthread(libc),
+        semaphore(libc),
+        spawn(libc),
+        bits_fortify(libc),
+    ]
+}
+
+fn spawn(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "spawn.h");
+    h.include(&["sched.h", "signal.h"]);
+    h.opaque::<posix_spawnattr_t>("posix_spawnattr_t");
+    h.opaque::<posix_spawn_file_actions_t>("posix_spawn_file_actions_t");
+    defines!(
+        h,
+        POSIX_SPAWN_RESETIDS,
+        POSIX_SPAWN_SETPGROUP,
+        POSIX_SPAWN_SETSIGDEF,
+        POSIX_SPAWN_SETSIGMASK,
+        POSIX_SPAWN_SETSCHEDPARAM,
+        POSIX_SPAWN_SETSCHEDULER,
+        POSIX_SPAWN_SETSID,
+    );
+    h.functions(
+        "int posix_spawn(pid_t *pid, const char *path,\n\
+         \x20               const posix_spawn_file_actions_t *file_actions,\n\
+         \x20               const posix_spawnattr_t *attr, char *const argv[], char *const envp[]);\n\
+         int posix_spawnp(pid_t *pid, const char *file,\n\
+         \x20                const posix_spawn_file_actions_t *file_actions,\n\
+         \x20                const posix_spawnattr_t *attr, char *const argv[], char *const envp[]);\n\
+         int pidfd_spawn(int *pidfd, const char *path,\n\
+         \x20               const posix_spawn_file_actions_t *file_actions,\n\
+         \x20               const posix_spawnattr_t *attr, char *const argv[], char *const envp[]);\n\
+         int pidfd_spawnp(int *pidfd, const char *file,\n\
+         \x20                const posix_spawn_file_actions_t *file_actions,\n\
+         \x20                const posix_spawnattr_t *attr, char *const argv[], char *const envp[]);\n\
+         int posix_spawnattr_init(posix_spawnattr_t *attr);\n\
+         int posix_spawnattr_destroy(posix_spawnattr_t *attr);\n\
+         int posix_spawnattr_getflags(const posix_spawnattr_t *attr, short *flags);\n\
+         int posix_spawnattr_setflags(posix_spawnattr_t *attr, short flags);\n\
+         int posix_spawnattr_getpgroup(const posix_spawnattr_t *attr, pid_t *pgroup);\n\
+         int posix_spawnattr_setpgroup(posix_spawnattr_t *attr, pid_t pgroup);\n\
+         int posix_spawnattr_getsigmask(const posix_spawnattr_t *attr, sigset_t *mask);\n\
+         int posix_spawnattr_setsigmask(posix_spawnattr_t *attr, const sigset_t *mask);\n\
+         int posix_spawnattr_getsigdefault(const posix_spawnattr_t *attr, sigset_t *set);\n\
+         int posix_spawnattr_setsigdefault(posix_spawnattr_t *attr, const sigset_t *set);\n\
+         int posix_spawnattr_getschedpolicy(const posix_spawnattr_t *attr, int *policy);\n\
+         int posix_spawnattr_setschedpolicy(posix_spawnattr_t *attr, int policy);\n\
+         int posix_spawnattr_getschedparam(const posix_spawnattr_t *attr, struct sched_param *param);\n\
+         int posix_spawnattr_setschedparam(posix_spawnattr_t *attr, const struct sched_param *param);\n\
+         int posix_spawn_file_actions_init(posix_spawn_file_actions_t *file_actions);\n\
+         int posix_spawn_file_actions_destroy(posix_spawn_file_actions_t *file_actions);\n\
+         int posix_spawn_file_actions_addclose(posix_spawn_file_actions_t *file_actions, int fd);\n\
+         int posix_spawn_file_actions_adddup2(posix_spawn_file_actions_t *file_actions, int fd,\n\
+         \x20                                    int newfd);\n\
+         int posix_spawn_file_actions_addopen(posix_spawn_file_actions_t *file_actions, int fd,\n\
+         \x20                                    const char *path, int oflag, mode_t mode);\n\
+         int posix_spawn_file_actions_addchdir(posix_spawn_file_actions_t *file_actions,\n\
+         \x20                                     const char *path);\n\
+         int posix_spawn_file_actions_addchdir_np(posix_spawn_file_actions_t *file_actions,\n\
+         \x20                                        const char *path);\n\
+         int posix_spawn_file_actions_addfchdir(posix_spawn_file_actions_t *file_actions, int fd);\n\
+         int posix_spawn_file_actions_addfchdir_np(posix_spawn_file_actions_t *file_actions, int fd);",
+    );
+    h
+}
+
+fn bits_fortify(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "bits/fortify.h");
+    // Inline-only definitions, which are never emitted, so calls the
+    // compiler doesn't inline still reach the libc's functions.
+    h.text(
+        "#define __mustang_fortify extern __inline__ __attribute__((__always_inline__, __gnu_inline__))\n\
+         /* The size of the object a pointer points into, or (size_t)-1. Strings use\n\
+         \x20  the innermost enclosing subobject at level 2 and above. */\n\
+         #define __mustang_bos0(p) __builtin_object_size((p), 0)\n\
+         #define __mustang_bos(p) __builtin_object_size((p), _FORTIFY_SOURCE > 1)",
+    );
+    h
+}
+
+fn stdint(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "stdint.h");
+    h.typedef::<i8>("int8_t");
+    h.typedef::<i16>("int16_t");
+    h.typedef::<i32>("int32_t");
+    h.typedef::<i64>("int64_t");
+    h.typedef::<u8>("uint8_t");
+    h.typedef::<u16>("uint16_t");
+    h.typedef::<u32>("uint32_t");
+    h.typedef::<u64>("uint64_t");
+    h.typedef::<isize>("intptr_t");
+    h.typedef::<usize>("uintptr_t");
+    h.typedef::<i64>("intmax_t");
+    h.typedef::<u64>("uintmax_t");
+    for bits in [8, 16, 32, 64] {
+        h.text(&format!(
+            "typedef int{0}_t int_least{0}_t;\n\
+             typedef uint{0}_t uint_least{0}_t;\n\
+             typedef int{0}_t int_fast{0}_t;\n\
+             typedef uint{0}_t uint_fast{0}_t;",
+            bits
+        ));
+    }
+    h.define("INT8_MIN", i8::MIN);
+    h.define("INT8_MAX", i8::MAX);
+    h.define("UINT8_MAX", u8::MAX);
+    h.define("INT16_MIN", i16::MIN);
+    h.define("INT16_MAX", i16::MAX);
+    h.define("UINT16_MAX", u16::MAX);
+    h.text("#define INT32_MIN (-2147483647 - 1)");
+    h.define("INT32_MAX", i32::MAX);
+    h.define("UINT32_MAX", u32::MAX);
+    h.text("#define INT64_MIN (-INT64_MAX - 1)");
+    h.define("INT64_MAX", i64::MAX);
+    h.define("UINT64_MAX", u64::MAX);
+    h.text(if size_of::<usize>() == 8 {
+        "#define INTPTR_MIN INT64_MIN\n\
+         #define INTPTR_MAX INT64_MAX\n\
+         #define UINTPTR_MAX UINT64_MAX\n\
+         #define SIZE_MAX UINT64_MAX"
+    } else {
+        "#define INTPTR_MIN INT32_MIN\n\
+         #define INTPTR_MAX INT32_MAX\n\
+         #define UINTPTR_MAX UINT32_MAX\n\
+         #define SIZE_MAX UINT32_MAX"
+    });
+    h.text(
+        "#define INTMAX_MIN INT64_MIN\n\
+         #define INTMAX_MAX INT64_MAX\n\
+         #define UINTMAX_MAX UINT64_MAX",
+    );
+    h
+}
+
+fn limits(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "limits.h");
+    h.define("CHAR_BIT", 8);
+    h.define("SCHAR_MIN", i8::MIN);
+    h.define("SCHAR_MAX", i8::MAX);
+    h.define("UCHAR_MAX", u8::MAX);
+    if c_char::MIN == 0 {
+        h.text("#define CHAR_MIN 0\n#define CHAR_MAX UCHAR_MAX");
+    } else {
+        h.text("#define CHAR_MIN SCHAR_MIN\n#define CHAR_MAX SCHAR_MAX");
+    }
+    h.define("SHRT_MIN", c_short::MIN);
+    h.define("SHRT_MAX", c_short::MAX);
+    h.define("USHRT_MAX", c_ushort::MAX);
+    h.text("#define INT_MIN (-INT_MAX - 1)");
+    h.define("INT_MAX", c_int::MAX);
+    h.define("UINT_MAX", c_uint::MAX);
+    h.text("#define LONG_MIN (-LONG_MAX - 1L)");
+    h.define("LONG_MAX", c_long::MAX);
+    h.define("ULONG_MAX", c_ulong::MAX);
+    h.text("#define LLONG_MIN (-LLONG_MAX - 1LL)");
+    h.text(&format!("#define LLONG_MAX {}LL", c_longlong::MAX));
+    h.text(&format!("#define ULLONG_MAX {}ULL", c_ulonglong::MAX));
+    defines!(h, PATH_MAX, PIPE_BUF);
+    h.define("NAME_MAX", 255);
+    h.define("IOV_MAX", 1024);
+    h
+}
+
+fn sys_types(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "sys/types.h");
+    h.include(&["stddef.h", "stdint.h"]);
+    h.typedef::<ssize_t>("ssize_t");
+    h.typedef::<off_t>("off_t");
+    h.typedef::<pid_t>("pid_t");
+    h.typedef::<uid_t>("uid_t");
+    h.typedef::<gid_t>("gid_t");
+    h.typedef::<id_t>("id_t");
+    h.typedef::<mode_t>("mode_t");
+    h.typedef::<dev_t>("dev_t");
+    h.typedef::<ino_t>("ino_t");
+    h.typedef::<nlink_t>("nlink_t");
+    h.typedef::<blksize_t>("blksize_t");
+    h.typedef::<blkcnt_t>("blkcnt_t");
+    h.typedef::<time_t>("time_t");
+    h.typedef::<suseconds_t>("suseconds_t");
+    h.typedef::<clock_t>("clock_t");
+    h.typedef::<clockid_t>("clockid_t");
+    h.typedef::<useconds_t>("useconds_t");
+    h.typedef::<key_t>("key_t");
+    h.text("");
+    h.typedef::<pthread_t>("pthread_t");
+    h.typedef::<pthread_key_t>("pthread_key_t");
+    h.typedef::<pthread_once_t>("pthread_once_t");
+    h.opaque::<pthread_attr_t>("pthread_attr_t");
+    h.opaque::<pthread_mutex_t>("pthread_mutex_t");
+    h.opaque::<pthread_mutexattr_t>("pthread_mutexattr_t");
+    h.opaque::<pthread_cond_t>("pthread_cond_t");
+    h.opaque::<pthread_condattr_t>("pthread_condattr_t");
+    h.opaque::<pthread_rwlock_t>("pthread_rwlock_t");
+    h.opaque::<pthread_rwlockattr_t>("pthread_rwlockattr_t");
+    h.opaque::<pthread_barrier_t>("pthread_barrier_t");
+    h.opaque::<pthread_barrierattr_t>("pthread_barrierattr_t");
+    h.typedef::<pthread_spinlock_t>("pthread_spinlock_t");
+    h
+}
+
+fn errno(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "errno.h");
+    h.functions("int *__errno_location(void);");
+    h.text("#define errno (*__errno_location())");
+    defines!(
+        h,
+        EPERM,
+        ENOENT,
+        ESRCH,
+        EINTR,
+        EIO,
+        ENXIO,
+        E2BIG,
+        ENOEXEC,
+        EBADF,
+        ECHILD,
+        EAGAIN,
+        ENOMEM,
+        EACCES,
+        EFAULT,
+        ENOTBLK,
+        EBUSY,
+        EEXIST,
+        EXDEV,
+        ENODEV,
+        ENOTDIR,
+        EISDIR,
+        EINVAL,
+        ENFILE,
+        EMFILE,
+        ENOTTY,
+        ETXTBSY,
+        EFBIG,
+        ENOSPC,
+        ESPIPE,
+        EROFS,
+        EMLINK,
+        EPIPE,
+        EDOM,
+        ERANGE,
+        EDEADLK,
+        ENAMETOOLONG,
+        ENOLCK,
+        ENOSYS,
+        ENOTEMPTY,
+        ELOOP,
+        EWOULDBLOCK,
+        ENOMSG,
+        EIDRM,
+        ENOSTR,
+        ENODATA,
+        ETIME,
+        ENOSR,
+        ENOLINK,
+        EPROTO,
+        EMULTIHOP,
+        EBADMSG,
+        EOVERFLOW,
+        EILSEQ,
+        EUSERS,
+        ENOTSOCK,
+        EDESTADDRREQ,
+        EMSGSIZE,
+        EPROTOTYPE,
+        ENOPROTOOPT,
+        EPROTONOSUPPORT,
+        ESOCKTNOSUPPORT,
+        EOPNOTSUPP,
+        ENOTSUP,
+        EPFNOSUPPORT,
+        EAFNOSUPPORT,
+        EADDRINUSE,
+        EADDRNOTAVAIL,
+        ENETDOWN,
+        ENETUNREACH,
+        ENETRESET,
+        ECONNABORTED,
+        ECONNRESET,
+        ENOBUFS,
+        EISCONN,
+        ENOTCONN,
+        ESHUTDOWN,
+        ETOOMANYREFS,
+        ETIMEDOUT,
+        ECONNREFUSED,
+        EHOSTDOWN,
+        EHOSTUNREACH,
+        EALREADY,
+        EINPROGRESS,
+        ESTALE,
+        EDQUOT,
+        ECANCELED,
+        EOWNERDEAD,
+        ENOTRECOVERABLE,
+    );
+    h
+}
+
+fn fcntl(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "fcntl.h");
+    h.include(&["sys/types.h"]);
+    h.strukt::<flock>(
+        "flock",
+        fields!(flock {
+            l_type: "short",
+            l_whence: "short",
+            l_start: "off_t",
+            l_len: "off_t",
+            l_pid: "pid_t",
+        }),
+    );
+    defines!(
+        h,
+        O_RDONLY,
+        O_WRONLY,
+        O_RDWR,
+        O_ACCMODE,
+        O_CREAT,
+        O_EXCL,
+        O_NOCTTY,
+        O_TRUNC,
+        O_APPEND,
+        O_NONBLOCK,
+        O_DSYNC,
+        O_SYNC,
+        O_RSYNC,
+        O_DIRECTORY,
+        O_NOFOLLOW,
+        O_CLOEXEC,
+        O_PATH,
+        O_TMPFILE,
+        O_LARGEFILE,
+        F_DUPFD,
+        F_DUPFD_CLOEXEC,
+        F_GETFD,
+        F_SETFD,
+        F_GETFL,
+        F_SETFL,
+        F_GETLK,
+        F_SETLK,
+        F_SETLKW,
+        F_RDLCK,
+        F_WRLCK,
+        F_UNLCK,
+        FD_CLOEXEC,
+        AT_FDCWD,
+        AT_SYMLINK_NOFOLLOW,
+        AT_REMOVEDIR,
+        AT_SYMLINK_FOLLOW,
+        AT_EMPTY_PATH,
+        AT_EACCESS,
+    );
+    h.functions(
+        "int open(const char *path, int flags, ...);\n\
+         int openat(int dirfd, const char *path, int flags, ...);\n\
+         int creat(const char *path, mode_t mode);\n\
+         int fcntl(int fd, int cmd, ...);",
+    );
+    h
+}
+
+fn unistd(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "unistd.h");
+    h.include(&["sys/types.h"]);
+    defines!(
+        h,
+        STDIN_FILENO,
+        STDOUT_FILENO,
+        STDERR_FILENO,
+        SEEK_SET,
+        SEEK_CUR,
+        SEEK_END
+    );
+    defines!(h, F_OK, R_OK, W_OK, X_OK);
+    defines!(
+        h,
+        _SC_ARG_MAX,
+        _SC_CLK_TCK,
+        _SC_OPEN_MAX,
+        _SC_PAGESIZE,
+        _SC_PAGE_SIZE,
+        _SC_NPROCESSORS_CONF,
+        _SC_NPROCESSORS_ONLN,
+    );
+    h.functions(
+        "ssize_t read(int fd, void *buf, size_t count);\n\
+         ssize_t write(int fd, const void *buf, size_t count);\n\
+         ssize_t pread(int fd, void *buf, size_t count, off_t offset);\n\
+         ssize_t pwrite(int fd, const void *buf, size_t count, off_t offset);\n\
+         off_t lseek(int fd, off_t offset, int whence);\n\
+         int close(int fd);\n\
+         int dup(int fd);\n\
+         int dup2(int fd, int newfd);\n\
+         int dup3(int fd, int newfd, int flags);\n\
+         int pipe(int fds[2]);\n\
+         int pipe2(int fds[2], int flags);\n\
+         int fsync(int fd);\n\
+         int fdatasync(int fd);\n\
+         int ftruncate(int fd, off_t length);\n\
+         int truncate(const char *path, off_t length);\n\
+         int access(const char *path, int mode);\n\
+         int faccessat(int dirfd, const char *path, int mode, int flags);\n\
+         int chdir(const char *path);\n\
+         int fchdir(int fd);\n\
+         char *getcwd(char *buf, size_t size);\n\
+         int unlink(const char *path);\n\
+         int unlinkat(int dirfd, const char *path, int flags);\n\
+         int rmdir(const char *path);\n\
+         int link(const char *oldpath, const char *newpath);\n\
+         int symlink(const char *target, const char *linkpath);\n\
+         ssize_t readlink(const char *path, char *buf, size_t size);\n\
+         int isatty(int fd);\n\
+         pid_t getpid(void);\n\
+         pid_t getppid(void);\n\
+         uid_t getuid(void);\n\
+         uid_t geteuid(void);\n\
+         gid_t getgid(void);\n\
+         gid_t getegid(void);\n\
+         long sysconf(int name);\n\
+         int getpagesize(void);\n\
+         unsigned int sleep(unsigned int seconds);\n\
+         int usleep(useconds_t usec);\n\
+         int gethostname(char *name, size_t len);\n\
+         pid_t fork(void);\n\
+         int execv(const char *path, char *const argv[]);\n\
+         int execve(const char *path, char *const argv[], char *const envp[]);\n\
+         int execvp(const char *file, char *const argv[]);\n\
+         void _exit(int status) __attribute__((__noreturn__));",
+    );
+    h.text("extern char **environ;");
+    // These call the plain function, through an alias, when the buffer's
+    // size isn't known.
+    h.fortify(
+        "__read_chk",
+        "ssize_t __read_chk(int fd, void *buf, size_t count, size_t buflen);\n\
+         ssize_t __read_alias(int fd, void *buf, size_t count) __asm__(\"read\");\n\
+         __mustang_fortify ssize_t read(int fd, void *buf, size_t count) {\n\
+         \x20   if (__mustang_bos0(buf) != (size_t)-1)\n\
+         \x20       return __read_chk(fd, buf, count, __mustang_bos0(buf));\n\
+         \x20   return __read_alias(fd, buf, count);\n\
+         }",
+    );
+    h.fortify(
+        "__pread_chk",
+        "ssize_t __pread_chk(int fd, void *buf, size_t count, off_t offset, size_t buflen);\n\
+         ssize_t __pread_alias(int fd, void *buf, size_t count, off_t offset) __asm__(\"pread\");\n\
+         __mustang_fortify ssize_t pread(int fd, void *buf, size_t count, off_t offset) {\n\
+         \x20   if (__mustang_bos0(buf) != (size_t)-1)\n\
+         \x20       return __pread_chk(fd, buf, count, offset, __mustang_bos0(buf));\n\
+         \x20   return __pread_alias(fd, buf, count, offset);\n\
+         }",
+    );
+    h.fortify(
+        "__readlink_chk",
+        "ssize_t __readlink_chk(const char *path, char *buf, size_t size, size_t buflen);\n\
+         ssize_t __readlink_alias(const char *path, char *buf, size_t size) __asm__(\"readlink\");\n\
+         __mustang_fortify ssize_t readlink(const char *path, char *buf, size_t size) {\n\
+         \x20   if (__mustang_bos(buf) != (size_t)-1)\n\
+         \x20       return __readlink_chk(path, buf, size, __mustang_bos(buf));\n\
+         \x20   return __readlink_alias(path, buf, size);\n\
+         }",
+    );
+    h.fortify(
+        "__getcwd_chk",
+        "char *__getcwd_chk(char *buf, size_t size, size_t buflen);\n\
+         char *__getcwd_alias(char *buf, size_t size) __asm__(\"getcwd\");\n\
+         __mustang_fortify char *getcwd(char *buf, size_t size) {\n\
+         \x20   if (__mustang_bos(buf) != (size_t)-1)\n\
+         \x20       return __getcwd_chk(buf, size, __mustang_bos(buf));\n\
+         \x20   return __getcwd_alias(buf, size);\n\
+         }",
+    );
+    h.fortify(
+        "__gethostname_chk",
+        "int __gethostname_chk(char *name, size_t len, size_t buflen);\n\
+         int __gethostname_alias(char *name, size_t len) __asm__(\"gethostname\");\n\
+         __mustang_fortify int gethostname(char *name, size_t len) {\n\
+         \x20   if (__mustang_bos(name) != (size_t)-1)\n\
+         \x20       return __gethostname_chk(name, len, __mustang_bos(name));\n\
+         \x20   return __gethostname_alias(name, len);\n\
+         }",
+    );
+    h
+}
+
+fn string(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "string.h");
+    h.include(&["stddef.h"]);
+    h.functions(
+        "void *memcpy(void *dest, const void *src, size_t n);\n\
+         void *memmove(void *dest, const void *src, size_t n);\n\
+         void *memset(void *s, int c, size_t n);\n\
+         int memcmp(const void *a, const void *b, size_t n);\n\
+         void *memchr(const void *s, int c, size_t n);\n\
+         void *memrchr(const void *s, int c, size_t n);\n\
+         void *mempcpy(void *dest, const void *src, size_t n);\n\
+         size_t strlen(const char *s);\n\
+         size_t strnlen(const char *s, size_t n);\n\
+         char *strcpy(char *dest, const char *src);\n\
+         char *strncpy(char *dest, const char *src, size_t n);\n\
+         char *stpcpy(char *dest, const char *src);\n\
+         char *stpncpy(char *dest, const char *src, size_t n);\n\
+         char *strcat(char *dest, const char *src);\n\
+         char *strncat(char *dest, const char *src, size_t n);\n\
+         int strcmp(const char *a, const char *b);\n\
+         int strncmp(const char *a, const char *b, size_t n);\n\
+         int strcoll(const char *a, const char *b);\n\
+         char *strchr(const char *s, int c);\n\
+         char *strrchr(const char *s, int c);\n\
+         char *strstr(const char *haystack, const char *needle);\n\
+         size_t strspn(const char *s, const char *accept);\n\
+         size_t strcspn(const char *s, const char *reject);\n\
+         char *strpbrk(const char *s, const char *accept);\n\
+         char *strtok(char *s, const char *delim);\n\
+         char *strtok_r(char *s, const char *delim, char **saveptr);\n\
+         char *strdup(const char *s);\n\
+         char *strndup(const char *s, size_t n);\n\
+         char *strerror(int errnum);\n\
+         int strerror_r(int errnum, char *buf, size_t len) __asm__(\"__xpg_strerror_r\");\n\
+         char *strsignal(int sig);",
+    );
+    // The compiler's checking builtins call the `__*_chk` functions when
+    // they can't prove a call stays in bounds.
+    h.fortify(
+        "__memcpy_chk",
+        "__mustang_fortify void *memcpy(void *dest, const void *src, size_t n) {\n\
+         \x20   return __builtin___memcpy_chk(dest, src, n, __mustang_bos0(dest));\n\
+         }",
+    );
+    h.fortify(
+        "__memmove_chk",
+        "__mustang_fortify void *memmove(void *dest, const void *src, size_t n) {\n\
+         \x20   return __builtin___memmove_chk(dest, src, n, __mustang_bos0(dest));\n\
+         }",
+    );
+    h.fortify(
+        "__mempcpy_chk",
+        "__mustang_fortify void *mempcpy(void *dest, const void *src, size_t n) {\n\
+         \x20   return __builtin___mempcpy_chk(dest, src, n, __mustang_bos0(dest));\n\
+         }",
+    );
+    h.fortify(
+        "__memset_chk",
+        "__mustang_fortify void *memset(void *dest, int c, size_t n) {\n\
+         \x20   return __builtin___memset_chk(dest, c, n, __mustang_bos0(dest));\n\
+         }",
+    );
+    h.fortify(
+        "__strcpy_chk",
+        "__mustang_fortify char *strcpy(char *dest, const char *src) {\n\
+         \x20   return __builtin___strcpy_chk(dest, src, __mustang_bos(dest));\n\
+         }",
+    );
+    h.fortify(
+        "__stpcpy_chk",
+        "__mustang_fortify char *stpcpy(char *dest, const char *src) {\n\
+         \x20   return __builtin___stpcpy_chk(dest, src, __mustang_bos(dest));\n\
+         }",
+    );
+    h.fortify(
+        "__strncpy_chk",
+        "__mustang_fortify char *strncpy(char *dest, const char *src, size_t n) {\n\
+         \x20   return __builtin___strncpy_chk(dest, src, n, __mustang_bos(dest));\n\
+         }",
+    );
+    h.fortify(
+        "__stpncpy_chk",
+        "__mustang_fortify char *stpncpy(char *dest, const char *src, size_t n) {\n\
+         \x20   return __builtin___stpncpy_chk(dest, src, n, __mustang_bos(dest));\n\
+         }",
+    );
+    h.fortify(
+        "__strcat_chk",
+        "__mustang_fortify char *strcat(char *dest, const char *src) {\n\
+         \x20   return __builtin___strcat_chk(dest, src, __mustang_bos(dest));\n\
+         }",
+    );
+    h.fortify(
+        "__strncat_chk",
+        "__mustang_fortify char *strncat(char *dest, const char *src, size_t n) {\n\
+         \x20   return __builtin___strncat_chk(dest, src, n, __mustang_bos(dest));\n\
+         }",
+    );
+    h
+}
+
+fn strings(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "strings.h");
+    h.include(&["stddef.h"]);
+    h.functions(
+        "int strcasecmp(const char *a, const char *b);\n\
+         int strncasecmp(const char *a, const char *b, size_t n);\n\
+         int ffs(int i);\n\
+         void bzero(void *s, size_t n);",
+    );
+    h
+}
+
+fn stdlib(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "stdlib.h");
+    h.include(&["stddef.h"]);
+    defines!(h, EXIT_SUCCESS, EXIT_FAILURE, RAND_MAX);
+    h.functions(
+        "void *malloc(size_t size);\n\
+         void *calloc(size_t nmemb, size_t size);\n\
+         void *realloc(void *ptr, size_t size);\n\
+         void *reallocarray(void *ptr, size_t nmemb, size_t size);\n\
+         void free(void *ptr);\n\
+         void *aligned_alloc(size_t alignment, size_t size);\n\
+         int posix_memalign(void **memptr, size_t alignment, size_t size);\n\
+         void abort(void) __attribute__((__noreturn__));\n\
+         void exit(int status) __attribute__((__noreturn__));\n\
+         void _Exit(int status) __attribute__((__noreturn__));\n\
+         int atexit(void (*function)(void));\n\
+         char *getenv(const char *name);\n\
+         char *secure_getenv(const char *name);\n\
+         int setenv(const char *name, const char *value, int overwrite);\n\
+         int unsetenv(const char *name);\n\
+         int clearenv(void);\n\
+         int atoi(const char *s);\n\
+         long atol(const char *s);\n\
+         long long atoll(const char *s);\n\
+         double atof(const char *s);\n\
+         long strtol(const char *s, char **end, int base);\n\
+         unsigned long strtoul(const char *s, char **end, int base);\n\
+         long long strtoll(const char *s, char **end, int base);\n\
+         unsigned long long strtoull(const char *s, char **end, int base);\n\
+         double strtod(const char *s, char **end);\n\
+         float strtof(const char *s, char **end);\n\
+         int abs(int i);\n\
+         long labs(long i);\n\
+         long long llabs(long long i);\n\
+         int rand(void);\n\
+         void srand(unsigned int seed);\n\
+         void qsort(void *base, size_t nmemb, size_t size,\n\
+         \x20          int (*compar)(const void *, const void *));\n\
+         void *bsearch(const void *key, const void *base, size_t nmemb, size_t size,\n\
+         \x20             int (*compar)(const void *, const void *));\n\
+         char *realpath(const char *path, char *resolved);\n\
+         int mkstemp(char *template);\n\
+         char *mkdtemp(char *template);",
+    );
+    h
+}
+
+fn stdio(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "stdio.h");
+    h.include(&["stdarg.h", "sys/types.h"]);
+    h.text(
+        "typedef struct _IO_FILE FILE;\n\
+         extern FILE *stdin;\n\
+         extern FILE *stdout;\n\
+         extern FILE *stderr;",
+    );
+    h.define("EOF", -1);
+    h.define("BUFSIZ", 8192);
+    h.define("_IOFBF", 0);
+    h.define("_IOLBF", 1);
+    h.define("_IONBF", 2);
+    defines!(h, SEEK_SET, SEEK_CUR, SEEK_END);
+    h.functions(
+        "int printf(const char *format, ...);\n\
+         int fprintf(FILE *stream, const char *format, ...);\n\
+         int dprintf(int fd, const char *format, ...);\n\
+         int sprintf(char *str, const char *format, ...);\n\
+         int snprintf(char *str, size_t size, const char *format, ...);\n\
+         int asprintf(char **strp, const char *format, ...);\n\
+         int vprintf(const char *format, va_list ap);\n\
+         int vfprintf(FILE *stream, const char *format, va_list ap);\n\
+         int vdprintf(int fd, const char *format, va_list ap);\n\
+         int vsprintf(char *str, const char *format, va_list ap);\n\
+         int vsnprintf(char *str, size_t size, const char *format, va_list ap);\n\
+         int vasprintf(char **strp, const char *format, va_list ap);\n\
+         int sscanf(const char *str, const char *format, ...);\n\
+         int puts(const char *s);\n\
+         int fputs(const char *s, FILE *stream);\n\
+         int putchar(int c);\n\
+         int fputc(int c, FILE *stream);\n\
+         int putc(int c, FILE *stream);\n\
+         char *fgets(char *s, int size, FILE *stream);\n\
+         int fgetc(FILE *stream);\n\
+         int getc(FILE *stream);\n\
+         int getchar(void);\n\
+         int ungetc(int c, FILE *stream);\n\
+         ssize_t getline(char **lineptr, size_t *n, FILE *stream);\n\
+         ssize_t getdelim(char **lineptr, size_t *n, int delim, FILE *stream);\n\
+         size_t fread(void *ptr, size_t size, size_t nmemb, FILE *stream);\n\
+         size_t fwrite(const void *ptr, size_t size, size_t nmemb, FILE *stream);\n\
+         FILE *fopen(const char *path, const char *mode);\n\
+         FILE *fdopen(int fd, const char *mode);\n\
+         int fclose(FILE *stream);\n\
+         int fflush(FILE *stream);\n\
+         int fseek(FILE *stream, long offset, int whence);\n\
+         int fseeko(FILE *stream, off_t offset, int whence);\n\
+         long ftell(FILE *stream);\n\
+         off_t ftello(FILE *stream);\n\
+         void rewind(FILE *stream);\n\
+         int feof(FILE *stream);\n\
+         int ferror(FILE *stream);\n\
+         void clearerr(FILE *stream);\n\
+         int fileno(FILE *stream);\n\
+         int setvbuf(FILE *stream, char *buf, int mode, size_t size);\n\
+         void perror(const char *s);\n\
+         int remove(const char *path);\n\
+         int rename(const char *oldpath, const char *newpath);",
+    );
+    // `sprintf` and `snprintf` are macros, since only GCC can forward
+    // variadic arguments from an inline function.
+    h.fortify(
+        "__sprintf_chk",
+        "#define sprintf(str, ...) \\\n\
+         \x20   __builtin___sprintf_chk((str), _FORTIFY_SOURCE - 1, __mustang_bos(str), __VA_ARGS__)",
+    );
+    h.fortify(
+        "__snprintf_chk",
+        "#define snprintf(str, size, ...) \\\n\
+         \x20   __builtin___snprintf_chk((str), (size), _FORTIFY_SOURCE - 1, __mustang_bos(str), __VA_ARGS__)",
+    );
+    h.fortify(
+        "__vsprintf_chk",
+        "__mustang_fortify int vsprintf(char *str, const char *format, va_list ap) {\n\
+         \x20   return __builtin___vsprintf_chk(str, _FORTIFY_SOURCE - 1, __mustang_bos(str), format, ap);\n\
+         }",
+    );
+    h.fortify(
+        "__vsnprintf_chk",
+        "__mustang_fortify int vsnprintf(char *str, size_t size, const char *format, va_list ap) {\n\
+         \x20   return __builtin___vsnprintf_chk(str, size, _FORTIFY_SOURCE - 1, __mustang_bos(str), format,\n\
+         \x20                                    ap);\n\
+         }",
+    );
+    h.fortify(
+        "__fgets_chk",
+        "char *__fgets_chk(char *s, size_t len, int size, FILE *stream);\n\
+         char *__fgets_alias(char *s, int size, FILE *stream) __asm__(\"fgets\");\n\
+         __mustang_fortify char *fgets(char *s, int size, FILE *stream) {\n\
+         \x20   if (__mustang_bos(s) != (size_t)-1)\n\
+         \x20       return __fgets_chk(s, __mustang_bos(s), size, stream);\n\
+         \x20   return __fgets_alias(s, size, stream);\n\
+         }",
+    );
+    h.fortify(
+        "__fread_chk",
+        "size_t __fread_chk(void *ptr, size_t len, size_t size, size_t nmemb, FILE *stream);\n\
+         size_t __fread_alias(void *ptr, size_t size, size_t nmemb, FILE *stream) __asm__(\"fread\");\n\
+         __mustang_fortify size_t fread(void *ptr, size_t size, size_t nmemb, FILE *stream) {\n\
+         \x20   if (__mustang_bos0(ptr) != (size_t)-1)\n\
+         \x20       return __fread_chk(ptr, __mustang_bos0(ptr), size, nmemb, stream);\n\
+         \x20   return __fread_alias(ptr, size, nmemb, stream);\n\
+         }",
+    );
+    h
+}
+
+fn time(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "time.h");
+    h.include(&["sys/types.h"]);
+    h.strukt::<timespec>(
+        "timespec",
+        fields!(timespec {
+            tv_sec: "time_t",
+            tv_nsec: "long",
+        }),
+    );
+    h.strukt::<tm>(
+        "tm",
+        fields!(tm {
+            tm_sec: "int",
+            tm_min: "int",
+            tm_hour: "int",
+            tm_mday: "int",
+            tm_mon: "int",
+            tm_year: "int",
+            tm_wday: "int",
+            tm_yday: "int",
+            tm_isdst: "int",
+            tm_gmtoff: "long",
+            tm_zone: "const char *",
+        }),
+    );
+    defines!(
+        h,
+        CLOCK_REALTIME,
+        CLOCK_MONOTONIC,
+        CLOCK_PROCESS_CPUTIME_ID,
+        CLOCK_THREAD_CPUTIME_ID,
+        CLOCK_MONOTONIC_RAW,
+        CLOCK_REALTIME_COARSE,
+        CLOCK_MONOTONIC_COARSE,
+        CLOCK_BOOTTIME,
+        TIMER_ABSTIME,
+    );
+    h.define("CLOCKS_PER_SEC", 1_000_000_i64);
+    h.functions(
+        "time_t time(time_t *t);\n\
+         clock_t clock(void);\n\
+         int clock_gettime(clockid_t clock, struct timespec *tp);\n\
+         int clock_getres(clockid_t clock, struct timespec *res);\n\
+         int clock_nanosleep(clockid_t clock, int flags, const struct timespec *request,\n\
+         \x20                   struct timespec *remain);\n\
+         int nanosleep(const struct timespec *request, struct timespec *remain);\n\
+         struct tm *gmtime_r(const time_t *t, struct tm *result);\n\
+         struct tm *localtime_r(const time_t *t, struct tm *result);\n\
+         time_t mktime(struct tm *tm);\n\
+         time_t timegm(struct tm *tm);\n\
+         size_t strftime(char *s, size_t max, const char *format, const struct tm *tm);",
+    );
+    h
+}
+
+fn sys_time(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "sys/time.h");
+    h.include(&["sys/types.h"]);
+    h.strukt::<timeval>(
+        "timeval",
+        fields!(timeval {
+            tv_sec: "time_t",
+            tv_usec: "suseconds_t",
+        }),
+    );
+    h.functions("int gettimeofday(struct timeval *tv, void *tz);");
+    h
+}
+
+fn sys_stat(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "sys/stat.h");
+    h.include(&["time.h"]);
+
+    // The `libc` crate splits the timestamps into separate seconds and
+    // nanoseconds fields; C has them as `struct timespec`s.
+    let mut fields = fields!(stat {
+        st_dev: "dev_t",
+        st_ino: "ino_t",
+        st_nlink: "nlink_t",
+        st_mode: "mode_t",
+        st_uid: "uid_t",
+        st_gid: "gid_t",
+        st_rdev: "dev_t",
+        st_size: "off_t",
+        st_blksize: "blksize_t",
+        st_blocks: "blkcnt_t",
+    });
+    fields.extend([
+        Field::span(
+            "st_atim",
+            "struct timespec",
+            offset_of!(stat, st_atime),
+            end_of!(stat, st_atime_nsec),
+        ),
+        Field::span(
+            "st_mtim",
+            "struct timespec",
+            offset_of!(stat, st_mtime),
+            end_of!(stat, st_mtime_nsec),
+        ),
+        Field::span(
+            "st_ctim",
+            "struct timespec",
+            offset_of!(stat, st_ctime),
+            end_of!(stat, st_ctime_nsec),
+        ),
+    ]);
+    h.strukt::<stat>("stat", fields);
+    h.text(
+        "#define st_atime st_atim.tv_sec\n\
+         #define st_mtime st_mtim.tv_sec\n\
+         #define st_ctime st_ctim.tv_sec",
+    );
+    defines!(
+        h, S_IFMT, S_IFSOCK, S_IFLNK, S_IFREG, S_IFBLK, S_IFDIR, S_IFCHR, S_IFIFO, S_ISUID,
+        S_ISGID, S_ISVTX, S_IRWXU, S_IRUSR, S_IWUSR, S_IXUSR, S_IRWXG, S_IRGRP, S_IWGRP, S_IXGRP,
+        S_IRWXO, S_IROTH, S_IWOTH, S_IXOTH, UTIME_NOW, UTIME_OMIT,
+    );
+    h.text(
+        "#define S_ISREG(m) (((m) & S_IFMT) == S_IFREG)\n\
+         #define S_ISDIR(m) (((m) & S_IFMT) == S_IFDIR)\n\
+         #define S_ISCHR(m) (((m) & S_IFMT) == S_IFCHR)\n\
+         #define S_ISBLK(m) (((m) & S_IFMT) == S_IFBLK)\n\
+         #define S_ISFIFO(m) (((m) & S_IFMT) == S_IFIFO)\n\
+         #define S_ISLNK(m) (((m) & S_IFMT) == S_IFLNK)\n\
+         #define S_ISSOCK(m) (((m) & S_IFMT) == S_IFSOCK)",
+    );
+    h.functions(
+        "int stat(const char *path, struct stat *buf);\n\
+         int fstat(int fd, struct stat *buf);\n\
+         int lstat(const char *path, struct stat *buf);\n\
+         int fstatat(int dirfd, const char *path, struct stat *buf, int flags);\n\
+         int chmod(const char *path, mode_t mode);\n\
+         int fchmod(int fd, mode_t mode);\n\
+         int mkdir(const char *path, mode_t mode);\n\
+         int mkdirat(int dirfd, const char *path, mode_t mode);\n\
+         mode_t umask(mode_t mask);\n\
+         int utimensat(int dirfd, const char *path, const struct timespec times[2], int flags);\n\
+         int futimens(int fd, const struct timespec times[2]);",
+    );
+    h
+}
+
+fn dirent(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "dirent.h");
+    h.include(&["sys/types.h"]);
+    h.strukt::<dirent>(
+        "dirent",
+        fields!(dirent {
+            d_ino: "ino_t",
+            d_off: "off_t",
+            d_reclen: "unsigned short",
+            d_type: "unsigned char",
+            d_name: "char[]",
+        }),
+    );
+    defines!(h, DT_UNKNOWN, DT_FIFO, DT_CHR, DT_DIR, DT_BLK, DT_REG, DT_LNK, DT_SOCK);
+    h.text("typedef struct __dirstream DIR;");
+    h.functions(
+        "DIR *opendir(const char *path);\n\
+         DIR *fdopendir(int fd);\n\
+         struct dirent *readdir(DIR *dir);\n\
+         void rewinddir(DIR *dir);\n\
+         int closedir(DIR *dir);\n\
+         int dirfd(DIR *dir);",
+    );
+    h
+}
+
+fn signal(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "signal.h");
+    h.include(&["sys/types.h"]);
+    h.opaque::<sigset_t>("sigset_t");
+    h.text(&format!(
+        "typedef {} sig_atomic_t;\n\
+         typedef void (*sighandler_t)(int);\n\
+         #define SIG_DFL ((sighandler_t)0)\n\
+         #define SIG_IGN ((sighandler_t)1)\n\
+         #define SIG_ERR ((sighandler_t)-1)",
+        c_int_type(true, size_of::<c_int>())
+    ));
+    defines!(
+        h,
+        SIGHUP,
+        SIGINT,
+        SIGQUIT,
+        SIGILL,
+        SIGTRAP,
+        SIGABRT,
+        SIGBUS,
+        SIGFPE,
+        SIGKILL,
+        SIGUSR1,
+        SIGSEGV,
+        SIGUSR2,
+        SIGPIPE,
+        SIGALRM,
+        SIGTERM,
+        SIGCHLD,
+        SIGCONT,
+        SIGSTOP,
+        SIGTSTP,
+        SIGTTIN,
+        SIGTTOU,
+        SIGURG,
+        SIGXCPU,
+        SIGXFSZ,
+        SIGVTALRM,
+        SIGPROF,
+        SIGWINCH,
+        SIGIO,
+        SIGSYS,
+        SIG_BLOCK,
+        SIG_UNBLOCK,
+        SIG_SETMASK,
+    );
+    h.functions(
+        "sighandler_t signal(int sig, sighandler_t handler);\n\
+         int raise(int sig);\n\
+         int kill(pid_t pid, int sig);\n\
+         int sigemptyset(sigset_t *set);\n\
+         int sigfillset(sigset_t *set);\n\
+         int sigaddset(sigset_t *set, int sig);\n\
+         int sigdelset(sigset_t *set, int sig);\n\
+         int sigismember(const sigset_t *set, int sig);\n\
+         int sigprocmask(int how, const sigset_t *set, sigset_t *oldset);\n\
+         int pthread_sigmask(int how, const sigset_t *set, sigset_t *oldset);",
+    );
+    h
+}
+
+fn sched(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "sched.h");
+    h.include(&["sys/types.h"]);
+    h.strukt::<sched_param>(
+        "sched_param",
+        fields!(sched_param {
+            sched_priority: "int",
+        }),
+    );
+    defines!(
+        h,
+        SCHED_OTHER,
+        SCHED_FIFO,
+        SCHED_RR,
+        SCHED_BATCH,
+        SCHED_IDLE,
+        SCHED_DEADLINE,
+        SCHED_RESET_ON_FORK,
+        SCHED_FLAG_RESET_ON_FORK,
+        SCHED_FLAG_RECLAIM,
+        SCHED_FLAG_DL_OVERRUN,
+        SCHED_FLAG_KEEP_POLICY,
+        SCHED_FLAG_KEEP_PARAMS,
+        CPU_SETSIZE,
+    );
+    h.text("");
+    h.strukt::<sched_attr>(
+        "sched_attr",
+        fields!(sched_attr {
+            size: "uint32_t",
+            sched_policy: "uint32_t",
+            sched_flags: "uint64_t",
+            sched_nice: "int32_t",
+            sched_priority: "uint32_t",
+            sched_runtime: "uint64_t",
+            sched_deadline: "uint64_t",
+            sched_period: "uint64_t",
+        }),
+    );
+    // The kernel's CPU masks are arrays of `unsigned long`.
+    h.text(&format!(
+        "typedef struct {{ unsigned long __bits[{}]; }} cpu_set_t;\n\
+         _Static_assert(sizeof(cpu_set_t) == {}, \"cpu_set_t size\");\n\
+         #define __CPU_BITS (8 * sizeof(unsigned long))\n\
+         #define CPU_ZERO(set) __builtin_memset((set), 0, sizeof(cpu_set_t))\n\
+         #define CPU_SET(cpu, set) \\\n\
+         \x20   ((set)->__bits[(cpu) / __CPU_BITS] |= 1UL << ((cpu) % __CPU_BITS))\n\
+         #define CPU_CLR(cpu, set) \\\n\
+         \x20   ((set)->__bits[(cpu) / __CPU_BITS] &= ~(1UL << ((cpu) % __CPU_BITS)))\n\
+         #define CPU_ISSET(cpu, set) \\\n\
+         \x20   ((int)(((set)->__bits[(cpu) / __CPU_BITS] >> ((cpu) % __CPU_BITS)) & 1))",
+        size_of::<cpu_set_t>() / size_of::<c_ulong>(),
+        size_of::<cpu_set_t>(),
+    ));
+    h.functions(
+        "int sched_yield(void);\n\
+         int sched_getcpu(void);\n\
+         int sched_setaffinity(pid_t pid, size_t size, const cpu_set_t *set);\n\
+         int sched_getaffinity(pid_t pid, size_t size, cpu_set_t *set);\n\
+         int sched_setattr(pid_t pid, struct sched_attr *attr, unsigned flags);\n\
+         int sched_getattr(pid_t pid, struct sched_attr *attr, unsigned size, unsigned flags);",
+    );
+    h
+}
+
+fn pthread(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "pthread.h");
+    h.include(&["sched.h", "time.h"]);
+    defines!(
+        h,
+        PTHREAD_MUTEX_NORMAL,
+        PTHREAD_MUTEX_RECURSIVE,
+        PTHREAD_MUTEX_ERRORCHECK,
+        PTHREAD_MUTEX_DEFAULT,
+        PTHREAD_CREATE_JOINABLE,
+        PTHREAD_CREATE_DETACHED,
+        PTHREAD_PROCESS_PRIVATE,
+        PTHREAD_PROCESS_SHARED,
+        PTHREAD_MUTEX_STALLED,
+        PTHREAD_MUTEX_ROBUST,
+        PTHREAD_PRIO_NONE,
+        PTHREAD_PRIO_INHERIT,
+        PTHREAD_PRIO_PROTECT,
+        PTHREAD_BARRIER_SERIAL_THREAD,
+    );
+    h.define("PTHREAD_ONCE_INIT", 0);
+    h.initializer("PTHREAD_MUTEX_INITIALIZER", &PTHREAD_MUTEX_INITIALIZER);
+    h.initializer("PTHREAD_COND_INITIALIZER", &PTHREAD_COND_INITIALIZER);
+    h.initializer("PTHREAD_RWLOCK_INITIALIZER", &PTHREAD_RWLOCK_INITIALIZER);
+    // The libc crate doesn't have the cancellation constants.
+    h.define("PTHREAD_CANCEL_ENABLE", 0);
+    h.define("PTHREAD_CANCEL_DISABLE", 1);
+    h.define("PTHREAD_CANCEL_DEFERRED", 0);
+    h.define("PTHREAD_CANCEL_ASYNCHRONOUS", 1);
+    h.text("#define PTHREAD_CANCELED ((void *)-1)");
+    h.text(
+        "struct _pthread_cleanup_buffer {\n\
+         \x20   void (*__routine)(void *);\n\
+         \x20   void *__arg;\n\
+         \x20   int __canceltype;\n\
+         \x20   struct _pthread_cleanup_buffer *__prev;\n\
+         };",
+    );
+    h.functions(
+        "void _pthread_cleanup_push(struct _pthread_cleanup_buffer *buffer,\n\
+         \x20                          void (*routine)(void *), void *arg);\n\
+         void _pthread_cleanup_pop(struct _pthread_cleanup_buffer *buffer, int execute);",
+    );
+    h.text(
+        "#define pthread_cleanup_push(routine, arg) \\\n\
+         \x20   do { \\\n\
+         \x20       struct _pthread_cleanup_buffer __cleanup_buffer; \\\n\
+         \x20       _pthread_cleanup_push(&__cleanup_buffer, (routine), (arg));\n\
+         #define pthread_cleanup_pop(execute) \\\n\
+         \x20       _pthread_cleanup_pop(&__cleanup_buffer, (execute)); \\\n\
+         \x20   } while (0)",
+    );
+    h.functions(
+        "int pthread_create(pthread_t *thread, const pthread_attr_t *attr,\n\
+         \x20                  void *(*start)(void *), void *arg);\n\
+         int pthread_attr_init(pthread_attr_t *attr);\n\
+         int pthread_getattr_default_np(pthread_attr_t *attr);\n\
+         int pthread_setattr_default_np(const pthread_attr_t *attr);",
+    );
+    h.functions(
+        "int pthread_getattr_np(pthread_t thread, pthread_attr_t *attr);\n\
+         int pthread_attr_getstack(const pthread_attr_t *attr, void **addr, size_t *size);",
+    );
+    h.functions(
+        "int pthread_setname_np(pthread_t thread, const char *name);\n\
+         int pthread_getname_np(pthread_t thread, char *name, size_t len);\n\
+         int pthread_setaffinity_np(pthread_t thread, size_t size, const cpu_set_t *set);\n\
+         int pthread_getaffinity_np(pthread_t thread, size_t size, cpu_set_t *set);\n\
+         int pthread_setschedparam(pthread_t thread, int policy, const struct sched_param *param);\n\
+         int pthread_getschedparam(pthread_t thread, int *policy, struct sched_param *param);",
+    );
+    h.functions(
+        "int pthread_join(pthread_t thread, void **retval);\n\
+         int pthread_detach(pthread_t thread);\n\
+         pthread_t pthread_self(void);\n\
+         int pthread_equal(pthread_t a, pthread_t b);\n\
+         void pthread_exit(void *retval) __attribute__((__noreturn__));\n\
+         int pthread_cancel(pthread_t thread);\n\
+         int pthread_setcancelstate(int state, int *oldstate);\n\
+         int pthread_setcanceltype(int type, int *oldtype);\n\
+         void pthread_testcancel(void);\n\
+         int pthread_once(pthread_once_t *once, void (*init)(void));\n\
+         int pthread_attr_destroy(pthread_attr_t *attr);\n\
+         int pthread_attr_setstacksize(pthread_attr_t *attr, size_t size);\n\
+         int pthread_attr_getstacksize(const pthread_attr_t *attr, size_t *size);\n\
+         int pthread_attr_setguardsize(pthread_attr_t *attr, size_t size);\n\
+         int pthread_attr_getguardsize(const pthread_attr_t *attr, size_t *size);\n\
+         int pthread_attr_setdetachstate(pthread_attr_t *attr, int state);\n\
+         int pthread_key_create(pthread_key_t *key, void (*destructor)(void *));\n\
+         int pthread_key_delete(pthread_key_t key);\n\
+         void *pthread_getspecific(pthread_key_t key);\n\
+         int pthread_setspecific(pthread_key_t key, const void *value);",
+    );
+    // Mutexes, condition variables, rwlocks and barriers are implemented by
+    // mustang rather than c-scape, for robust, priority-inheritance and
+    // process-shared support; mustang binds these names to its own
+    // implementations at link time.
+    h.functions(
+        "int pthread_mutex_init(pthread_mutex_t *mutex, const pthread_mutexattr_t *attr);\n\
+         int pthread_mutex_destroy(pthread_mutex_t *mutex);\n\
+         int pthread_mutex_lock(pthread_mutex_t *mutex);\n\
+         int pthread_mutex_trylock(pthread_mutex_t *mutex);\n\
+         int pthread_mutex_timedlock(pthread_mutex_t *mutex, const struct timespec *abstime);\n\
+         int pthread_mutex_unlock(pthread_mutex_t *mutex);\n\
+         int pthread_mutex_consistent(pthread_mutex_t *mutex);\n\
+         int pthread_mutexattr_init(pthread_mutexattr_t *attr);\n\
+         int pthread_mutexattr_destroy(pthread_mutexattr_t *attr);\n\
+         int pthread_mutexattr_settype(pthread_mutexattr_t *attr, int type);\n\
+         int pthread_mutexattr_gettype(const pthread_mutexattr_t *attr, int *type);\n\
+         int pthread_mutexattr_setpshared(pthread_mutexattr_t *attr, int pshared);\n\
+         int pthread_mutexattr_getpshared(const pthread_mutexattr_t *attr, int *pshared);\n\
+         int pthread_mutexattr_setrobust(pthread_mutexattr_t *attr, int robust);\n\
+         int pthread_mutexattr_getrobust(const pthread_mutexattr_t *attr, int *robust);\n\
+         int pthread_mutexattr_setprotocol(pthread_mutexattr_t *attr, int protocol);\n\
+         int pthread_mutexattr_getprotocol(const pthread_mutexattr_t *attr, int *protocol);\n\
+         int pthread_cond_init(pthread_cond_t *cond, const pthread_condattr_t *attr);\n\
+         int pthread_cond_destroy(pthread_cond_t *cond);\n\
+         int pthread_cond_wait(pthread_cond_t *cond, pthread_mutex_t *mutex);\n\
+         int pthread_cond_timedwait(pthread_cond_t *cond, pthread_mutex_t *mutex,\n\
+         \x20                          const struct timespec *abstime);\n\
+         int pthread_cond_signal(pthread_cond_t *cond);\n\
+         int pthread_cond_broadcast(pthread_cond_t *cond);\n\
+         int pthread_condattr_init(pthread_condattr_t *attr);\n\
+         int pthread_condattr_destroy(pthread_condattr_t *attr);\n\
+         int pthread_condattr_setclock(pthread_condattr_t *attr, clockid_t clock);\n\
+         int pthread_condattr_getclock(const pthread_condattr_t *attr, clockid_t *clock);\n\
+         int pthread_condattr_setpshared(pthread_condattr_t *attr, int pshared);\n\
+         int pthread_condattr_getpshared(const pthread_condattr_t *attr, int *pshared);\n\
+         int pthread_rwlock_init(pthread_rwlock_t *rwlock, const pthread_rwlockattr_t *attr);\n\
+         int pthread_rwlock_destroy(pthread_rwlock_t *rwlock);\n\
+         int pthread_rwlock_rdlock(pthread_rwlock_t *rwlock);\n\
+         int pthread_rwlock_tryrdlock(pthread_rwlock_t *rwlock);\n\
+         int pthread_rwlock_timedrdlock(pthread_rwlock_t *rwlock, const struct timespec *abstime);\n\
+         int pthread_rwlock_wrlock(pthread_rwlock_t *rwlock);\n\
+         int pthread_rwlock_trywrlock(pthread_rwlock_t *rwlock);\n\
+         int pthread_rwlock_timedwrlock(pthread_rwlock_t *rwlock, const struct timespec *abstime);\n\
+         int pthread_rwlock_unlock(pthread_rwlock_t *rwlock);\n\
+         int pthread_rwlockattr_init(pthread_rwlockattr_t *attr);\n\
+         int pthread_rwlockattr_destroy(pthread_rwlockattr_t *attr);\n\
+         int pthread_rwlockattr_setpshared(pthread_rwlockattr_t *attr, int pshared);\n\
+         int pthread_rwlockattr_getpshared(const pthread_rwlockattr_t *attr, int *pshared);\n\
+         int pthread_barrier_init(pthread_barrier_t *barrier, const pthread_barrierattr_t *attr,\n\
+         \x20                        unsigned count);\n\
+         int pthread_barrier_destroy(pthread_barrier_t *barrier);\n\
+         int pthread_barrier_wait(pthread_barrier_t *barrier);\n\
+         int pthread_barrierattr_init(pthread_barrierattr_t *attr);\n\
+         int pthread_barrierattr_destroy(pthread_barrierattr_t *attr);\n\
+         int pthread_barrierattr_setpshared(pthread_barrierattr_t *attr, int pshared);\n\
+         int pthread_barrierattr_getpshared(const pthread_barrierattr_t *attr, int *pshared);",
+    );
+    h
+}
+
+fn semaphore(libc: &Libc) -> Header<'_> {
+    let mut h = Header::new(libc, "semaphore.h");
+    h.include(&["fcntl.h", "time.h"]);
+    h.opaque::<sem_t>("sem_t");
+    h.define("SEM_VALUE_MAX", i32::MAX);
+    h.text("#define SEM_FAILED ((sem_t *)0)");
+    // Semaphores are implemented by mustang rather than c-scape, for named
+    // semaphores; mustang binds these names to its own implementations at
+    // link time.
+    h.functions(
+        "int sem_init(sem_t *sem, int pshared, unsigned value);\n\
+         int sem_destroy(sem_t *sem);\n\
+         int sem_wait(sem_t *sem);\n\
+         int sem_trywait(sem_t *sem);\n\
+         int sem_timedwait(sem_t *sem, const struct timespec *abstime);\n\
+         int sem_post(sem_t *sem);\n\
+         int sem_getvalue(sem_t *sem, int *value);\n\
+         sem_t *sem_open(const char *name, int oflag, ...);\n\
+         int sem_close(sem_t *sem);\n\
+         int sem_unlink(const char *name);",
+    );
+    h
+}
@@ -0,0 +1,475 @@
+//! Generate C headers describing the ABI mustang's libc implements.
+//!
+//! ```console
+//! $ mustang-headers <sysroot> <rlib>...
+//! ```
+//!
+//! This writes headers into `<sysroot>/<target>/include`, where `<target>`
+//! is the mustang target for the architecture the generator runs on. Type
+//! sizes, struct layouts, and constant values all come from the `libc`
+//! crate, which c-scape uses to implement libc, so the headers describe
+//! exactly the ABI the runtime provides. Each struct is laid out with
+//! explicit padding to match the `libc` crate's layout, and the headers
+//! contain static assertions of every field offset and struct size, so a
+//! mismatch is a compile error rather than memory corruption.
+//!
+//! Functions are declared only if one of the `<rlib>`s, typically c-scape's
+//! and mustang's rlibs built for the target, exports them, so the
+//! headers never declare a function which would fail to link. Likewise, the
+//! `_FORTIFY_SOURCE` wrappers are only defined for the `__*_chk` functions
+//! the libraries export.
+//!
+//! The values are those of the architecture the generator runs on, so to
+//! generate headers for another architecture, build the generator for the
+//! corresponding `*-unknown-linux-gnu` target and run it there, or under
+//! qemu, with the same sysroot. Each target's headers go in their own
+//! directory.
+//!
+//! Compiler-provided headers such as `<stddef.h>`, `<stdarg.h>`, and
+//! `<stdbool.h>` are used as-is; see the `mustang-cc` script.
+
+mod headers;
+
+use object::read::archive::ArchiveFile;
+use object::{Architecture, Object, ObjectSymbol};
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::mem::{align_of, size_of};
+use std::path::Path;
+use std::process::exit;
+
+/// The mustang target for the architecture the generator runs on, and its
+/// ELF machine.
+#[cfg(target_arch = "x86_64")]
+const TARGET: (&str, Architecture) = ("x86_64-mustang-linux-gnu", Architecture::X86_64);
+#[cfg(target_arch = "x86")]
+const TARGET: (&str, Architecture) = ("i686-mustang-linux-gnu", Architecture::I386);
+#[cfg(target_arch = "aarch64")]
+const TARGET: (&str, Architecture) = ("aarch64-mustang-linux-gnu", Architecture::Aarch64);
+#[cfg(target_arch = "riscv64")]
+const TARGET: (&str, Architecture) = ("riscv64gc-mustang-linux-gnu", Architecture::Riscv64);
+#[cfg(target_arch = "arm")]
+const TARGET: (&str, Architecture) = ("armv5te-mustang-linux-gnueabi", Architecture::Arm);
+
+/// The symbols the libc exports.
+pub(crate) struct Libc {
+    symbols: HashSet<String>,
+}
+
+impl Libc {
+    /// Read the symbols defined by the rlibs, archives, or object files at
+    /// `paths`.
+    fn load(paths: &[String]) -> Result<Self, String> {
+        let mut libc = Self {
+            symbols: HashSet::new(),
+        };
+        for path in paths {
+            let data = std::fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+            match ArchiveFile::parse(&*data) {
+                Ok(archive) => {
+                    for member in archive.members() {
+                        let member = member
+                            .and_then(|member| member.data(&*data))
+                            .map_err(|err| format!("{}: {}", path, err))?;
+                        // rlibs also contain metadata, which isn't an object.
+                        if member.starts_with(b"\x7fELF") {
+                            libc.add(path, member)?;
+                        }
+                    }
+                }
+                Err(_) => libc.add(path, &data)?,
+            }
+        }
+        Ok(libc)
+    }
+
+    /// Add the symbols the object file `data` defines.
+    fn add(&mut self, path: &str, data: &[u8]) -> Result<(), String> {
+        // Archive members aren't necessarily aligned, and the `object` crate
+        // reads ELF headers in place.
+        let mut aligned = vec![0_u64; data.len().div_ceil(size_of::<u64>())];
+        // SAFETY: `aligned` has at least `data.len()` bytes, and any bytes
+        // are valid `u64`s.
+        let aligned = unsafe {
+            let bytes =
+                std::slice::from_raw_parts_mut(aligned.as_mut_ptr().cast::<u8>(), data.len());
+            bytes.copy_from_slice(data);
+            &*bytes
+        };
+        let object = object::File::parse(aligned).map_err(|err| format!("{}: {}", path, err))?;
+        if object.architecture() != TARGET.1 {
+            return Err(format!(
+                "{}: built for {:?}, but this generator is for {}",
+                path,
+                object.architecture(),
+                TARGET.0
+            ));
+        }
+        for symbol in object.symbols() {
+            if symbol.is_local() || symbol.is_undefined() {
+                continue;
+            }
+            if let Ok(name) = symbol.name() {
+                // mustang binds the standard names of the functions it
+                // implements as `__mustang_*` to them at link time.
+                if let Some(name) = name.strip_prefix("__mustang_") {
+                    self.symbols.insert(name.to_owned());
+                }
+                self.symbols.insert(name.to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    /// Does the libc export `symbol`?
+    pub(crate) fn exports(&self, symbol: &str) -> bool {
+        self.symbols.contains(symbol)
+    }
+}
+
+/// The symbol a C function declaration refers to: the name in its
+/// `__asm__` label if it has one, and otherwise the identifier before the
+/// first parameter list.
+fn declared_symbol(decl: &str) -> &str {
+    if let Some((_, label)) = decl.split_once("__asm__(\"") {
+        return &label[..label.find('"').unwrap()];
+    }
+    let mut rest = decl;
+    while let Some(paren) = rest.find('(') {
+        let before = &rest[..paren];
+        let start = before
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        if start < paren {
+            return &before[start..];
+        }
+        rest = &rest[paren + 1..];
+    }
+    panic!("no function name in declaration: {}", decl)
+}
+
+/// An integer type from the `libc` crate.
+pub(crate) trait Int: Copy {
+    const SIGNED: bool;
+    const SIZE: usize;
+    fn to_i128(self) -> i128;
+}
+
+macro_rules! int {
+    ($($ty:ty: $signed:literal),*) => {
+        $(
+            impl Int for $ty {
+                const SIGNED: bool = $signed;
+                const SIZE: usize = size_of::<$ty>();
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+            }
+        )*
+    };
+}
+
+int!(i8: true, u8: false, i16: true, u16: false, i32: true, u32: false, i64: true, u64: false,
+     isize: true, usize: false);
+
+/// The C integer type with the given signedness and size.
+pub(crate) fn c_int_type(signed: bool, size: usize) -> &'static str {
+    let long = size_of::<libc::c_long>();
+    match (signed, size) {
+        (true, 1) => "signed char",
+        (false, 1) => "unsigned char",
+        (true, 2) => "short",
+        (false, 2) => "unsigned short",
+        (true, 4) => "int",
+        (false, 4) => "unsigned int",
+        (true, 8) if long == 8 => "long",
+        (false, 8) if long == 8 => "unsigned long",
+        (true, 8) => "long long",
+        (false, 8) => "unsigned long long",
+        _ => panic!("no C integer type of size {}", size),
+    }
+}
+
+/// Format `value` as a C integer literal of a type that can hold it.
+fn literal<T: Int>(value: T) -> String {
+    let value = value.to_i128();
+    let long = size_of::<libc::c_long>();
+    if i128::from(i32::MIN) <= value && value <= i128::from(i32::MAX) {
+        value.to_string()
+    } else if !T::SIGNED && value <= i128::from(u32::MAX) {
+        format!("{}u", value)
+    } else {
+        match (T::SIGNED, long) {
+            (true, 8) => format!("{}L", value),
+            (false, 8) => format!("{}UL", value),
+            (true, _) => format!("{}LL", value),
+            (false, _) => format!("{}ULL", value),
+        }
+    }
+}
+
+/// A struct field, with its offset and size in the `libc` crate's layout.
+pub(crate) struct Field {
+    name: &'static str,
+    cty: &'static str,
+    offset: usize,
+    size: usize,
+}
+
+impl Field {
+    /// Describe a field. `cty` is the C type, or `char[]` for a character
+    /// array, whose length is determined by the field's size.
+    pub(crate) fn new<S, T>(
+        name: &'static str,
+        cty: &'static str,
+        offset: usize,
+        _field: fn(&S) -> &T,
+    ) -> Self {
+        Self {
+            name,
+            cty,
+            offset,
+            size: size_of::<T>(),
+        }
+    }
+
+    /// Describe a C field which covers several `libc` crate fields, from
+    /// `offset` up to `end`.
+    pub(crate) fn span(name: &'static str, cty: &'static str, offset: usize, end: usize) -> Self {
+        Self {
+            name,
+            cty,
+            offset,
+            size: end - offset,
+        }
+    }
+}
+
+/// A C header under construction.
+pub(crate) struct Header<'a> {
+    path: &'static str,
+    out: String,
+    /// The `_FORTIFY_SOURCE` wrappers, which go at the end.
+    fortify: String,
+    libc: &'a Libc,
+}
+
+impl<'a> Header<'a> {
+    pub(crate) fn new(libc: &'a Libc, path: &'static str) -> Self {
+        let mut out = String::new();
+        let guard = path.replace(['/', '.', '-'], "_").to_uppercase();
+        writeln!(
+            out,
+            "/* Generated by mustang-headers for {} from the libc crate's definitions. */\n",
+            TARGET.0
+        )
+        .unwrap();
+        writeln!(out, "#ifndef _MUSTANG_{}", guard).unwrap();
+        writeln!(out, "#define _MUSTANG_{}\n", guard).unwrap();
+        Self {
+            path,
+            out,
+            fortify: String::new(),
+            libc,
+        }
+    }
+
+    /// Append raw text.
+    pub(crate) fn text(&mut self, text: &str) {
+        self.out.push_str(text.trim_start_matches('\n'));
+        if !text.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.out.push('\n');
+    }
+
+    /// Append function declarations, each ending with a `;` at the end of a
+    /// line, leaving out those the libc doesn't export.
+    pub(crate) fn functions(&mut self, decls: &str) {
+        let mut decl = String::new();
+        for line in decls.lines() {
+            decl.push_str(line);
+            decl.push('\n');
+            if !line.ends_with(';') {
+                continue;
+            }
+            let symbol = declared_symbol(&decl);
+            if self.libc.exports(symbol) {
+                self.out.push_str(&decl);
+            } else {
+                eprintln!(
+                    "mustang-headers: {}: not declaring `{}`, which the libc doesn't export",
+                    self.path, symbol
+                );
+            }
+            decl.clear();
+        }
+        assert!(decl.is_empty(), "unterminated declaration: {}", decl);
+        self.out.push('\n');
+    }
+
+    /// Define `_FORTIFY_SOURCE` wrappers, which call `chk`, if the libc
+    /// exports it.
+    pub(crate) fn fortify(&mut self, chk: &str, text: &str) {
+        if self.libc.exports(chk) {
+            self.fortify.push_str(text.trim_start_matches('\n'));
+            if !text.ends_with('\n') {
+                self.fortify.push('\n');
+            }
+            self.fortify.push('\n');
+        }
+    }
+
+    pub(crate) fn include(&mut self, headers: &[&str]) {
+        for header in headers {
+            writeln!(self.out, "#include <{}>", header).unwrap();
+        }
+        self.out.push('\n');
+    }
+
+    pub(crate) fn define<T: Int>(&mut self, name: &str, value: T) {
+        writeln!(self.out, "#define {} {}", name, literal(value)).unwrap();
+    }
+
+    /// Declare `name` as an integer typedef with the same size and
+    /// signedness as `T`.
+    pub(crate) fn typedef<T: Int>(&mut self, name: &str) {
+        writeln!(
+            self.out,
+            "typedef {} {};",
+            c_int_type(T::SIGNED, T::SIZE),
+            name
+        )
+        .unwrap();
+    }
+
+    /// Declare `name` as an opaque type with the same size and alignment as
+    /// `T`.
+    pub(crate) fn opaque<T>(&mut self, name: &str) {
+        writeln!(
+            self.out,
+            "typedef struct {{ _Alignas({}) unsigned char __opaque[{}]; }} {};",
+            align_of::<T>(),
+            size_of::<T>(),
+            name
+        )
+        .unwrap();
+    }
+
+    /// Define `name` as an initializer for an opaque type, with the bytes of
+    /// `value`.
+    pub(crate) fn initializer<T>(&mut self, name: &str, value: &T) {
+        // SAFETY: `value` is a valid `T`, and we only read its bytes.
+        let bytes =
+            unsafe { std::slice::from_raw_parts((value as *const T).cast::<u8>(), size_of::<T>()) };
+        if bytes.iter().all(|byte| *byte == 0) {
+            writeln!(self.out, "#define {} {{ {{ 0 }} }}", name).unwrap();
+        } else {
+            let bytes = bytes.iter().map(u8::to_string).collect::<Vec<_>>();
+            writeln!(
+                self.out,
+                "#define {} {{ {{ {} }} }}",
+                name,
+                bytes.join(", ")
+            )
+            .unwrap();
+        }
+    }
+
+    /// Define `struct name` with the layout of `T`.
+    pub(crate) fn strukt<T>(&mut self, name: &str, mut fields: Vec<Field>) {
+        fields.sort_by_key(|field| field.offset);
+
+        writeln!(self.out, "struct {} {{", name).unwrap();
+        let mut end = 0;
+        let mut pads = 0;
+        let mut pad = |out: &mut String, len: usize| {
+            writeln!(out, "    unsigned char __pad{}[{}];", pads, len).unwrap();
+            pads += 1;
+        };
+        for field in &fields {
+            assert!(field.offset >= end, "overlapping fields in struct {}", name);
+            if field.offset > end {
+                pad(&mut self.out, field.offset - end);
+            }
+            match field.cty.strip_suffix("[]") {
+                Some(elem) => {
+                    writeln!(self.out, "    {} {}[{}];", elem, field.name, field.size).unwrap()
+                }
+                None => writeln!(self.out, "    {} {};", field.cty, field.name).unwrap(),
+            }
+            end = field.offset + field.size;
+        }
+        if end < size_of::<T>() {
+            pad(&mut self.out, size_of::<T>() - end);
+        }
+        writeln!(self.out, "}};").unwrap();
+
+        writeln!(
+            self.out,
+            "_Static_assert(sizeof(struct {}) == {}, \"struct {} size\");",
+            name,
+            size_of::<T>(),
+            name
+        )
+        .unwrap();
+        writeln!(
+            self.out,
+            "_Static_assert(_Alignof(struct {}) == {}, \"struct {} alignment\");",
+            name,
+            align_of::<T>(),
+            name
+        )
+        .unwrap();
+        for field in &fields {
+            writeln!(
+                self.out,
+                "_Static_assert(__builtin_offsetof(struct {}, {}) == {}, \"struct {} field {}\");",
+                name, field.name, field.offset, name, field.name
+            )
+            .unwrap();
+        }
+        self.out.push('\n');
+    }
+
+    fn finish(mut self) -> String {
+        if !self.fortify.is_empty() {
+            self.out.push_str(
+                "#if defined(_FORTIFY_SOURCE) && _FORTIFY_SOURCE > 0 && defined(__OPTIMIZE__)\n",
+            );
+            self.out.push_str("#include <bits/fortify.h>\n\n");
+            self.out.push_str(&self.fortify);
+            self.out.push_str("#endif\n\n");
+        }
+        self.out.push_str("#endif\n");
+        self.out
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let sysroot = args.next();
+    let libcs = args.collect::<Vec<_>>();
+    let (Some(sysroot), false) = (sysroot, libcs.is_empty()) else {
+        eprintln!("usage: mustang-headers <sysroot> <rlib>...");
+        exit(2);
+    };
+
+    let libc = match Libc::load(&libcs) {
+        Ok(libc) => libc,
+        Err(err) => {
+            eprintln!("mustang-headers: {}", err);
+            exit(1);
+        }
+    };
+
+    let include = Path::new(&sysroot).join(TARGET.0).join("include");
+    for header in headers::all(&libc) {
+        let path = include.join(header.path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        if let Err(err) = std::fs::write(&path, header.finish()) {
+            eprintln!("mustang-headers: {}: {}", path.display(), err);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Per-architecture syscall-site recognition.
+//!
+//! Each architecture has a small decoder which splits the code into
+//! instructions, starting at the section start and at each function symbol,
+//! and classifies each instruction by what it does to the register that
+//! holds the syscall number. Then, for each syscall instruction, we walk
+//! backward through the straight-line code before it, looking for the
+//! instruction that loads that register with a constant.
+//!
+//! The walk gives up, leaving the site unresolved, if it reaches an
+//! instruction which may write the register in some other way, a call, a
+//! branch target, or the end of a block, since then the number may come from
+//! somewhere else. Decoders classify any instruction they don't fully
+//! understand as possibly writing the register, so that an unresolved site
+//! is reported rather than a wrong number.
+//!
+//! That's what rustix's and c-scape's syscall sequences look like once
+//! they're inlined, which happens in optimized builds. Sites where the
+//! number isn't a nearby constant, such as in a function that takes the
+//! syscall number as an argument, are reported as unresolved.
+
+mod aarch64;
+mod arm;
+mod riscv64;
+mod x86_64;
+
+#[cfg(test)]
+mod tests;
+
+use object::Architecture;
+use std::collections::BTreeSet;
+
+/// How many instructions back to look for the instruction that sets the
+/// syscall number.
+const WINDOW: usize = 16;
+
+/// An architecture supported by the mustang target specs in
+/// `target-specs/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Arch {
+    X86_64,
+    Aarch64,
+    Riscv64,
+    Arm,
+}
+
+/// What an instruction does to the syscall number register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Effect {
+    /// Nothing.
+    None,
+    /// It's a syscall instruction.
+    Syscall,
+    /// It sets the register to a constant.
+    SetNr(u32),
+    /// It may write the register some other way, or it's a call, which may
+    /// clobber it.
+    Clobber,
+    /// It pushes a constant on the stack. On x86-64, `push imm; pop rax`
+    /// sets the number in fewer bytes than a `mov`.
+    Push(u32),
+    /// It pops the register from the stack.
+    PopNr,
+}
+
+/// A decoded instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Insn {
+    /// The offset of the instruction in the code.
+    pub(crate) offset: usize,
+    /// The length of the instruction, in bytes.
+    pub(crate) len: usize,
+    pub(crate) effect: Effect,
+    /// Whether execution can continue with the next instruction.
+    pub(crate) falls_through: bool,
+    /// The offset in the code of a direct branch's or call's target.
+    pub(crate) target: Option<usize>,
+}
+
+impl Insn {
+    /// An instruction which falls through, with no branch target.
+    fn new(offset: usize, len: usize, effect: Effect) -> Self {
+        Self {
+            offset,
+            len,
+            effect,
+            falls_through: true,
+            target: None,
+        }
+    }
+
+    /// Bytes which don't decode as an instruction. Execution can't continue
+    /// through them, and we assume the worst about their effect.
+    fn invalid(offset: usize, len: usize) -> Self {
+        Self {
+            offset,
+            len,
+            effect: Effect::Clobber,
+            falls_through: false,
+            target: None,
+        }
+    }
+
+    /// An unconditional branch to `target`, or an indirect branch or return
+    /// if it's `None`.
+    fn jump(offset: usize, len: usize, target: Option<usize>) -> Self {
+        Self {
+            offset,
+            len,
+            effect: Effect::None,
+            falls_through: false,
+            target,
+        }
+    }
+
+    /// A conditional branch to `target`.
+    fn branch(offset: usize, len: usize, target: Option<usize>) -> Self {
+        Self {
+            offset,
+            len,
+            effect: Effect::None,
+            falls_through: true,
+            target,
+        }
+    }
+
+    /// A call to `target`, or an indirect call if it's `None`.
+    fn call(offset: usize, len: usize, target: Option<usize>) -> Self {
+        Self {
+            offset,
+            len,
+            effect: Effect::Clobber,
+            falls_through: true,
+            target,
+        }
+    }
+}
+
+/// Return the offset of a branch target `disp` bytes from `base`, if it's
+/// within `code`.
+fn target(code: &[u8], base: usize, disp: i64) -> Option<usize> {
+    let target = usize::try_from(i64::try_from(base).ok()?.checked_add(disp)?).ok()?;
+    (target < code.len()).then_some(target)
+}
+
+impl Arch {
+    /// Return the `Arch` for `arch`, or an error explaining why it isn't
+    /// supported.
+    pub(crate) fn from_object(arch: Architecture) -> Result<Self, String> {
+        match arch {
+            Architecture::X86_64 => Ok(Self::X86_64),
+            Architecture::Aarch64 => Ok(Self::Aarch64),
+            Architecture::Riscv64 => Ok(Self::Riscv64),
+            Architecture::Arm => Ok(Self::Arm),
+            // rustix makes syscalls on x86 with indirect calls through the
+            // vDSO's `__kernel_vsyscall`, which can't be found without
+            // following data flow.
+            Architecture::I386 => Err("i686 isn't supported: rustix makes syscalls there \
+                                       through indirect calls to the vDSO's \
+                                       `__kernel_vsyscall`, which can't be located \
+                                       statically"
+                .to_owned()),
+            arch => Err(format!("unsupported architecture {:?}", arch)),
+        }
+    }
+
+    /// The name of the corresponding target spec.
+    pub(crate) fn target(self) -> &'static str {
+        match self {
+            Self::X86_64 => "x86_64-mustang-linux-gnu",
+            Self::Aarch64 => "aarch64-mustang-linux-gnu",
+            Self::Riscv64 => "riscv64gc-mustang-linux-gnu",
+            Self::Arm => "armv5te-mustang-linux-gnueabi",
+        }
+    }
+
+    /// The `AUDIT_ARCH_*` value seccomp reports for this architecture.
+    pub(crate) fn audit_arch(self) -> u32 {
+        match self {
+            Self::X86_64 => 0xc000_003e,
+            Self::Aarch64 => 0xc000_00b7,
+            Self::Riscv64 => 0xc000_00f3,
+            Self::Arm => 0x4000_0028,
+        }
+    }
+
+    /// Syscalls which don't appear in the binary but which it can make
+    /// anyway: the vDSO's fallbacks for when the clocksource can't be read
+    /// from userspace, and signal returns, which may go through a
+    /// kernel-provided trampoline.
+    pub(crate) fn implicit_syscalls(self) -> &'static [u32] {
+        match self {
+            // clock_gettime, clock_getres, gettimeofday, time, getcpu,
+            // rt_sigreturn
+            Self::X86_64 => &[228, 229, 96, 201, 309, 15],
+            // clock_gettime, clock_getres, gettimeofday, rt_sigreturn
+            Self::Aarch64 => &[113, 114, 169, 139],
+            // clock_gettime, clock_getres, gettimeofday, getcpu,
+            // riscv_hwprobe, rt_sigreturn
+            Self::Riscv64 => &[113, 114, 169, 168, 258, 139],
+            // clock_gettime64, clock_getres_time64, clock_gettime,
+            // clock_getres, gettimeofday, sigreturn, rt_sigreturn
+            Self::Arm => &[403, 406, 263, 264, 78, 119, 173],
+        }
+    }
+
+    /// Decode the instruction at `offset` in `code`.
+    fn decode(self, code: &[u8], offset: usize) -> Insn {
+        match self {
+            Self::X86_64 => x86_64::decode(code, offset),
+            Self::Aarch64 => aarch64::decode(code, offset),
+            Self::Riscv64 => riscv64::decode(code, offset),
+            Self::Arm => arm::decode(code, offset),
+        }
+    }
+
+    /// Decode all of `code`, starting at the beginning and at each offset in
+    /// `starts`, which are the offsets of functions in it.
+    pub(crate) fn decode_all(self, code: &[u8], starts: &BTreeSet<usize>) -> Vec<Insn> {
+        let mut insns = Vec::new();
+        let mut offset = 0;
+        while offset < code.len() {
+            let mut insn = self.decode(code, offset);
+            // If an instruction runs into the start of a function, we're
+            // out of sync; truncate it and start again from the function.
+            if let Some(&start) = starts.range(offset + 1..offset + insn.len).next() {
+                insn = Insn::invalid(offset, start - offset);
+            }
+            offset += insn.len;
+            insns.push(insn);
+        }
+        insns
+    }
+
+    /// Find the syscall sites in `code`, and the syscall numbers they use,
+    /// where they can be determined. `starts` holds the offsets of the
+    /// functions in `code`.
+    ///
+    /// Returns offsets into `code`.
+    pub(crate) fn scan(self, code: &[u8], starts: &BTreeSet<usize>) -> Vec<(usize, Option<u32>)> {
+        let insns = self.decode_all(code, starts);
+
+        // Anything that's branched to, or that's the start of a function,
+        // can be reached from somewhere other than the instruction before
+        // it.
+        let mut targets = starts.clone();
+        targets.extend(insns.iter().filter_map(|insn| insn.target));
+
+        insns
+            .iter()
+            .enumerate()
+            .filter(|(_, insn)| insn.effect == Effect::Syscall)
+            .map(|(i, insn)| {
+                let nr = resolve(&insns[..i], insn.offset, &targets)
+                    .filter(|&nr| self.is_syscall_number(nr));
+                (insn.offset, nr)
+            })
+            .collect()
+    }
+
+    fn is_syscall_number(self, nr: u32) -> bool {
+        match self {
+            // Exclude the x32 ABI's numbers, which have bit 30 set.
+            Self::X86_64 | Self::Aarch64 | Self::Riscv64 => nr < 1024,
+            // ARM has private syscalls, such as `set_tls`, at 0xf0000.
+            Self::Arm => nr < 1024 || (0xf_0000..0xf_0800).contains(&nr),
+        }
+    }
+}
+
+/// Find the constant the syscall number register holds at `site`, which
+/// follows the instructions `before`.
+fn resolve(before: &[Insn], site: usize, targets: &BTreeSet<usize>) -> Option<u32> {
+    let mut next = site;
+    for (i, insn) in before.iter().enumerate().rev().take(WINDOW) {
+        // If the next instruction can be reached some other way, or this one
+        // doesn't lead to it, the number may come from somewhere else.
+        if targets.contains(&next) || !insn.falls_through {
+            return None;
+        }
+        match insn.effect {
+            Effect::None | Effect::Push(_) => {}
+            Effect::SetNr(nr) => return Some(nr),
+            Effect::Syscall | Effect::Clobber => return None,
+            Effect::PopNr => {
+                return match i.checked_sub(1).map(|i| &before[i]) {
+                    Some(prev) if prev.falls_through && !targets.contains(&insn.offset) => {
+                        match prev.effect {
+                            Effect::Push(nr) => Some(nr),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+            }
+        }
+        next = insn.offset;
+    }
+    None
+}
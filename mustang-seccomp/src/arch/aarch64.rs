@@ -0,0 +1,101 @@
+//! An AArch64 instruction decoder, which knows the branches, and which
+//! instructions may write `x8`, the syscall number register.
+//!
+//! Most instructions that write a general-purpose register put it in bits
+//! 0-4, so any instruction with 8 there that we don't otherwise recognize is
+//! assumed to write `x8`.
+
+use super::{target, Effect, Insn};
+
+/// The syscall number register.
+const X8: u32 = 8;
+
+/// Decode the instruction at `offset` in `code`.
+pub(super) fn decode(code: &[u8], offset: usize) -> Insn {
+    let Some(bytes) = code.get(offset..offset + 4) else {
+        return Insn::invalid(offset, code.len() - offset);
+    };
+    let insn = u32::from_le_bytes(bytes.try_into().unwrap());
+    let disp = |bits: u32, shift: u32| {
+        let field = (insn >> shift) & ((1 << bits) - 1);
+        i64::from(((field << (32 - bits)) as i32) >> (32 - bits)) * 4
+    };
+
+    match insn {
+        // `svc`; Linux ignores the immediate.
+        _ if insn & 0xffe0_001f == 0xd400_0001 => Insn::new(offset, 4, Effect::Syscall),
+        // `movz w8, #imm` and `movz x8, #imm`, without a shift.
+        _ if insn & 0x7fe0_001f == 0x5280_0008 => {
+            Insn::new(offset, 4, Effect::SetNr((insn >> 5) & 0xffff))
+        }
+        // `b`
+        _ if insn & 0xfc00_0000 == 0x1400_0000 => {
+            Insn::jump(offset, 4, target(code, offset, disp(26, 0)))
+        }
+        // `bl`
+        _ if insn & 0xfc00_0000 == 0x9400_0000 => {
+            Insn::call(offset, 4, target(code, offset, disp(26, 0)))
+        }
+        // `b.cond`
+        _ if insn & 0xff00_0010 == 0x5400_0000 => {
+            Insn::branch(offset, 4, target(code, offset, disp(19, 5)))
+        }
+        // `cbz` and `cbnz`
+        _ if insn & 0x7e00_0000 == 0x3400_0000 => {
+            Insn::branch(offset, 4, target(code, offset, disp(19, 5)))
+        }
+        // `tbz` and `tbnz`
+        _ if insn & 0x7e00_0000 == 0x3600_0000 => {
+            Insn::branch(offset, 4, target(code, offset, disp(14, 5)))
+        }
+        // `br`, `ret`, `eret`, and their authenticated forms.
+        _ if insn & 0xfe9f_0000 == 0xd61f_0000 => Insn::jump(offset, 4, None),
+        // `blr` and its authenticated forms.
+        _ if insn & 0xfeff_0000 == 0xd63f_0000 => Insn::call(offset, 4, None),
+        // `udf` and `brk`
+        _ if insn & 0xffff_0000 == 0 || insn & 0xffe0_001f == 0xd420_0000 => {
+            Insn::jump(offset, 4, None)
+        }
+        // System instructions, other than `mrs`, don't write registers.
+        _ if insn & 0xffe0_0000 == 0xd500_0000 => Insn::new(offset, 4, Effect::None),
+        // Loads and stores.
+        _ if insn & 0x0a00_0000 == 0x0800_0000 => Insn::new(offset, 4, load_store(insn)),
+        _ if insn & 0x1f == X8 => Insn::new(offset, 4, Effect::Clobber),
+        _ => Insn::new(offset, 4, Effect::None),
+    }
+}
+
+/// Return the effect of `insn`, a load or store, on `x8`.
+fn load_store(insn: u32) -> Effect {
+    let rt = insn & 0x1f;
+    let rt2 = (insn >> 10) & 0x1f;
+    let rn = (insn >> 5) & 0x1f;
+    let simd = insn & 0x0400_0000 != 0;
+    let (loads, writes_back) = match (insn >> 27) & 7 {
+        // Single registers, and atomic memory operations.
+        0b111 => {
+            let atomic = insn & 0x0120_0c00 == 0x0020_0000;
+            let loads = atomic || (insn >> 22) & 3 != 0;
+            let writes_back = insn & 0x0120_0400 == 0x0000_0400;
+            (loads, writes_back)
+        }
+        // Pairs; the second register is in bits 10-14.
+        0b101 => {
+            let loads = insn & 0x0040_0000 != 0;
+            if loads && !simd && rt2 == X8 {
+                return Effect::Clobber;
+            }
+            (loads, insn & 0x0080_0000 != 0)
+        }
+        // Literal loads.
+        0b011 => (true, false),
+        // Exclusive and ordered accesses, which may write a status register
+        // in bits 16-20, and everything else.
+        _ => return Effect::Clobber,
+    };
+    if (loads && !simd && rt == X8) || (writes_back && rn == X8) {
+        Effect::Clobber
+    } else {
+        Effect::None
+    }
+}
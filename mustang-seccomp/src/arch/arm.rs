@@ -0,0 +1,82 @@
+//! An A32 instruction decoder, which knows the branches, and which
+//! instructions may write `r7`, the syscall number register.
+//!
+//! Most instructions name the registers they write in bits 12-15 or 16-19,
+//! so any instruction with 7 in either field, or with `r7` in the register
+//! list of a load-multiple, is assumed to write `r7`.
+
+use super::{target, Effect, Insn};
+
+/// The syscall number register.
+const R7: u32 = 7;
+
+/// The program counter.
+const PC: u32 = 15;
+
+/// The "always" condition.
+const AL: u32 = 0xe;
+
+/// Decode the instruction at `offset` in `code`.
+pub(super) fn decode(code: &[u8], offset: usize) -> Insn {
+    let Some(bytes) = code.get(offset..offset + 4) else {
+        return Insn::invalid(offset, code.len() - offset);
+    };
+    let insn = u32::from_le_bytes(bytes.try_into().unwrap());
+    let cond = insn >> 28;
+    let rd = (insn >> 12) & 0xf;
+    let rn = (insn >> 16) & 0xf;
+
+    // An instruction which writes `pc` is an unconditional branch if it
+    // always executes, and a conditional one otherwise.
+    let writes_pc = |offset, target| {
+        if cond == AL {
+            Insn::jump(offset, 4, target)
+        } else {
+            Insn::branch(offset, 4, target)
+        }
+    };
+
+    match insn {
+        // `svc`; Linux's EABI ignores the immediate.
+        _ if insn & 0x0f00_0000 == 0x0f00_0000 && cond != 0xf => {
+            Insn::new(offset, 4, Effect::Syscall)
+        }
+        // `mov r7, #imm`
+        _ if insn & 0xffff_f000 == 0xe3a0_7000 => {
+            let rotate = (insn >> 8) & 0xf;
+            let nr = (insn & 0xff).rotate_right(rotate * 2);
+            Insn::new(offset, 4, Effect::SetNr(nr))
+        }
+        // `movw r7, #imm`
+        _ if insn & 0xfff0_f000 == 0xe300_7000 => {
+            let nr = ((insn >> 4) & 0xf000) | (insn & 0xfff);
+            Insn::new(offset, 4, Effect::SetNr(nr))
+        }
+        // `udf`
+        _ if insn & 0xfff0_00f0 == 0xe7f0_00f0 => Insn::invalid(offset, 4),
+        // `b`, `bl`, and `blx` with an immediate.
+        _ if insn & 0x0e00_0000 == 0x0a00_0000 => {
+            let disp = i64::from(((insn << 8) as i32) >> 6) + 8;
+            let target = target(code, offset, disp);
+            if cond == 0xf || insn & 0x0100_0000 != 0 {
+                Insn::call(offset, 4, target)
+            } else {
+                writes_pc(offset, target)
+            }
+        }
+        // `bx` and `blx` with a register.
+        _ if insn & 0x0fff_fff0 == 0x012f_ff10 => writes_pc(offset, None),
+        _ if insn & 0x0fff_fff0 == 0x012f_ff30 => Insn::call(offset, 4, None),
+        // Loads to `pc`, including `pop {..., pc}`.
+        _ if insn & 0x0c10_0000 == 0x0410_0000 && rd == PC => writes_pc(offset, None),
+        _ if insn & 0x0e10_8000 == 0x0810_8000 => writes_pc(offset, None),
+        // Data processing with `pc` as the destination.
+        _ if insn & 0x0c00_0000 == 0 && rd == PC => writes_pc(offset, None),
+        // Load-multiples with `r7` in the list.
+        _ if insn & 0x0e10_0000 == 0x0810_0000 && insn & (1 << R7) != 0 => {
+            Insn::new(offset, 4, Effect::Clobber)
+        }
+        _ if rd == R7 || rn == R7 => Insn::new(offset, 4, Effect::Clobber),
+        _ => Insn::new(offset, 4, Effect::None),
+    }
+}
@@ -0,0 +1,142 @@
+//! A RISC-V instruction decoder, for RV64GC, which knows the branches, and
+//! which instructions may write `a7`, the syscall number register.
+
+use super::{target, Effect, Insn};
+
+/// The syscall number register, `a7`.
+const A7: u32 = 17;
+
+/// Sign-extend the low `bits` bits of `value`.
+fn sext(value: u32, bits: u32) -> i64 {
+    i64::from(((value << (32 - bits)) as i32) >> (32 - bits))
+}
+
+/// Extract bits `lo..=hi` of `value`, and place them at `at`.
+fn bits(value: u32, hi: u32, lo: u32, at: u32) -> u32 {
+    ((value >> lo) & ((1 << (hi - lo + 1)) - 1)) << at
+}
+
+/// Decode the instruction at `offset` in `code`.
+pub(super) fn decode(code: &[u8], offset: usize) -> Insn {
+    let Some(half) = code.get(offset..offset + 2) else {
+        return Insn::invalid(offset, code.len() - offset);
+    };
+    let half = u16::from_le_bytes(half.try_into().unwrap());
+    match half & 3 {
+        3 if half & 0x1c == 0x1c => Insn::invalid(offset, 2),
+        3 => match code.get(offset..offset + 4) {
+            Some(word) => decode32(code, offset, u32::from_le_bytes(word.try_into().unwrap())),
+            None => Insn::invalid(offset, code.len() - offset),
+        },
+        _ => decode16(code, offset, u32::from(half)),
+    }
+}
+
+/// Decode a 32-bit instruction.
+fn decode32(code: &[u8], offset: usize, word: u32) -> Insn {
+    let rd = (word >> 7) & 0x1f;
+    match word & 0x7f {
+        // `ecall`
+        0x73 if word == 0x0000_0073 => Insn::new(offset, 4, Effect::Syscall),
+        // `ebreak`
+        0x73 if word == 0x0010_0073 => Insn::jump(offset, 4, None),
+        // `li a7, imm`, which is `addi a7, zero, imm`.
+        0x13 if word & 0xf_ffff == (A7 << 7) | 0x13 => {
+            Insn::new(offset, 4, Effect::SetNr(((word as i32) >> 20) as u32))
+        }
+        // `jal`
+        0x6f => {
+            let disp = sext(
+                bits(word, 31, 31, 20)
+                    | bits(word, 30, 21, 1)
+                    | bits(word, 20, 20, 11)
+                    | bits(word, 19, 12, 12),
+                21,
+            );
+            let target = target(code, offset, disp);
+            if rd == 0 {
+                Insn::jump(offset, 4, target)
+            } else {
+                Insn::call(offset, 4, target)
+            }
+        }
+        // `jalr`
+        0x67 if rd == 0 => Insn::jump(offset, 4, None),
+        0x67 => Insn::call(offset, 4, None),
+        // Conditional branches.
+        0x63 => {
+            let disp = sext(
+                bits(word, 31, 31, 12)
+                    | bits(word, 30, 25, 5)
+                    | bits(word, 11, 8, 1)
+                    | bits(word, 7, 7, 11),
+                13,
+            );
+            Insn::branch(offset, 4, target(code, offset, disp))
+        }
+        // Stores, and instructions that write floating-point registers,
+        // have something other than a destination register in bits 7-11.
+        0x23 | 0x27 | 0x07 | 0x43 | 0x47 | 0x4b | 0x4f | 0x0f => Insn::new(offset, 4, Effect::None),
+        _ if rd == A7 => Insn::new(offset, 4, Effect::Clobber),
+        _ => Insn::new(offset, 4, Effect::None),
+    }
+}
+
+/// Decode a compressed instruction. Only the forms with a full 5-bit
+/// register field can name `a7`.
+fn decode16(code: &[u8], offset: usize, half: u32) -> Insn {
+    let rd = (half >> 7) & 0x1f;
+    let rs2 = (half >> 2) & 0x1f;
+    let funct3 = half >> 13;
+    match (half & 3, funct3) {
+        // `unimp`
+        _ if half == 0 => Insn::invalid(offset, 2),
+        // `c.li a7, imm`
+        (1, 2) if rd == A7 => {
+            let imm = sext(bits(half, 12, 12, 5) | bits(half, 6, 2, 0), 6);
+            Insn::new(offset, 2, Effect::SetNr(imm as u32))
+        }
+        // `c.addi`, `c.addiw`, and `c.lui`
+        (1, 0 | 1 | 3) if rd == A7 => Insn::new(offset, 2, Effect::Clobber),
+        // `c.j`
+        (1, 5) => {
+            let disp = sext(
+                bits(half, 12, 12, 11)
+                    | bits(half, 11, 11, 4)
+                    | bits(half, 10, 9, 8)
+                    | bits(half, 8, 8, 10)
+                    | bits(half, 7, 7, 6)
+                    | bits(half, 6, 6, 7)
+                    | bits(half, 5, 3, 1)
+                    | bits(half, 2, 2, 5),
+                12,
+            );
+            Insn::jump(offset, 2, target(code, offset, disp))
+        }
+        // `c.beqz` and `c.bnez`
+        (1, 6 | 7) => {
+            let disp = sext(
+                bits(half, 12, 12, 8)
+                    | bits(half, 11, 10, 3)
+                    | bits(half, 6, 5, 6)
+                    | bits(half, 4, 3, 1)
+                    | bits(half, 2, 2, 5),
+                9,
+            );
+            Insn::branch(offset, 2, target(code, offset, disp))
+        }
+        // `c.slli`, `c.lwsp`, and `c.ldsp`
+        (2, 0 | 2 | 3) if rd == A7 => Insn::new(offset, 2, Effect::Clobber),
+        // `c.jr`, `c.jalr`, and `c.ebreak`
+        (2, 4) if rs2 == 0 => {
+            if half & 0x1000 == 0 || rd == 0 {
+                Insn::jump(offset, 2, None)
+            } else {
+                Insn::call(offset, 2, None)
+            }
+        }
+        // `c.mv` and `c.add`
+        (2, 4) if rd == A7 => Insn::new(offset, 2, Effect::Clobber),
+        _ => Insn::new(offset, 2, Effect::None),
+    }
+}
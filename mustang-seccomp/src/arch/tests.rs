@@ -0,0 +1,268 @@
+//! Tests over hand-assembled instruction sequences.
+
+use super::{Arch, Effect, Insn};
+use std::collections::BTreeSet;
+
+/// Scan `code` as a single function.
+fn scan(arch: Arch, code: &[u8]) -> Vec<(usize, Option<u32>)> {
+    arch.scan(code, &BTreeSet::from([0]))
+}
+
+/// Encode 32-bit instruction words.
+fn words(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+/// Encode 16-bit and 32-bit RISC-V instructions, in order.
+fn riscv(insns: &[u32]) -> Vec<u8> {
+    insns
+        .iter()
+        .flat_map(|&insn| {
+            if insn & 3 == 3 {
+                insn.to_le_bytes().to_vec()
+            } else {
+                (insn as u16).to_le_bytes().to_vec()
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn x86_64_mov() {
+    // mov eax, 39; syscall
+    let code = [0xb8, 39, 0, 0, 0, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(5, Some(39))]);
+
+    // mov rax, 39; syscall
+    let code = [0x48, 0xc7, 0xc0, 39, 0, 0, 0, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(7, Some(39))]);
+
+    // movabs rax, 39; syscall
+    let code = [0x48, 0xb8, 39, 0, 0, 0, 0, 0, 0, 0, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(10, Some(39))]);
+
+    // xor eax, eax; syscall
+    let code = [0x31, 0xc0, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(2, Some(0))]);
+
+    // push 39; pop rax; syscall
+    let code = [0x6a, 39, 0x58, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(3, Some(39))]);
+
+    // mov r8d, 39; syscall
+    let code = [0x41, 0xb8, 39, 0, 0, 0, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(6, None)]);
+}
+
+#[test]
+fn x86_64_lengths() {
+    // mov eax, 39; mov [rsp + 8], rcx; lea rdi, [rip + 0x100];
+    // vzeroupper; mov r10d, 0x1234; nop dword [rax + rax]; syscall
+    let code = [
+        0xb8, 39, 0, 0, 0, 0x48, 0x89, 0x4c, 0x24, 0x08, 0x48, 0x8d, 0x3d, 0, 1, 0, 0, 0xc5, 0xf8,
+        0x77, 0x41, 0xba, 0x34, 0x12, 0, 0, 0x0f, 0x1f, 0x44, 0, 0, 0x0f, 0x05,
+    ];
+    assert_eq!(scan(Arch::X86_64, &code), [(31, Some(39))]);
+}
+
+#[test]
+fn x86_64_writes() {
+    // mov eax, 39; mov eax, ecx; syscall
+    let code = [0xb8, 39, 0, 0, 0, 0x89, 0xc8, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(7, None)]);
+
+    // mov eax, 39; mov al, 1; syscall
+    let code = [0xb8, 39, 0, 0, 0, 0xb0, 1, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(7, None)]);
+
+    // mov eax, 39; lea rax, [rip]; syscall
+    let code = [0xb8, 39, 0, 0, 0, 0x48, 0x8d, 0x05, 0, 0, 0, 0, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(12, None)]);
+
+    // mov eax, 39; cpuid; syscall
+    let code = [0xb8, 39, 0, 0, 0, 0x0f, 0xa2, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(7, None)]);
+
+    // mov eax, 39; vmovd eax, xmm0; syscall
+    let code = [0xb8, 39, 0, 0, 0, 0xc5, 0xf9, 0x7e, 0xc0, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(9, None)]);
+
+    // mov eax, 39; call 0; syscall
+    let code = [0xb8, 39, 0, 0, 0, 0xe8, 0xf6, 0xff, 0xff, 0xff, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(10, None)]);
+
+    // mov eax, 39; syscall; syscall
+    let code = [0xb8, 39, 0, 0, 0, 0x0f, 0x05, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(5, Some(39)), (7, None)]);
+}
+
+#[test]
+fn x86_64_control_flow() {
+    // nop; nop; mov eax, 39; nop; nop; syscall
+    let code = [0x90, 0x90, 0xb8, 39, 0, 0, 0, 0x90, 0x90, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(9, Some(39))]);
+
+    // je 8; mov eax, 39; nop; nop; syscall
+    let code = [0x74, 0x06, 0xb8, 39, 0, 0, 0, 0x90, 0x90, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(9, None)]);
+
+    // je 9; mov eax, 39; nop; nop; syscall
+    let code = [0x74, 0x07, 0xb8, 39, 0, 0, 0, 0x90, 0x90, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(9, None)]);
+
+    // mov eax, 39; ret; syscall
+    let code = [0xb8, 39, 0, 0, 0, 0xc3, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(6, None)]);
+
+    // A function starting between the `mov` and the `syscall`.
+    let code = [0xb8, 39, 0, 0, 0, 0x90, 0x0f, 0x05];
+    assert_eq!(
+        Arch::X86_64.scan(&code, &BTreeSet::from([0, 5])),
+        [(6, None)]
+    );
+}
+
+#[test]
+fn x86_64_resync() {
+    // Bytes which decode as a `mov eax, imm32` running into a function
+    // starting at 3, which is `mov eax, 39; syscall`.
+    let code = [0x90, 0x90, 0xb8, 0xb8, 39, 0, 0, 0, 0x0f, 0x05];
+    let starts = BTreeSet::from([0, 3]);
+    let insns = Arch::X86_64.decode_all(&code, &starts);
+    assert_eq!(insns[2], Insn::invalid(2, 1));
+    assert_eq!(insns[3].effect, Effect::SetNr(39));
+    assert_eq!(Arch::X86_64.scan(&code, &starts), [(8, Some(39))]);
+}
+
+#[test]
+fn x86_64_not_a_syscall_number() {
+    // mov eax, 0x40000027 (an x32 syscall number); syscall
+    let code = [0xb8, 39, 0, 0, 0x40, 0x0f, 0x05];
+    assert_eq!(scan(Arch::X86_64, &code), [(5, None)]);
+}
+
+#[test]
+fn aarch64() {
+    const MOVZ_W8_93: u32 = 0x5280_0ba8;
+    const SVC: u32 = 0xd400_0001;
+    const NOP: u32 = 0xd503_201f;
+
+    // mov w8, #93; nop; svc #0
+    let code = words(&[MOVZ_W8_93, NOP, SVC]);
+    assert_eq!(scan(Arch::Aarch64, &code), [(8, Some(93))]);
+
+    // mov w8, #93; str x8, [sp]; ldr x0, [sp, #8]; svc #0
+    let code = words(&[MOVZ_W8_93, 0xf900_03e8, 0xf940_07e0, SVC]);
+    assert_eq!(scan(Arch::Aarch64, &code), [(12, Some(93))]);
+
+    // mov w8, #93; mov x8, x0; svc #0
+    let code = words(&[MOVZ_W8_93, 0xaa00_03e8, SVC]);
+    assert_eq!(scan(Arch::Aarch64, &code), [(8, None)]);
+
+    // mov w8, #93; ldr x8, [sp]; svc #0
+    let code = words(&[MOVZ_W8_93, 0xf940_03e8, SVC]);
+    assert_eq!(scan(Arch::Aarch64, &code), [(8, None)]);
+
+    // mov w8, #93; ldp x0, x8, [sp]; svc #0
+    let code = words(&[MOVZ_W8_93, 0xa940_23e0, SVC]);
+    assert_eq!(scan(Arch::Aarch64, &code), [(8, None)]);
+
+    // mov w8, #93; ldr x0, [x8], #8; svc #0
+    let code = words(&[MOVZ_W8_93, 0xf840_8500, SVC]);
+    assert_eq!(scan(Arch::Aarch64, &code), [(8, None)]);
+
+    // mov w8, #93; bl 0; svc #0
+    let code = words(&[MOVZ_W8_93, 0x97ff_ffff, SVC]);
+    assert_eq!(scan(Arch::Aarch64, &code), [(8, None)]);
+
+    // b.eq 12; mov w8, #93; nop; svc #0
+    let code = words(&[0x5400_0060, MOVZ_W8_93, NOP, SVC]);
+    assert_eq!(scan(Arch::Aarch64, &code), [(12, None)]);
+
+    // mov w8, #93; ret; svc #0
+    let code = words(&[MOVZ_W8_93, 0xd65f_03c0, SVC]);
+    assert_eq!(scan(Arch::Aarch64, &code), [(8, None)]);
+}
+
+#[test]
+fn riscv64() {
+    const LI_A7_64: u32 = 0x0400_0893;
+    const ECALL: u32 = 0x0000_0073;
+    const C_NOP: u32 = 0x0001;
+
+    // li a7, 64; c.nop; ecall
+    let code = riscv(&[LI_A7_64, C_NOP, ECALL]);
+    assert_eq!(scan(Arch::Riscv64, &code), [(6, Some(64))]);
+
+    // c.li a7, 5; ecall
+    let code = riscv(&[0x4895, ECALL]);
+    assert_eq!(scan(Arch::Riscv64, &code), [(2, Some(5))]);
+
+    // li a7, 64; sd a7, 0(sp); ecall
+    let code = riscv(&[LI_A7_64, 0x0111_3023, ECALL]);
+    assert_eq!(scan(Arch::Riscv64, &code), [(8, Some(64))]);
+
+    // li a7, 64; c.mv a7, a0; ecall
+    let code = riscv(&[LI_A7_64, 0x88aa, ECALL]);
+    assert_eq!(scan(Arch::Riscv64, &code), [(6, None)]);
+
+    // li a7, 64; ld a7, 0(sp); ecall
+    let code = riscv(&[LI_A7_64, 0x0001_3883, ECALL]);
+    assert_eq!(scan(Arch::Riscv64, &code), [(8, None)]);
+
+    // li a7, 64; call 0; ecall
+    let code = riscv(&[LI_A7_64, 0xffdf_f0ef, ECALL]);
+    assert_eq!(scan(Arch::Riscv64, &code), [(8, None)]);
+
+    // c.bnez a0, 6; li a7, 64; c.nop; ecall
+    let code = riscv(&[0xe119, LI_A7_64, C_NOP, ECALL]);
+    assert_eq!(scan(Arch::Riscv64, &code), [(8, None)]);
+
+    // li a7, 64; ret; ecall
+    let code = riscv(&[LI_A7_64, 0x8082, ECALL]);
+    assert_eq!(scan(Arch::Riscv64, &code), [(6, None)]);
+}
+
+#[test]
+fn arm() {
+    const MOV_R7_4: u32 = 0xe3a0_7004;
+    const SVC: u32 = 0xef00_0000;
+
+    // push {r7}; mov r7, #4; svc 0
+    let code = words(&[0xe52d_7004, MOV_R7_4, SVC]);
+    assert_eq!(scan(Arch::Arm, &code), [(8, Some(4))]);
+
+    // mov r7, #0xf0000; svc 0
+    let code = words(&[0xe3a0_780f, SVC]);
+    assert_eq!(scan(Arch::Arm, &code), [(4, Some(0xf_0000))]);
+
+    // movw r7, #403; svc 0
+    let code = words(&[0xe300_7193, SVC]);
+    assert_eq!(scan(Arch::Arm, &code), [(4, Some(403))]);
+
+    // mov r7, #4; ldr r7, [sp]; svc 0
+    let code = words(&[MOV_R7_4, 0xe59d_7000, SVC]);
+    assert_eq!(scan(Arch::Arm, &code), [(8, None)]);
+
+    // mov r7, #4; pop {r4, r7}; svc 0
+    let code = words(&[MOV_R7_4, 0xe8bd_0090, SVC]);
+    assert_eq!(scan(Arch::Arm, &code), [(8, None)]);
+
+    // mov r7, #4; bl 0; svc 0
+    let code = words(&[MOV_R7_4, 0xebff_fffd, SVC]);
+    assert_eq!(scan(Arch::Arm, &code), [(8, None)]);
+
+    // beq 12; mov r7, #4; mov r0, r0; svc 0
+    let code = words(&[0x0a00_0001, MOV_R7_4, 0xe1a0_0000, SVC]);
+    assert_eq!(scan(Arch::Arm, &code), [(12, None)]);
+
+    // mov r7, #4; bx lr; svc 0
+    let code = words(&[MOV_R7_4, 0xe12f_ff1e, SVC]);
+    assert_eq!(scan(Arch::Arm, &code), [(8, None)]);
+}
+
+#[test]
+fn i686_is_rejected() {
+    let err = Arch::from_object(object::Architecture::I386).unwrap_err();
+    assert!(err.contains("i686"), "{}", err);
+}
@@ -0,0 +1,792 @@
+//! An x86-64 instruction decoder, which knows the length of every
+//! instruction in the general-purpose, x87, MMX, SSE, AVX, and AVX-512
+//! encodings, and which instructions write `rax`.
+//!
+//! Writes to parts of `rax` count as writes to it. Instructions which write
+//! a general-purpose register we don't identify are assumed to write `rax`.
+
+use super::{target, Effect, Insn};
+
+/// The longest an x86 instruction can be.
+const MAX_LEN: usize = 15;
+
+/// The register number of `rax`.
+const RAX: u8 = 0;
+
+/// The register number which means `ah` in byte instructions without a REX
+/// prefix, and `rsp` otherwise.
+const AH_OR_RSP: u8 = 4;
+
+/// The size of an instruction's immediate operand.
+#[derive(Clone, Copy)]
+enum Imm {
+    /// 16 or 32 bits, depending on the operand size.
+    Z,
+    /// 16, 32, or 64 bits, depending on the operand size.
+    V,
+    /// An address, 32 or 64 bits depending on the address size.
+    Moffs,
+    /// `enter`'s 16-bit and 8-bit immediates.
+    Enter,
+}
+
+/// The prefixes of an instruction.
+#[derive(Default, Clone, Copy)]
+struct Prefixes {
+    /// 0x66
+    opsize: bool,
+    /// 0x67
+    addrsize: bool,
+    /// 0xf3
+    rep: bool,
+    /// 0xf2
+    repne: bool,
+    rex: u8,
+}
+
+impl Prefixes {
+    fn rex_w(self) -> bool {
+        self.rex & 8 != 0
+    }
+
+    fn rex_r(self) -> u8 {
+        (self.rex & 4) << 1
+    }
+
+    fn rex_b(self) -> u8 {
+        (self.rex & 1) << 3
+    }
+
+    fn imm_len(self, imm: Imm) -> usize {
+        match imm {
+            Imm::Z if self.opsize => 2,
+            Imm::Z => 4,
+            Imm::V if self.rex_w() => 8,
+            Imm::V if self.opsize => 2,
+            Imm::V => 4,
+            Imm::Moffs if self.addrsize => 4,
+            Imm::Moffs => 8,
+            Imm::Enter => 3,
+        }
+    }
+}
+
+/// A ModRM byte, with the REX extensions applied.
+#[derive(Clone, Copy)]
+struct ModRm {
+    mod_: u8,
+    reg: u8,
+    rm: u8,
+}
+
+/// Test whether writing register `reg` writes to `rax`. Without a REX
+/// prefix, register 4 may mean `ah`, so we count it too.
+fn is_rax(reg: u8, prefixes: Prefixes) -> bool {
+    reg == RAX || (reg == AH_OR_RSP && prefixes.rex == 0)
+}
+
+impl ModRm {
+    /// The effect of writing the register in the `reg` field.
+    fn writes_reg(self, prefixes: Prefixes) -> Effect {
+        if is_rax(self.reg, prefixes) {
+            Effect::Clobber
+        } else {
+            Effect::None
+        }
+    }
+
+    /// The effect of writing the `rm` operand, which is a register if `mod`
+    /// is 3, or memory otherwise.
+    fn writes_rm(self, prefixes: Prefixes) -> Effect {
+        if self.mod_ == 3 && is_rax(self.rm, prefixes) {
+            Effect::Clobber
+        } else {
+            Effect::None
+        }
+    }
+
+    /// The effect of writing both the `reg` and `rm` operands.
+    fn writes_both(self, prefixes: Prefixes) -> Effect {
+        match self.writes_reg(prefixes) {
+            Effect::None => self.writes_rm(prefixes),
+            effect => effect,
+        }
+    }
+
+    /// The effect of writing the `rm` operand if `mod` is 3, and of
+    /// something we don't model otherwise.
+    fn writes_rm_or_clobbers(self, prefixes: Prefixes) -> Effect {
+        if self.mod_ == 3 {
+            self.writes_rm(prefixes)
+        } else {
+            Effect::Clobber
+        }
+    }
+}
+
+/// Return the length of a ModRM byte and the SIB byte and displacement which
+/// follow it, at the start of `bytes`.
+fn modrm_len(bytes: &[u8]) -> Option<usize> {
+    let modrm = *bytes.first()?;
+    let mod_ = modrm >> 6;
+    let rm = modrm & 7;
+    let mut len = 1;
+    if mod_ != 3 && rm == 4 {
+        let sib = *bytes.get(1)?;
+        len += 1;
+        if mod_ == 0 && sib & 7 == 5 {
+            len += 4;
+        }
+    }
+    match mod_ {
+        // `rip`-relative.
+        0 if rm == 5 => len += 4,
+        1 => len += 1,
+        2 => len += 4,
+        _ => {}
+    }
+    Some(len)
+}
+
+/// Decode the instruction at `offset` in `code`.
+pub(super) fn decode(code: &[u8], offset: usize) -> Insn {
+    let end = code.len().min(offset + MAX_LEN);
+    match decode_in(&code[offset..end], code, offset) {
+        Some(insn) => insn,
+        None => Insn::invalid(offset, 1),
+    }
+}
+
+/// Cursor over an instruction's bytes.
+struct Bytes<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Bytes<'_> {
+    fn next(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    /// Read a ModRM byte, and skip its SIB byte and displacement.
+    fn modrm(&mut self, prefixes: Prefixes) -> Option<ModRm> {
+        let byte = self.peek()?;
+        self.pos += modrm_len(&self.bytes[self.pos..])?;
+        Some(ModRm {
+            mod_: byte >> 6,
+            reg: ((byte >> 3) & 7) | prefixes.rex_r(),
+            rm: (byte & 7) | prefixes.rex_b(),
+        })
+    }
+
+    /// Read an immediate of `len` bytes, as a sign-extended value.
+    fn imm(&mut self, len: usize) -> Option<i64> {
+        let bytes = self.bytes.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(match len {
+            0 => 0,
+            1 => i64::from(bytes[0] as i8),
+            2 => i64::from(i16::from_le_bytes(bytes.try_into().unwrap())),
+            3 => i64::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+            4 => i64::from(i32::from_le_bytes(bytes.try_into().unwrap())),
+            _ => i64::from_le_bytes(bytes.try_into().unwrap()),
+        })
+    }
+}
+
+fn decode_in(bytes: &[u8], code: &[u8], offset: usize) -> Option<Insn> {
+    let mut b = Bytes { bytes, pos: 0 };
+    let mut p = Prefixes::default();
+
+    // Legacy prefixes, then an optional REX prefix.
+    let mut op = loop {
+        match b.next()? {
+            0x66 => p.opsize = true,
+            0x67 => p.addrsize = true,
+            0xf3 => p.rep = true,
+            0xf2 => p.repne = true,
+            0xf0 | 0x26 | 0x2e | 0x36 | 0x3e | 0x64 | 0x65 => {}
+            op => break op,
+        }
+    };
+    if op & 0xf0 == 0x40 {
+        p.rex = op;
+        op = b.next()?;
+    }
+
+    let (effect, flow) = match op {
+        0x0f => return decode_0f(&mut b, p, code, offset),
+        0xc4 | 0xc5 | 0x62 => return decode_vex(&mut b, p, op, offset),
+
+        // The arithmetic instructions: `add`, `or`, `adc`, `sbb`, `and`,
+        // `sub`, `xor`, and `cmp`.
+        0x00..=0x3f if op & 7 <= 5 => {
+            let writes = op >> 3 != 7;
+            let effect = match op & 7 {
+                0..=3 => {
+                    let m = b.modrm(p)?;
+                    if matches!(op, 0x29 | 0x2b | 0x31 | 0x33)
+                        && m.mod_ == 3
+                        && m.reg == RAX
+                        && m.rm == RAX
+                    {
+                        // `xor eax, eax` and `sub eax, eax`.
+                        Effect::SetNr(0)
+                    } else if !writes {
+                        Effect::None
+                    } else if op & 2 == 0 {
+                        m.writes_rm(p)
+                    } else {
+                        m.writes_reg(p)
+                    }
+                }
+                _ => {
+                    b.imm(if op & 7 == 4 { 1 } else { p.imm_len(Imm::Z) })?;
+                    if writes {
+                        Effect::Clobber
+                    } else {
+                        Effect::None
+                    }
+                }
+            };
+            (effect, Flow::Next)
+        }
+        0x00..=0x3f => return None,
+
+        // `push r`
+        0x50..=0x57 => (Effect::None, Flow::Next),
+        // `pop r`
+        0x58..=0x5f => {
+            if (op & 7) | p.rex_b() == RAX {
+                (Effect::PopNr, Flow::Next)
+            } else {
+                (Effect::None, Flow::Next)
+            }
+        }
+        // `movsxd`
+        0x63 => (b.modrm(p)?.writes_reg(p), Flow::Next),
+        // `push imm`
+        0x68 => (Effect::Push(b.imm(p.imm_len(Imm::Z))? as u32), Flow::Next),
+        0x6a => (Effect::Push(b.imm(1)? as u32), Flow::Next),
+        // `imul r, r/m, imm`
+        0x69 | 0x6b => {
+            let m = b.modrm(p)?;
+            b.imm(if op == 0x69 { p.imm_len(Imm::Z) } else { 1 })?;
+            (m.writes_reg(p), Flow::Next)
+        }
+        // `ins` and `outs`
+        0x6c..=0x6f => (Effect::None, Flow::Next),
+        // `jcc rel8`
+        0x70..=0x7f => (Effect::None, Flow::Branch(b.imm(1)?)),
+        // Arithmetic with an immediate; `/7` is `cmp`.
+        0x80 | 0x81 | 0x83 => {
+            let m = b.modrm(p)?;
+            b.imm(if op == 0x81 { p.imm_len(Imm::Z) } else { 1 })?;
+            let effect = if m.reg & 7 == 7 {
+                Effect::None
+            } else {
+                m.writes_rm(p)
+            };
+            (effect, Flow::Next)
+        }
+        // `test`
+        0x84 | 0x85 => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Next)
+        }
+        // `xchg`
+        0x86 | 0x87 => (b.modrm(p)?.writes_both(p), Flow::Next),
+        // `mov r/m, r` and `mov r/m, sreg`
+        0x88 | 0x89 | 0x8c => (b.modrm(p)?.writes_rm(p), Flow::Next),
+        // `mov r, r/m` and `lea`
+        0x8a | 0x8b | 0x8d => (b.modrm(p)?.writes_reg(p), Flow::Next),
+        // `mov sreg, r/m`
+        0x8e => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Next)
+        }
+        // `pop r/m`, or an XOP prefix, which we don't decode.
+        0x8f => {
+            let m = b.modrm(p)?;
+            if m.reg & 7 != 0 {
+                return None;
+            }
+            (m.writes_rm(p), Flow::Next)
+        }
+        // `nop`, or `xchg r8, rax` with REX.B.
+        0x90 if p.rex_b() == 0 => (Effect::None, Flow::Next),
+        // `xchg r, rax`, `cbw`/`cwde`/`cdqe`
+        0x90..=0x98 => (Effect::Clobber, Flow::Next),
+        // `cwd`/`cdq`/`cqo`, `fwait`, `pushf`, `popf`, `sahf`
+        0x99 | 0x9b..=0x9e => (Effect::None, Flow::Next),
+        // `lahf`
+        0x9f => (Effect::Clobber, Flow::Next),
+        // `mov al/rax, moffs` and `mov moffs, al/rax`
+        0xa0..=0xa3 => {
+            b.imm(p.imm_len(Imm::Moffs))?;
+            let effect = if op <= 0xa1 {
+                Effect::Clobber
+            } else {
+                Effect::None
+            };
+            (effect, Flow::Next)
+        }
+        // `movs`, `cmps`, `stos`, `scas`
+        0xa4..=0xa7 | 0xaa | 0xab | 0xae | 0xaf => (Effect::None, Flow::Next),
+        // `test al/rax, imm`
+        0xa8 => {
+            b.imm(1)?;
+            (Effect::None, Flow::Next)
+        }
+        0xa9 => {
+            b.imm(p.imm_len(Imm::Z))?;
+            (Effect::None, Flow::Next)
+        }
+        // `lods`
+        0xac | 0xad => (Effect::Clobber, Flow::Next),
+        // `mov r8, imm8`
+        0xb0..=0xb7 => {
+            b.imm(1)?;
+            let effect = if is_rax((op & 7) | p.rex_b(), p) {
+                Effect::Clobber
+            } else {
+                Effect::None
+            };
+            (effect, Flow::Next)
+        }
+        // `mov r, imm`
+        0xb8..=0xbf => {
+            let imm = b.imm(p.imm_len(Imm::V))?;
+            let effect = if (op & 7) | p.rex_b() != RAX {
+                Effect::None
+            } else if p.opsize {
+                Effect::Clobber
+            } else if p.rex_w() {
+                match u32::try_from(imm) {
+                    Ok(imm) => Effect::SetNr(imm),
+                    Err(_) => Effect::Clobber,
+                }
+            } else {
+                Effect::SetNr(imm as u32)
+            };
+            (effect, Flow::Next)
+        }
+        // Shifts and rotates.
+        0xc0 | 0xc1 | 0xd0..=0xd3 => {
+            let m = b.modrm(p)?;
+            if op <= 0xc1 {
+                b.imm(1)?;
+            }
+            (m.writes_rm(p), Flow::Next)
+        }
+        // `ret imm16`, `ret`, `retf imm16`, `retf`
+        0xc2 | 0xca => {
+            b.imm(2)?;
+            (Effect::None, Flow::Stop)
+        }
+        0xc3 | 0xcb => (Effect::None, Flow::Stop),
+        // `mov r/m8, imm8`, or `xabort`
+        0xc6 => {
+            let m = b.modrm(p)?;
+            b.imm(1)?;
+            (m.writes_rm_or_clobbers(p), Flow::Next)
+        }
+        // `mov r/m, imm`, or `xbegin`
+        0xc7 => {
+            let m = b.modrm(p)?;
+            let imm = b.imm(p.imm_len(Imm::Z))?;
+            let effect = if m.reg & 7 != 0 {
+                Effect::Clobber
+            } else if m.mod_ == 3 && m.rm == RAX && !p.opsize {
+                match u32::try_from(imm) {
+                    Ok(imm) => Effect::SetNr(imm),
+                    Err(_) => Effect::Clobber,
+                }
+            } else {
+                m.writes_rm(p)
+            };
+            (effect, Flow::Next)
+        }
+        // `enter`
+        0xc8 => {
+            b.imm(p.imm_len(Imm::Enter))?;
+            (Effect::None, Flow::Next)
+        }
+        // `leave`
+        0xc9 => (Effect::None, Flow::Next),
+        // `int3`, `int imm8`, `iret`
+        0xcc | 0xcf => (Effect::None, Flow::Stop),
+        0xcd => {
+            b.imm(1)?;
+            (Effect::Clobber, Flow::Next)
+        }
+        // `xlat`
+        0xd7 => (Effect::Clobber, Flow::Next),
+        // x87; `fnstsw ax` is `df e0`.
+        0xd8..=0xdf => {
+            let m = b.modrm(p)?;
+            let effect = if op == 0xdf && m.mod_ == 3 && m.reg & 7 == 4 {
+                Effect::Clobber
+            } else {
+                Effect::None
+            };
+            (effect, Flow::Next)
+        }
+        // `loopnz`, `loopz`, `loop`, `jrcxz`
+        0xe0..=0xe3 => (Effect::None, Flow::Branch(b.imm(1)?)),
+        // `in al/eax, imm8`, `out imm8, al/eax`
+        0xe4..=0xe7 => {
+            b.imm(1)?;
+            let effect = if op <= 0xe5 {
+                Effect::Clobber
+            } else {
+                Effect::None
+            };
+            (effect, Flow::Next)
+        }
+        // `call rel32`, `jmp rel32`, `jmp rel8`
+        0xe8 => (Effect::Clobber, Flow::Call(Some(b.imm(4)?))),
+        0xe9 => (Effect::None, Flow::Jump(Some(b.imm(4)?))),
+        0xeb => (Effect::None, Flow::Jump(Some(b.imm(1)?))),
+        // `in al/eax, dx`, `out dx, al/eax`
+        0xec | 0xed => (Effect::Clobber, Flow::Next),
+        0xee | 0xef => (Effect::None, Flow::Next),
+        // `int1`, `cmc`, and the flag instructions
+        0xf1 | 0xf5 | 0xf8..=0xfd => (Effect::None, Flow::Next),
+        // `hlt`
+        0xf4 => (Effect::None, Flow::Stop),
+        // `test`, `not`, `neg`, `mul`, `imul`, `div`, `idiv`
+        0xf6 | 0xf7 => {
+            let m = b.modrm(p)?;
+            let effect = match m.reg & 7 {
+                0 | 1 => {
+                    b.imm(if op == 0xf6 { 1 } else { p.imm_len(Imm::Z) })?;
+                    Effect::None
+                }
+                2 | 3 => m.writes_rm(p),
+                _ => Effect::Clobber,
+            };
+            (effect, Flow::Next)
+        }
+        // `inc` and `dec`
+        0xfe => {
+            let m = b.modrm(p)?;
+            let effect = if m.reg & 7 <= 1 {
+                m.writes_rm(p)
+            } else {
+                Effect::Clobber
+            };
+            (effect, Flow::Next)
+        }
+        // `inc`, `dec`, `call`, `jmp`, `push`
+        0xff => {
+            let m = b.modrm(p)?;
+            match m.reg & 7 {
+                0 | 1 => (m.writes_rm(p), Flow::Next),
+                2 | 3 => (Effect::Clobber, Flow::Call(None)),
+                4 | 5 => (Effect::None, Flow::Jump(None)),
+                6 => (Effect::None, Flow::Next),
+                _ => return None,
+            }
+        }
+        // Invalid in 64-bit mode.
+        _ => return None,
+    };
+
+    Some(flow.insn(code, offset, b.pos, effect))
+}
+
+/// How execution continues after an instruction.
+enum Flow {
+    Next,
+    /// A conditional branch, with its displacement.
+    Branch(i64),
+    /// An unconditional branch, with its displacement if it's direct.
+    Jump(Option<i64>),
+    /// A call, with its displacement if it's direct.
+    Call(Option<i64>),
+    /// A return, or something else that doesn't continue.
+    Stop,
+}
+
+impl Flow {
+    fn insn(self, code: &[u8], offset: usize, len: usize, effect: Effect) -> Insn {
+        let end = offset + len;
+        match self {
+            Self::Next => Insn::new(offset, len, effect),
+            Self::Branch(disp) => Insn {
+                effect,
+                ..Insn::branch(offset, len, target(code, end, disp))
+            },
+            Self::Jump(disp) => Insn {
+                effect,
+                ..Insn::jump(offset, len, disp.and_then(|disp| target(code, end, disp)))
+            },
+            Self::Call(disp) => {
+                Insn::call(offset, len, disp.and_then(|disp| target(code, end, disp)))
+            }
+            Self::Stop => Insn {
+                effect,
+                ..Insn::jump(offset, len, None)
+            },
+        }
+    }
+}
+
+/// Decode an instruction in the two-byte and three-byte opcode maps, after
+/// the 0x0f.
+fn decode_0f(b: &mut Bytes<'_>, p: Prefixes, code: &[u8], offset: usize) -> Option<Insn> {
+    let op = b.next()?;
+    let (effect, flow) = match op {
+        // `syscall`
+        0x05 => (Effect::Syscall, Flow::Next),
+        // `sysret`, `ud2`, `sysexit`
+        0x07 | 0x0b | 0x35 => (Effect::None, Flow::Stop),
+        // `clts`, `invd`, `wbinvd`, `femms`, `wrmsr`
+        0x06 | 0x08 | 0x09 | 0x0e | 0x30 => (Effect::None, Flow::Next),
+        // `rdtsc`, `rdmsr`, `rdpmc`, `sysenter`, `getsec`
+        0x31..=0x34 | 0x37 => (Effect::Clobber, Flow::Next),
+        // System instructions, including `xgetbv` and `rdtscp`.
+        0x00 | 0x01 => (b.modrm(p)?.writes_rm_or_clobbers(p), Flow::Next),
+        // `lar` and `lsl`
+        0x02 | 0x03 => (b.modrm(p)?.writes_reg(p), Flow::Next),
+        // 3DNow!
+        0x0f => {
+            b.modrm(p)?;
+            b.imm(1)?;
+            (Effect::None, Flow::Next)
+        }
+        // `endbr64` and the hint NOPs, except `rdssp`, which writes `rm`.
+        0x1e => {
+            let m = b.modrm(p)?;
+            let effect = if p.rep && m.mod_ == 3 && m.reg & 7 == 1 {
+                m.writes_rm(p)
+            } else {
+                Effect::None
+            };
+            (effect, Flow::Next)
+        }
+        // Prefetches, hint NOPs, and SSE moves, which write memory or vector
+        // registers.
+        0x0d | 0x10..=0x1d | 0x1f => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Next)
+        }
+        // `mov r, cr` and `mov r, dr`
+        0x20 | 0x21 => (b.modrm(p)?.writes_rm(p), Flow::Next),
+        0x22 | 0x23 => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Next)
+        }
+        // `cvttss2si` and friends write `reg`; the rest write vector
+        // registers or memory.
+        0x2c | 0x2d if p.rep || p.repne => (b.modrm(p)?.writes_reg(p), Flow::Next),
+        0x28..=0x2f => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Next)
+        }
+        0x38 => {
+            let op = b.next()?;
+            let m = b.modrm(p)?;
+            // `movbe`, `crc32`, `adcx`, `adox`, and friends write `reg`; the
+            // rest write vector registers.
+            let effect = match op {
+                0xf1 if !p.repne => Effect::None,
+                0xf0..=0xff => m.writes_reg(p),
+                _ => Effect::None,
+            };
+            (effect, Flow::Next)
+        }
+        0x3a => {
+            let op = b.next()?;
+            let m = b.modrm(p)?;
+            b.imm(1)?;
+            // `pextrb`, `pextrw`, `pextrd`, `extractps`
+            let effect = match op {
+                0x14..=0x17 => m.writes_rm(p),
+                _ => Effect::None,
+            };
+            (effect, Flow::Next)
+        }
+        // `cmov`
+        0x40..=0x4f => (b.modrm(p)?.writes_reg(p), Flow::Next),
+        // `movmskps`
+        0x50 => (b.modrm(p)?.writes_reg(p), Flow::Next),
+        // Shuffles and shifts with an immediate.
+        0x70..=0x73 => {
+            b.modrm(p)?;
+            b.imm(1)?;
+            (Effect::None, Flow::Next)
+        }
+        // `emms`
+        0x77 => (Effect::None, Flow::Next),
+        // `vmread`
+        0x78 => (b.modrm(p)?.writes_rm(p), Flow::Next),
+        // `movd r/m, mm` and `movd r/m, xmm`; `movq xmm, xmm/m64` with 0xf3.
+        0x7e if !p.rep => (b.modrm(p)?.writes_rm(p), Flow::Next),
+        0x51..=0x6f | 0x74..=0x76 | 0x79 | 0x7c..=0x7f => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Next)
+        }
+        // `jcc rel32`
+        0x80..=0x8f => (Effect::None, Flow::Branch(b.imm(4)?)),
+        // `setcc`
+        0x90..=0x9f => (b.modrm(p)?.writes_rm(p), Flow::Next),
+        // `push fs`, `pop fs`, `push gs`, `pop gs`, `rsm`
+        0xa0 | 0xa1 | 0xa8..=0xaa => (Effect::None, Flow::Next),
+        // `cpuid`
+        0xa2 => (Effect::Clobber, Flow::Next),
+        // `bt`
+        0xa3 => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Next)
+        }
+        // `shld` and `shrd`
+        0xa4 | 0xac => {
+            let m = b.modrm(p)?;
+            b.imm(1)?;
+            (m.writes_rm(p), Flow::Next)
+        }
+        // `shld`, `bts`, `shrd`, `btr`, `btc`
+        0xa5 | 0xab | 0xad | 0xb3 | 0xbb => (b.modrm(p)?.writes_rm(p), Flow::Next),
+        // `fxsave`, `ldmxcsr`, fences, and friends; `rdfsbase` and
+        // `rdgsbase` write `rm`.
+        0xae => {
+            let m = b.modrm(p)?;
+            let effect = if p.rep && m.mod_ == 3 && m.reg & 7 <= 1 {
+                m.writes_rm(p)
+            } else {
+                Effect::None
+            };
+            (effect, Flow::Next)
+        }
+        // `imul`, `lss`, `lfs`, `lgs`, `movzx`, `bsf`, `bsr`, `movsx`
+        0xaf | 0xb2 | 0xb4..=0xb7 | 0xbc..=0xbf => (b.modrm(p)?.writes_reg(p), Flow::Next),
+        // `cmpxchg`, which writes `rax`
+        0xb0 | 0xb1 => {
+            b.modrm(p)?;
+            (Effect::Clobber, Flow::Next)
+        }
+        // `popcnt`
+        0xb8 if p.rep => (b.modrm(p)?.writes_reg(p), Flow::Next),
+        // `ud1`
+        0xb9 => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Stop)
+        }
+        // `bt`, `bts`, `btr`, `btc` with an immediate
+        0xba => {
+            let m = b.modrm(p)?;
+            b.imm(1)?;
+            let effect = match m.reg & 7 {
+                4 => Effect::None,
+                5..=7 => m.writes_rm(p),
+                _ => return None,
+            };
+            (effect, Flow::Next)
+        }
+        // `xadd`
+        0xc0 | 0xc1 => (b.modrm(p)?.writes_both(p), Flow::Next),
+        // `cmpps`, `pinsrw`, `shufps`
+        0xc2 | 0xc4 | 0xc6 => {
+            b.modrm(p)?;
+            b.imm(1)?;
+            (Effect::None, Flow::Next)
+        }
+        // `movnti`
+        0xc3 => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Next)
+        }
+        // `pextrw`
+        0xc5 => {
+            let m = b.modrm(p)?;
+            b.imm(1)?;
+            (m.writes_reg(p), Flow::Next)
+        }
+        // `cmpxchg8b` and `cmpxchg16b`, which write `rax`, and `rdrand`,
+        // `rdseed`, and `rdpid`, which write `rm`.
+        0xc7 => (b.modrm(p)?.writes_rm_or_clobbers(p), Flow::Next),
+        // `bswap`
+        0xc8..=0xcf => {
+            let effect = if (op & 7) | p.rex_b() == RAX {
+                Effect::Clobber
+            } else {
+                Effect::None
+            };
+            (effect, Flow::Next)
+        }
+        // `pmovmskb`
+        0xd7 => (b.modrm(p)?.writes_reg(p), Flow::Next),
+        // `ud0`
+        0xff => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Stop)
+        }
+        // MMX and SSE.
+        0xd0..=0xfe => {
+            b.modrm(p)?;
+            (Effect::None, Flow::Next)
+        }
+        _ => return None,
+    };
+
+    Some(flow.insn(code, offset, b.pos, effect))
+}
+
+/// Decode a VEX- or EVEX-encoded instruction, starting with the byte after
+/// the `0xc4`, `0xc5`, or `0x62`.
+fn decode_vex(b: &mut Bytes<'_>, p: Prefixes, escape: u8, offset: usize) -> Option<Insn> {
+    if p.rex != 0 || p.opsize || p.rep || p.repne {
+        return None;
+    }
+    let map = match escape {
+        0xc5 => {
+            b.next()?;
+            1
+        }
+        0xc4 => {
+            let map = b.next()? & 0x1f;
+            b.next()?;
+            map
+        }
+        _ => {
+            let map = b.next()? & 7;
+            b.next()?;
+            b.next()?;
+            map
+        }
+    };
+    let op = b.next()?;
+
+    // `vzeroupper` and `vzeroall` have no ModRM byte.
+    if !(escape != 0x62 && map == 1 && op == 0x77) {
+        b.modrm(p)?;
+    }
+    let imm = match map {
+        1 => matches!(op, 0x70..=0x73 | 0xc2 | 0xc4..=0xc6),
+        3 => true,
+        _ => false,
+    };
+    if imm {
+        b.imm(1)?;
+    }
+
+    // The instructions which write general-purpose registers. Rather than
+    // work out which one they write, assume it's `rax`.
+    let effect = match (map, op) {
+        (1, 0x2c | 0x2d | 0x50 | 0x7e | 0xc5 | 0xd7) => Effect::Clobber,
+        (2, 0xf0..=0xff) => Effect::Clobber,
+        (3, 0x14..=0x17 | 0xf0) => Effect::Clobber,
+        (5, 0x2c | 0x2d | 0x7e) => Effect::Clobber,
+        (1..=3 | 5 | 6, _) => Effect::None,
+        _ => return None,
+    };
+    Some(Insn::new(offset, b.pos, effect))
+}
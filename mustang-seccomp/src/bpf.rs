@@ -0,0 +1,109 @@
+//! Seccomp-BPF allowlist generation, and the layout of the section the
+//! "seccomp" feature reads the filter from.
+
+/// The name of the section mustang reserves for the filter. This must match
+/// `mustang::seccomp::SECTION`.
+pub(crate) const SECTION: &str = ".mustang_seccomp";
+
+/// The magic number at the start of the section.
+pub(crate) const MAGIC: &[u8; 8] = b"MUSTSECC";
+
+/// The size of the section header: the magic number, followed by a `u32`
+/// instruction count and a `u32` of padding.
+pub(crate) const HEADER_LEN: usize = 16;
+
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_JMP_JEQ_K: u16 = 0x15;
+const BPF_RET_K: u16 = 0x06;
+
+/// Offsets of fields in `struct seccomp_data`.
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+/// What to do when a syscall isn't in the allowlist.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Action {
+    Kill,
+    Trap,
+    Errno(u16),
+    Log,
+}
+
+impl Action {
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "kill" => Some(Self::Kill),
+            "trap" => Some(Self::Trap),
+            "log" => Some(Self::Log),
+            _ => s
+                .strip_prefix("errno=")
+                .and_then(|errno| errno.parse().ok())
+                .map(Self::Errno),
+        }
+    }
+
+    fn ret(self) -> u32 {
+        match self {
+            Self::Kill => SECCOMP_RET_KILL_PROCESS,
+            Self::Trap => SECCOMP_RET_TRAP,
+            Self::Errno(errno) => SECCOMP_RET_ERRNO | u32::from(errno),
+            Self::Log => SECCOMP_RET_LOG,
+        }
+    }
+}
+
+/// A `struct sock_filter`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Insn {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+impl Insn {
+    const fn new(code: u16, jt: u8, jf: u8, k: u32) -> Self {
+        Self { code, jt, jf, k }
+    }
+
+    /// Encode this instruction in the target's byte order.
+    pub(crate) fn encode(self, little_endian: bool) -> [u8; 8] {
+        let mut bytes = [0; 8];
+        if little_endian {
+            bytes[..2].copy_from_slice(&self.code.to_le_bytes());
+            bytes[4..].copy_from_slice(&self.k.to_le_bytes());
+        } else {
+            bytes[..2].copy_from_slice(&self.code.to_be_bytes());
+            bytes[4..].copy_from_slice(&self.k.to_be_bytes());
+        }
+        bytes[2] = self.jt;
+        bytes[3] = self.jf;
+        bytes
+    }
+}
+
+/// Build a filter which allows the syscalls in `allowed`, which must be
+/// sorted and deduplicated, and takes `action` for all others, and for
+/// syscalls made with a different architecture's calling convention.
+pub(crate) fn allowlist(audit_arch: u32, allowed: &[u32], action: Action) -> Vec<Insn> {
+    let mut insns = vec![
+        Insn::new(BPF_LD_W_ABS, 0, 0, SECCOMP_DATA_ARCH),
+        Insn::new(BPF_JMP_JEQ_K, 1, 0, audit_arch),
+        Insn::new(BPF_RET_K, 0, 0, SECCOMP_RET_KILL_PROCESS),
+        Insn::new(BPF_LD_W_ABS, 0, 0, SECCOMP_DATA_NR),
+    ];
+    // Jump offsets are only 8 bits, so pair each comparison with its own
+    // return rather than jumping to a shared one.
+    for &nr in allowed {
+        insns.push(Insn::new(BPF_JMP_JEQ_K, 0, 1, nr));
+        insns.push(Insn::new(BPF_RET_K, 0, 0, SECCOMP_RET_ALLOW));
+    }
+    insns.push(Insn::new(BPF_RET_K, 0, 0, action.ret()));
+    insns
+}
@@ -0,0 +1,223 @@
+//! Generate a seccomp-BPF allowlist from a mustang binary's syscall sites.
+//!
+//! Because mustang binaries contain their entire libc, the set of syscalls
+//! they can make is visible in the binary itself. This tool finds the syscall
+//! instructions in a binary, determines which syscall each makes, and
+//! produces a filter that allows exactly those syscalls.
+//!
+//! ```console
+//! $ mustang-seccomp [options] <binary>
+//! ```
+//!
+//! Options:
+//!
+//!  - `-o <file>`: Write the filter to `<file>` as an array of
+//!    `struct sock_filter`, the format accepted by, for example, `bwrap
+//!    --seccomp`.
+//!  - `--patch`: Write the filter into the binary's `.mustang_seccomp`
+//!    section, which the mustang "seccomp" feature reserves, so that the
+//!    binary installs it at startup.
+//!  - `--extra <nr>`: Also allow syscall number `<nr>`. May be repeated.
+//!  - `--allow-unresolved`: Don't fail if some syscall sites' numbers can't
+//!    be determined. Syscalls made from those sites will be denied unless
+//!    they're also made elsewhere or added with `--extra`.
+//!  - `--action <kill|trap|log|errno=N>`: What to do on a denied syscall.
+//!    The default is `kill`, which kills the process.
+//!
+//! Syscall numbers are determined by decoding the straight-line code before
+//! each syscall instruction and finding the instruction that loads a
+//! constant into the syscall number register, so this works best on
+//! optimized builds, where rustix's syscall wrappers are inlined.
+
+mod arch;
+mod bpf;
+
+use arch::Arch;
+use bpf::Action;
+use object::{Object, ObjectSection, ObjectSymbol, SectionKind, SymbolKind};
+use std::collections::BTreeSet;
+use std::process::exit;
+
+struct Options {
+    binary: String,
+    output: Option<String>,
+    patch: bool,
+    extra: Vec<u32>,
+    allow_unresolved: bool,
+    action: Action,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: mustang-seccomp [-o <file>] [--patch] [--extra <nr>]... \
+         [--allow-unresolved] [--action <kill|trap|log|errno=N>] <binary>"
+    );
+    exit(2)
+}
+
+fn parse_args() -> Options {
+    let mut binary = None;
+    let mut output = None;
+    let mut patch = false;
+    let mut extra = Vec::new();
+    let mut allow_unresolved = false;
+    let mut action = Action::Kill;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(args.next().unwrap_or_else(|| usage())),
+            "--patch" => patch = true,
+            "--extra" => match args.next().and_then(|nr| nr.parse().ok()) {
+                Some(nr) => extra.push(nr),
+                None => usage(),
+            },
+            "--allow-unresolved" => allow_unresolved = true,
+            "--action" => match args.next().and_then(|action| Action::parse(&action)) {
+                Some(a) => action = a,
+                None => usage(),
+            },
+            _ if arg.starts_with('-') || binary.is_some() => usage(),
+            _ => binary = Some(arg),
+        }
+    }
+
+    Options {
+        binary: binary.unwrap_or_else(|| usage()),
+        output,
+        patch,
+        extra,
+        allow_unresolved,
+        action,
+    }
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("mustang-seccomp: {}", message);
+    exit(1)
+}
+
+fn main() {
+    let options = parse_args();
+    let mut data = std::fs::read(&options.binary)
+        .unwrap_or_else(|err| fail(format_args!("{}: {}", options.binary, err)));
+
+    let (insns, little_endian, section) = {
+        let file = object::File::parse(&*data)
+            .unwrap_or_else(|err| fail(format_args!("{}: {}", options.binary, err)));
+        let arch = Arch::from_object(file.architecture()).unwrap_or_else(|err| fail(err));
+        eprintln!("target: {}", arch.target());
+
+        let mut allowed = BTreeSet::new();
+        let mut sites = 0;
+        let mut unresolved = Vec::new();
+        for section in file.sections() {
+            if section.kind() != SectionKind::Text {
+                continue;
+            }
+            let code = section
+                .data()
+                .unwrap_or_else(|err| fail(format_args!("{}: {}", options.binary, err)));
+            // Functions in the section, where decoding can start.
+            let starts = file
+                .symbols()
+                .filter(|symbol| {
+                    symbol.kind() == SymbolKind::Text
+                        && symbol.section_index() == Some(section.index())
+                })
+                .filter_map(|symbol| symbol.address().checked_sub(section.address()))
+                .map(|offset| offset as usize)
+                .chain([0])
+                .collect::<BTreeSet<_>>();
+            for (offset, nr) in arch.scan(code, &starts) {
+                sites += 1;
+                match nr {
+                    Some(nr) => {
+                        allowed.insert(nr);
+                    }
+                    None => unresolved.push(section.address() + offset as u64),
+                }
+            }
+        }
+
+        eprintln!(
+            "{} syscall sites, {} distinct syscalls, {} unresolved sites",
+            sites,
+            allowed.len(),
+            unresolved.len()
+        );
+        for addr in &unresolved {
+            eprintln!("  unresolved syscall site at {:#x}", addr);
+        }
+        if !unresolved.is_empty() && !options.allow_unresolved {
+            fail(
+                "some syscall sites are unresolved; use --allow-unresolved and --extra to proceed",
+            );
+        }
+
+        allowed.extend(arch.implicit_syscalls());
+        allowed.extend(&options.extra);
+        for nr in &allowed {
+            println!("{}", nr);
+        }
+
+        let allowed = allowed.into_iter().collect::<Vec<_>>();
+        let insns = bpf::allowlist(arch.audit_arch(), &allowed, options.action);
+
+        let section = file
+            .section_by_name(bpf::SECTION)
+            .and_then(|section| section.file_range());
+        (insns, file.is_little_endian(), section)
+    };
+
+    let encoded = insns
+        .iter()
+        .flat_map(|insn| insn.encode(little_endian))
+        .collect::<Vec<u8>>();
+
+    if let Some(output) = &options.output {
+        std::fs::write(output, &encoded)
+            .unwrap_or_else(|err| fail(format_args!("{}: {}", output, err)));
+    }
+
+    if options.patch {
+        let Some((offset, size)) = section else {
+            fail(format_args!(
+                "{} has no {} section; was it built with mustang's \"seccomp\" feature?",
+                options.binary,
+                bpf::SECTION
+            ));
+        };
+        let section = &mut data[offset as usize..][..size as usize];
+        if !section.starts_with(bpf::MAGIC) {
+            fail(format_args!(
+                "{} has an unrecognized {} section",
+                options.binary,
+                bpf::SECTION
+            ));
+        }
+        let (header, body) = section.split_at_mut(bpf::HEADER_LEN);
+        if encoded.len() > body.len() {
+            fail(format_args!(
+                "the filter has {} instructions, but the section only has room for {}",
+                insns.len(),
+                body.len() / 8
+            ));
+        }
+        let len = insns.len() as u32;
+        header[8..12].copy_from_slice(&if little_endian {
+            len.to_le_bytes()
+        } else {
+            len.to_be_bytes()
+        });
+        body[..encoded.len()].copy_from_slice(&encoded);
+        body[encoded.len()..].fill(0);
+        std::fs::write(&options.binary, &data)
+            .unwrap_or_else(|err| fail(format_args!("{}: {}", options.binary, err)));
+        eprintln!(
+            "patched {} instructions into {}",
+            insns.len(),
+            options.binary
+        );
+    }
+}
@@ -0,0 +1,488 @@
+//! Structured program arguments passed in a memfd.
+//!
+//! The C `argc`/`argv`/`envp` convention passes arguments and environment
+//! variables on the initial stack, which the kernel limits in size (see
+//! `ARG_MAX`), and which can hold arbitrary non-UTF-8 bytes. This module
+//! implements an alternative: the launching process writes the arguments and
+//! environment variables into a sealed memfd, and passes the file descriptor
+//! number to the new program in the `MUSTANG_ARGS_FD` environment variable.
+//!
+//! The block has this layout, with all integers little-endian:
+//!
+//! ```text
+//! magic:   b"MUSTARG1"
+//! argc:    u32
+//! envc:    u32
+//! records: argc arguments, then envc "KEY=VALUE" environment variables,
+//!          each a u32 length, then that many bytes of UTF-8, then a NUL
+//! ```
+//!
+//! With the "memfd-args" feature, mustang programs look for
+//! `MUSTANG_ARGS_FD` at startup, check that the memfd is sealed against
+//! writing and shrinking, validate the block, and make its environment
+//! variables the process environment, so that `std::env::var` and friends
+//! see them. The arguments are available from [`args`]; note
+//! that `std::env::args` still sees the C-style `argv` the launcher passed.
+//! Programs in secure-execution mode, such as setuid programs, ignore
+//! `MUSTANG_ARGS_FD`, since it comes from a less privileged process.
+//!
+//! [`exec`] is the launcher side.
+
+use core::ffi::{c_char, CStr};
+use core::fmt;
+use core::ptr::null;
+use rustix::fd::AsFd;
+use rustix::fs::{MemfdFlags, SealFlags};
+use rustix::io;
+use rustix::mm::{MapFlags, ProtFlags};
+
+/// The name of the environment variable that holds the memfd number.
+pub const FD_ENV: &str = "MUSTANG_ARGS_FD";
+
+const MAGIC: &[u8; 8] = b"MUSTARG1";
+const HEADER_LEN: usize = MAGIC.len() + 4 + 4;
+
+/// An error in a structured argument block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// The block doesn't start with the expected magic number.
+    BadMagic,
+    /// The block ends in the middle of a record, or has trailing bytes.
+    BadLength,
+    /// A record isn't valid UTF-8.
+    InvalidUtf8,
+    /// A record contains a NUL byte, or isn't followed by one.
+    BadNul,
+    /// An environment variable record doesn't contain a `=`.
+    MissingEquals,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::BadMagic => "bad magic number",
+            Self::BadLength => "record lengths don't match the block length",
+            Self::InvalidUtf8 => "record isn't valid UTF-8",
+            Self::BadNul => "record has a missing or interior NUL",
+            Self::MissingEquals => "environment variable has no '='",
+        })
+    }
+}
+
+/// A validated structured argument block.
+#[derive(Debug, Clone, Copy)]
+pub struct Block<'a> {
+    bytes: &'a [u8],
+    argc: usize,
+    envc: usize,
+}
+
+impl<'a> Block<'a> {
+    /// Validate `bytes` as a structured argument block.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        if bytes.len() < HEADER_LEN || bytes[..MAGIC.len()] != *MAGIC {
+            return Err(ParseError::BadMagic);
+        }
+        let argc = read_u32(bytes, MAGIC.len()) as usize;
+        let envc = read_u32(bytes, MAGIC.len() + 4) as usize;
+
+        let mut records = Records {
+            bytes,
+            pos: HEADER_LEN,
+            remaining: argc.checked_add(envc).ok_or(ParseError::BadLength)?,
+        };
+        let mut index = 0;
+        while records.remaining != 0 {
+            let record = records.next_checked()?;
+            if index >= argc && !record.contains('=') {
+                return Err(ParseError::MissingEquals);
+            }
+            index += 1;
+        }
+        if records.pos != bytes.len() {
+            return Err(ParseError::BadLength);
+        }
+
+        Ok(Self { bytes, argc, envc })
+    }
+
+    /// The number of arguments.
+    #[inline]
+    pub fn argc(&self) -> usize {
+        self.argc
+    }
+
+    /// The number of environment variables.
+    #[inline]
+    pub fn envc(&self) -> usize {
+        self.envc
+    }
+
+    /// Iterate over the arguments.
+    pub fn args(&self) -> Records<'a> {
+        Records {
+            bytes: self.bytes,
+            pos: HEADER_LEN,
+            remaining: self.argc,
+        }
+    }
+
+    /// Iterate over the environment variables, as `(key, value)` pairs.
+    pub fn vars(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.env_records()
+            .map(|record| record.split_once('=').unwrap())
+    }
+
+    /// Iterate over the environment variables, as `KEY=VALUE` strings.
+    fn env_records(&self) -> Records<'a> {
+        let mut records = self.args();
+        for _ in records.by_ref() {}
+        records.remaining = self.envc;
+        records
+    }
+
+    /// Iterate over the environment variables as pointers to NUL-terminated
+    /// `KEY=VALUE` strings.
+    #[cfg(feature = "memfd-args")]
+    fn env_ptrs(&self) -> impl Iterator<Item = *const c_char> + 'a {
+        let bytes = self.bytes;
+        let mut records = self.env_records();
+        core::iter::from_fn(move || {
+            let pos = records.pos + 4;
+            records.next().map(|_| bytes[pos..].as_ptr().cast())
+        })
+    }
+}
+
+/// An iterator over the records in a [`Block`].
+#[derive(Debug, Clone)]
+pub struct Records<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    remaining: usize,
+}
+
+impl<'a> Records<'a> {
+    fn next_checked(&mut self) -> Result<&'a str, ParseError> {
+        let bytes = self.bytes;
+        if bytes.len() - self.pos < 4 {
+            return Err(ParseError::BadLength);
+        }
+        let len = read_u32(bytes, self.pos) as usize;
+        let start = self.pos + 4;
+        if bytes.len() - start <= len {
+            return Err(ParseError::BadLength);
+        }
+        let record = &bytes[start..start + len];
+        if bytes[start + len] != 0 || record.contains(&0) {
+            return Err(ParseError::BadNul);
+        }
+        let record = core::str::from_utf8(record).map_err(|_| ParseError::InvalidUtf8)?;
+        self.pos = start + len + 1;
+        self.remaining -= 1;
+        Ok(record)
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // The block was validated when it was parsed.
+        Some(self.next_checked().unwrap())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Records<'_> {}
+
+fn read_u32(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap())
+}
+
+/// Return the size of the block that [`encode`] produces for `args` and
+/// `vars`.
+pub fn encoded_len(args: &[&str], vars: &[(&str, &str)]) -> usize {
+    HEADER_LEN
+        + args.iter().map(|arg| 4 + arg.len() + 1).sum::<usize>()
+        + vars
+            .iter()
+            .map(|(key, value)| 4 + key.len() + 1 + value.len() + 1)
+            .sum::<usize>()
+}
+
+/// Encode `args` and `vars` into `buf`, which must be exactly
+/// [`encoded_len`] bytes long.
+///
+/// # Panics
+///
+/// Panics if `buf` is the wrong length, if any string contains a NUL, if any
+/// key contains a `=`, or if any string is longer than `u32::MAX` bytes.
+pub fn encode(buf: &mut [u8], args: &[&str], vars: &[(&str, &str)]) {
+    assert_eq!(buf.len(), encoded_len(args, vars));
+
+    fn put<'b>(buf: &'b mut [u8], bytes: &[u8]) -> &'b mut [u8] {
+        let (head, tail) = buf.split_at_mut(bytes.len());
+        head.copy_from_slice(bytes);
+        tail
+    }
+    fn put_len(buf: &mut [u8], len: usize) -> &mut [u8] {
+        put(buf, &u32::try_from(len).unwrap().to_le_bytes())
+    }
+
+    let mut buf = put(buf, MAGIC);
+    buf = put_len(buf, args.len());
+    buf = put_len(buf, vars.len());
+    for arg in args {
+        assert!(!arg.contains('\0'));
+        buf = put_len(buf, arg.len());
+        buf = put(buf, arg.as_bytes());
+        buf = put(buf, b"\0");
+    }
+    for (key, value) in vars {
+        assert!(!key.contains(['\0', '=']) && !value.contains('\0'));
+        buf = put_len(buf, key.len() + 1 + value.len());
+        buf = put(buf, key.as_bytes());
+        buf = put(buf, b"=");
+        buf = put(buf, value.as_bytes());
+        buf = put(buf, b"\0");
+    }
+    debug_assert!(buf.is_empty());
+}
+
+/// Execute the program at `path`, passing it `args` and `vars` in a memfd.
+///
+/// The program sees just `argv0` in its C-style `argv`, and just
+/// `MUSTANG_ARGS_FD` in its C-style environment.
+///
+/// This only returns if something fails.
+pub fn exec(path: &CStr, argv0: &CStr, args: &[&str], vars: &[(&str, &str)]) -> io::Errno {
+    let fd = match rustix::fs::memfd_create(
+        c"mustang-args",
+        MemfdFlags::CLOEXEC | MemfdFlags::ALLOW_SEALING,
+    ) {
+        Ok(fd) => fd,
+        Err(err) => return err,
+    };
+
+    if let Err(err) = write_block(fd.as_fd(), args, vars) {
+        return err;
+    }
+
+    // Clear `FD_CLOEXEC` so that the fd is inherited.
+    if let Err(err) = rustix::io::fcntl_setfd(&fd, rustix::io::FdFlags::empty()) {
+        return err;
+    }
+
+    // Format `MUSTANG_ARGS_FD=<fd>` on the stack.
+    let mut env_buf = [0_u8; FD_ENV.len() + 1 + 10 + 1];
+    let mut fd_digits = [0_u8; 10];
+    let mut n = rustix::fd::AsRawFd::as_raw_fd(&fd) as u32;
+    let mut num_digits = 0;
+    loop {
+        fd_digits[fd_digits.len() - 1 - num_digits] = b'0' + (n % 10) as u8;
+        num_digits += 1;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    let env_len = FD_ENV.len() + 1 + num_digits;
+    env_buf[..FD_ENV.len()].copy_from_slice(FD_ENV.as_bytes());
+    env_buf[FD_ENV.len()] = b'=';
+    env_buf[FD_ENV.len() + 1..env_len].copy_from_slice(&fd_digits[fd_digits.len() - num_digits..]);
+
+    let argv = [argv0.as_ptr(), null()];
+    let envp = [env_buf.as_ptr().cast::<c_char>(), null()];
+
+    // SAFETY: `argv` and `envp` are NULL-terminated arrays of NUL-terminated
+    // strings.
+    unsafe {
+        libc::execve(path.as_ptr(), argv.as_ptr(), envp.as_ptr());
+        io::Errno::from_raw_os_error(*libc::__errno_location())
+    }
+}
+
+/// Write the block for `args` and `vars` into the memfd `fd`, and seal it.
+fn write_block<Fd: AsFd>(fd: Fd, args: &[&str], vars: &[(&str, &str)]) -> io::Result<()> {
+    let fd = fd.as_fd();
+    let len = encoded_len(args, vars);
+    rustix::fs::ftruncate(fd, len as u64)?;
+
+    // SAFETY: We map a fresh shared mapping of the memfd, write to it, and
+    // unmap it.
+    unsafe {
+        let ptr = rustix::mm::mmap(
+            core::ptr::null_mut(),
+            len,
+            ProtFlags::READ | ProtFlags::WRITE,
+            MapFlags::SHARED,
+            fd,
+            0,
+        )?;
+        encode(core::slice::from_raw_parts_mut(ptr.cast(), len), args, vars);
+        rustix::mm::munmap(ptr, len)?;
+    }
+
+    rustix::fs::fcntl_add_seals(
+        fd,
+        SealFlags::SEAL | SealFlags::SHRINK | SealFlags::GROW | SealFlags::WRITE,
+    )
+}
+
+#[cfg(feature = "memfd-args")]
+mod receive {
+    use super::{Block, FD_ENV};
+    use crate::startup::fail;
+    use core::ffi::{c_char, c_int, CStr};
+    use core::ptr::null_mut;
+    use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+    use rustix::fd::{FromRawFd, OwnedFd};
+    use rustix::fs::SealFlags;
+    use rustix::mm::{MapFlags, ProtFlags};
+
+    static BLOCK_PTR: AtomicPtr<u8> = AtomicPtr::new(null_mut());
+    static BLOCK_LEN: AtomicUsize = AtomicUsize::new(0);
+    static BLOCK_ARGC: AtomicUsize = AtomicUsize::new(0);
+    static BLOCK_ENVC: AtomicUsize = AtomicUsize::new(0);
+
+    extern "C" {
+        static mut environ: *mut *mut c_char;
+    }
+
+    /// Return the block passed to this process, if any.
+    pub(super) fn block() -> Option<Block<'static>> {
+        let ptr = BLOCK_PTR.load(Ordering::Acquire);
+        if ptr.is_null() {
+            return None;
+        }
+
+        // SAFETY: The block was mapped at startup and is never unmapped, and
+        // it was validated then too.
+        Some(Block {
+            bytes: unsafe { core::slice::from_raw_parts(ptr, BLOCK_LEN.load(Ordering::Relaxed)) },
+            argc: BLOCK_ARGC.load(Ordering::Relaxed),
+            envc: BLOCK_ENVC.load(Ordering::Relaxed),
+        })
+    }
+
+    #[link_section = ".init_array.00001"]
+    #[used]
+    static INIT_ARGS: unsafe extern "C" fn(c_int, *mut *mut c_char, *mut *mut c_char) = {
+        unsafe extern "C" fn function(
+            _argc: c_int,
+            _argv: *mut *mut c_char,
+            envp: *mut *mut c_char,
+        ) {
+            // Don't let a less privileged process replace the environment
+            // of a setuid program.
+            if envp.is_null() || crate::auxv::secure() {
+                return;
+            }
+
+            let mut ptr = envp;
+            while !(*ptr).is_null() {
+                let var = CStr::from_ptr(*ptr).to_bytes();
+                if let Some(value) = var
+                    .strip_prefix(FD_ENV.as_bytes())
+                    .and_then(|rest| rest.strip_prefix(b"="))
+                {
+                    init(value);
+                    return;
+                }
+                ptr = ptr.add(1);
+            }
+        }
+        function
+    };
+
+    /// Map and validate the block in the fd named by `fd`, and install its
+    /// environment variables.
+    unsafe fn init(fd: &[u8]) {
+        let fd = match core::str::from_utf8(fd)
+            .ok()
+            .and_then(|s| s.parse::<c_int>().ok())
+        {
+            Some(fd) if fd >= 0 => fd,
+            _ => fail(format_args!("invalid {}", FD_ENV)),
+        };
+        let fd = OwnedFd::from_raw_fd(fd);
+
+        // The block is read in place, so it must not change after it's
+        // validated.
+        match rustix::fs::fcntl_get_seals(&fd) {
+            Ok(seals) if seals.contains(SealFlags::WRITE | SealFlags::SHRINK) => {}
+            Ok(_) => fail(format_args!("{} isn't sealed", FD_ENV)),
+            Err(err) => fail(format_args!("can't get the seals of {}: {}", FD_ENV, err)),
+        }
+
+        let len = match rustix::fs::fstat(&fd) {
+            Ok(stat) => stat.st_size as usize,
+            Err(err) => fail(format_args!("can't stat {}: {}", FD_ENV, err)),
+        };
+        let ptr =
+            match rustix::mm::mmap(null_mut(), len, ProtFlags::READ, MapFlags::PRIVATE, &fd, 0) {
+                Ok(ptr) => ptr.cast::<u8>(),
+                Err(err) => fail(format_args!("can't map {}: {}", FD_ENV, err)),
+            };
+        // The mapping stays valid after the fd is closed, and closing it
+        // keeps it from leaking into child processes.
+        drop(fd);
+
+        let block = match Block::parse(core::slice::from_raw_parts(ptr, len)) {
+            Ok(block) => block,
+            Err(err) => fail(format_args!("invalid argument block: {}", err)),
+        };
+
+        // Build a C-style environment array pointing into the block.
+        let envp_len = (block.envc() + 1) * core::mem::size_of::<*const c_char>();
+        let envp = match rustix::mm::mmap_anonymous(
+            null_mut(),
+            envp_len,
+            ProtFlags::READ | ProtFlags::WRITE,
+            MapFlags::PRIVATE,
+        ) {
+            Ok(envp) => envp.cast::<*const c_char>(),
+            Err(err) => fail(format_args!("can't allocate the environment: {}", err)),
+        };
+        for (i, var) in block.env_ptrs().enumerate() {
+            envp.add(i).write(var);
+        }
+        envp.add(block.envc()).write(core::ptr::null());
+        environ = envp.cast();
+
+        BLOCK_LEN.store(len, Ordering::Relaxed);
+        BLOCK_ARGC.store(block.argc(), Ordering::Relaxed);
+        BLOCK_ENVC.store(block.envc(), Ordering::Relaxed);
+        BLOCK_PTR.store(ptr, Ordering::Release);
+    }
+}
+
+/// Return the structured argument block passed to this process, if any.
+#[cfg(feature = "memfd-args")]
+#[inline]
+pub fn block() -> Option<Block<'static>> {
+    receive::block()
+}
+
+/// Iterate over the structured arguments passed to this process.
+///
+/// This is empty if the process wasn't launched with [`exec`].
+#[cfg(feature = "memfd-args")]
+pub fn args() -> Records<'static> {
+    match block() {
+        Some(block) => block.args(),
+        None => Records {
+            bytes: &[],
+            pos: 0,
+            remaining: 0,
+        },
+    }
+}
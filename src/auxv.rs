@@ -0,0 +1,422 @@
+//! Typed access to the auxiliary vector.
+//!
+//! Mustang programs are started directly by the kernel, which places the
+//! auxiliary vector ("auxv") on the initial stack, just past the end of the
+//! environment. That memory lives for the whole life of the process, so we
+//! record where it is during early startup and read it in place, without
+//! going through `getauxval` or `/proc/self/auxv`.
+//!
+//! The location is recorded by an `.init_array.00000` function, so it's
+//! available to everything except other functions registered at that same
+//! priority.
+
+use core::ffi::{c_char, c_int, c_void, CStr};
+use core::ptr::{null, null_mut};
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::{fmt, slice};
+
+/// End of the vector.
+pub const AT_NULL: usize = 0;
+/// Entry should be ignored.
+pub const AT_IGNORE: usize = 1;
+/// File descriptor of the program.
+pub const AT_EXECFD: usize = 2;
+/// Address of the program headers.
+pub const AT_PHDR: usize = 3;
+/// Size of a program header entry.
+pub const AT_PHENT: usize = 4;
+/// Number of program headers.
+pub const AT_PHNUM: usize = 5;
+/// System page size.
+pub const AT_PAGESZ: usize = 6;
+/// Base address of the interpreter.
+pub const AT_BASE: usize = 7;
+/// Flags.
+pub const AT_FLAGS: usize = 8;
+/// Entry point of the program.
+pub const AT_ENTRY: usize = 9;
+/// Program is not ELF.
+pub const AT_NOTELF: usize = 10;
+/// Real uid.
+pub const AT_UID: usize = 11;
+/// Effective uid.
+pub const AT_EUID: usize = 12;
+/// Real gid.
+pub const AT_GID: usize = 13;
+/// Effective gid.
+pub const AT_EGID: usize = 14;
+/// String identifying the CPU, for optimizations.
+pub const AT_PLATFORM: usize = 15;
+/// Architecture-dependent hints at CPU capabilities.
+pub const AT_HWCAP: usize = 16;
+/// Frequency at which `times` increments.
+pub const AT_CLKTCK: usize = 17;
+/// Secure mode boolean.
+pub const AT_SECURE: usize = 23;
+/// String identifying the real platform.
+pub const AT_BASE_PLATFORM: usize = 24;
+/// Address of 16 random bytes.
+pub const AT_RANDOM: usize = 25;
+/// Extension of `AT_HWCAP`.
+pub const AT_HWCAP2: usize = 26;
+/// rseq supported feature size.
+pub const AT_RSEQ_FEATURE_SIZE: usize = 27;
+/// rseq allocation alignment.
+pub const AT_RSEQ_ALIGN: usize = 28;
+/// Extension of `AT_HWCAP`.
+pub const AT_HWCAP3: usize = 29;
+/// Extension of `AT_HWCAP`.
+pub const AT_HWCAP4: usize = 30;
+/// Filename of the program.
+pub const AT_EXECFN: usize = 31;
+/// Entry point of the vsyscall page (x86 only).
+pub const AT_SYSINFO: usize = 32;
+/// Address of the vDSO ELF header.
+pub const AT_SYSINFO_EHDR: usize = 33;
+/// Minimal stack size for signal delivery.
+pub const AT_MINSIGSTKSZ: usize = 51;
+
+/// Pointer to the first entry of the auxv, or null if it hasn't been
+/// recorded yet.
+static AUXV: AtomicPtr<usize> = AtomicPtr::new(null_mut());
+
+#[link_section = ".init_array.00000"]
+#[used]
+static INIT_AUXV: unsafe extern "C" fn(c_int, *mut *mut c_char, *mut *mut c_char) = {
+    unsafe extern "C" fn function(_argc: c_int, _argv: *mut *mut c_char, envp: *mut *mut c_char) {
+        if envp.is_null() {
+            return;
+        }
+
+        // The auxv starts just after the NULL that terminates `envp`.
+        let mut ptr = envp;
+        while !(*ptr).is_null() {
+            ptr = ptr.add(1);
+        }
+        let auxv = ptr.add(1).cast::<usize>();
+        AUXV.store(auxv, Ordering::Relaxed);
+
+        // This replaces the `AT_RANDOM` bytes, so it runs before they're used
+        // to seed the stack protector guard.
+        #[cfg(feature = "deterministic")]
+        crate::deterministic::init(envp);
+
+        #[cfg(feature = "no-vdso")]
+        crate::time::disable_vdso(envp, auxv);
+
+        crate::hwcap::init();
+
+        #[cfg(feature = "record-replay")]
+        crate::replay::init(envp);
+    }
+    function
+};
+
+/// A raw auxv entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawEntry {
+    /// The `AT_*` key.
+    pub key: usize,
+    /// The value, which may be a number or an address depending on `key`.
+    pub value: usize,
+}
+
+/// A decoded auxv entry.
+#[derive(Clone, Copy)]
+#[non_exhaustive]
+pub enum Entry {
+    /// `AT_PHDR`
+    Phdr(*const c_void),
+    /// `AT_PHENT`
+    Phent(usize),
+    /// `AT_PHNUM`
+    Phnum(usize),
+    /// `AT_PAGESZ`
+    Pagesz(usize),
+    /// `AT_BASE`
+    Base(*const c_void),
+    /// `AT_FLAGS`
+    Flags(usize),
+    /// `AT_ENTRY`
+    EntryPoint(*const c_void),
+    /// `AT_UID`
+    Uid(u32),
+    /// `AT_EUID`
+    Euid(u32),
+    /// `AT_GID`
+    Gid(u32),
+    /// `AT_EGID`
+    Egid(u32),
+    /// `AT_PLATFORM`
+    Platform(&'static CStr),
+    /// `AT_HWCAP`
+    Hwcap(usize),
+    /// `AT_CLKTCK`
+    Clktck(usize),
+    /// `AT_SECURE`
+    Secure(bool),
+    /// `AT_BASE_PLATFORM`
+    BasePlatform(&'static CStr),
+    /// `AT_RANDOM`
+    Random(&'static [u8; 16]),
+    /// `AT_HWCAP2`
+    Hwcap2(usize),
+    /// `AT_HWCAP3`
+    Hwcap3(usize),
+    /// `AT_HWCAP4`
+    Hwcap4(usize),
+    /// `AT_EXECFN`
+    Execfn(&'static CStr),
+    /// `AT_SYSINFO`
+    Sysinfo(*const c_void),
+    /// `AT_SYSINFO_EHDR`
+    SysinfoEhdr(*const c_void),
+    /// `AT_MINSIGSTKSZ`
+    Minsigstksz(usize),
+    /// Any entry not decoded above.
+    Other(RawEntry),
+}
+
+impl Entry {
+    /// Decode a raw entry.
+    ///
+    /// # Safety
+    ///
+    /// For keys whose values are pointers to strings or bytes, the value must
+    /// point to memory that lives for the rest of the process, as the
+    /// kernel-provided values do.
+    unsafe fn decode(raw: RawEntry) -> Self {
+        let RawEntry { key, value } = raw;
+        match key {
+            AT_PHDR => Self::Phdr(value as *const c_void),
+            AT_PHENT => Self::Phent(value),
+            AT_PHNUM => Self::Phnum(value),
+            AT_PAGESZ => Self::Pagesz(value),
+            AT_BASE => Self::Base(value as *const c_void),
+            AT_FLAGS => Self::Flags(value),
+            AT_ENTRY => Self::EntryPoint(value as *const c_void),
+            AT_UID => Self::Uid(value as u32),
+            AT_EUID => Self::Euid(value as u32),
+            AT_GID => Self::Gid(value as u32),
+            AT_EGID => Self::Egid(value as u32),
+            AT_PLATFORM if value != 0 => Self::Platform(CStr::from_ptr(value as *const c_char)),
+            AT_HWCAP => Self::Hwcap(value),
+            AT_CLKTCK => Self::Clktck(value),
+            AT_SECURE => Self::Secure(value != 0),
+            AT_BASE_PLATFORM if value != 0 => {
+                Self::BasePlatform(CStr::from_ptr(value as *const c_char))
+            }
+            AT_RANDOM if value != 0 => Self::Random(&*(value as *const [u8; 16])),
+            AT_HWCAP2 => Self::Hwcap2(value),
+            AT_HWCAP3 => Self::Hwcap3(value),
+            AT_HWCAP4 => Self::Hwcap4(value),
+            AT_EXECFN if value != 0 => Self::Execfn(CStr::from_ptr(value as *const c_char)),
+            AT_SYSINFO => Self::Sysinfo(value as *const c_void),
+            AT_SYSINFO_EHDR => Self::SysinfoEhdr(value as *const c_void),
+            AT_MINSIGSTKSZ => Self::Minsigstksz(value),
+            _ => Self::Other(raw),
+        }
+    }
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Phdr(p) => f.debug_tuple("Phdr").field(p).finish(),
+            Self::Phent(n) => f.debug_tuple("Phent").field(n).finish(),
+            Self::Phnum(n) => f.debug_tuple("Phnum").field(n).finish(),
+            Self::Pagesz(n) => f.debug_tuple("Pagesz").field(n).finish(),
+            Self::Base(p) => f.debug_tuple("Base").field(p).finish(),
+            Self::Flags(n) => f.debug_tuple("Flags").field(n).finish(),
+            Self::EntryPoint(p) => f.debug_tuple("EntryPoint").field(p).finish(),
+            Self::Uid(n) => f.debug_tuple("Uid").field(n).finish(),
+            Self::Euid(n) => f.debug_tuple("Euid").field(n).finish(),
+            Self::Gid(n) => f.debug_tuple("Gid").field(n).finish(),
+            Self::Egid(n) => f.debug_tuple("Egid").field(n).finish(),
+            Self::Platform(s) => f.debug_tuple("Platform").field(s).finish(),
+            Self::Hwcap(n) => f
+                .debug_tuple("Hwcap")
+                .field(&format_args!("{:#x}", n))
+                .finish(),
+            Self::Clktck(n) => f.debug_tuple("Clktck").field(n).finish(),
+            Self::Secure(b) => f.debug_tuple("Secure").field(b).finish(),
+            Self::BasePlatform(s) => f.debug_tuple("BasePlatform").field(s).finish(),
+            // Don't print the random bytes, as they may be used for seeding.
+            Self::Random(_) => f.debug_tuple("Random").field(&format_args!("..")).finish(),
+            Self::Hwcap2(n) => f
+                .debug_tuple("Hwcap2")
+                .field(&format_args!("{:#x}", n))
+                .finish(),
+            Self::Hwcap3(n) => f
+                .debug_tuple("Hwcap3")
+                .field(&format_args!("{:#x}", n))
+                .finish(),
+            Self::Hwcap4(n) => f
+                .debug_tuple("Hwcap4")
+                .field(&format_args!("{:#x}", n))
+                .finish(),
+            Self::Execfn(s) => f.debug_tuple("Execfn").field(s).finish(),
+            Self::Sysinfo(p) => f.debug_tuple("Sysinfo").field(p).finish(),
+            Self::SysinfoEhdr(p) => f.debug_tuple("SysinfoEhdr").field(p).finish(),
+            Self::Minsigstksz(n) => f.debug_tuple("Minsigstksz").field(n).finish(),
+            Self::Other(raw) => f.debug_tuple("Other").field(raw).finish(),
+        }
+    }
+}
+
+// SAFETY: The pointers in `Entry` refer to memory set up by the kernel that
+// is never freed or written to.
+unsafe impl Send for Entry {}
+unsafe impl Sync for Entry {}
+
+/// An iterator over the raw auxv entries, not including the terminating
+/// `AT_NULL` entry.
+#[derive(Clone)]
+pub struct RawEntries {
+    ptr: *const usize,
+}
+
+impl Iterator for RawEntries {
+    type Item = RawEntry;
+
+    fn next(&mut self) -> Option<RawEntry> {
+        if self.ptr.is_null() {
+            return None;
+        }
+
+        // SAFETY: `ptr` points into the kernel-provided auxv, which is
+        // terminated by an `AT_NULL` entry.
+        unsafe {
+            let key = *self.ptr;
+            if key == AT_NULL {
+                self.ptr = null();
+                return None;
+            }
+            let value = *self.ptr.add(1);
+            self.ptr = self.ptr.add(2);
+            Some(RawEntry { key, value })
+        }
+    }
+}
+
+/// An iterator over the decoded auxv entries.
+#[derive(Clone)]
+pub struct Entries {
+    raw: RawEntries,
+}
+
+impl Iterator for Entries {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        // SAFETY: All values come from the kernel-provided auxv.
+        self.raw.next().map(|raw| unsafe { Entry::decode(raw) })
+    }
+}
+
+/// Iterate over all the raw auxv entries.
+///
+/// This yields nothing if called from an `.init_array.00000` function that
+/// runs before the auxv has been recorded.
+#[inline]
+pub fn raw_entries() -> RawEntries {
+    RawEntries {
+        ptr: AUXV.load(Ordering::Relaxed),
+    }
+}
+
+/// Iterate over all the auxv entries, decoded.
+#[inline]
+pub fn entries() -> Entries {
+    Entries { raw: raw_entries() }
+}
+
+/// Return the value for `key`, or `None` if there's no such entry.
+///
+/// Unlike `getauxval`, this distinguishes between an entry that is absent
+/// and an entry whose value is zero.
+pub fn get(key: usize) -> Option<usize> {
+    raw_entries()
+        .find(|entry| entry.key == key)
+        .map(|entry| entry.value)
+}
+
+/// Return the `AT_HWCAP` value, or 0 if it's absent.
+#[inline]
+pub fn hwcap() -> usize {
+    get(AT_HWCAP).unwrap_or(0)
+}
+
+/// Return the `AT_HWCAP2` value, or 0 if it's absent.
+#[inline]
+pub fn hwcap2() -> usize {
+    get(AT_HWCAP2).unwrap_or(0)
+}
+
+/// Test whether the process is in secure-execution mode, as setuid and
+/// setgid programs are, from `AT_SECURE`.
+///
+/// In this mode, the environment comes from a less privileged process, so
+/// it shouldn't be trusted.
+#[inline]
+pub fn secure() -> bool {
+    get(AT_SECURE).is_some_and(|value| value != 0)
+}
+
+/// Return the `AT_PLATFORM` string.
+pub fn platform() -> Option<&'static CStr> {
+    entries().find_map(|entry| match entry {
+        Entry::Platform(s) => Some(s),
+        _ => None,
+    })
+}
+
+/// Return the 16 bytes of kernel-provided randomness from `AT_RANDOM`.
+pub fn random() -> Option<&'static [u8; 16]> {
+    entries().find_map(|entry| match entry {
+        Entry::Random(bytes) => Some(bytes),
+        _ => None,
+    })
+}
+
+/// Return the `AT_EXECFN` string, the filename the program was executed with.
+pub fn execfn() -> Option<&'static CStr> {
+    entries().find_map(|entry| match entry {
+        Entry::Execfn(s) => Some(s),
+        _ => None,
+    })
+}
+
+/// Return the address of the vDSO's ELF header, from `AT_SYSINFO_EHDR`.
+pub fn sysinfo_ehdr() -> Option<*const c_void> {
+    entries().find_map(|entry| match entry {
+        Entry::SysinfoEhdr(p) if !p.is_null() => Some(p),
+        _ => None,
+    })
+}
+
+/// Return the `AT_MINSIGSTKSZ` value, the minimum stack size the kernel
+/// needs for delivering a signal.
+#[inline]
+pub fn minsigstksz() -> Option<usize> {
+    get(AT_MINSIGSTKSZ)
+}
+
+/// Return the whole auxv as a slice of words, including the terminating
+/// `AT_NULL` entry, for passing to code that wants it in its raw form.
+pub fn as_words() -> &'static [usize] {
+    let ptr = AUXV.load(Ordering::Relaxed);
+    if ptr.is_null() {
+        return &[];
+    }
+
+    let mut len = 0;
+    // SAFETY: `ptr` points into the kernel-provided auxv, which is terminated
+    // by an `AT_NULL` entry.
+    unsafe {
+        while *ptr.add(len) != AT_NULL {
+            len += 2;
+        }
+        slice::from_raw_parts(ptr, len + 2)
+    }
+}
@@ -0,0 +1,140 @@
+//! Process-shared barriers.
+//!
+//! Like mutexes, `pthread_barrier_*` are bound to the implementations here,
+//! for C and Rust code alike, so that `PTHREAD_PROCESS_SHARED` barriers can
+//! be placed in shared memory. See the mutex module for details.
+
+use core::ffi::{c_int, c_uint};
+use core::mem::size_of;
+use core::sync::atomic::{AtomicU32, Ordering};
+use rustix::thread::futex;
+
+use crate::mutex::{futex_flags, futex_wait, Timeout};
+
+// Bind the standard names to the implementations here.
+link_args!(
+    "-Wl,--defsym=pthread_barrierattr_init=__mustang_pthread_barrierattr_init",
+    "-Wl,--defsym=pthread_barrierattr_destroy=__mustang_pthread_barrierattr_destroy",
+    "-Wl,--defsym=pthread_barrierattr_setpshared=__mustang_pthread_barrierattr_setpshared",
+    "-Wl,--defsym=pthread_barrierattr_getpshared=__mustang_pthread_barrierattr_getpshared",
+    "-Wl,--defsym=pthread_barrier_init=__mustang_pthread_barrier_init",
+    "-Wl,--defsym=pthread_barrier_destroy=__mustang_pthread_barrier_destroy",
+    "-Wl,--defsym=pthread_barrier_wait=__mustang_pthread_barrier_wait",
+);
+
+/// The bit of `pthread_barrierattr_t`, and of a barrier's `attr`, for
+/// `PTHREAD_PROCESS_SHARED`.
+const PSHARED: u32 = 1;
+
+/// Our layout of a `pthread_barrier_t`.
+#[repr(C)]
+struct Barrier {
+    count: u32,
+    /// How many threads are waiting in the current round.
+    waiting: AtomicU32,
+    /// Incremented at the end of each round.
+    seq: AtomicU32,
+    attr: u32,
+}
+
+const _: () = assert!(size_of::<Barrier>() <= size_of::<libc::pthread_barrier_t>());
+const _: () = assert!(size_of::<u32>() <= size_of::<libc::pthread_barrierattr_t>());
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_barrierattr_init(
+    attr: *mut libc::pthread_barrierattr_t,
+) -> c_int {
+    attr.cast::<u32>().write(0);
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_barrierattr_destroy(
+    _attr: *mut libc::pthread_barrierattr_t,
+) -> c_int {
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_barrierattr_setpshared(
+    attr: *mut libc::pthread_barrierattr_t,
+    pshared: c_int,
+) -> c_int {
+    match pshared {
+        libc::PTHREAD_PROCESS_PRIVATE => attr.cast::<u32>().write(0),
+        libc::PTHREAD_PROCESS_SHARED => attr.cast::<u32>().write(PSHARED),
+        _ => return libc::EINVAL,
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_barrierattr_getpshared(
+    attr: *const libc::pthread_barrierattr_t,
+    pshared: *mut c_int,
+) -> c_int {
+    *pshared = if *attr.cast::<u32>() & PSHARED != 0 {
+        libc::PTHREAD_PROCESS_SHARED
+    } else {
+        libc::PTHREAD_PROCESS_PRIVATE
+    };
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_barrier_init(
+    barrier: *mut libc::pthread_barrier_t,
+    attr: *const libc::pthread_barrierattr_t,
+    count: c_uint,
+) -> c_int {
+    if count == 0 {
+        return libc::EINVAL;
+    }
+    let attr = if attr.is_null() {
+        0
+    } else {
+        *attr.cast::<u32>()
+    };
+    barrier.cast::<Barrier>().write(Barrier {
+        count,
+        waiting: AtomicU32::new(0),
+        seq: AtomicU32::new(0),
+        attr,
+    });
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_barrier_destroy(
+    _barrier: *mut libc::pthread_barrier_t,
+) -> c_int {
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_barrier_wait(
+    barrier: *mut libc::pthread_barrier_t,
+) -> c_int {
+    let barrier = &*barrier.cast::<Barrier>();
+    let flags = futex_flags(barrier.attr & PSHARED != 0);
+    let seq = barrier.seq.load(Ordering::Acquire);
+    if barrier.waiting.fetch_add(1, Ordering::AcqRel) + 1 == barrier.count {
+        // The last thread to arrive starts the next round, and releases
+        // this one.
+        barrier.waiting.store(0, Ordering::Relaxed);
+        barrier.seq.fetch_add(1, Ordering::Release);
+        let _ = futex::wake(&barrier.seq, flags, i32::MAX as u32);
+        return libc::PTHREAD_BARRIER_SERIAL_THREAD;
+    }
+    while barrier.seq.load(Ordering::Acquire) == seq {
+        let _ = futex_wait(&barrier.seq, flags, seq, Timeout::Never);
+    }
+    0
+}
@@ -0,0 +1,1263 @@
+//! Thread cancellation.
+//!
+//! `pthread_cancel` sends the second reserved real-time signal to the
+//! target thread. Its handler marks cancellation as pending and, if the
+//! thread has cancellation enabled, decides whether to act on it now:
+//! always with `PTHREAD_CANCEL_ASYNCHRONOUS`, and with the default
+//! `PTHREAD_CANCEL_DEFERRED` only when the thread is in a cancellation
+//! point. The cancellation points are `pthread_testcancel`,
+//! `pthread_cond_wait`, `pthread_cond_timedwait`, `sem_wait` and
+//! `sem_timedwait`. Each acts on pending cancellation when it's called, and
+//! sets a per-thread flag while it runs, which tells the handler that the
+//! thread is in one. Other functions, such as `pthread_join`, aren't
+//! cancellation points.
+//!
+//! With the "cancellation-points" feature, so are the functions the
+//! `points` module defines: `read`, `readv`, `write`, `writev`, `open`,
+//! `openat`, `creat`, `close`, `fsync`, `fdatasync`, `nanosleep`,
+//! `clock_nanosleep`, `poll`, `wait`, `waitpid`, `accept`, `accept4`,
+//! `connect`, `send`, `sendto`, `sendmsg`, `recv`, `recvfrom` and
+//! `recvmsg`. They're bound to the standard names for Rust code as well as
+//! C, so std's I/O goes through them too, which is why they're opt-in.
+//! c-scape's definitions take precedence over weak ones, so the functions
+//! there are named `__mustang_read` and so on, and bound with `--defsym`,
+//! as the mutex module does.
+//!
+//! The handler is installed without `SA_RESTART`, so with deferred
+//! cancellation, a syscall it interrupts in a cancellation point fails with
+//! `EINTR`, and the cancellation point acts on cancellation then. A syscall
+//! which completes keeps its result, and cancellation stays pending. The
+//! `points` module makes its syscalls with `__mustang_syscall_cp`, which
+//! checks a flag the handler sets just before making the syscall, and which
+//! the handler sends to the cancellation code if the signal arrives after
+//! the check and before the syscall starts, so that the thread doesn't
+//! block with cancellation pending. `sem_wait`, `sem_timedwait`, and the
+//! sleeps with the "deterministic" feature don't check, so cancellation
+//! which arrives in the moment before they block takes effect at the next
+//! cancellation point. A thread with cancellation disabled sees `EINTR`
+//! when a cancellation request interrupts it, as it would for any handler
+//! installed without `SA_RESTART`.
+//!
+//! Cancellation points act on cancellation by unwinding from their own
+//! frame with `_Unwind_ForcedUnwind`, running Rust destructors and the
+//! thread's `pthread_cleanup_push` handlers in order. With asynchronous
+//! cancellation, the handler edits the interrupted context so that
+//! returning from the handler enters the cancellation code, rather than
+//! unwinding through the signal frame. On x86_64 and x86, it builds a fake
+//! call frame, described by hand-written CFI, so that forced unwinding
+//! continues from the interrupted frame through the rest of the thread's
+//! stack. On other architectures, the return address of an interrupted
+//! leaf function can't be described that way, so asynchronous cancellation
+//! there only runs the cleanup handlers, as all cancellation does on arm.
+//! Either way, the thread then exits with `PTHREAD_CANCELED`.
+//!
+//! A thread cancelled in `pthread_cond_wait` or `pthread_cond_timedwait`
+//! reacquires the mutex before acting on cancellation, so that its cleanup
+//! handlers run with the mutex held, as POSIX requires. Rather than
+//! interrupting the wait, the handler wakes the condition variable's
+//! waiters, and the cancelled thread acts on cancellation once it has
+//! relocked the mutex.
+//!
+//! Frames unwound by cancellation must allow unwinding: C code compiled
+//! with unwind tables, and Rust functions with the `"C-unwind"` ABI rather
+//! than `"C"`. If unwinding fails partway, the remaining cleanup handlers
+//! still run before the thread exits. Threads spawned with `std::thread`
+//! catch unwinding at their entry, and can't catch a foreign exception, so
+//! cancellation is for threads created with `pthread_create`.
+
+use core::cell::{Cell, UnsafeCell};
+use core::ffi::{c_int, c_void};
+use core::mem::zeroed;
+use core::ptr::{null, null_mut};
+use core::sync::atomic::{compiler_fence, AtomicBool, AtomicU32, Ordering};
+use rustix::io;
+use rustix::thread::futex;
+
+use crate::mutex::futex_flags;
+use crate::signal::RESERVED;
+
+/// The signal `pthread_cancel` sends.
+const SIGCANCEL: c_int = RESERVED[1];
+
+const PTHREAD_CANCEL_ENABLE: c_int = 0;
+const PTHREAD_CANCEL_DISABLE: c_int = 1;
+const PTHREAD_CANCEL_DEFERRED: c_int = 0;
+const PTHREAD_CANCEL_ASYNCHRONOUS: c_int = 1;
+
+/// The value a cancelled thread exits with.
+const PTHREAD_CANCELED: *mut c_void = -1_isize as *mut c_void;
+
+/// glibc's `struct _pthread_cleanup_buffer`, which the `pthread_cleanup_push`
+/// macro allocates in the caller's frame.
+#[repr(C)]
+struct CleanupBuffer {
+    routine: extern "C" fn(*mut c_void),
+    arg: *mut c_void,
+    #[allow(dead_code)]
+    canceltype: c_int,
+    prev: *mut CleanupBuffer,
+}
+
+#[thread_local]
+static STATE: Cell<c_int> = Cell::new(PTHREAD_CANCEL_ENABLE);
+#[thread_local]
+static TYPE: Cell<c_int> = Cell::new(PTHREAD_CANCEL_DEFERRED);
+#[thread_local]
+static PENDING: Cell<bool> = Cell::new(false);
+/// Set once the thread has started acting on cancellation.
+#[thread_local]
+static CANCELING: Cell<bool> = Cell::new(false);
+/// The innermost cleanup buffer.
+#[thread_local]
+static CLEANUP: Cell<*mut CleanupBuffer> = Cell::new(null_mut());
+/// Set while the thread is in a cancellation point.
+#[thread_local]
+static IN_POINT: Cell<bool> = Cell::new(false);
+/// The futex word of the condition variable the thread is waiting on, and
+/// whether it's process-shared, or null.
+#[thread_local]
+static COND: Cell<(*const AtomicU32, bool)> = Cell::new((null(), false));
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Install the `SIGCANCEL` handler, if it isn't installed already.
+fn install() -> c_int {
+    if INSTALLED.load(Ordering::Acquire) {
+        return 0;
+    }
+    // Racing installations install the same handler.
+    unsafe {
+        let mut action: libc::sigaction = zeroed();
+        action.sa_sigaction = handle as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(SIGCANCEL, &action, null_mut()) != 0 {
+            return *libc::__errno_location();
+        }
+    }
+    INSTALLED.store(true, Ordering::Release);
+    0
+}
+
+unsafe extern "C" fn handle(_sig: c_int, _info: *mut libc::siginfo_t, context: *mut c_void) {
+    if CANCELING.get() {
+        return;
+    }
+    PENDING.set(true);
+    if STATE.get() != PTHREAD_CANCEL_ENABLE {
+        return;
+    }
+
+    let errno = *libc::__errno_location();
+    let (seq, pshared) = COND.get();
+    if !seq.is_null() {
+        // Wake the waiter, which acts on cancellation once it has the mutex
+        // again.
+        (*seq).fetch_add(1, Ordering::Relaxed);
+        let _ = futex::wake(&*seq, futex_flags(pshared), i32::MAX as u32);
+    } else if TYPE.get() == PTHREAD_CANCEL_ASYNCHRONOUS {
+        CANCELING.set(true);
+        redirect(&mut *context.cast::<libc::ucontext_t>());
+    } else if IN_POINT.get() {
+        // A syscall the signal interrupted fails with `EINTR`, and the
+        // cancellation point acts on cancellation then.
+        #[cfg(feature = "cancellation-points")]
+        points::interrupt(&mut *context.cast::<libc::ucontext_t>());
+    }
+    *libc::__errno_location() = errno;
+}
+
+/// Re-send a pending cancellation to the calling thread, if it has
+/// asynchronous cancellation, for its handler to act on.
+unsafe fn kick() {
+    if pending() && TYPE.get() == PTHREAD_CANCEL_ASYNCHRONOUS {
+        libc::pthread_kill(libc::pthread_self(), SIGCANCEL);
+    }
+}
+
+/// Whether the thread should act on cancellation at a cancellation point.
+fn pending() -> bool {
+    PENDING.get() && !CANCELING.get() && STATE.get() == PTHREAD_CANCEL_ENABLE
+}
+
+/// Act on cancellation, if it's pending.
+pub(crate) unsafe fn test() {
+    if pending() {
+        unwind();
+    }
+}
+
+/// Run `f`, a call which may block, as a cancellation point. If `f` fails
+/// with `EINTR` because cancellation interrupted it, act on cancellation.
+pub(crate) unsafe fn point<T>(f: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    let outer = IN_POINT.replace(true);
+    #[cfg(feature = "cancellation-points")]
+    points::INTERRUPTED.set(false);
+    compiler_fence(Ordering::SeqCst);
+    // Cancellation which arrived before the flag was set is pending now.
+    test();
+    let result = f();
+    compiler_fence(Ordering::SeqCst);
+    IN_POINT.set(outer);
+    if let Err(io::Errno::INTR) = result {
+        test();
+    }
+    result
+}
+
+/// Run `wait`, which unlocks a mutex, waits on a condition variable whose
+/// futex word is `seq` while it's the value passed in, and relocks the
+/// mutex, as a cancellation point which acts on cancellation with the mutex
+/// held.
+pub(crate) unsafe fn cond_wait<R>(
+    seq: &AtomicU32,
+    pshared: bool,
+    wait: impl FnOnce(u32) -> R,
+) -> R {
+    COND.set((seq, pshared));
+    compiler_fence(Ordering::SeqCst);
+    // Cancellation from here on changes the value, so the wait returns
+    // immediately, and cancellation from before is pending now.
+    let value = seq.load(Ordering::Relaxed);
+    if pending() {
+        COND.set((null(), false));
+        unwind();
+    }
+    let result = wait(value);
+    compiler_fence(Ordering::SeqCst);
+    COND.set((null(), false));
+    test();
+    result
+}
+
+/// The Thumb state bit in `cpsr`.
+#[cfg(target_arch = "arm")]
+const THUMB: libc::c_ulong = 1 << 5;
+
+// The fake frame `redirect` builds: `__mustang_cancel_entry` runs with the
+// interrupted stack pointer in a callee-saved register, which is its CFA,
+// and that register's interrupted value and the return address, which is
+// the interrupted program counter plus one so that the unwinder looks up
+// the interrupted instruction, saved just below the CFA, or on x86_64, just
+// below the interrupted frame's red zone.
+#[cfg(all(panic = "unwind", target_arch = "x86_64"))]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_cancel_entry,\"ax\",@progbits",
+    ".p2align 4",
+    ".type __mustang_cancel_entry, @function",
+    "__mustang_cancel_entry:",
+    ".cfi_startproc",
+    ".cfi_def_cfa r12, 0",
+    ".cfi_offset r12, -136",
+    ".cfi_offset rip, -144",
+    "call {unwind}",
+    "ud2",
+    ".cfi_endproc",
+    ".size __mustang_cancel_entry, .-__mustang_cancel_entry",
+    ".popsection",
+    unwind = sym unwind,
+);
+
+#[cfg(all(panic = "unwind", target_arch = "x86"))]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_cancel_entry,\"ax\",@progbits",
+    ".p2align 4",
+    ".type __mustang_cancel_entry, @function",
+    "__mustang_cancel_entry:",
+    ".cfi_startproc",
+    ".cfi_def_cfa esi, 0",
+    ".cfi_offset esi, -4",
+    ".cfi_offset eip, -8",
+    "call {unwind}",
+    "ud2",
+    ".cfi_endproc",
+    ".size __mustang_cancel_entry, .-__mustang_cancel_entry",
+    ".popsection",
+    unwind = sym unwind,
+);
+
+#[cfg(all(panic = "unwind", any(target_arch = "x86_64", target_arch = "x86")))]
+extern "C" {
+    fn __mustang_cancel_entry();
+}
+
+/// Make returning from the handler enter the cancellation code.
+#[cfg(all(panic = "unwind", target_arch = "x86_64"))]
+unsafe fn redirect(context: &mut libc::ucontext_t) {
+    let gregs = &mut context.uc_mcontext.gregs;
+    let sp = gregs[libc::REG_RSP as usize] as usize;
+    let save = (sp - 128) as *mut usize;
+    save.sub(1).write(gregs[libc::REG_R12 as usize] as usize);
+    save.sub(2)
+        .write(gregs[libc::REG_RIP as usize] as usize + 1);
+    gregs[libc::REG_R12 as usize] = sp as i64;
+    gregs[libc::REG_RSP as usize] = (save.sub(2) as usize & !15) as i64;
+    gregs[libc::REG_RIP as usize] = __mustang_cancel_entry as *const () as usize as i64;
+}
+
+#[cfg(all(panic = "unwind", target_arch = "x86"))]
+unsafe fn redirect(context: &mut libc::ucontext_t) {
+    let gregs = &mut context.uc_mcontext.gregs;
+    let sp = gregs[libc::REG_ESP as usize] as usize;
+    let save = sp as *mut usize;
+    save.sub(1).write(gregs[libc::REG_ESI as usize] as usize);
+    save.sub(2)
+        .write(gregs[libc::REG_EIP as usize] as usize + 1);
+    gregs[libc::REG_ESI as usize] = sp as i32;
+    gregs[libc::REG_ESP as usize] = (save.sub(2) as usize & !15) as i32;
+    gregs[libc::REG_EIP as usize] = __mustang_cancel_entry as *const () as usize as i32;
+}
+
+#[cfg(all(not(panic = "unwind"), target_arch = "x86_64"))]
+unsafe fn redirect(context: &mut libc::ucontext_t) {
+    let gregs = &mut context.uc_mcontext.gregs;
+    let sp = gregs[libc::REG_RSP as usize] as usize;
+    gregs[libc::REG_RSP as usize] = (((sp - 128) & !15) - 8) as i64;
+    gregs[libc::REG_RIP as usize] = canceled as *const () as usize as i64;
+}
+
+#[cfg(all(not(panic = "unwind"), target_arch = "x86"))]
+unsafe fn redirect(context: &mut libc::ucontext_t) {
+    let gregs = &mut context.uc_mcontext.gregs;
+    let sp = gregs[libc::REG_ESP as usize] as usize;
+    gregs[libc::REG_ESP as usize] = ((sp & !15) - 4) as i32;
+    gregs[libc::REG_EIP as usize] = canceled as *const () as usize as i32;
+}
+
+#[cfg(target_arch = "aarch64")]
+unsafe fn redirect(context: &mut libc::ucontext_t) {
+    let mcontext = &mut context.uc_mcontext;
+    mcontext.sp &= !15;
+    mcontext.regs[30] = 0;
+    mcontext.pc = canceled as *const () as usize as u64;
+}
+
+#[cfg(target_arch = "riscv64")]
+unsafe fn redirect(context: &mut libc::ucontext_t) {
+    let gregs = &mut context.uc_mcontext.__gregs;
+    gregs[2] &= !15;
+    gregs[1] = 0;
+    gregs[0] = canceled as *const () as usize as _;
+}
+
+#[cfg(target_arch = "arm")]
+unsafe fn redirect(context: &mut libc::ucontext_t) {
+    let mcontext = &mut context.uc_mcontext;
+    let entry = canceled as *const () as usize as libc::c_ulong;
+    mcontext.arm_sp &= !7;
+    mcontext.arm_lr = 0;
+    mcontext.arm_pc = entry & !1;
+    if entry & 1 != 0 {
+        mcontext.arm_cpsr |= THUMB;
+    } else {
+        mcontext.arm_cpsr &= !THUMB;
+    }
+}
+
+/// Where cancelled threads go from the handler when they don't unwind.
+#[cfg(not(all(panic = "unwind", any(target_arch = "x86_64", target_arch = "x86"))))]
+unsafe extern "C" fn canceled() -> ! {
+    exit_canceled()
+}
+
+/// Run the cleanup handlers whose buffers are below `limit`, innermost
+/// first.
+unsafe fn run_cleanups(limit: usize) {
+    loop {
+        let buffer = CLEANUP.get();
+        if buffer.is_null() || buffer as usize >= limit {
+            break;
+        }
+        CLEANUP.set((*buffer).prev);
+        ((*buffer).routine)((*buffer).arg);
+    }
+}
+
+/// Run the remaining cleanup handlers, and exit the thread.
+unsafe fn exit_canceled() -> ! {
+    run_cleanups(usize::MAX);
+    libc::pthread_exit(PTHREAD_CANCELED)
+}
+
+/// Act on cancellation.
+#[cfg(all(panic = "unwind", not(target_arch = "arm")))]
+unsafe extern "C-unwind" fn unwind() -> ! {
+    CANCELING.set(true);
+    let exception = EXCEPTION.get();
+    exception.write(UnwindException {
+        class: CANCEL_CLASS,
+        cleanup: None,
+        private: [0; 6],
+    });
+    _Unwind_ForcedUnwind(exception, stop, null_mut());
+    exit_canceled()
+}
+
+#[cfg(not(all(panic = "unwind", not(target_arch = "arm"))))]
+unsafe extern "C-unwind" fn unwind() -> ! {
+    CANCELING.set(true);
+    exit_canceled()
+}
+
+/// The exception class of cancellation unwinding: "MUSTCNCL".
+#[cfg(all(panic = "unwind", not(target_arch = "arm")))]
+const CANCEL_CLASS: u64 = u64::from_be_bytes(*b"MUSTCNCL");
+
+#[cfg(all(panic = "unwind", not(target_arch = "arm")))]
+// The unwinder reads these fields.
+#[allow(dead_code)]
+#[repr(C, align(16))]
+struct UnwindException {
+    class: u64,
+    cleanup: Option<unsafe extern "C" fn(c_int, *mut UnwindException)>,
+    private: [usize; 6],
+}
+
+#[cfg(all(panic = "unwind", not(target_arch = "arm")))]
+#[thread_local]
+static EXCEPTION: UnsafeCell<UnwindException> = UnsafeCell::new(UnwindException {
+    class: 0,
+    cleanup: None,
+    private: [0; 6],
+});
+
+#[cfg(all(panic = "unwind", not(target_arch = "arm")))]
+const _URC_NO_REASON: c_int = 0;
+#[cfg(all(panic = "unwind", not(target_arch = "arm")))]
+const _UA_END_OF_STACK: c_int = 16;
+
+#[cfg(all(panic = "unwind", not(target_arch = "arm")))]
+type StopFn = unsafe extern "C" fn(
+    c_int,
+    c_int,
+    u64,
+    *mut UnwindException,
+    *mut c_void,
+    *mut c_void,
+) -> c_int;
+
+#[cfg(all(panic = "unwind", not(target_arch = "arm")))]
+extern "C-unwind" {
+    fn _Unwind_ForcedUnwind(
+        exception: *mut UnwindException,
+        stop: StopFn,
+        arg: *mut c_void,
+    ) -> c_int;
+}
+
+#[cfg(all(panic = "unwind", not(target_arch = "arm")))]
+extern "C" {
+    fn _Unwind_GetCFA(context: *mut c_void) -> usize;
+}
+
+/// Called for each frame as it's unwound: run the cleanup handlers pushed
+/// by frames up to this one, whose buffers are below its CFA.
+#[cfg(all(panic = "unwind", not(target_arch = "arm")))]
+unsafe extern "C" fn stop(
+    _version: c_int,
+    actions: c_int,
+    _class: u64,
+    _exception: *mut UnwindException,
+    context: *mut c_void,
+    _arg: *mut c_void,
+) -> c_int {
+    if actions & _UA_END_OF_STACK != 0 {
+        exit_canceled();
+    }
+    run_cleanups(_Unwind_GetCFA(context));
+    _URC_NO_REASON
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn pthread_cancel(thread: libc::pthread_t) -> c_int {
+    match install() {
+        0 => libc::pthread_kill(thread, SIGCANCEL),
+        err => err,
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn pthread_setcancelstate(state: c_int, old: *mut c_int) -> c_int {
+    if state != PTHREAD_CANCEL_ENABLE && state != PTHREAD_CANCEL_DISABLE {
+        return libc::EINVAL;
+    }
+    let previous = STATE.replace(state);
+    if !old.is_null() {
+        *old = previous;
+    }
+    if state == PTHREAD_CANCEL_ENABLE {
+        kick();
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn pthread_setcanceltype(type_: c_int, old: *mut c_int) -> c_int {
+    if type_ != PTHREAD_CANCEL_DEFERRED && type_ != PTHREAD_CANCEL_ASYNCHRONOUS {
+        return libc::EINVAL;
+    }
+    let previous = TYPE.replace(type_);
+    if !old.is_null() {
+        *old = previous;
+    }
+    if type_ == PTHREAD_CANCEL_ASYNCHRONOUS {
+        kick();
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C-unwind" fn pthread_testcancel() {
+    test();
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn _pthread_cleanup_push(
+    buffer: *mut CleanupBuffer,
+    routine: extern "C" fn(*mut c_void),
+    arg: *mut c_void,
+) {
+    buffer.write(CleanupBuffer {
+        routine,
+        arg,
+        canceltype: TYPE.get(),
+        prev: CLEANUP.get(),
+    });
+    CLEANUP.set(buffer);
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn _pthread_cleanup_pop(buffer: *mut CleanupBuffer, execute: c_int) {
+    CLEANUP.set((*buffer).prev);
+    if execute != 0 {
+        ((*buffer).routine)((*buffer).arg);
+    }
+}
+
+#[cfg(feature = "cancellation-points")]
+mod points {
+    use super::{point, unwind};
+    #[cfg(not(target_arch = "x86"))]
+    use crate::syscall::nr::{ACCEPT4, CONNECT, RECVMSG, SENDMSG};
+    use crate::syscall::{check, nr, KernelTimespec};
+    use core::cell::Cell;
+    use core::ffi::{c_char, c_int, c_uint, c_void};
+    use core::mem::zeroed;
+    use core::ptr::{null, null_mut};
+    use rustix::io;
+
+    // Bind the standard names to the implementations here; see above.
+    link_args!(
+        "-Wl,--defsym=read=__mustang_read",
+        "-Wl,--defsym=readv=__mustang_readv",
+        "-Wl,--defsym=write=__mustang_write",
+        "-Wl,--defsym=writev=__mustang_writev",
+        "-Wl,--defsym=open=__mustang_open",
+        "-Wl,--defsym=openat=__mustang_openat",
+        "-Wl,--defsym=creat=__mustang_creat",
+        "-Wl,--defsym=close=__mustang_close",
+        "-Wl,--defsym=fsync=__mustang_fsync",
+        "-Wl,--defsym=fdatasync=__mustang_fdatasync",
+        "-Wl,--defsym=nanosleep=__mustang_nanosleep",
+        "-Wl,--defsym=clock_nanosleep=__mustang_clock_nanosleep",
+        "-Wl,--defsym=poll=__mustang_poll",
+        "-Wl,--defsym=wait=__mustang_wait",
+        "-Wl,--defsym=waitpid=__mustang_waitpid",
+        "-Wl,--defsym=accept=__mustang_accept",
+        "-Wl,--defsym=accept4=__mustang_accept4",
+        "-Wl,--defsym=connect=__mustang_connect",
+        "-Wl,--defsym=send=__mustang_send",
+        "-Wl,--defsym=sendto=__mustang_sendto",
+        "-Wl,--defsym=sendmsg=__mustang_sendmsg",
+        "-Wl,--defsym=recv=__mustang_recv",
+        "-Wl,--defsym=recvfrom=__mustang_recvfrom",
+        "-Wl,--defsym=recvmsg=__mustang_recvmsg",
+    );
+
+    /// Set by the handler when it finds the thread in a cancellation point,
+    /// and cleared as each one starts.
+    #[thread_local]
+    pub(super) static INTERRUPTED: Cell<bool> = Cell::new(false);
+
+    // `__mustang_syscall_cp` makes a syscall, with the number and up to five
+    // arguments after the address of `INTERRUPTED`, unless the flag is set,
+    // in which case it acts on cancellation instead. A signal which arrives
+    // after it checks the flag and before the syscall starts finds the
+    // thread between `__mustang_cp_begin` and `__mustang_cp_end`, and the
+    // handler sends it to `__mustang_cp_cancel`, so that it doesn't block.
+    // Once the syscall starts, the signal interrupts it, or it completes and
+    // keeps its result. `__mustang_cp_cancel` leaves the stack as it was on
+    // entry, so unwinding continues from the caller.
+    extern "C-unwind" {
+        fn __mustang_syscall_cp(
+            interrupted: *const bool,
+            nr: usize,
+            a0: usize,
+            a1: usize,
+            a2: usize,
+            a3: usize,
+            a4: usize,
+        ) -> usize;
+        fn __mustang_cp_begin();
+        fn __mustang_cp_end();
+        fn __mustang_cp_cancel();
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    core::arch::global_asm!(
+        ".pushsection .text.__mustang_syscall_cp,\"ax\",@progbits",
+        ".hidden __mustang_syscall_cp",
+        ".hidden __mustang_cp_begin",
+        ".hidden __mustang_cp_end",
+        ".hidden __mustang_cp_cancel",
+        ".p2align 4",
+        ".type __mustang_syscall_cp, @function",
+        "__mustang_syscall_cp:",
+        ".cfi_startproc",
+        "__mustang_cp_begin:",
+        "cmp byte ptr [rdi], 0",
+        "jne __mustang_cp_cancel",
+        "mov rax, rsi",
+        "mov rdi, rdx",
+        "mov rsi, rcx",
+        "mov rdx, r8",
+        "mov r10, r9",
+        "mov r8, qword ptr [rsp + 8]",
+        "syscall",
+        "__mustang_cp_end:",
+        "ret",
+        "__mustang_cp_cancel:",
+        "jmp {unwind}",
+        ".cfi_endproc",
+        ".size __mustang_syscall_cp, .-__mustang_syscall_cp",
+        ".popsection",
+        unwind = sym unwind,
+    );
+
+    // `ebx`, `esi` and `edi` are callee-saved.
+    #[cfg(target_arch = "x86")]
+    core::arch::global_asm!(
+        ".pushsection .text.__mustang_syscall_cp,\"ax\",@progbits",
+        ".hidden __mustang_syscall_cp",
+        ".hidden __mustang_cp_begin",
+        ".hidden __mustang_cp_end",
+        ".hidden __mustang_cp_cancel",
+        ".p2align 4",
+        ".type __mustang_syscall_cp, @function",
+        "__mustang_syscall_cp:",
+        ".cfi_startproc",
+        "push edi",
+        ".cfi_adjust_cfa_offset 4",
+        ".cfi_rel_offset edi, 0",
+        "push esi",
+        ".cfi_adjust_cfa_offset 4",
+        ".cfi_rel_offset esi, 0",
+        "push ebx",
+        ".cfi_adjust_cfa_offset 4",
+        ".cfi_rel_offset ebx, 0",
+        "__mustang_cp_begin:",
+        "mov eax, dword ptr [esp + 16]",
+        "cmp byte ptr [eax], 0",
+        "jne __mustang_cp_cancel",
+        "mov eax, dword ptr [esp + 20]",
+        "mov ebx, dword ptr [esp + 24]",
+        "mov ecx, dword ptr [esp + 28]",
+        "mov edx, dword ptr [esp + 32]",
+        "mov esi, dword ptr [esp + 36]",
+        "mov edi, dword ptr [esp + 40]",
+        "int 0x80",
+        "__mustang_cp_end:",
+        ".cfi_remember_state",
+        "pop ebx",
+        ".cfi_adjust_cfa_offset -4",
+        ".cfi_restore ebx",
+        "pop esi",
+        ".cfi_adjust_cfa_offset -4",
+        ".cfi_restore esi",
+        "pop edi",
+        ".cfi_adjust_cfa_offset -4",
+        ".cfi_restore edi",
+        "ret",
+        ".cfi_restore_state",
+        ".cfi_def_cfa_offset 16",
+        "__mustang_cp_cancel:",
+        "pop ebx",
+        ".cfi_def_cfa_offset 12",
+        ".cfi_restore ebx",
+        "pop esi",
+        ".cfi_def_cfa_offset 8",
+        ".cfi_restore esi",
+        "pop edi",
+        ".cfi_def_cfa_offset 4",
+        ".cfi_restore edi",
+        "jmp {unwind}",
+        ".cfi_endproc",
+        ".size __mustang_syscall_cp, .-__mustang_syscall_cp",
+        ".popsection",
+        unwind = sym unwind,
+    );
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::global_asm!(
+        ".pushsection .text.__mustang_syscall_cp,\"ax\",@progbits",
+        ".hidden __mustang_syscall_cp",
+        ".hidden __mustang_cp_begin",
+        ".hidden __mustang_cp_end",
+        ".hidden __mustang_cp_cancel",
+        ".p2align 2",
+        ".type __mustang_syscall_cp, @function",
+        "__mustang_syscall_cp:",
+        ".cfi_startproc",
+        "__mustang_cp_begin:",
+        "ldrb w9, [x0]",
+        "cbnz w9, __mustang_cp_cancel",
+        "mov x8, x1",
+        "mov x0, x2",
+        "mov x1, x3",
+        "mov x2, x4",
+        "mov x3, x5",
+        "mov x4, x6",
+        "svc #0",
+        "__mustang_cp_end:",
+        "ret",
+        "__mustang_cp_cancel:",
+        "b {unwind}",
+        ".cfi_endproc",
+        ".size __mustang_syscall_cp, .-__mustang_syscall_cp",
+        ".popsection",
+        unwind = sym unwind,
+    );
+
+    #[cfg(target_arch = "riscv64")]
+    core::arch::global_asm!(
+        ".pushsection .text.__mustang_syscall_cp,\"ax\",@progbits",
+        ".hidden __mustang_syscall_cp",
+        ".hidden __mustang_cp_begin",
+        ".hidden __mustang_cp_end",
+        ".hidden __mustang_cp_cancel",
+        ".p2align 2",
+        ".type __mustang_syscall_cp, @function",
+        "__mustang_syscall_cp:",
+        ".cfi_startproc",
+        "__mustang_cp_begin:",
+        "lbu t0, 0(a0)",
+        "bnez t0, __mustang_cp_cancel",
+        "mv a7, a1",
+        "mv a0, a2",
+        "mv a1, a3",
+        "mv a2, a4",
+        "mv a3, a5",
+        "mv a4, a6",
+        "ecall",
+        "__mustang_cp_end:",
+        "ret",
+        "__mustang_cp_cancel:",
+        "tail {unwind}",
+        ".cfi_endproc",
+        ".size __mustang_syscall_cp, .-__mustang_syscall_cp",
+        ".popsection",
+        unwind = sym unwind,
+    );
+
+    // `r4`, `r5` and `r7` are callee-saved, and the fifth argument is
+    // above them on the stack.
+    #[cfg(target_arch = "arm")]
+    core::arch::global_asm!(
+        ".pushsection .text.__mustang_syscall_cp,\"ax\",%progbits",
+        ".hidden __mustang_syscall_cp",
+        ".hidden __mustang_cp_begin",
+        ".hidden __mustang_cp_end",
+        ".hidden __mustang_cp_cancel",
+        ".p2align 2",
+        ".type __mustang_syscall_cp, %function",
+        "__mustang_syscall_cp:",
+        ".cfi_startproc",
+        "push {{r4, r5, r7, lr}}",
+        ".cfi_adjust_cfa_offset 16",
+        ".cfi_rel_offset r4, 0",
+        ".cfi_rel_offset r5, 4",
+        ".cfi_rel_offset r7, 8",
+        ".cfi_rel_offset lr, 12",
+        "__mustang_cp_begin:",
+        "ldrb r4, [r0]",
+        "cmp r4, #0",
+        "bne __mustang_cp_cancel",
+        "mov r7, r1",
+        "mov r0, r2",
+        "mov r1, r3",
+        "ldr r2, [sp, #16]",
+        "ldr r3, [sp, #20]",
+        "ldr r4, [sp, #24]",
+        "svc #0",
+        "__mustang_cp_end:",
+        "pop {{r4, r5, r7, pc}}",
+        "__mustang_cp_cancel:",
+        "pop {{r4, r5, r7, lr}}",
+        ".cfi_adjust_cfa_offset -16",
+        ".cfi_restore r4",
+        ".cfi_restore r5",
+        ".cfi_restore r7",
+        ".cfi_restore lr",
+        "b {unwind}",
+        ".cfi_endproc",
+        ".size __mustang_syscall_cp, .-__mustang_syscall_cp",
+        ".popsection",
+        unwind = sym unwind,
+    );
+
+    /// The interrupted program counter in `context`.
+    #[cfg(target_arch = "x86_64")]
+    fn pc(context: &mut libc::ucontext_t) -> &mut i64 {
+        &mut context.uc_mcontext.gregs[libc::REG_RIP as usize]
+    }
+
+    #[cfg(target_arch = "x86")]
+    fn pc(context: &mut libc::ucontext_t) -> &mut i32 {
+        &mut context.uc_mcontext.gregs[libc::REG_EIP as usize]
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    fn pc(context: &mut libc::ucontext_t) -> &mut u64 {
+        &mut context.uc_mcontext.pc
+    }
+
+    #[cfg(target_arch = "riscv64")]
+    fn pc(context: &mut libc::ucontext_t) -> &mut libc::c_ulong {
+        &mut context.uc_mcontext.__gregs[0]
+    }
+
+    #[cfg(target_arch = "arm")]
+    fn pc(context: &mut libc::ucontext_t) -> &mut libc::c_ulong {
+        &mut context.uc_mcontext.arm_pc
+    }
+
+    /// Called by the handler when it finds the thread in a cancellation
+    /// point with deferred cancellation: make `__mustang_syscall_cp` act on
+    /// cancellation rather than start its syscall.
+    pub(super) fn interrupt(context: &mut libc::ucontext_t) {
+        INTERRUPTED.set(true);
+        let pc = pc(context);
+        let begin = __mustang_cp_begin as *const () as usize;
+        let end = __mustang_cp_end as *const () as usize;
+        if (begin..end).contains(&(*pc as usize)) {
+            *pc = __mustang_cp_cancel as *const () as usize as _;
+        }
+    }
+
+    /// Make a syscall in a cancellation point.
+    unsafe fn syscall(nr: u32, args: [usize; 5]) -> io::Result<usize> {
+        check(__mustang_syscall_cp(
+            INTERRUPTED.as_ptr(),
+            nr as usize,
+            args[0],
+            args[1],
+            args[2],
+            args[3],
+            args[4],
+        ))
+    }
+
+    /// Convert a syscall's result into a C return value, setting `errno` on
+    /// failure.
+    unsafe fn ret(result: io::Result<usize>) -> isize {
+        match result {
+            Ok(value) => value as isize,
+            Err(err) => {
+                *libc::__errno_location() = err.raw_os_error();
+                -1
+            }
+        }
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_read(fd: c_int, buf: *mut c_void, count: usize) -> isize {
+        ret(point(|| {
+            syscall(nr::READ, [fd as usize, buf as usize, count, 0, 0])
+        }))
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_readv(
+        fd: c_int,
+        iov: *const libc::iovec,
+        iovcnt: c_int,
+    ) -> isize {
+        let args = [fd as usize, iov as usize, iovcnt as usize, 0, 0];
+        ret(point(|| syscall(nr::READV, args)))
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_write(
+        fd: c_int,
+        buf: *const c_void,
+        count: usize,
+    ) -> isize {
+        ret(point(|| {
+            syscall(nr::WRITE, [fd as usize, buf as usize, count, 0, 0])
+        }))
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_writev(
+        fd: c_int,
+        iov: *const libc::iovec,
+        iovcnt: c_int,
+    ) -> isize {
+        let args = [fd as usize, iov as usize, iovcnt as usize, 0, 0];
+        ret(point(|| syscall(nr::WRITEV, args)))
+    }
+
+    /// Whether `open` and `openat` take a mode argument with `flags`.
+    fn takes_mode(flags: c_int) -> bool {
+        flags & libc::O_CREAT != 0 || flags & libc::O_TMPFILE == libc::O_TMPFILE
+    }
+
+    unsafe fn openat(dirfd: c_int, path: *const c_char, flags: c_int, mode: c_uint) -> c_int {
+        // Always enable support for large files, as rustix does.
+        let flags = flags | libc::O_LARGEFILE;
+        let args = [
+            dirfd as usize,
+            path as usize,
+            flags as usize,
+            mode as usize,
+            0,
+        ];
+        ret(point(|| syscall(nr::OPENAT, args))) as c_int
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_open(
+        path: *const c_char,
+        flags: c_int,
+        mut args: ...
+    ) -> c_int {
+        let mode = if takes_mode(flags) {
+            args.arg::<c_uint>()
+        } else {
+            0
+        };
+        openat(libc::AT_FDCWD, path, flags, mode)
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_openat(
+        dirfd: c_int,
+        path: *const c_char,
+        flags: c_int,
+        mut args: ...
+    ) -> c_int {
+        let mode = if takes_mode(flags) {
+            args.arg::<c_uint>()
+        } else {
+            0
+        };
+        openat(dirfd, path, flags, mode)
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_creat(path: *const c_char, mode: libc::mode_t) -> c_int {
+        openat(
+            libc::AT_FDCWD,
+            path,
+            libc::O_CREAT | libc::O_WRONLY | libc::O_TRUNC,
+            mode as c_uint,
+        )
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_close(fd: c_int) -> c_int {
+        ret(point(|| syscall(nr::CLOSE, [fd as usize, 0, 0, 0, 0]))) as c_int
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_fsync(fd: c_int) -> c_int {
+        ret(point(|| syscall(nr::FSYNC, [fd as usize, 0, 0, 0, 0]))) as c_int
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_fdatasync(fd: c_int) -> c_int {
+        ret(point(|| syscall(nr::FDATASYNC, [fd as usize, 0, 0, 0, 0]))) as c_int
+    }
+
+    /// `clock_nanosleep`, with the kernel's 64-bit `timespec`s, returning an
+    /// error number.
+    // `time_t` and `c_long` are 32-bit on some targets.
+    #[allow(clippy::useless_conversion)]
+    unsafe fn sleep(
+        clock: libc::clockid_t,
+        flags: c_int,
+        request: *const libc::timespec,
+        remain: *mut libc::timespec,
+    ) -> c_int {
+        let request = KernelTimespec {
+            tv_sec: (*request).tv_sec.into(),
+            tv_nsec: (*request).tv_nsec.into(),
+        };
+        let mut left = KernelTimespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        let result = point(|| {
+            // With the "deterministic" feature, sleeping advances the virtual
+            // clock.
+            #[cfg(feature = "deterministic")]
+            let result = crate::deterministic::clock_nanosleep(clock, flags, &request, &mut left);
+            #[cfg(not(feature = "deterministic"))]
+            let result = syscall(
+                nr::CLOCK_NANOSLEEP,
+                [
+                    clock as usize,
+                    flags as usize,
+                    &request as *const KernelTimespec as usize,
+                    &mut left as *mut KernelTimespec as usize,
+                    0,
+                ],
+            );
+            result
+        });
+        match result {
+            Ok(_) => 0,
+            Err(err) => {
+                if err == io::Errno::INTR && flags & libc::TIMER_ABSTIME == 0 && !remain.is_null() {
+                    (*remain).tv_sec = left.tv_sec as _;
+                    (*remain).tv_nsec = left.tv_nsec as _;
+                }
+                err.raw_os_error()
+            }
+        }
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_nanosleep(
+        request: *const libc::timespec,
+        remain: *mut libc::timespec,
+    ) -> c_int {
+        // Linux's `nanosleep` measures with `CLOCK_MONOTONIC`.
+        match sleep(libc::CLOCK_MONOTONIC, 0, request, remain) {
+            0 => 0,
+            err => {
+                *libc::__errno_location() = err;
+                -1
+            }
+        }
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_clock_nanosleep(
+        clock: libc::clockid_t,
+        flags: c_int,
+        request: *const libc::timespec,
+        remain: *mut libc::timespec,
+    ) -> c_int {
+        sleep(clock, flags, request, remain)
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_poll(
+        fds: *mut libc::pollfd,
+        nfds: libc::nfds_t,
+        timeout: c_int,
+    ) -> c_int {
+        let ts = KernelTimespec {
+            tv_sec: (timeout / 1000).into(),
+            tv_nsec: (timeout % 1000 * 1_000_000).into(),
+        };
+        let ts = if timeout < 0 {
+            null()
+        } else {
+            &ts as *const KernelTimespec
+        };
+        let args = [fds as usize, nfds as usize, ts as usize, 0, 0];
+        ret(point(|| syscall(nr::PPOLL, args))) as c_int
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_wait(status: *mut c_int) -> libc::pid_t {
+        __mustang_waitpid(-1, status, 0)
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_waitpid(
+        pid: libc::pid_t,
+        status: *mut c_int,
+        options: c_int,
+    ) -> libc::pid_t {
+        let args = [pid as usize, status as usize, options as usize, 0, 0];
+        ret(point(|| syscall(nr::WAIT4, args))) as libc::pid_t
+    }
+
+    // On x86, the `socketcall` call numbers.
+    #[cfg(target_arch = "x86")]
+    const CONNECT: u32 = 3;
+    #[cfg(target_arch = "x86")]
+    const SENDMSG: u32 = 16;
+    #[cfg(target_arch = "x86")]
+    const RECVMSG: u32 = 17;
+    #[cfg(target_arch = "x86")]
+    const ACCEPT4: u32 = 18;
+
+    /// Make a socket syscall.
+    #[cfg(not(target_arch = "x86"))]
+    unsafe fn socketcall(call: u32, args: [usize; 4]) -> io::Result<usize> {
+        syscall(call, [args[0], args[1], args[2], args[3], 0])
+    }
+
+    /// Make a socket syscall through `socketcall`, as rustix does on x86, so
+    /// that `mustang::replay` records it.
+    #[cfg(target_arch = "x86")]
+    unsafe fn socketcall(call: u32, args: [usize; 4]) -> io::Result<usize> {
+        syscall(
+            nr::SOCKETCALL,
+            [call as usize, args.as_ptr() as usize, 0, 0, 0],
+        )
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_accept(
+        fd: c_int,
+        addr: *mut libc::sockaddr,
+        len: *mut libc::socklen_t,
+    ) -> c_int {
+        __mustang_accept4(fd, addr, len, 0)
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_accept4(
+        fd: c_int,
+        addr: *mut libc::sockaddr,
+        len: *mut libc::socklen_t,
+        flags: c_int,
+    ) -> c_int {
+        let args = [fd as usize, addr as usize, len as usize, flags as usize];
+        ret(point(|| socketcall(ACCEPT4, args))) as c_int
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_connect(
+        fd: c_int,
+        addr: *const libc::sockaddr,
+        len: libc::socklen_t,
+    ) -> c_int {
+        let args = [fd as usize, addr as usize, len as usize, 0];
+        ret(point(|| socketcall(CONNECT, args))) as c_int
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_sendmsg(
+        fd: c_int,
+        msg: *const libc::msghdr,
+        flags: c_int,
+    ) -> isize {
+        let args = [fd as usize, msg as usize, flags as usize, 0];
+        ret(point(|| socketcall(SENDMSG, args)))
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_recvmsg(
+        fd: c_int,
+        msg: *mut libc::msghdr,
+        flags: c_int,
+    ) -> isize {
+        let args = [fd as usize, msg as usize, flags as usize, 0];
+        ret(point(|| socketcall(RECVMSG, args)))
+    }
+
+    // `sendto` and `recvfrom` take six arguments, so they're made with
+    // `sendmsg` and `recvmsg`.
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_send(
+        fd: c_int,
+        buf: *const c_void,
+        len: usize,
+        flags: c_int,
+    ) -> isize {
+        __mustang_sendto(fd, buf, len, flags, null(), 0)
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_sendto(
+        fd: c_int,
+        buf: *const c_void,
+        len: usize,
+        flags: c_int,
+        addr: *const libc::sockaddr,
+        addrlen: libc::socklen_t,
+    ) -> isize {
+        let mut iov = libc::iovec {
+            iov_base: buf.cast_mut(),
+            iov_len: len,
+        };
+        let mut msg: libc::msghdr = zeroed();
+        msg.msg_name = addr.cast_mut().cast();
+        msg.msg_namelen = addrlen;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        __mustang_sendmsg(fd, &msg, flags)
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_recv(
+        fd: c_int,
+        buf: *mut c_void,
+        len: usize,
+        flags: c_int,
+    ) -> isize {
+        __mustang_recvfrom(fd, buf, len, flags, null_mut(), null_mut())
+    }
+
+    #[no_mangle]
+    #[linkage = "weak"]
+    unsafe extern "C-unwind" fn __mustang_recvfrom(
+        fd: c_int,
+        buf: *mut c_void,
+        len: usize,
+        flags: c_int,
+        addr: *mut libc::sockaddr,
+        addrlen: *mut libc::socklen_t,
+    ) -> isize {
+        let mut iov = libc::iovec {
+            iov_base: buf,
+            iov_len: len,
+        };
+        let mut msg: libc::msghdr = zeroed();
+        msg.msg_name = addr.cast();
+        msg.msg_namelen = if addrlen.is_null() { 0 } else { *addrlen };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        let result = __mustang_recvmsg(fd, &mut msg, flags);
+        if result >= 0 && !addrlen.is_null() {
+            *addrlen = msg.msg_namelen;
+        }
+        result
+    }
+}
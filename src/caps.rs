@@ -0,0 +1,276 @@
+//! Inherited, named file descriptors ("preopens").
+//!
+//! Similar to WASI preopens, a launching process can give a mustang program a
+//! table of open directories (or files), each with a name, which the program
+//! then accesses paths through, instead of through the global filesystem
+//! namespace.
+//!
+//! The table is passed in the `MUSTANG_PREOPENS` environment variable, as a
+//! `:`-separated list of `<fd>=<name>` entries, for example
+//! `MUSTANG_PREOPENS=3=/data:4=/tmp`. Names are typically absolute paths, and
+//! can't contain `:`. [`env_value`] formats such a value.
+//!
+//! With the "preopens" feature, mustang reads the table at startup, before
+//! `main` and `.init_array` functions at normal priorities, and marks the fds
+//! close-on-exec so that they aren't leaked to child processes.
+//!
+//! With the "preopens-enforce" feature, mustang additionally uses Landlock to
+//! restrict the process's filesystem access to the preopens, so that for
+//! example `open` of an absolute path outside of them fails with `EACCES`.
+//! The restriction is applied after mustang's other startup functions, and
+//! before `.init_array` functions at normal priorities.
+//! This requires Linux 5.13 or later with Landlock enabled; if it isn't
+//! available, the program aborts at startup rather than run unrestricted.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use rustix::fd::{AsFd, BorrowedFd, OwnedFd};
+use rustix::fs::{Mode, OFlags, ResolveFlags};
+use rustix::io;
+
+/// The name of the environment variable that holds the table.
+pub const PREOPENS_ENV: &str = "MUSTANG_PREOPENS";
+
+/// The maximum number of preopens.
+pub const MAX_PREOPENS: usize = 64;
+
+/// A named, inherited file descriptor.
+#[derive(Debug, Clone, Copy)]
+pub struct Preopen {
+    fd: BorrowedFd<'static>,
+    name: &'static str,
+}
+
+impl Preopen {
+    /// The name the launching process gave this fd.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The file descriptor.
+    #[inline]
+    pub fn fd(&self) -> BorrowedFd<'static> {
+        self.fd
+    }
+}
+
+impl AsFd for Preopen {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd
+    }
+}
+
+struct Table(UnsafeCell<[Option<Preopen>; MAX_PREOPENS]>);
+
+// SAFETY: The table is only written during startup, before `LEN` is
+// published, and is only read after.
+unsafe impl Sync for Table {}
+
+static TABLE: Table = Table(UnsafeCell::new([None; MAX_PREOPENS]));
+static LEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Iterate over the preopens passed to this process.
+pub fn preopens() -> impl Iterator<Item = Preopen> {
+    let len = LEN.load(Ordering::Acquire);
+    // SAFETY: The first `len` entries were written before `LEN` was
+    // published, and are never written again.
+    let table = unsafe { &(&*TABLE.0.get())[..len] };
+    table.iter().map(|preopen| preopen.unwrap())
+}
+
+/// Return the preopen named `name`.
+pub fn get(name: &str) -> Option<Preopen> {
+    preopens().find(|preopen| preopen.name == name)
+}
+
+/// Find the preopen whose name is the longest path prefix of `path`, and
+/// return it along with the rest of `path`, relative to it.
+///
+/// Relative paths only match a preopen named `.`.
+pub fn resolve(path: &str) -> Option<(Preopen, &str)> {
+    let mut best: Option<(Preopen, &str)> = None;
+    for preopen in preopens() {
+        let name = preopen.name.trim_end_matches('/');
+        let rest = if preopen.name == "." && !path.starts_with('/') {
+            path
+        } else if name.is_empty() && path.starts_with('/') {
+            // A preopen named `/`.
+            &path[1..]
+        } else {
+            match path.strip_prefix(name) {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+                _ => continue,
+            }
+        };
+        if best.is_none_or(|(best, _)| best.name.len() < preopen.name.len()) {
+            best = Some((preopen, rest));
+        }
+    }
+    best.map(|(preopen, rest)| {
+        let rest = rest.trim_start_matches('/');
+        (preopen, if rest.is_empty() { "." } else { rest })
+    })
+}
+
+/// Open `path` through the preopen it falls under.
+///
+/// The path is resolved with `openat2` and `RESOLVE_BENEATH`, so `..` and
+/// symlinks can't escape the preopen. If `path` isn't under any preopen, this
+/// fails with `EACCES`.
+pub fn open(path: &str, flags: OFlags, mode: Mode) -> io::Result<OwnedFd> {
+    let (preopen, rest) = resolve(path).ok_or(io::Errno::ACCESS)?;
+    rustix::fs::openat2(
+        preopen.fd,
+        rest,
+        flags | OFlags::CLOEXEC,
+        mode,
+        ResolveFlags::BENEATH | ResolveFlags::NO_MAGICLINKS,
+    )
+}
+
+/// Format a `MUSTANG_PREOPENS` value for passing `preopens` to a child
+/// process.
+///
+/// The fds must not be close-on-exec in the launching process.
+pub fn env_value<'a>(preopens: &'a [(BorrowedFd<'a>, &'a str)]) -> impl fmt::Display + 'a {
+    struct EnvValue<'a>(&'a [(BorrowedFd<'a>, &'a str)]);
+
+    impl fmt::Display for EnvValue<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            for (i, (fd, name)) in self.0.iter().enumerate() {
+                assert!(!name.contains(':'), "preopen names can't contain ':'");
+                if i != 0 {
+                    f.write_str(":")?;
+                }
+                write!(f, "{}={}", rustix::fd::AsRawFd::as_raw_fd(fd), name)?;
+            }
+            Ok(())
+        }
+    }
+
+    EnvValue(preopens)
+}
+
+#[cfg(feature = "preopens")]
+mod startup {
+    use super::{Preopen, LEN, MAX_PREOPENS, PREOPENS_ENV, TABLE};
+    use crate::startup::fail;
+    use core::ffi::{c_char, c_int, CStr};
+    use core::sync::atomic::Ordering;
+    use rustix::fd::BorrowedFd;
+    use rustix::io::FdFlags;
+
+    // This runs after `mustang::args` has installed a structured
+    // environment, if there is one.
+    #[link_section = ".init_array.00002"]
+    #[used]
+    static INIT_PREOPENS: unsafe extern "C" fn(c_int, *mut *mut c_char, *mut *mut c_char) = {
+        unsafe extern "C" fn function(
+            _argc: c_int,
+            _argv: *mut *mut c_char,
+            envp: *mut *mut c_char,
+        ) {
+            if let Some(value) = find_env(envp) {
+                init(value);
+            }
+        }
+        function
+    };
+
+    // This runs after mustang's other startup functions, such as the one
+    // which reads `/proc/self/maps` for the main thread's stack bounds, as
+    // the `sandbox!` macro's does.
+    #[cfg(feature = "preopens-enforce")]
+    #[link_section = ".init_array.00004"]
+    #[used]
+    static INIT_ENFORCE: unsafe extern "C" fn(c_int, *mut *mut c_char, *mut *mut c_char) = {
+        unsafe extern "C" fn function(
+            _argc: c_int,
+            _argv: *mut *mut c_char,
+            _envp: *mut *mut c_char,
+        ) {
+            enforce();
+        }
+        function
+    };
+
+    /// Find the value of `MUSTANG_PREOPENS`.
+    unsafe fn find_env(envp: *mut *mut c_char) -> Option<&'static str> {
+        #[cfg(feature = "memfd-args")]
+        if let Some(block) = crate::args::block() {
+            return block
+                .vars()
+                .find(|(key, _)| *key == PREOPENS_ENV)
+                .map(|(_, value)| value);
+        }
+
+        if envp.is_null() {
+            return None;
+        }
+        let mut ptr = envp;
+        while !(*ptr).is_null() {
+            let var = CStr::from_ptr(*ptr).to_bytes();
+            if let Some(value) = var
+                .strip_prefix(PREOPENS_ENV.as_bytes())
+                .and_then(|rest| rest.strip_prefix(b"="))
+            {
+                return match core::str::from_utf8(value) {
+                    Ok(value) => Some(value),
+                    Err(_) => fail(format_args!("{} isn't valid UTF-8", PREOPENS_ENV)),
+                };
+            }
+            ptr = ptr.add(1);
+        }
+        None
+    }
+
+    /// Parse the table and record it.
+    unsafe fn init(value: &'static str) {
+        let table = &mut *TABLE.0.get();
+        let mut len = 0;
+        for entry in value.split(':').filter(|entry| !entry.is_empty()) {
+            let Some((fd, name)) = entry.split_once('=') else {
+                fail(format_args!("invalid {} entry {:?}", PREOPENS_ENV, entry));
+            };
+            let Ok(fd) = fd.parse::<c_int>() else {
+                fail(format_args!("invalid {} fd {:?}", PREOPENS_ENV, fd));
+            };
+            if fd < 0 || name.is_empty() {
+                fail(format_args!("invalid {} entry {:?}", PREOPENS_ENV, entry));
+            }
+            if len == MAX_PREOPENS {
+                fail(format_args!("too many {} entries", PREOPENS_ENV));
+            }
+
+            // The launching process gave us this fd; it's ours for the rest
+            // of the process.
+            let fd = BorrowedFd::borrow_raw(fd);
+            if let Err(err) = rustix::io::fcntl_setfd(fd, FdFlags::CLOEXEC) {
+                fail(format_args!("preopen {:?} isn't open: {}", name, err));
+            }
+            table[len] = Some(Preopen { fd, name });
+            len += 1;
+        }
+        LEN.store(len, Ordering::Release);
+    }
+
+    /// Restrict filesystem access to the preopens.
+    #[cfg(feature = "preopens-enforce")]
+    fn enforce() {
+        use crate::sandbox::{AccessFs, AccessNet, Sandbox};
+
+        let result = (|| {
+            let mut sandbox = Sandbox::new(AccessFs::all(), AccessNet::empty())?;
+            for preopen in super::preopens() {
+                sandbox = sandbox.allow_fd(preopen.fd, AccessFs::all())?;
+            }
+            sandbox.restrict_self()
+        })();
+        if let Err(err) = result {
+            fail(format_args!("can't restrict filesystem access: {}", err));
+        }
+    }
+}
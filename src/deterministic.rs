@@ -0,0 +1,380 @@
+//! Deterministic execution, for reproducing failures.
+//!
+//! With the "deterministic" feature, mustang derives the program's
+//! randomness and time from a seed, read from the [`SEED_ENV`] environment
+//! variable at startup:
+//!
+//!  - `getrandom` returns a pseudorandom stream generated from the seed.
+//!    std's `RandomState` gets its keys this way, so hash maps iterate in the
+//!    same order on every run, and so does anything seeded from them.
+//!  - The `AT_RANDOM` bytes, which seed the stack protector guard, are
+//!    replaced by the start of that stream.
+//!  - `clock_gettime` reads a virtual clock, which advances by [`STEP`] on
+//!    every read. The realtime clocks and `CLOCK_TAI` start at
+//!    [`REALTIME_START`], and the others at zero.
+//!  - `nanosleep` and `clock_nanosleep` advance the virtual clock by the time
+//!    they sleep. They still sleep for real, so that other threads can make
+//!    progress.
+//!
+//! The seed is a decimal number, or a hexadecimal one prefixed with `0x`,
+//! and is zero if the variable isn't set. An invalid seed aborts the program
+//! at startup.
+//!
+//! The results depend only on the seed and on the order of the calls, so a
+//! single-threaded program sees the same values on every run. Threads which
+//! race to make these calls can get them in different orders.
+//!
+//! The functions are mustang's own `getrandom`, `getentropy`,
+//! `clock_gettime`, `gettimeofday`, `time`, `nanosleep` and
+//! `clock_nanosleep`. c-scape's definitions take precedence over weak ones,
+//! so the functions here are named `__mustang_getrandom` and so on, and bound
+//! to the standard names with `--defsym`, as the mutex module does. With the
+//! "cancellation-points" feature, `nanosleep` and `clock_nanosleep` are the
+//! cancellation points in the cancel module, which sleep the same way.
+//!
+//! Nothing is installed in the kernel, so other programs the process runs
+//! aren't affected, and it keeps the privileges it had. Only calls to these
+//! functions are affected: syscalls made directly, for example by rustix
+//! inside c-scape and origin, reading `/dev/urandom`, and CPU instructions
+//! such as `rdrand` and `rdtsc` aren't.
+
+use crate::startup::fail;
+use crate::syscall::{nr, syscall4, KernelTimespec};
+use core::ffi::{c_char, c_int, c_uint, c_void, CStr};
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+use rustix::io::Errno;
+
+/// The name of the environment variable that sets the seed.
+pub const SEED_ENV: &str = "MUSTANG_SEED";
+
+/// How far the virtual clock advances on each read.
+pub const STEP: Duration = Duration::from_millis(1);
+
+/// The realtime clock's virtual time at startup, as a duration since the
+/// Unix epoch: midnight UTC on January 1, 2000.
+pub const REALTIME_START: Duration = Duration::from_secs(946_684_800);
+
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+/// The number of 8-byte blocks of the random stream used so far.
+static BLOCKS: AtomicU64 = AtomicU64::new(0);
+
+/// The virtual time since startup, in nanoseconds.
+static NOW: AtomicU64 = AtomicU64::new(0);
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+const CLOCK_REALTIME: c_int = 0;
+const CLOCK_REALTIME_COARSE: c_int = 5;
+const CLOCK_REALTIME_ALARM: c_int = 8;
+const CLOCK_SGI_CYCLE: c_int = 10;
+const CLOCK_TAI: c_int = 11;
+const TIMER_ABSTIME: c_int = 1;
+
+/// The most `getentropy` fills in one call.
+const GETENTROPY_MAX: usize = 256;
+
+// Bind the standard names to the implementations here; see above.
+link_args!(
+    "-Wl,--defsym=getrandom=__mustang_getrandom",
+    "-Wl,--defsym=getentropy=__mustang_getentropy",
+    "-Wl,--defsym=clock_gettime=__mustang_clock_gettime",
+    "-Wl,--defsym=gettimeofday=__mustang_gettimeofday",
+    "-Wl,--defsym=time=__mustang_time",
+);
+#[cfg(not(feature = "cancellation-points"))]
+link_args!(
+    "-Wl,--defsym=nanosleep=__mustang_nanosleep",
+    "-Wl,--defsym=clock_nanosleep=__mustang_clock_nanosleep",
+);
+
+/// Return the seed.
+pub fn seed() -> u64 {
+    SEED.load(Ordering::Relaxed)
+}
+
+/// Return the virtual time since startup, without advancing it.
+pub fn elapsed() -> Duration {
+    Duration::from_nanos(NOW.load(Ordering::Relaxed))
+}
+
+/// Read the seed, and replace the `AT_RANDOM` bytes.
+///
+/// # Safety
+///
+/// This must be called from `mustang::auxv`'s startup function, before the
+/// stack protector guard is seeded.
+#[inline(never)]
+pub(crate) unsafe fn init(envp: *mut *mut c_char) {
+    if let Some(value) = find_env(envp) {
+        match parse_seed(value) {
+            Some(seed) => SEED.store(seed, Ordering::Relaxed),
+            None => fail(format_args!("invalid {} {:?}", SEED_ENV, value)),
+        }
+    }
+
+    // The kernel puts the `AT_RANDOM` bytes on the initial stack, which is
+    // writable.
+    if let Some(random) = crate::auxv::get(crate::auxv::AT_RANDOM) {
+        fill(&mut *(random as *mut [u8; 16]));
+    }
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal seed.
+fn parse_seed(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Find the value of `MUSTANG_SEED` in the kernel-provided environment.
+unsafe fn find_env(envp: *mut *mut c_char) -> Option<&'static str> {
+    if envp.is_null() {
+        return None;
+    }
+    let mut ptr = envp;
+    while !(*ptr).is_null() {
+        let var = CStr::from_ptr(*ptr).to_bytes();
+        if let Some(value) = var
+            .strip_prefix(SEED_ENV.as_bytes())
+            .and_then(|rest| rest.strip_prefix(b"="))
+        {
+            return match core::str::from_utf8(value) {
+                Ok(value) => Some(value),
+                Err(_) => fail(format_args!("{} isn't valid UTF-8", SEED_ENV)),
+            };
+        }
+        ptr = ptr.add(1);
+    }
+    None
+}
+
+/// Convert a `Result` into a C return value, setting `errno` on failure.
+unsafe fn result(result: Result<usize, Errno>) -> c_int {
+    match result {
+        Ok(value) => value as c_int,
+        Err(err) => {
+            *libc::__errno_location() = err.raw_os_error();
+            -1
+        }
+    }
+}
+
+/// Return block `index` of the random stream, with SplitMix64.
+fn block(index: u64) -> u64 {
+    let mut z = seed().wrapping_add(index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Fill `buf` from the random stream.
+fn fill(buf: &mut [u8]) {
+    let first = BLOCKS.fetch_add(buf.len().div_ceil(8) as u64, Ordering::Relaxed);
+    for (index, chunk) in (first..).zip(buf.chunks_mut(8)) {
+        chunk.copy_from_slice(&block(index).to_le_bytes()[..chunk.len()]);
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_getrandom(buf: *mut c_void, len: usize, _flags: c_uint) -> isize {
+    if len == 0 {
+        return 0;
+    }
+    if buf.is_null() {
+        return result(Err(Errno::FAULT)) as isize;
+    }
+    fill(core::slice::from_raw_parts_mut(buf.cast(), len));
+    len as isize
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_getentropy(buf: *mut c_void, len: usize) -> c_int {
+    if len > GETENTROPY_MAX {
+        return result(Err(Errno::IO));
+    }
+    match __mustang_getrandom(buf, len, 0) {
+        -1 => -1,
+        _ => 0,
+    }
+}
+
+/// Return the virtual time of `clock` at startup, in nanoseconds, or `None`
+/// if it isn't a valid clock.
+fn start(clock: c_int) -> Option<u64> {
+    match clock {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE | CLOCK_REALTIME_ALARM | CLOCK_TAI => {
+            Some(REALTIME_START.as_nanos() as u64)
+        }
+        CLOCK_SGI_CYCLE => None,
+        clock if clock > CLOCK_TAI => None,
+        // The monotonic and boot-time clocks, and the CPU-time clocks, which
+        // have negative ids.
+        _ => Some(0),
+    }
+}
+
+fn timespec(nanos: u64) -> KernelTimespec {
+    KernelTimespec {
+        tv_sec: (nanos / NANOS_PER_SEC) as i64,
+        tv_nsec: (nanos % NANOS_PER_SEC) as i64,
+    }
+}
+
+/// Read `clock`, advancing the virtual clock.
+fn now(clock: c_int) -> Result<KernelTimespec, Errno> {
+    let start = start(clock).ok_or(Errno::INVAL)?;
+    let step = STEP.as_nanos() as u64;
+    let now = NOW.fetch_add(step, Ordering::Relaxed) + step;
+    Ok(timespec(start + now))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_clock_gettime(clock: c_int, ts: *mut libc::timespec) -> c_int {
+    result(now(clock).and_then(|now| {
+        if ts.is_null() {
+            return Err(Errno::FAULT);
+        }
+        (*ts).tv_sec = now.tv_sec as _;
+        (*ts).tv_nsec = now.tv_nsec as _;
+        Ok(0)
+    }))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_gettimeofday(tv: *mut libc::timeval, _tz: *mut c_void) -> c_int {
+    let now = now(CLOCK_REALTIME).unwrap();
+    if !tv.is_null() {
+        (*tv).tv_sec = now.tv_sec as _;
+        (*tv).tv_usec = (now.tv_nsec / 1000) as _;
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_time(t: *mut libc::time_t) -> libc::time_t {
+    let now = now(CLOCK_REALTIME).unwrap().tv_sec as libc::time_t;
+    if !t.is_null() {
+        *t = now;
+    }
+    now
+}
+
+/// Sleep as `clock_nanosleep` does, and advance the virtual clock by the time
+/// slept.
+///
+/// # Safety
+///
+/// `req` must be valid to read, and `rem` must be null or valid to write.
+pub(crate) unsafe fn clock_nanosleep(
+    clock: c_int,
+    flags: c_int,
+    req: *const KernelTimespec,
+    rem: *mut KernelTimespec,
+) -> Result<usize, Errno> {
+    let start = start(clock).ok_or(Errno::INVAL)?;
+    if req.is_null() {
+        return Err(Errno::FAULT);
+    }
+    let req = &*req;
+    if req.tv_sec < 0 || !(0..NANOS_PER_SEC as i64).contains(&req.tv_nsec) {
+        return Err(Errno::INVAL);
+    }
+    let req = (req.tv_sec as u64)
+        .saturating_mul(NANOS_PER_SEC)
+        .saturating_add(req.tv_nsec as u64);
+    let duration = if flags & TIMER_ABSTIME != 0 {
+        req.saturating_sub(start + NOW.load(Ordering::Relaxed))
+    } else {
+        req
+    };
+
+    // `ppoll` with no file descriptors sleeps, and updates the timeout with
+    // the time remaining if it's interrupted.
+    let mut timeout = timespec(duration);
+    let result = syscall4(
+        nr::PPOLL,
+        0,
+        0,
+        &mut timeout as *mut KernelTimespec as usize,
+        0,
+    );
+    let remaining = (timeout.tv_sec as u64)
+        .saturating_mul(NANOS_PER_SEC)
+        .saturating_add(timeout.tv_nsec as u64);
+    NOW.fetch_add(duration.saturating_sub(remaining), Ordering::Relaxed);
+
+    if result == Err(Errno::INTR) && flags & TIMER_ABSTIME == 0 && !rem.is_null() {
+        rem.write(timeout);
+    }
+    result
+}
+
+/// `clock_nanosleep` with C `timespec`s, returning an error number.
+#[cfg(not(feature = "cancellation-points"))]
+// `time_t` and `c_long` are 32-bit on some targets.
+#[allow(clippy::useless_conversion)]
+unsafe fn sleep(
+    clock: c_int,
+    flags: c_int,
+    request: *const libc::timespec,
+    remain: *mut libc::timespec,
+) -> c_int {
+    if request.is_null() {
+        return Errno::FAULT.raw_os_error();
+    }
+    let request = KernelTimespec {
+        tv_sec: (*request).tv_sec.into(),
+        tv_nsec: (*request).tv_nsec.into(),
+    };
+    let mut left = KernelTimespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    match clock_nanosleep(clock, flags, &request, &mut left) {
+        Ok(_) => 0,
+        Err(err) => {
+            if err == Errno::INTR && flags & TIMER_ABSTIME == 0 && !remain.is_null() {
+                (*remain).tv_sec = left.tv_sec as _;
+                (*remain).tv_nsec = left.tv_nsec as _;
+            }
+            err.raw_os_error()
+        }
+    }
+}
+
+#[cfg(not(feature = "cancellation-points"))]
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_nanosleep(
+    request: *const libc::timespec,
+    remain: *mut libc::timespec,
+) -> c_int {
+    // Linux's `nanosleep` measures with `CLOCK_MONOTONIC`.
+    match sleep(libc::CLOCK_MONOTONIC, 0, request, remain) {
+        0 => 0,
+        err => {
+            *libc::__errno_location() = err;
+            -1
+        }
+    }
+}
+
+#[cfg(not(feature = "cancellation-points"))]
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_clock_nanosleep(
+    clock: libc::clockid_t,
+    flags: c_int,
+    request: *const libc::timespec,
+    remain: *mut libc::timespec,
+) -> c_int {
+    sleep(clock, flags, request, remain)
+}
@@ -0,0 +1,811 @@
+//! `_FORTIFY_SOURCE` support for C code linked into mustang programs.
+//!
+//! When C code is compiled with `-D_FORTIFY_SOURCE=2` or higher, calls to
+//! many libc functions on buffers whose size the compiler knows are replaced
+//! with calls to `__*_chk` variants which take the buffer size as an extra
+//! argument. These check the size and abort with "buffer overflow detected"
+//! if the call would overflow the buffer, and otherwise behave like the
+//! plain function.
+//!
+//! The printf family's `flag` argument, which glibc uses to reject `%n` in
+//! writable format strings, is ignored.
+//!
+//! These symbols are defined with weak linkage, so that if the libc
+//! implementation also defines them, its definitions are used. The
+//! non-string functions call the corresponding plain libc functions, so
+//! they're only defined for functions c-scape implements.
+
+use core::ffi::{c_char, c_int, c_long, c_void, VaList};
+use core::ptr;
+use libc::{
+    gid_t, nfds_t, off64_t, off_t, pollfd, sigset_t, size_t, sockaddr, socklen_t, ssize_t,
+    timespec, wchar_t, FILE,
+};
+
+extern "C" {
+    fn read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t;
+    fn pread(fd: c_int, buf: *mut c_void, count: size_t, offset: off_t) -> ssize_t;
+    fn pread64(fd: c_int, buf: *mut c_void, count: size_t, offset: off64_t) -> ssize_t;
+    fn readlink(path: *const c_char, buf: *mut c_char, len: size_t) -> ssize_t;
+    fn readlinkat(dirfd: c_int, path: *const c_char, buf: *mut c_char, len: size_t) -> ssize_t;
+    fn getcwd(buf: *mut c_char, size: size_t) -> *mut c_char;
+    fn recv(fd: c_int, buf: *mut c_void, len: size_t, flags: c_int) -> ssize_t;
+    fn recvfrom(
+        fd: c_int,
+        buf: *mut c_void,
+        len: size_t,
+        flags: c_int,
+        addr: *mut sockaddr,
+        addrlen: *mut socklen_t,
+    ) -> ssize_t;
+    fn fgets(buf: *mut c_char, n: c_int, stream: *mut FILE) -> *mut c_char;
+    fn fread(ptr: *mut c_void, size: size_t, n: size_t, stream: *mut FILE) -> size_t;
+    fn ttyname_r(fd: c_int, buf: *mut c_char, buflen: size_t) -> c_int;
+    fn gethostname(buf: *mut c_char, len: size_t) -> c_int;
+    fn getgroups(size: c_int, list: *mut gid_t) -> c_int;
+    fn realpath(path: *const c_char, resolved: *mut c_char) -> *mut c_char;
+    fn poll(fds: *mut pollfd, nfds: nfds_t, timeout: c_int) -> c_int;
+    fn ppoll(
+        fds: *mut pollfd,
+        nfds: nfds_t,
+        timeout: *const timespec,
+        sigmask: *const sigset_t,
+    ) -> c_int;
+    fn open(path: *const c_char, flags: c_int, ...) -> c_int;
+    fn openat(dirfd: c_int, path: *const c_char, flags: c_int, ...) -> c_int;
+
+    fn vsnprintf(s: *mut c_char, n: size_t, fmt: *const c_char, ap: VaList) -> c_int;
+    fn vprintf(fmt: *const c_char, ap: VaList) -> c_int;
+    fn vfprintf(stream: *mut FILE, fmt: *const c_char, ap: VaList) -> c_int;
+    fn vdprintf(fd: c_int, fmt: *const c_char, ap: VaList) -> c_int;
+    fn vasprintf(strp: *mut *mut c_char, fmt: *const c_char, ap: VaList) -> c_int;
+}
+
+/// Abort with a "buffer overflow detected" message.
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __chk_fail() -> ! {
+    crate::startup::fail(format_args!("buffer overflow detected"))
+}
+
+/// Fail if a buffer of `buflen` units is too small for `len` units.
+#[inline]
+fn check(len: usize, buflen: usize) {
+    if len > buflen {
+        // SAFETY: `__chk_fail` is always safe to call.
+        unsafe { __chk_fail() }
+    }
+}
+
+/// A `char` or `wchar_t`.
+trait Char: Copy + Default + PartialEq {}
+
+impl Char for c_char {}
+impl Char for wchar_t {}
+
+/// The length of the NUL-terminated string `s`.
+unsafe fn len<T: Char>(s: *const T) -> usize {
+    let mut n = 0;
+    while *s.add(n) != T::default() {
+        n += 1;
+    }
+    n
+}
+
+/// The length of the NUL-terminated string `s`, or `max` if it's longer.
+unsafe fn nlen<T: Char>(s: *const T, max: usize) -> usize {
+    let mut n = 0;
+    while n < max && *s.add(n) != T::default() {
+        n += 1;
+    }
+    n
+}
+
+/// `stpcpy`, checked against `dstlen`.
+unsafe fn cpy<T: Char>(dst: *mut T, src: *const T, dstlen: usize) -> *mut T {
+    let n = len(src);
+    check(n + 1, dstlen);
+    ptr::copy_nonoverlapping(src, dst, n + 1);
+    dst.add(n)
+}
+
+/// `stpncpy`, checked against `dstlen`.
+unsafe fn ncpy<T: Char>(dst: *mut T, src: *const T, n: usize, dstlen: usize) -> *mut T {
+    check(n, dstlen);
+    let len = nlen(src, n);
+    ptr::copy_nonoverlapping(src, dst, len);
+    for i in len..n {
+        *dst.add(i) = T::default();
+    }
+    dst.add(len)
+}
+
+/// `strncat` with `n` of `usize::MAX` meaning `strcat`, checked against
+/// `dstlen`.
+unsafe fn cat<T: Char>(dst: *mut T, src: *const T, n: usize, dstlen: usize) -> *mut T {
+    let dst_len = nlen(dst, dstlen);
+    check(dst_len + 1, dstlen);
+    let src_len = nlen(src, n);
+    check(dst_len + src_len + 1, dstlen);
+    ptr::copy_nonoverlapping(src, dst.add(dst_len), src_len);
+    *dst.add(dst_len + src_len) = T::default();
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __memcpy_chk(
+    dst: *mut c_void,
+    src: *const c_void,
+    len: size_t,
+    dstlen: size_t,
+) -> *mut c_void {
+    check(len, dstlen);
+    ptr::copy_nonoverlapping(src.cast::<u8>(), dst.cast::<u8>(), len);
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __memmove_chk(
+    dst: *mut c_void,
+    src: *const c_void,
+    len: size_t,
+    dstlen: size_t,
+) -> *mut c_void {
+    check(len, dstlen);
+    ptr::copy(src.cast::<u8>(), dst.cast::<u8>(), len);
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mempcpy_chk(
+    dst: *mut c_void,
+    src: *const c_void,
+    len: size_t,
+    dstlen: size_t,
+) -> *mut c_void {
+    check(len, dstlen);
+    ptr::copy_nonoverlapping(src.cast::<u8>(), dst.cast::<u8>(), len);
+    dst.cast::<u8>().add(len).cast()
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __memset_chk(
+    dst: *mut c_void,
+    c: c_int,
+    len: size_t,
+    dstlen: size_t,
+) -> *mut c_void {
+    check(len, dstlen);
+    ptr::write_bytes(dst.cast::<u8>(), c as u8, len);
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __explicit_bzero_chk(dst: *mut c_void, len: size_t, dstlen: size_t) {
+    check(len, dstlen);
+    for i in 0..len {
+        dst.cast::<u8>().add(i).write_volatile(0);
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __strcpy_chk(
+    dst: *mut c_char,
+    src: *const c_char,
+    dstlen: size_t,
+) -> *mut c_char {
+    cpy(dst, src, dstlen);
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __stpcpy_chk(
+    dst: *mut c_char,
+    src: *const c_char,
+    dstlen: size_t,
+) -> *mut c_char {
+    cpy(dst, src, dstlen)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __strncpy_chk(
+    dst: *mut c_char,
+    src: *const c_char,
+    n: size_t,
+    dstlen: size_t,
+) -> *mut c_char {
+    ncpy(dst, src, n, dstlen);
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __stpncpy_chk(
+    dst: *mut c_char,
+    src: *const c_char,
+    n: size_t,
+    dstlen: size_t,
+) -> *mut c_char {
+    ncpy(dst, src, n, dstlen)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __strcat_chk(
+    dst: *mut c_char,
+    src: *const c_char,
+    dstlen: size_t,
+) -> *mut c_char {
+    cat(dst, src, usize::MAX, dstlen)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __strncat_chk(
+    dst: *mut c_char,
+    src: *const c_char,
+    n: size_t,
+    dstlen: size_t,
+) -> *mut c_char {
+    cat(dst, src, n, dstlen)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __strlcpy_chk(
+    dst: *mut c_char,
+    src: *const c_char,
+    size: size_t,
+    dstlen: size_t,
+) -> size_t {
+    check(size, dstlen);
+    let src_len = len(src);
+    if size != 0 {
+        let n = src_len.min(size - 1);
+        ptr::copy_nonoverlapping(src, dst, n);
+        *dst.add(n) = 0;
+    }
+    src_len
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __strlcat_chk(
+    dst: *mut c_char,
+    src: *const c_char,
+    size: size_t,
+    dstlen: size_t,
+) -> size_t {
+    check(size, dstlen);
+    let dst_len = nlen(dst, size);
+    let src_len = len(src);
+    if dst_len == size {
+        return size + src_len;
+    }
+    let n = src_len.min(size - dst_len - 1);
+    ptr::copy_nonoverlapping(src, dst.add(dst_len), n);
+    *dst.add(dst_len + n) = 0;
+    dst_len + src_len
+}
+
+// The wide-character functions' buffer sizes are in units of `wchar_t`.
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __wmemcpy_chk(
+    dst: *mut wchar_t,
+    src: *const wchar_t,
+    n: size_t,
+    dstlen: size_t,
+) -> *mut wchar_t {
+    check(n, dstlen);
+    ptr::copy_nonoverlapping(src, dst, n);
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __wmemmove_chk(
+    dst: *mut wchar_t,
+    src: *const wchar_t,
+    n: size_t,
+    dstlen: size_t,
+) -> *mut wchar_t {
+    check(n, dstlen);
+    ptr::copy(src, dst, n);
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __wmempcpy_chk(
+    dst: *mut wchar_t,
+    src: *const wchar_t,
+    n: size_t,
+    dstlen: size_t,
+) -> *mut wchar_t {
+    check(n, dstlen);
+    ptr::copy_nonoverlapping(src, dst, n);
+    dst.add(n)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __wmemset_chk(
+    dst: *mut wchar_t,
+    c: wchar_t,
+    n: size_t,
+    dstlen: size_t,
+) -> *mut wchar_t {
+    check(n, dstlen);
+    for i in 0..n {
+        *dst.add(i) = c;
+    }
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __wcscpy_chk(
+    dst: *mut wchar_t,
+    src: *const wchar_t,
+    dstlen: size_t,
+) -> *mut wchar_t {
+    cpy(dst, src, dstlen);
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __wcpcpy_chk(
+    dst: *mut wchar_t,
+    src: *const wchar_t,
+    dstlen: size_t,
+) -> *mut wchar_t {
+    cpy(dst, src, dstlen)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __wcsncpy_chk(
+    dst: *mut wchar_t,
+    src: *const wchar_t,
+    n: size_t,
+    dstlen: size_t,
+) -> *mut wchar_t {
+    ncpy(dst, src, n, dstlen);
+    dst
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __wcpncpy_chk(
+    dst: *mut wchar_t,
+    src: *const wchar_t,
+    n: size_t,
+    dstlen: size_t,
+) -> *mut wchar_t {
+    ncpy(dst, src, n, dstlen)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __wcscat_chk(
+    dst: *mut wchar_t,
+    src: *const wchar_t,
+    dstlen: size_t,
+) -> *mut wchar_t {
+    cat(dst, src, usize::MAX, dstlen)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __wcsncat_chk(
+    dst: *mut wchar_t,
+    src: *const wchar_t,
+    n: size_t,
+    dstlen: size_t,
+) -> *mut wchar_t {
+    cat(dst, src, n, dstlen)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __sprintf_chk(
+    s: *mut c_char,
+    flag: c_int,
+    slen: size_t,
+    fmt: *const c_char,
+    mut args: ...
+) -> c_int {
+    __vsprintf_chk(s, flag, slen, fmt, args.as_va_list())
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __vsprintf_chk(
+    s: *mut c_char,
+    _flag: c_int,
+    slen: size_t,
+    fmt: *const c_char,
+    ap: VaList,
+) -> c_int {
+    check(1, slen);
+    let n = vsnprintf(s, slen, fmt, ap);
+    if n >= 0 {
+        check(n as usize + 1, slen);
+    }
+    n
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __snprintf_chk(
+    s: *mut c_char,
+    maxlen: size_t,
+    flag: c_int,
+    slen: size_t,
+    fmt: *const c_char,
+    mut args: ...
+) -> c_int {
+    __vsnprintf_chk(s, maxlen, flag, slen, fmt, args.as_va_list())
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __vsnprintf_chk(
+    s: *mut c_char,
+    maxlen: size_t,
+    _flag: c_int,
+    slen: size_t,
+    fmt: *const c_char,
+    ap: VaList,
+) -> c_int {
+    check(maxlen, slen);
+    vsnprintf(s, maxlen, fmt, ap)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __printf_chk(_flag: c_int, fmt: *const c_char, mut args: ...) -> c_int {
+    vprintf(fmt, args.as_va_list())
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __vprintf_chk(_flag: c_int, fmt: *const c_char, ap: VaList) -> c_int {
+    vprintf(fmt, ap)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __fprintf_chk(
+    stream: *mut FILE,
+    _flag: c_int,
+    fmt: *const c_char,
+    mut args: ...
+) -> c_int {
+    vfprintf(stream, fmt, args.as_va_list())
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __vfprintf_chk(
+    stream: *mut FILE,
+    _flag: c_int,
+    fmt: *const c_char,
+    ap: VaList,
+) -> c_int {
+    vfprintf(stream, fmt, ap)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __dprintf_chk(
+    fd: c_int,
+    _flag: c_int,
+    fmt: *const c_char,
+    mut args: ...
+) -> c_int {
+    vdprintf(fd, fmt, args.as_va_list())
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __vdprintf_chk(
+    fd: c_int,
+    _flag: c_int,
+    fmt: *const c_char,
+    ap: VaList,
+) -> c_int {
+    vdprintf(fd, fmt, ap)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __asprintf_chk(
+    strp: *mut *mut c_char,
+    _flag: c_int,
+    fmt: *const c_char,
+    mut args: ...
+) -> c_int {
+    vasprintf(strp, fmt, args.as_va_list())
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __vasprintf_chk(
+    strp: *mut *mut c_char,
+    _flag: c_int,
+    fmt: *const c_char,
+    ap: VaList,
+) -> c_int {
+    vasprintf(strp, fmt, ap)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __read_chk(
+    fd: c_int,
+    buf: *mut c_void,
+    count: size_t,
+    buflen: size_t,
+) -> ssize_t {
+    check(count, buflen);
+    read(fd, buf, count)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __pread_chk(
+    fd: c_int,
+    buf: *mut c_void,
+    count: size_t,
+    offset: off_t,
+    buflen: size_t,
+) -> ssize_t {
+    check(count, buflen);
+    pread(fd, buf, count, offset)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __pread64_chk(
+    fd: c_int,
+    buf: *mut c_void,
+    count: size_t,
+    offset: off64_t,
+    buflen: size_t,
+) -> ssize_t {
+    check(count, buflen);
+    pread64(fd, buf, count, offset)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __readlink_chk(
+    path: *const c_char,
+    buf: *mut c_char,
+    len: size_t,
+    buflen: size_t,
+) -> ssize_t {
+    check(len, buflen);
+    readlink(path, buf, len)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __readlinkat_chk(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut c_char,
+    len: size_t,
+    buflen: size_t,
+) -> ssize_t {
+    check(len, buflen);
+    readlinkat(dirfd, path, buf, len)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __getcwd_chk(buf: *mut c_char, size: size_t, buflen: size_t) -> *mut c_char {
+    check(size, buflen);
+    getcwd(buf, size)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __recv_chk(
+    fd: c_int,
+    buf: *mut c_void,
+    len: size_t,
+    buflen: size_t,
+    flags: c_int,
+) -> ssize_t {
+    check(len, buflen);
+    recv(fd, buf, len, flags)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __recvfrom_chk(
+    fd: c_int,
+    buf: *mut c_void,
+    len: size_t,
+    buflen: size_t,
+    flags: c_int,
+    addr: *mut sockaddr,
+    addrlen: *mut socklen_t,
+) -> ssize_t {
+    check(len, buflen);
+    recvfrom(fd, buf, len, flags, addr, addrlen)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __fgets_chk(
+    buf: *mut c_char,
+    buflen: size_t,
+    n: c_int,
+    stream: *mut FILE,
+) -> *mut c_char {
+    check(n.max(0) as usize, buflen);
+    fgets(buf, n, stream)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __fgets_unlocked_chk(
+    buf: *mut c_char,
+    buflen: size_t,
+    n: c_int,
+    stream: *mut FILE,
+) -> *mut c_char {
+    __fgets_chk(buf, buflen, n, stream)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __fread_chk(
+    ptr: *mut c_void,
+    buflen: size_t,
+    size: size_t,
+    n: size_t,
+    stream: *mut FILE,
+) -> size_t {
+    match size.checked_mul(n) {
+        Some(len) => check(len, buflen),
+        None => __chk_fail(),
+    }
+    fread(ptr, size, n, stream)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __fread_unlocked_chk(
+    ptr: *mut c_void,
+    buflen: size_t,
+    size: size_t,
+    n: size_t,
+    stream: *mut FILE,
+) -> size_t {
+    __fread_chk(ptr, buflen, size, n, stream)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __ttyname_r_chk(
+    fd: c_int,
+    buf: *mut c_char,
+    len: size_t,
+    buflen: size_t,
+) -> c_int {
+    check(len, buflen);
+    ttyname_r(fd, buf, len)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __gethostname_chk(buf: *mut c_char, len: size_t, buflen: size_t) -> c_int {
+    check(len, buflen);
+    gethostname(buf, len)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __getgroups_chk(size: c_int, list: *mut gid_t, listlen: size_t) -> c_int {
+    if size > 0 {
+        check(size as usize * core::mem::size_of::<gid_t>(), listlen);
+    }
+    getgroups(size, list)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __realpath_chk(
+    path: *const c_char,
+    resolved: *mut c_char,
+    resolvedlen: size_t,
+) -> *mut c_char {
+    check(libc::PATH_MAX as usize, resolvedlen);
+    realpath(path, resolved)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __poll_chk(
+    fds: *mut pollfd,
+    nfds: nfds_t,
+    timeout: c_int,
+    fdslen: size_t,
+) -> c_int {
+    check(nfds as usize, fdslen / core::mem::size_of::<pollfd>());
+    poll(fds, nfds, timeout)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __ppoll_chk(
+    fds: *mut pollfd,
+    nfds: nfds_t,
+    timeout: *const timespec,
+    sigmask: *const sigset_t,
+    fdslen: size_t,
+) -> c_int {
+    check(nfds as usize, fdslen / core::mem::size_of::<pollfd>());
+    ppoll(fds, nfds, timeout, sigmask)
+}
+
+/// Check an fd used with `FD_SET` and friends, and return the index of the
+/// word in the `fd_set` that holds it.
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __fdelt_chk(fd: c_long) -> c_long {
+    if fd < 0 || fd >= libc::FD_SETSIZE as c_long {
+        __chk_fail();
+    }
+    fd / (8 * core::mem::size_of::<c_long>() as c_long)
+}
+
+/// Check that an `open` without a mode argument doesn't need one.
+unsafe fn check_open_flags(flags: c_int) {
+    if flags & libc::O_CREAT != 0 || flags & libc::O_TMPFILE == libc::O_TMPFILE {
+        crate::startup::fail(format_args!(
+            "invalid open call: O_CREAT or O_TMPFILE without mode"
+        ));
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __open_2(path: *const c_char, flags: c_int) -> c_int {
+    check_open_flags(flags);
+    open(path, flags)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __open64_2(path: *const c_char, flags: c_int) -> c_int {
+    __open_2(path, flags)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __openat_2(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    check_open_flags(flags);
+    openat(dirfd, path, flags)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __openat64_2(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    __openat_2(dirfd, path, flags)
+}
@@ -0,0 +1,332 @@
+//! Hardware capability detection.
+//!
+//! The kernel reports CPU features in the `AT_HWCAP` and `AT_HWCAP2` auxv
+//! entries. We read them once, at startup, into a lock-free cache, and decode
+//! the bits according to the current architecture's conventions.
+//!
+//! `std_detect` reads the same values through `getauxval`. c-scape's
+//! definition takes precedence over a weak one, so mustang's is named
+//! `__mustang_getauxval`, and bound to the standard name with `--defsym`, as
+//! the mutex module does. It answers `AT_HWCAP` and `AT_HWCAP2` from the
+//! cache, and other keys from the auxv, so `is_aarch64_feature_detected!`
+//! and friends, and C code, agree with what this module reports. The
+//! `cpu-features` example, which `tests/examples.rs` runs, checks both of
+//! these.
+
+use crate::auxv;
+use core::ffi::c_ulong;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// Bind the standard name to the implementation here; see above.
+link_args!("-Wl,--defsym=getauxval=__mustang_getauxval");
+
+static HWCAP: AtomicUsize = AtomicUsize::new(0);
+static HWCAP2: AtomicUsize = AtomicUsize::new(0);
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Fill in the cache from the auxv. This is called by `auxv` as soon as it
+/// has recorded the auxv location.
+pub(crate) fn init() {
+    HWCAP.store(auxv::hwcap(), Ordering::Relaxed);
+    HWCAP2.store(auxv::hwcap2(), Ordering::Relaxed);
+    INITIALIZED.store(true, Ordering::Release);
+}
+
+/// Which auxv word a feature bit lives in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Word {
+    /// `AT_HWCAP`
+    Hwcap,
+    /// `AT_HWCAP2`
+    Hwcap2,
+}
+
+/// A single CPU feature, identified by its bit in `AT_HWCAP` or `AT_HWCAP2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Feature {
+    /// The name the kernel uses for this feature in `/proc/cpuinfo`.
+    pub name: &'static str,
+    /// Which word the bit is in.
+    pub word: Word,
+    /// The bit number.
+    pub bit: u32,
+}
+
+/// A snapshot of the hardware capability words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HwCap {
+    hwcap: usize,
+    hwcap2: usize,
+}
+
+impl HwCap {
+    /// The raw `AT_HWCAP` value.
+    #[inline]
+    pub fn hwcap(&self) -> usize {
+        self.hwcap
+    }
+
+    /// The raw `AT_HWCAP2` value.
+    #[inline]
+    pub fn hwcap2(&self) -> usize {
+        self.hwcap2
+    }
+
+    /// Test whether `feature` is present.
+    #[inline]
+    pub fn has(&self, feature: Feature) -> bool {
+        let word = match feature.word {
+            Word::Hwcap => self.hwcap,
+            Word::Hwcap2 => self.hwcap2,
+        };
+        feature.bit < usize::BITS && word & (1 << feature.bit) != 0
+    }
+
+    /// Look up a feature by its `/proc/cpuinfo` name, returning `None` if the
+    /// name isn't known on this architecture.
+    pub fn has_named(&self, name: &str) -> Option<bool> {
+        FEATURES
+            .iter()
+            .find(|feature| feature.name == name)
+            .map(|feature| self.has(*feature))
+    }
+
+    /// Iterate over all the features known on this architecture, along with
+    /// whether each is present.
+    pub fn features(&self) -> impl Iterator<Item = (Feature, bool)> + '_ {
+        FEATURES
+            .iter()
+            .map(move |feature| (*feature, self.has(*feature)))
+    }
+}
+
+/// Return the cached hardware capabilities.
+#[inline]
+pub fn get() -> HwCap {
+    if !INITIALIZED.load(Ordering::Acquire) {
+        // We're running before the auxv hook; this can happen in other
+        // `.init_array.00000` functions. Reading the auxv is idempotent, so
+        // it's fine if several threads race to do it.
+        init();
+    }
+    HwCap {
+        hwcap: HWCAP.load(Ordering::Relaxed),
+        hwcap2: HWCAP2.load(Ordering::Relaxed),
+    }
+}
+
+/// Test whether `feature` is present on the current CPU.
+#[inline]
+pub fn has(feature: Feature) -> bool {
+    get().has(feature)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_getauxval(key: c_ulong) -> c_ulong {
+    let value = match key as usize {
+        auxv::AT_HWCAP => Some(get().hwcap),
+        auxv::AT_HWCAP2 => Some(get().hwcap2),
+        key => auxv::get(key),
+    };
+    match value {
+        Some(value) => value as c_ulong,
+        None => {
+            *libc::__errno_location() = libc::ENOENT;
+            0
+        }
+    }
+}
+
+macro_rules! features {
+    ($($word:ident: [$(($konst:ident, $name:literal, $bit:literal),)*],)*) => {
+        $($(
+            #[doc = concat!("`", $name, "`")]
+            pub const $konst: Feature = Feature {
+                name: $name,
+                word: Word::$word,
+                bit: $bit,
+            };
+        )*)*
+
+        /// All the features known on this architecture.
+        pub const FEATURES: &[Feature] = &[$($($konst,)*)*];
+    };
+}
+
+#[cfg(target_arch = "aarch64")]
+features! {
+    Hwcap: [
+        (FP, "fp", 0),
+        (ASIMD, "asimd", 1),
+        (EVTSTRM, "evtstrm", 2),
+        (AES, "aes", 3),
+        (PMULL, "pmull", 4),
+        (SHA1, "sha1", 5),
+        (SHA2, "sha2", 6),
+        (CRC32, "crc32", 7),
+        (ATOMICS, "atomics", 8),
+        (FPHP, "fphp", 9),
+        (ASIMDHP, "asimdhp", 10),
+        (CPUID, "cpuid", 11),
+        (ASIMDRDM, "asimdrdm", 12),
+        (JSCVT, "jscvt", 13),
+        (FCMA, "fcma", 14),
+        (LRCPC, "lrcpc", 15),
+        (DCPOP, "dcpop", 16),
+        (SHA3, "sha3", 17),
+        (SM3, "sm3", 18),
+        (SM4, "sm4", 19),
+        (ASIMDDP, "asimddp", 20),
+        (SHA512, "sha512", 21),
+        (SVE, "sve", 22),
+        (ASIMDFHM, "asimdfhm", 23),
+        (DIT, "dit", 24),
+        (USCAT, "uscat", 25),
+        (ILRCPC, "ilrcpc", 26),
+        (FLAGM, "flagm", 27),
+        (SSBS, "ssbs", 28),
+        (SB, "sb", 29),
+        (PACA, "paca", 30),
+        (PACG, "pacg", 31),
+    ],
+    Hwcap2: [
+        (DCPODP, "dcpodp", 0),
+        (SVE2, "sve2", 1),
+        (SVEAES, "sveaes", 2),
+        (SVEPMULL, "svepmull", 3),
+        (SVEBITPERM, "svebitperm", 4),
+        (SVESHA3, "svesha3", 5),
+        (SVESM4, "svesm4", 6),
+        (FLAGM2, "flagm2", 7),
+        (FRINT, "frint", 8),
+        (SVEI8MM, "svei8mm", 9),
+        (SVEF32MM, "svef32mm", 10),
+        (SVEF64MM, "svef64mm", 11),
+        (SVEBF16, "svebf16", 12),
+        (I8MM, "i8mm", 13),
+        (BF16, "bf16", 14),
+        (DGH, "dgh", 15),
+        (RNG, "rng", 16),
+        (BTI, "bti", 17),
+        (MTE, "mte", 18),
+        (ECV, "ecv", 19),
+        (AFP, "afp", 20),
+        (RPRES, "rpres", 21),
+        (MTE3, "mte3", 22),
+        (SME, "sme", 23),
+        (SME_I16I64, "smei16i64", 24),
+        (SME_F64F64, "smef64f64", 25),
+        (SME_I8I32, "smei8i32", 26),
+        (SME_F16F32, "smef16f32", 27),
+        (SME_B16F32, "smeb16f32", 28),
+        (SME_F32F32, "smef32f32", 29),
+        (SME_FA64, "smefa64", 30),
+        (WFXT, "wfxt", 31),
+        (EBF16, "ebf16", 32),
+        (SVE_EBF16, "sveebf16", 33),
+        (CSSC, "cssc", 34),
+        (RPRFM, "rprfm", 35),
+        (SVE2P1, "sve2p1", 36),
+        (SME2, "sme2", 37),
+        (SME2P1, "sme2p1", 38),
+        (SME_I16I32, "smei16i32", 39),
+        (SME_BI32I32, "smebi32i32", 40),
+        (SME_B16B16, "smeb16b16", 41),
+        (SME_F16F16, "smef16f16", 42),
+        (MOPS, "mops", 43),
+        (HBC, "hbc", 44),
+    ],
+}
+
+#[cfg(target_arch = "arm")]
+features! {
+    Hwcap: [
+        (SWP, "swp", 0),
+        (HALF, "half", 1),
+        (THUMB, "thumb", 2),
+        (BIT26, "26bit", 3),
+        (FASTMULT, "fastmult", 4),
+        (FPA, "fpa", 5),
+        (VFP, "vfp", 6),
+        (EDSP, "edsp", 7),
+        (JAVA, "java", 8),
+        (IWMMXT, "iwmmxt", 9),
+        (CRUNCH, "crunch", 10),
+        (THUMBEE, "thumbee", 11),
+        (NEON, "neon", 12),
+        (VFPV3, "vfpv3", 13),
+        (VFPV3D16, "vfpv3d16", 14),
+        (TLS, "tls", 15),
+        (VFPV4, "vfpv4", 16),
+        (IDIVA, "idiva", 17),
+        (IDIVT, "idivt", 18),
+        (VFPD32, "vfpd32", 19),
+        (LPAE, "lpae", 20),
+        (EVTSTRM, "evtstrm", 21),
+    ],
+    Hwcap2: [
+        (AES, "aes", 0),
+        (PMULL, "pmull", 1),
+        (SHA1, "sha1", 2),
+        (SHA2, "sha2", 3),
+        (CRC32, "crc32", 4),
+    ],
+}
+
+// On RISC-V, `AT_HWCAP` has one bit per single-letter ISA extension, at
+// `letter - 'a'`.
+#[cfg(target_arch = "riscv64")]
+features! {
+    Hwcap: [
+        (A, "a", 0),
+        (C, "c", 2),
+        (D, "d", 3),
+        (F, "f", 5),
+        (H, "h", 7),
+        (I, "i", 8),
+        (M, "m", 12),
+        (Q, "q", 16),
+        (V, "v", 21),
+    ],
+}
+
+// On x86, `AT_HWCAP` holds the `edx` value of CPUID leaf 1. `std_detect`
+// queries CPUID directly on x86, so this is just for completeness.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+features! {
+    Hwcap: [
+        (FPU, "fpu", 0),
+        (VME, "vme", 1),
+        (DE, "de", 2),
+        (PSE, "pse", 3),
+        (TSC, "tsc", 4),
+        (MSR, "msr", 5),
+        (PAE, "pae", 6),
+        (MCE, "mce", 7),
+        (CX8, "cx8", 8),
+        (APIC, "apic", 9),
+        (SEP, "sep", 11),
+        (MTRR, "mtrr", 12),
+        (PGE, "pge", 13),
+        (MCA, "mca", 14),
+        (CMOV, "cmov", 15),
+        (PAT, "pat", 16),
+        (PSE36, "pse36", 17),
+        (CLFLUSH, "clflush", 19),
+        (MMX, "mmx", 23),
+        (FXSR, "fxsr", 24),
+        (SSE, "sse", 25),
+        (SSE2, "sse2", 26),
+        (HT, "ht", 28),
+    ],
+}
+
+#[cfg(not(any(
+    target_arch = "aarch64",
+    target_arch = "arm",
+    target_arch = "riscv64",
+    target_arch = "x86",
+    target_arch = "x86_64"
+)))]
+features! {}
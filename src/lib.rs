@@ -1,11 +1,36 @@
 #![doc = include_str!("../README.md")]
+// README.md is written for GitHub's Markdown renderer, which reads the lists
+// in its porting section as intended.
+#![allow(clippy::doc_lazy_continuation)]
 #![no_std]
+#![cfg_attr(
+    target_vendor = "mustang",
+    feature(c_variadic, link_arg_attribute, linkage)
+)]
+#![cfg_attr(
+    all(target_vendor = "mustang", feature = "thread"),
+    feature(thread_local)
+)]
+#![cfg_attr(all(target_vendor = "mustang", feature = "std"), feature(linux_pidfd))]
 
 /// Declare that a program can be compiled and run by `mustang`.
 ///
 /// To use this, put `mustang::can_run_this!()` in the top-level `main.rs`. In
 /// `*-mustang-*` builds, this arranges for `mustang` libraries to be used. In
 /// all other builds, this does nothing.
+///
+/// It optionally takes settings for the process, applied at startup:
+///
+/// ```no_run
+/// mustang::can_run_this!(
+///     thread_stack_size = 1 << 20,
+///     thread_guard_size = 64 << 10,
+/// );
+/// # fn main() {}
+/// ```
+///
+/// See `mustang::thread` for what each setting means, and the environment
+/// variables which override them.
 #[macro_export]
 macro_rules! can_run_this {
     // This expands to nothing, yet appears to be sufficient.
@@ -18,7 +43,151 @@ macro_rules! can_run_this {
     // It appears that just this empty macro is sufficient to make rustc
     // believe that the crate is used, which gives us the chance we need.
     () => {};
+
+    ($($option:ident = $value:expr),+ $(,)?) => {
+        #[cfg(target_vendor = "mustang")]
+        const _: () = {
+            // This runs after `mustang::auxv` has initialized, and before
+            // the environment variables are read.
+            #[link_section = ".init_array.00001"]
+            #[used]
+            static INIT_OPTIONS: unsafe extern "C" fn(
+                ::core::ffi::c_int,
+                *mut *mut ::core::ffi::c_char,
+                *mut *mut ::core::ffi::c_char,
+            ) = {
+                unsafe extern "C" fn function(
+                    _argc: ::core::ffi::c_int,
+                    _argv: *mut *mut ::core::ffi::c_char,
+                    _envp: *mut *mut ::core::ffi::c_char,
+                ) {
+                    $($crate::thread::__can_run_this::$option($value);)+
+                }
+                function
+            };
+        };
+    };
+}
+
+/// Declare a Landlock sandbox to apply at startup.
+///
+/// The ruleset is placed in the binary's `.mustang_sandbox` section, and is
+/// applied before `.init_array` functions at normal priorities, `#[ctor]`
+/// functions, and `main` run. If it can't be applied, for example because the
+/// kernel doesn't support Landlock, the program aborts.
+///
+/// ```no_run
+/// mustang::sandbox! {
+///     read: ["/usr", "/etc/ssl"],
+///     write: ["/tmp"],
+///     execute: ["/usr/bin"],
+///     connect_tcp: [443],
+/// }
+/// # fn main() {}
+/// ```
+///
+/// See `mustang::sandbox::Policy` for what each rule means. In builds for
+/// targets other than `*-mustang-*`, this does nothing.
+#[macro_export]
+macro_rules! sandbox {
+    ($($rule:ident: [$($value:expr),* $(,)?]),* $(,)?) => {
+        #[cfg(target_vendor = "mustang")]
+        const _: () = {
+            #[link_section = ".mustang_sandbox"]
+            #[used]
+            static POLICY: $crate::sandbox::Policy =
+                $crate::sandbox::Policy::new()$(.$rule(&[$($value),*]))*;
+
+            // This runs after mustang's own startup functions, which read
+            // the auxv and the options, set up the main thread's stack, and
+            // install the seccomp filter.
+            #[link_section = ".init_array.00004"]
+            #[used]
+            static INIT_SANDBOX: unsafe extern "C" fn(
+                ::core::ffi::c_int,
+                *mut *mut ::core::ffi::c_char,
+                *mut *mut ::core::ffi::c_char,
+            ) = {
+                unsafe extern "C" fn function(
+                    _argc: ::core::ffi::c_int,
+                    _argv: *mut *mut ::core::ffi::c_char,
+                    _envp: *mut *mut ::core::ffi::c_char,
+                ) {
+                    $crate::sandbox::__apply_declared(&POLICY);
+                }
+                function
+            };
+        };
+    };
+}
+
+/// Pass each argument to the linker, for every program which links mustang.
+/// The modules which implement functions c-scape also defines use this to
+/// bind the standard names to mustang's implementations with `--defsym`.
+#[cfg(target_vendor = "mustang")]
+macro_rules! link_args {
+    ($($arg:literal),* $(,)?) => {
+        $(
+            #[link(kind = "link-arg", name = $arg, modifiers = "+verbatim")]
+            extern "C" {}
+        )*
+    };
 }
 
 #[cfg(target_vendor = "mustang")]
 extern crate c_gull;
+
+#[cfg(target_vendor = "mustang")]
+pub mod args;
+#[cfg(target_vendor = "mustang")]
+pub mod auxv;
+#[cfg(all(target_vendor = "mustang", feature = "thread"))]
+mod barrier;
+#[cfg(all(target_vendor = "mustang", feature = "thread"))]
+mod cancel;
+#[cfg(target_vendor = "mustang")]
+pub mod caps;
+#[cfg(all(target_vendor = "mustang", feature = "deterministic"))]
+pub mod deterministic;
+#[cfg(target_vendor = "mustang")]
+mod fortify;
+#[cfg(target_vendor = "mustang")]
+pub mod hwcap;
+#[cfg(all(target_vendor = "mustang", feature = "thread"))]
+mod mutex;
+#[cfg(all(target_vendor = "mustang", feature = "std"))]
+pub mod process;
+#[cfg(all(target_vendor = "mustang", feature = "record-replay"))]
+pub mod replay;
+#[cfg(all(target_vendor = "mustang", feature = "thread"))]
+mod rwlock;
+#[cfg(target_vendor = "mustang")]
+pub mod sandbox;
+#[cfg(all(target_vendor = "mustang", feature = "seccomp"))]
+pub mod seccomp;
+#[cfg(all(target_vendor = "mustang", feature = "thread"))]
+mod semaphore;
+#[cfg(target_vendor = "mustang")]
+pub mod signal;
+#[cfg(target_vendor = "mustang")]
+mod spawn;
+#[cfg(target_vendor = "mustang")]
+pub mod stack;
+#[cfg(target_vendor = "mustang")]
+pub mod stack_protector;
+#[cfg(target_vendor = "mustang")]
+mod startup;
+#[cfg(target_vendor = "mustang")]
+mod syscall;
+#[cfg(all(target_vendor = "mustang", feature = "thread"))]
+pub mod thread;
+#[cfg(target_vendor = "mustang")]
+pub mod time;
+#[cfg(target_vendor = "mustang")]
+mod timer;
+#[cfg(target_vendor = "mustang")]
+pub mod tls;
+#[cfg(all(target_vendor = "mustang", feature = "record-replay"))]
+mod trap;
+#[cfg(target_vendor = "mustang")]
+mod vdso;
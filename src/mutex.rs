@@ -0,0 +1,819 @@
+//! Robust, priority-inheritance and process-shared mutexes.
+//!
+//! The pthread mutexes c-scape implements don't support
+//! `PTHREAD_MUTEX_ROBUST` or `PTHREAD_PRIO_INHERIT`, and c-scape's
+//! definitions take precedence over weak ones, so the implementations here
+//! are named `__mustang_pthread_mutex_lock` and so on, and this module passes
+//! the linker `--defsym` options which bind the standard names to them. C
+//! code, and Rust code using the `libc` crate's bindings, both call these,
+//! so they can share mutexes and condition variables.
+//!
+//! In builds with fat LTO, rustc may bind or inline Rust calls to c-scape's
+//! definitions before the linker sees them, and the options then only
+//! affect C code. Don't share these objects between C and Rust code in such
+//! builds.
+//!
+//! A mutex's futex word holds its owner's tid, with the kernel's
+//! `FUTEX_WAITERS` and `FUTEX_OWNER_DIED` bits, which is the protocol the
+//! kernel needs for both robust and PI futexes. Robust mutexes are linked
+//! into their owning thread's robust list, which is registered with
+//! `set_robust_list` the first time a thread locks one, since origin doesn't
+//! register one. When a thread exits holding robust mutexes, the kernel sets
+//! `FUTEX_OWNER_DIED` in each and wakes a waiter, whose lock returns
+//! `EOWNERDEAD`; unlocking the mutex without calling
+//! `pthread_mutex_consistent` first makes it permanently unusable, and
+//! further locks return `ENOTRECOVERABLE`. `PTHREAD_PRIO_INHERIT` mutexes
+//! wait with `FUTEX_LOCK_PI`, so the kernel boosts the owner's priority to
+//! that of its highest-priority waiter.
+//!
+//! Robust mutexes always use shared futex operations, because that's how
+//! the kernel wakes their waiters when an owner dies; other mutexes, and
+//! condition variables, use private ones unless they're
+//! `PTHREAD_PROCESS_SHARED`. The rwlock, barrier and semaphore modules
+//! follow the same scheme.
+
+use core::cell::{Cell, UnsafeCell};
+use core::ffi::c_int;
+use core::mem::{align_of, offset_of, size_of};
+use core::num::NonZeroU32;
+use core::ptr::addr_of_mut;
+use core::sync::atomic::{AtomicU32, Ordering};
+use rustix::io::{self, Errno};
+use rustix::thread::futex::{self, Flags, Timespec, OWNER_DIED, WAITERS};
+
+use crate::cancel;
+use crate::syscall::{nr, syscall2};
+
+// Bind the standard names to the implementations here; see above.
+link_args!(
+    "-Wl,--defsym=pthread_mutexattr_init=__mustang_pthread_mutexattr_init",
+    "-Wl,--defsym=pthread_mutexattr_destroy=__mustang_pthread_mutexattr_destroy",
+    "-Wl,--defsym=pthread_mutexattr_settype=__mustang_pthread_mutexattr_settype",
+    "-Wl,--defsym=pthread_mutexattr_gettype=__mustang_pthread_mutexattr_gettype",
+    "-Wl,--defsym=pthread_mutexattr_setpshared=__mustang_pthread_mutexattr_setpshared",
+    "-Wl,--defsym=pthread_mutexattr_getpshared=__mustang_pthread_mutexattr_getpshared",
+    "-Wl,--defsym=pthread_mutexattr_setrobust=__mustang_pthread_mutexattr_setrobust",
+    "-Wl,--defsym=pthread_mutexattr_getrobust=__mustang_pthread_mutexattr_getrobust",
+    "-Wl,--defsym=pthread_mutexattr_setprotocol=__mustang_pthread_mutexattr_setprotocol",
+    "-Wl,--defsym=pthread_mutexattr_getprotocol=__mustang_pthread_mutexattr_getprotocol",
+    "-Wl,--defsym=pthread_mutex_init=__mustang_pthread_mutex_init",
+    "-Wl,--defsym=pthread_mutex_destroy=__mustang_pthread_mutex_destroy",
+    "-Wl,--defsym=pthread_mutex_lock=__mustang_pthread_mutex_lock",
+    "-Wl,--defsym=pthread_mutex_trylock=__mustang_pthread_mutex_trylock",
+    "-Wl,--defsym=pthread_mutex_timedlock=__mustang_pthread_mutex_timedlock",
+    "-Wl,--defsym=pthread_mutex_unlock=__mustang_pthread_mutex_unlock",
+    "-Wl,--defsym=pthread_mutex_consistent=__mustang_pthread_mutex_consistent",
+    "-Wl,--defsym=pthread_condattr_init=__mustang_pthread_condattr_init",
+    "-Wl,--defsym=pthread_condattr_destroy=__mustang_pthread_condattr_destroy",
+    "-Wl,--defsym=pthread_condattr_setclock=__mustang_pthread_condattr_setclock",
+    "-Wl,--defsym=pthread_condattr_getclock=__mustang_pthread_condattr_getclock",
+    "-Wl,--defsym=pthread_condattr_setpshared=__mustang_pthread_condattr_setpshared",
+    "-Wl,--defsym=pthread_condattr_getpshared=__mustang_pthread_condattr_getpshared",
+    "-Wl,--defsym=pthread_cond_init=__mustang_pthread_cond_init",
+    "-Wl,--defsym=pthread_cond_destroy=__mustang_pthread_cond_destroy",
+    "-Wl,--defsym=pthread_cond_wait=__mustang_pthread_cond_wait",
+    "-Wl,--defsym=pthread_cond_timedwait=__mustang_pthread_cond_timedwait",
+    "-Wl,--defsym=pthread_cond_signal=__mustang_pthread_cond_signal",
+    "-Wl,--defsym=pthread_cond_broadcast=__mustang_pthread_cond_broadcast",
+);
+
+/// The owner's tid in a futex word.
+const TID_MASK: u32 = 0x3fff_ffff;
+
+// The bits of a mutex's `kind`, and of a `pthread_mutexattr_t`. The low two
+// bits are the type.
+const TYPE_MASK: u32 = 3;
+const ROBUST: u32 = 1 << 2;
+const PI: u32 = 1 << 3;
+const PSHARED: u32 = 1 << 4;
+/// A robust mutex whose owner died, until `pthread_mutex_consistent`.
+const INCONSISTENT: u32 = 1 << 5;
+/// A robust mutex unlocked while inconsistent, which can't be locked again.
+const NOTRECOVERABLE: u32 = 1 << 6;
+
+const NORMAL: u32 = libc::PTHREAD_MUTEX_NORMAL as u32;
+const RECURSIVE: u32 = libc::PTHREAD_MUTEX_RECURSIVE as u32;
+const ERRORCHECK: u32 = libc::PTHREAD_MUTEX_ERRORCHECK as u32;
+
+/// Our layout of a `pthread_mutex_t`. A zeroed one, which is what
+/// `PTHREAD_MUTEX_INITIALIZER` is, is an unlocked normal mutex.
+#[repr(C)]
+struct Mutex {
+    /// The futex word.
+    word: AtomicU32,
+    /// How many more times a recursive mutex's owner has locked it.
+    count: UnsafeCell<u32>,
+    kind: AtomicU32,
+    /// The robust list links. The kernel only follows `next`, whose low bit
+    /// is set for PI mutexes.
+    next: UnsafeCell<usize>,
+    prev: UnsafeCell<usize>,
+}
+
+const _: () = assert!(size_of::<Mutex>() <= size_of::<libc::pthread_mutex_t>());
+const _: () = assert!(align_of::<Mutex>() <= align_of::<libc::pthread_mutex_t>());
+const _: () = assert!(offset_of!(Mutex, prev) == offset_of!(Mutex, next) + size_of::<usize>());
+const _: () = assert!(size_of::<u32>() <= size_of::<libc::pthread_mutexattr_t>());
+
+/// The kernel's `struct robust_list_head`.
+#[repr(C)]
+struct RobustListHead {
+    list: usize,
+    futex_offset: isize,
+    list_op_pending: usize,
+}
+
+#[thread_local]
+static HEAD: UnsafeCell<RobustListHead> = UnsafeCell::new(RobustListHead {
+    list: 0,
+    futex_offset: 0,
+    list_op_pending: 0,
+});
+/// The tid `HEAD` is registered for. After `fork`, the child's thread has a
+/// new tid and no robust list.
+#[thread_local]
+static REGISTERED: Cell<u32> = Cell::new(0);
+
+/// How long to wait for a lock.
+#[derive(Clone, Copy)]
+pub(crate) enum Timeout<'a> {
+    Never,
+    /// Until an absolute `CLOCK_REALTIME` time.
+    Until(&'a Timespec),
+    /// Don't wait.
+    Try,
+}
+
+pub(crate) fn tid() -> u32 {
+    rustix::thread::gettid().as_raw_nonzero().get() as u32
+}
+
+fn flags(kind: u32) -> Flags {
+    futex_flags(kind & (ROBUST | PSHARED) != 0)
+}
+
+/// The futex flags for an object which is or isn't process-shared.
+pub(crate) fn futex_flags(pshared: bool) -> Flags {
+    if pshared {
+        Flags::empty()
+    } else {
+        Flags::PRIVATE
+    }
+}
+
+/// Wait on `word` while it holds `val`. Spurious wakeups, and `EAGAIN` if
+/// it doesn't hold `val`, are reported as errors for callers to retry.
+pub(crate) fn futex_wait(
+    word: &AtomicU32,
+    flags: Flags,
+    val: u32,
+    timeout: Timeout<'_>,
+) -> Result<(), Errno> {
+    match timeout {
+        Timeout::Never => futex::wait(word, flags, val, None),
+        Timeout::Until(until) => futex::wait_bitset(
+            word,
+            flags | Flags::CLOCK_REALTIME,
+            val,
+            Some(until),
+            NonZeroU32::MAX,
+        ),
+        Timeout::Try => Err(Errno::BUSY),
+    }
+}
+
+/// The calling thread's robust list, registering it if needed.
+unsafe fn head(tid: u32) -> io::Result<*mut RobustListHead> {
+    let head = HEAD.get();
+    if REGISTERED.get() != tid {
+        // An empty list points to itself.
+        (*head).list = addr_of_mut!((*head).list) as usize;
+        (*head).futex_offset = offset_of!(Mutex, word) as isize - offset_of!(Mutex, next) as isize;
+        (*head).list_op_pending = 0;
+        syscall2(
+            nr::SET_ROBUST_LIST,
+            head as usize,
+            size_of::<RobustListHead>(),
+        )?;
+        REGISTERED.set(tid);
+    }
+    Ok(head)
+}
+
+impl Mutex {
+    /// This mutex's robust list entry, as the kernel expects to find it.
+    fn entry(&self, kind: u32) -> usize {
+        self.next.get() as usize | (kind & PI != 0) as usize
+    }
+
+    unsafe fn enqueue(&self, head: *mut RobustListHead, kind: u32) {
+        let list = addr_of_mut!((*head).list);
+        let first = *list;
+        *self.next.get() = first;
+        *self.prev.get() = list as usize;
+        if first & !1 != list as usize {
+            *((first & !1) as *mut usize).add(1) = self.next.get() as usize;
+        }
+        *list = self.entry(kind);
+    }
+
+    unsafe fn dequeue(&self, head: *mut RobustListHead) {
+        let next = *self.next.get();
+        let prev = *self.prev.get();
+        *(prev as *mut usize) = next;
+        if next & !1 != addr_of_mut!((*head).list) as usize {
+            *((next & !1) as *mut usize).add(1) = prev;
+        }
+    }
+
+    /// Lock the mutex, returning `EOWNERDEAD` if it's locked but
+    /// inconsistent.
+    unsafe fn lock(&self, timeout: Timeout<'_>) -> Result<(), Errno> {
+        let kind = self.kind.load(Ordering::Relaxed);
+        if kind & NOTRECOVERABLE != 0 {
+            return Err(Errno::NOTRECOVERABLE);
+        }
+        let tid = tid();
+        if self.word.load(Ordering::Relaxed) & TID_MASK == tid {
+            match kind & TYPE_MASK {
+                RECURSIVE => {
+                    let count = &mut *self.count.get();
+                    *count = count.checked_add(1).ok_or(Errno::AGAIN)?;
+                    return Ok(());
+                }
+                ERRORCHECK => return Err(Errno::DEADLK),
+                // Relocking a normal mutex deadlocks, except that the
+                // kernel refuses for PI mutexes, and like glibc, we refuse
+                // for robust ones.
+                _ if kind & (ROBUST | PI) != 0 => return Err(Errno::DEADLK),
+                _ => {}
+            }
+        }
+
+        let head = if kind & ROBUST != 0 {
+            let head = head(tid)?;
+            (*head).list_op_pending = self.entry(kind);
+            Some(head)
+        } else {
+            None
+        };
+        let result = if kind & PI != 0 {
+            self.lock_pi(kind, timeout)
+        } else {
+            self.lock_futex(kind, tid, timeout)
+        };
+        if let Some(head) = head {
+            if result.is_ok() {
+                self.enqueue(head, kind);
+            }
+            (*head).list_op_pending = 0;
+        }
+        let died = result?;
+        *self.count.get() = 0;
+
+        if self.kind.load(Ordering::Relaxed) & NOTRECOVERABLE != 0 {
+            // A PI mutex is handed to a waiter by the kernel, even when it
+            // was made unrecoverable.
+            self.unlock_locked(kind, tid);
+            return Err(Errno::NOTRECOVERABLE);
+        }
+        if died {
+            self.word.fetch_and(!OWNER_DIED, Ordering::Relaxed);
+            if kind & ROBUST != 0 {
+                self.kind.fetch_or(INCONSISTENT, Ordering::Relaxed);
+                return Err(Errno::OWNERDEAD);
+            }
+        }
+        Ok(())
+    }
+
+    /// Lock a non-PI mutex, returning whether its previous owner died.
+    unsafe fn lock_futex(&self, kind: u32, tid: u32, timeout: Timeout<'_>) -> Result<bool, Errno> {
+        let word = &self.word;
+        if word
+            .compare_exchange(0, tid, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            return Ok(false);
+        }
+        loop {
+            if self.kind.load(Ordering::Relaxed) & NOTRECOVERABLE != 0 {
+                return Err(Errno::NOTRECOVERABLE);
+            }
+            let current = word.load(Ordering::Relaxed);
+            if current & TID_MASK == 0 {
+                // It's free, perhaps because its owner died. Other threads
+                // may still be waiting, so keep `WAITERS` set.
+                if word
+                    .compare_exchange(current, tid | WAITERS, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return Ok(current & OWNER_DIED != 0);
+                }
+                continue;
+            }
+            if let Timeout::Try = timeout {
+                return Err(Errno::BUSY);
+            }
+            if current & WAITERS == 0
+                && word
+                    .compare_exchange(
+                        current,
+                        current | WAITERS,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    )
+                    .is_err()
+            {
+                continue;
+            }
+            match futex_wait(word, flags(kind), current | WAITERS, timeout) {
+                Ok(()) | Err(Errno::AGAIN) | Err(Errno::INTR) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Lock a PI mutex, returning whether its previous owner died.
+    unsafe fn lock_pi(&self, kind: u32, timeout: Timeout<'_>) -> Result<bool, Errno> {
+        let word = &self.word;
+        if word
+            .compare_exchange(0, tid(), Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            return Ok(false);
+        }
+        loop {
+            // The kernel takes `FUTEX_LOCK_PI` timeouts as absolute
+            // `CLOCK_REALTIME` times.
+            let result = match timeout {
+                Timeout::Never => futex::lock_pi(word, flags(kind), None),
+                Timeout::Until(until) => futex::lock_pi(word, flags(kind), Some(until)),
+                Timeout::Try => match futex::trylock_pi(word, flags(kind)) {
+                    Ok(true) => Ok(()),
+                    Ok(false) | Err(Errno::AGAIN) => Err(Errno::BUSY),
+                    Err(err) => Err(err),
+                },
+            };
+            match result {
+                Ok(()) => return Ok(word.load(Ordering::Relaxed) & OWNER_DIED != 0),
+                // The owner is exiting; try again.
+                Err(Errno::AGAIN) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    unsafe fn unlock(&self) -> Result<(), Errno> {
+        let mut kind = self.kind.load(Ordering::Relaxed);
+        let tid = tid();
+        if self.word.load(Ordering::Relaxed) & TID_MASK != tid
+            && (kind & TYPE_MASK != NORMAL || kind & (ROBUST | PI) != 0)
+        {
+            return Err(Errno::PERM);
+        }
+        if kind & TYPE_MASK == RECURSIVE && *self.count.get() != 0 {
+            *self.count.get() -= 1;
+            return Ok(());
+        }
+        if kind & INCONSISTENT != 0 {
+            kind = kind & !INCONSISTENT | NOTRECOVERABLE;
+            self.kind.store(kind, Ordering::Relaxed);
+        }
+        self.unlock_locked(kind, tid);
+        Ok(())
+    }
+
+    /// Unlock the mutex, which the calling thread has locked.
+    unsafe fn unlock_locked(&self, kind: u32, tid: u32) {
+        let head = if kind & ROBUST != 0 {
+            let head = HEAD.get();
+            (*head).list_op_pending = self.entry(kind);
+            self.dequeue(head);
+            Some(head)
+        } else {
+            None
+        };
+        if kind & PI != 0 {
+            if self
+                .word
+                .compare_exchange(tid, 0, Ordering::Release, Ordering::Relaxed)
+                .is_err()
+            {
+                let _ = futex::unlock_pi(&self.word, flags(kind));
+            }
+        } else if self.word.swap(0, Ordering::Release) & WAITERS != 0 {
+            // Wake everyone when the mutex becomes unrecoverable, so that
+            // their locks fail.
+            let count = if kind & NOTRECOVERABLE != 0 {
+                i32::MAX as u32
+            } else {
+                1
+            };
+            let _ = futex::wake(&self.word, flags(kind), count);
+        }
+        if let Some(head) = head {
+            (*head).list_op_pending = 0;
+        }
+    }
+}
+
+/// Our layout of a `pthread_cond_t`.
+#[repr(C)]
+struct Cond {
+    /// Incremented by each signal and broadcast.
+    seq: AtomicU32,
+    /// The clock, and `PSHARED`, as in a `pthread_condattr_t`.
+    attr: u32,
+}
+
+const _: () = assert!(size_of::<Cond>() <= size_of::<libc::pthread_cond_t>());
+const _: () = assert!(size_of::<u32>() <= size_of::<libc::pthread_condattr_t>());
+
+impl Cond {
+    /// Wait, as a cancellation point. See the cancel module.
+    unsafe fn wait(&self, mutex: &Mutex, timeout: Option<&Timespec>) -> Result<(), Errno> {
+        let pshared = self.attr & PSHARED != 0;
+        cancel::cond_wait(&self.seq, pshared, |seq| {
+            mutex.unlock()?;
+            let flags = futex_flags(pshared);
+            let result = match timeout {
+                Some(until) => {
+                    let flags = if self.attr & !PSHARED == libc::CLOCK_REALTIME as u32 {
+                        flags | Flags::CLOCK_REALTIME
+                    } else {
+                        flags
+                    };
+                    futex::wait_bitset(&self.seq, flags, seq, Some(until), NonZeroU32::MAX)
+                }
+                None => futex::wait(&self.seq, flags, seq, None),
+            };
+            mutex.lock(Timeout::Never)?;
+            match result {
+                Err(Errno::TIMEDOUT) => Err(Errno::TIMEDOUT),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    fn wake(&self, count: u32) {
+        self.seq.fetch_add(1, Ordering::Release);
+        let _ = futex::wake(&self.seq, futex_flags(self.attr & PSHARED != 0), count);
+    }
+}
+
+/// Convert an absolute timeout.
+// `time_t` and `c_long` are 32-bit on some targets.
+#[allow(clippy::useless_conversion)]
+pub(crate) unsafe fn timespec(ts: *const libc::timespec) -> Result<Timespec, Errno> {
+    let ts = &*ts;
+    if !(0..1_000_000_000).contains(&ts.tv_nsec) {
+        return Err(Errno::INVAL);
+    }
+    Ok(Timespec {
+        tv_sec: ts.tv_sec.into(),
+        tv_nsec: ts.tv_nsec.into(),
+    })
+}
+
+pub(crate) fn ret(result: Result<(), Errno>) -> c_int {
+    match result {
+        Ok(()) => 0,
+        Err(err) => err.raw_os_error(),
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutexattr_init(
+    attr: *mut libc::pthread_mutexattr_t,
+) -> c_int {
+    attr.cast::<u32>().write(0);
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutexattr_destroy(
+    _attr: *mut libc::pthread_mutexattr_t,
+) -> c_int {
+    0
+}
+
+/// Set the bits `mask` of `attr` to `value`.
+unsafe fn set(attr: *mut libc::pthread_mutexattr_t, mask: u32, value: u32) {
+    let attr = attr.cast::<u32>();
+    *attr = *attr & !mask | value;
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutexattr_settype(
+    attr: *mut libc::pthread_mutexattr_t,
+    type_: c_int,
+) -> c_int {
+    match type_ as u32 {
+        NORMAL | RECURSIVE | ERRORCHECK => {
+            set(attr, TYPE_MASK, type_ as u32);
+            0
+        }
+        _ => libc::EINVAL,
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutexattr_gettype(
+    attr: *const libc::pthread_mutexattr_t,
+    type_: *mut c_int,
+) -> c_int {
+    *type_ = (*attr.cast::<u32>() & TYPE_MASK) as c_int;
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutexattr_setpshared(
+    attr: *mut libc::pthread_mutexattr_t,
+    pshared: c_int,
+) -> c_int {
+    match pshared {
+        libc::PTHREAD_PROCESS_PRIVATE => set(attr, PSHARED, 0),
+        libc::PTHREAD_PROCESS_SHARED => set(attr, PSHARED, PSHARED),
+        _ => return libc::EINVAL,
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutexattr_getpshared(
+    attr: *const libc::pthread_mutexattr_t,
+    pshared: *mut c_int,
+) -> c_int {
+    *pshared = if *attr.cast::<u32>() & PSHARED != 0 {
+        libc::PTHREAD_PROCESS_SHARED
+    } else {
+        libc::PTHREAD_PROCESS_PRIVATE
+    };
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutexattr_setrobust(
+    attr: *mut libc::pthread_mutexattr_t,
+    robust: c_int,
+) -> c_int {
+    match robust {
+        libc::PTHREAD_MUTEX_STALLED => set(attr, ROBUST, 0),
+        libc::PTHREAD_MUTEX_ROBUST => set(attr, ROBUST, ROBUST),
+        _ => return libc::EINVAL,
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutexattr_getrobust(
+    attr: *const libc::pthread_mutexattr_t,
+    robust: *mut c_int,
+) -> c_int {
+    *robust = if *attr.cast::<u32>() & ROBUST != 0 {
+        libc::PTHREAD_MUTEX_ROBUST
+    } else {
+        libc::PTHREAD_MUTEX_STALLED
+    };
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutexattr_setprotocol(
+    attr: *mut libc::pthread_mutexattr_t,
+    protocol: c_int,
+) -> c_int {
+    match protocol {
+        libc::PTHREAD_PRIO_NONE => set(attr, PI, 0),
+        libc::PTHREAD_PRIO_INHERIT => set(attr, PI, PI),
+        libc::PTHREAD_PRIO_PROTECT => return libc::ENOTSUP,
+        _ => return libc::EINVAL,
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutexattr_getprotocol(
+    attr: *const libc::pthread_mutexattr_t,
+    protocol: *mut c_int,
+) -> c_int {
+    *protocol = if *attr.cast::<u32>() & PI != 0 {
+        libc::PTHREAD_PRIO_INHERIT
+    } else {
+        libc::PTHREAD_PRIO_NONE
+    };
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutex_init(
+    mutex: *mut libc::pthread_mutex_t,
+    attr: *const libc::pthread_mutexattr_t,
+) -> c_int {
+    let kind = if attr.is_null() {
+        0
+    } else {
+        *attr.cast::<u32>()
+    };
+    mutex.cast::<Mutex>().write(Mutex {
+        word: AtomicU32::new(0),
+        count: UnsafeCell::new(0),
+        kind: AtomicU32::new(kind),
+        next: UnsafeCell::new(0),
+        prev: UnsafeCell::new(0),
+    });
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutex_destroy(mutex: *mut libc::pthread_mutex_t) -> c_int {
+    if (*mutex.cast::<Mutex>()).word.load(Ordering::Relaxed) & TID_MASK != 0 {
+        return libc::EBUSY;
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutex_lock(mutex: *mut libc::pthread_mutex_t) -> c_int {
+    ret((*mutex.cast::<Mutex>()).lock(Timeout::Never))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutex_trylock(mutex: *mut libc::pthread_mutex_t) -> c_int {
+    ret((*mutex.cast::<Mutex>()).lock(Timeout::Try))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutex_timedlock(
+    mutex: *mut libc::pthread_mutex_t,
+    abstime: *const libc::timespec,
+) -> c_int {
+    ret(timespec(abstime).and_then(|until| (*mutex.cast::<Mutex>()).lock(Timeout::Until(&until))))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutex_unlock(mutex: *mut libc::pthread_mutex_t) -> c_int {
+    ret((*mutex.cast::<Mutex>()).unlock())
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_mutex_consistent(
+    mutex: *mut libc::pthread_mutex_t,
+) -> c_int {
+    let mutex = &*mutex.cast::<Mutex>();
+    let kind = mutex.kind.load(Ordering::Relaxed);
+    if kind & ROBUST == 0
+        || kind & INCONSISTENT == 0
+        || mutex.word.load(Ordering::Relaxed) & TID_MASK != tid()
+    {
+        return libc::EINVAL;
+    }
+    mutex.kind.fetch_and(!INCONSISTENT, Ordering::Relaxed);
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_condattr_init(attr: *mut libc::pthread_condattr_t) -> c_int {
+    attr.cast::<u32>().write(libc::CLOCK_REALTIME as u32);
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_condattr_destroy(
+    _attr: *mut libc::pthread_condattr_t,
+) -> c_int {
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_condattr_setclock(
+    attr: *mut libc::pthread_condattr_t,
+    clock: libc::clockid_t,
+) -> c_int {
+    match clock {
+        libc::CLOCK_REALTIME | libc::CLOCK_MONOTONIC => {
+            let attr = attr.cast::<u32>();
+            *attr = *attr & PSHARED | clock as u32;
+            0
+        }
+        _ => libc::EINVAL,
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_condattr_getclock(
+    attr: *const libc::pthread_condattr_t,
+    clock: *mut libc::clockid_t,
+) -> c_int {
+    *clock = (*attr.cast::<u32>() & !PSHARED) as libc::clockid_t;
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_condattr_setpshared(
+    attr: *mut libc::pthread_condattr_t,
+    pshared: c_int,
+) -> c_int {
+    let attr = attr.cast::<u32>();
+    match pshared {
+        libc::PTHREAD_PROCESS_PRIVATE => *attr &= !PSHARED,
+        libc::PTHREAD_PROCESS_SHARED => *attr |= PSHARED,
+        _ => return libc::EINVAL,
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_condattr_getpshared(
+    attr: *const libc::pthread_condattr_t,
+    pshared: *mut c_int,
+) -> c_int {
+    *pshared = if *attr.cast::<u32>() & PSHARED != 0 {
+        libc::PTHREAD_PROCESS_SHARED
+    } else {
+        libc::PTHREAD_PROCESS_PRIVATE
+    };
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_cond_init(
+    cond: *mut libc::pthread_cond_t,
+    attr: *const libc::pthread_condattr_t,
+) -> c_int {
+    let attr = if attr.is_null() {
+        libc::CLOCK_REALTIME as u32
+    } else {
+        *attr.cast::<u32>()
+    };
+    cond.cast::<Cond>().write(Cond {
+        seq: AtomicU32::new(0),
+        attr,
+    });
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_cond_destroy(_cond: *mut libc::pthread_cond_t) -> c_int {
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C-unwind" fn __mustang_pthread_cond_wait(
+    cond: *mut libc::pthread_cond_t,
+    mutex: *mut libc::pthread_mutex_t,
+) -> c_int {
+    ret((*cond.cast::<Cond>()).wait(&*mutex.cast::<Mutex>(), None))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C-unwind" fn __mustang_pthread_cond_timedwait(
+    cond: *mut libc::pthread_cond_t,
+    mutex: *mut libc::pthread_mutex_t,
+    abstime: *const libc::timespec,
+) -> c_int {
+    ret(timespec(abstime)
+        .and_then(|until| (*cond.cast::<Cond>()).wait(&*mutex.cast::<Mutex>(), Some(&until))))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_cond_signal(cond: *mut libc::pthread_cond_t) -> c_int {
+    (*cond.cast::<Cond>()).wake(1);
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_cond_broadcast(cond: *mut libc::pthread_cond_t) -> c_int {
+    (*cond.cast::<Cond>()).wake(i32::MAX as u32);
+    0
+}
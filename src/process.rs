@@ -0,0 +1,266 @@
+//! Child process management with pidfds.
+//!
+//! A pid can be reused as soon as the process it named has been reaped, so
+//! signaling a child by pid races with whoever waits for it. A pidfd refers
+//! to one specific process, so signaling and waiting through it is race-free.
+//!
+//! [`PidFd::spawn`] starts a child with `CLONE_PIDFD`, so that the pidfd
+//! exists from the start. For children started by `std::process::Command`,
+//! [`CommandExt::spawn_pidfd`] has std create a pidfd as part of spawning the
+//! child, and [`ChildExt`] uses the one std keeps in the `Child`. Opening a
+//! pidfd for a child's pid afterwards would race with anything else which
+//! reaps it, such as a `SIGCHLD` handler or a `waitpid(-1, ...)` elsewhere
+//! in the program.
+//!
+//! A pidfd is readable once the process has exited, so it can be used with
+//! `poll` and `epoll` to wait for children along with other events.
+//!
+//! Pidfds require Linux 5.3, and waiting with them requires Linux 5.4.
+
+extern crate alloc;
+extern crate std;
+
+use crate::syscall::{nr, syscall2, syscall4, syscall5};
+use alloc::vec::Vec;
+use core::ffi::{c_char, c_int, CStr};
+use core::mem::zeroed;
+use core::ptr::{null, null_mut};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::linux::process::CommandExt as _;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, Command, ExitStatus};
+
+/// A pidfd, referring to a process.
+#[derive(Debug)]
+pub struct PidFd(OwnedFd);
+
+impl PidFd {
+    /// Open a pidfd for the process with id `pid`.
+    pub fn open(pid: u32) -> io::Result<Self> {
+        // SAFETY: `pidfd_open` has no memory-safety preconditions.
+        let fd = unsafe { syscall2(nr::PIDFD_OPEN, pid as usize, 0) }.map_err(errno)?;
+        // SAFETY: `pidfd_open` returned a new fd.
+        Ok(Self(unsafe { OwnedFd::from_raw_fd(fd as RawFd) }))
+    }
+
+    /// Spawn a child process running the program at `path`, with arguments
+    /// `argv` and environment `envp`, or this process's environment if
+    /// `envp` is `None`, and return a pidfd for it.
+    ///
+    /// This uses mustang's `posix_spawn` implementation, with `CLONE_PIDFD`.
+    /// The child inherits the parent's file descriptors which aren't marked
+    /// close-on-exec.
+    pub fn spawn(path: &CStr, argv: &[&CStr], envp: Option<&[&CStr]>) -> io::Result<Self> {
+        extern "C" {
+            static environ: *const *mut c_char;
+        }
+
+        let argv = null_terminated(argv);
+        let envp = envp.map(null_terminated);
+        let mut pidfd = -1;
+        // SAFETY: The arrays are null-terminated arrays of C strings.
+        match unsafe {
+            crate::spawn::spawn(
+                null_mut(),
+                &mut pidfd,
+                path.as_ptr(),
+                false,
+                null(),
+                null(),
+                argv.as_ptr(),
+                envp.as_ref().map_or(environ, |envp| envp.as_ptr()),
+            )
+        } {
+            // SAFETY: `CLONE_PIDFD` gave us a new fd.
+            0 => Ok(Self(unsafe { OwnedFd::from_raw_fd(pidfd) })),
+            err => Err(io::Error::from_raw_os_error(err)),
+        }
+    }
+
+    /// The process's id, in our pid namespace.
+    ///
+    /// This fails with `ESRCH` if the process has exited and been reaped.
+    pub fn pid(&self) -> io::Result<u32> {
+        let fdinfo =
+            std::fs::read_to_string(std::format!("/proc/self/fdinfo/{}", self.0.as_raw_fd()))?;
+        let pid = fdinfo
+            .lines()
+            .find_map(|line| line.strip_prefix("Pid:"))
+            .and_then(|pid| pid.trim().parse::<i32>().ok())
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ENOSYS))?;
+        match pid {
+            -1 => Err(io::Error::from_raw_os_error(libc::ESRCH)),
+            // The process is in a pid namespace we can't see.
+            0 => Err(io::Error::from_raw_os_error(libc::EREMOTE)),
+            pid => Ok(pid as u32),
+        }
+    }
+
+    /// Send signal `sig` to the process.
+    ///
+    /// This fails with `ESRCH` if the process has exited, even if it hasn't
+    /// been reaped yet.
+    pub fn send_signal(&self, sig: c_int) -> io::Result<()> {
+        Self::send_signal_to(self.as_fd(), sig)
+    }
+
+    fn send_signal_to(pidfd: BorrowedFd<'_>, sig: c_int) -> io::Result<()> {
+        // SAFETY: `pidfd_send_signal` with a null `info` has no
+        // memory-safety preconditions.
+        unsafe {
+            syscall4(
+                nr::PIDFD_SEND_SIGNAL,
+                pidfd.as_raw_fd() as usize,
+                sig as usize,
+                0,
+                0,
+            )
+        }
+        .map_err(errno)?;
+        Ok(())
+    }
+
+    /// Wait for the process, which must be a child of this process, to exit,
+    /// and reap it.
+    pub fn wait(&self) -> io::Result<ExitStatus> {
+        loop {
+            match self.waitid(libc::WEXITED) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                result => return result.map(Option::unwrap),
+            }
+        }
+    }
+
+    /// If the process, which must be a child of this process, has exited,
+    /// reap it and return its exit status.
+    pub fn try_wait(&self) -> io::Result<Option<ExitStatus>> {
+        self.waitid(libc::WEXITED | libc::WNOHANG)
+    }
+
+    fn waitid(&self, options: c_int) -> io::Result<Option<ExitStatus>> {
+        // SAFETY: An all-zero `siginfo_t` is valid.
+        let mut info: libc::siginfo_t = unsafe { zeroed() };
+        // SAFETY: `info` is a valid `siginfo_t` for the kernel to write to.
+        unsafe {
+            syscall5(
+                nr::WAITID,
+                libc::P_PIDFD as usize,
+                self.0.as_raw_fd() as usize,
+                &mut info as *mut libc::siginfo_t as usize,
+                options as usize,
+                0,
+            )
+        }
+        .map_err(errno)?;
+
+        // With `WNOHANG`, if the process hasn't exited, `info` is left
+        // zeroed.
+        // SAFETY: `info` is zeroed or was filled in for a `SIGCHLD`.
+        let (pid, status) = unsafe { (info.si_pid(), info.si_status()) };
+        if pid == 0 {
+            return Ok(None);
+        }
+        let raw = match info.si_code {
+            libc::CLD_EXITED => (status & 0xff) << 8,
+            libc::CLD_KILLED => status & 0x7f,
+            libc::CLD_DUMPED => (status & 0x7f) | 0x80,
+            _ => return Err(io::Error::from_raw_os_error(libc::EINVAL)),
+        };
+        Ok(Some(ExitStatus::from_raw(raw)))
+    }
+}
+
+impl AsFd for PidFd {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl AsRawFd for PidFd {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for PidFd {
+    #[inline]
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+impl From<PidFd> for OwnedFd {
+    #[inline]
+    fn from(pidfd: PidFd) -> Self {
+        pidfd.0
+    }
+}
+
+/// Extensions to `std::process::Child` for using pidfds.
+///
+/// These use the pidfd std created when the child was spawned, so they fail
+/// for children which weren't spawned with [`CommandExt::spawn_pidfd`], or
+/// with std's `create_pidfd`.
+pub trait ChildExt {
+    /// Return a new pidfd for this child, duplicated from the one std
+    /// created when it was spawned.
+    ///
+    /// Use the pidfd for signaling, and keep using `Child::wait` to wait
+    /// for the child, so that `Child` knows it has been reaped.
+    fn pidfd(&self) -> io::Result<PidFd>;
+
+    /// Send signal `sig` to this child, through its pidfd.
+    fn send_signal(&self, sig: c_int) -> io::Result<()>;
+}
+
+impl ChildExt for Child {
+    fn pidfd(&self) -> io::Result<PidFd> {
+        let pidfd = std::os::linux::process::ChildExt::pidfd(self)?;
+        Ok(PidFd(pidfd.as_fd().try_clone_to_owned()?))
+    }
+
+    fn send_signal(&self, sig: c_int) -> io::Result<()> {
+        let pidfd = std::os::linux::process::ChildExt::pidfd(self)?;
+        PidFd::send_signal_to(pidfd.as_fd(), sig)
+    }
+}
+
+/// Extensions to `std::process::Command` for using pidfds.
+pub trait CommandExt {
+    /// Spawn the command, and return a pidfd for the child.
+    ///
+    /// This sets std's `create_pidfd` option on the command, so the pidfd is
+    /// created along with the child, and the `Child` keeps one too, for
+    /// [`ChildExt`].
+    fn spawn_pidfd(&mut self) -> io::Result<(Child, PidFd)>;
+}
+
+impl CommandExt for Command {
+    fn spawn_pidfd(&mut self) -> io::Result<(Child, PidFd)> {
+        let mut child = self.create_pidfd(true).spawn()?;
+        match ChildExt::pidfd(&child) {
+            Ok(pidfd) => Ok((child, pidfd)),
+            Err(err) => {
+                // We still have the child's pid, and it hasn't been waited
+                // for, so `Child` can clean it up.
+                let _ = child.kill();
+                let _ = child.wait();
+                Err(err)
+            }
+        }
+    }
+}
+
+fn null_terminated(strs: &[&CStr]) -> Vec<*mut c_char> {
+    strs.iter()
+        .map(|s| s.as_ptr().cast_mut())
+        .chain(core::iter::once(null_mut()))
+        .collect()
+}
+
+fn errno(err: rustix::io::Errno) -> io::Error {
+    io::Error::from_raw_os_error(err.raw_os_error())
+}
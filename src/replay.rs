@@ -0,0 +1,826 @@
+//! Recording and replaying syscall results.
+//!
+//! With the "record-replay" feature, setting the [`RECORD_ENV`] environment
+//! variable to a path makes mustang record the results of the program's
+//! nondeterministic syscalls in that file. Running the program again with
+//! [`REPLAY_ENV`] set to the file replays them: the recorded results, and the
+//! data the syscalls wrote into the program's memory, are fed back without
+//! making the syscalls, so a failing run can be reproduced, for example under
+//! a debugger.
+//!
+//! The recorded syscalls are:
+//!
+//!  - reads: `read`, `readv`, `pread64` and `preadv`,
+//!  - writes: `write` and `writev`,
+//!  - sockets: `socket`, `socketpair`, `bind`, `listen`, `accept`,
+//!    `accept4`, `connect`, `shutdown`, `getsockname`, `getpeername`,
+//!    `getsockopt`, `setsockopt`, and the `send` and `recv` families,
+//!  - polling: `ppoll`, `epoll_ctl`, `epoll_pwait` and `epoll_pwait2`,
+//!  - `getrandom` and `clock_gettime`.
+//!
+//! Other syscalls, such as opening files, `mmap` and `futex`, are made for
+//! real in both runs, so the replay should run in the same environment as
+//! the recording. When replaying, sockets are replaced by placeholder file
+//! descriptors with the same numbers, and writes aren't made, except to
+//! stdout and stderr, so that the program's output appears.
+//!
+//! Each thread is numbered in the order it first completes a recorded
+//! syscall, and the recording is in the order the syscalls completed. When
+//! replaying, threads take turns in that order, so a multithreaded program
+//! sees the same interleaving of results, as long as its threads make the
+//! same syscalls. If a thread makes a different syscall than the one
+//! recorded, the replay aborts. A thread which reaches the end of the
+//! recording blocks, as it was when the recording ended. Signals aren't
+//! recorded, and are delivered when they happen to arrive.
+//!
+//! Synchronization between threads isn't recorded, so threads which contend
+//! on a lock between recorded syscalls can take it in a different order when
+//! replaying. A thread holding the lock may then be waiting for its turn
+//! while the thread whose turn it is waits for the lock. When a thread has
+//! waited for its turn for five seconds while every other thread was
+//! blocked, the replay aborts, reporting that it stalled. A thread which
+//! sleeps, or blocks in a syscall which isn't recorded, for that long while
+//! another thread waits for its turn is taken for a stall too.
+//!
+//! This hides the vDSO, as the "no-vdso" feature does, and traps the
+//! syscalls with a seccomp-BPF filter. Installing the filter sets the
+//! process's `no_new_privs` flag, which can't be cleared and is kept across
+//! `execve`, so programs run while recording or replaying don't gain
+//! privileges from setuid bits or file capabilities. The environment
+//! variable is removed from the environment at startup, so that child
+//! processes don't record into the same file. It can't be used with the
+//! "deterministic" feature.
+
+use crate::startup::fail;
+use crate::syscall::{nr, KernelTimespec};
+use crate::trap;
+use core::cell::Cell;
+use core::ffi::{c_char, c_int, CStr};
+use core::mem::{size_of, MaybeUninit};
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicI32, AtomicPtr, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use rustix::fd::{AsRawFd, IntoRawFd, OwnedFd};
+use rustix::fs::{Mode as FileMode, OFlags, RawDir};
+use rustix::io::Errno;
+use rustix::mm::{MapFlags, ProtFlags};
+use rustix::thread::futex::{self, Flags, Timespec};
+
+/// The name of the environment variable that names the file to record to.
+pub const RECORD_ENV: &str = "MUSTANG_RECORD";
+
+/// The name of the environment variable that names the file to replay.
+pub const REPLAY_ENV: &str = "MUSTANG_REPLAY";
+
+/// Whether syscalls are being recorded or replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Neither.
+    Off,
+    /// Syscall results are being recorded.
+    Record,
+    /// Syscall results are being replayed.
+    Replay,
+}
+
+/// Return whether syscalls are being recorded or replayed.
+pub fn mode() -> Mode {
+    match MODE.load(Ordering::Relaxed) {
+        RECORD => Mode::Record,
+        REPLAY => Mode::Replay,
+        _ => Mode::Off,
+    }
+}
+
+const OFF: u8 = 0;
+const RECORD: u8 = 1;
+const REPLAY: u8 = 2;
+
+static MODE: AtomicU8 = AtomicU8::new(OFF);
+
+/// The recording's file descriptor, which is kept open in both modes, so
+/// that the program's file descriptors have the same numbers.
+static FD: AtomicI32 = AtomicI32::new(-1);
+
+/// A lock for writing to the recording.
+static LOCK: AtomicU32 = AtomicU32::new(0);
+
+/// The number of threads numbered so far.
+static THREADS: AtomicU32 = AtomicU32::new(0);
+
+/// The calling thread's number, or zero if it hasn't been numbered yet.
+#[thread_local]
+static THREAD: Cell<u32> = Cell::new(0);
+
+// When replaying, the mapped recording, the offset of the next entry, and a
+// futex word which is incremented after each entry is replayed.
+static LOG: AtomicPtr<u8> = AtomicPtr::new(null_mut());
+static LOG_LEN: AtomicUsize = AtomicUsize::new(0);
+static OFFSET: AtomicUsize = AtomicUsize::new(0);
+static TURN: AtomicU32 = AtomicU32::new(0);
+
+/// How long a thread waits for its turn before checking whether the replay
+/// has stalled.
+const STALL_CHECK: Timespec = Timespec {
+    tv_sec: 1,
+    tv_nsec: 0,
+};
+
+/// How many checks in a row must find the replay stalled before it aborts.
+const STALL_CHECKS: u32 = 5;
+
+/// The start of a recording.
+#[repr(C)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    arch: u32,
+}
+
+const MAGIC: [u8; 8] = *b"MUSTRPLY";
+const VERSION: u32 = 1;
+
+/// The start of each entry in a recording. It's followed by `len` bytes of
+/// data, padded to a multiple of 8 bytes.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Entry {
+    thread: u32,
+    nr: u32,
+    ret: u64,
+    len: u64,
+}
+
+const SYSCALLS: &[u32] = &[
+    nr::READ,
+    nr::READV,
+    nr::PREAD64,
+    nr::PREADV,
+    nr::WRITE,
+    nr::WRITEV,
+    nr::GETRANDOM,
+    nr::CLOCK_GETTIME,
+    nr::PPOLL,
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    nr::PPOLL_TIME32,
+    nr::EPOLL_CTL,
+    nr::EPOLL_PWAIT,
+    nr::EPOLL_PWAIT2,
+    #[cfg(target_arch = "x86")]
+    nr::SOCKETCALL,
+    #[cfg(not(target_arch = "x86"))]
+    nr::SOCKET,
+    #[cfg(not(target_arch = "x86"))]
+    nr::SOCKETPAIR,
+    #[cfg(not(target_arch = "x86"))]
+    nr::BIND,
+    #[cfg(not(target_arch = "x86"))]
+    nr::LISTEN,
+    #[cfg(not(target_arch = "x86"))]
+    nr::ACCEPT,
+    #[cfg(not(target_arch = "x86"))]
+    nr::ACCEPT4,
+    #[cfg(not(target_arch = "x86"))]
+    nr::CONNECT,
+    #[cfg(not(target_arch = "x86"))]
+    nr::SHUTDOWN,
+    #[cfg(not(target_arch = "x86"))]
+    nr::GETSOCKNAME,
+    #[cfg(not(target_arch = "x86"))]
+    nr::GETPEERNAME,
+    #[cfg(not(target_arch = "x86"))]
+    nr::GETSOCKOPT,
+    #[cfg(not(target_arch = "x86"))]
+    nr::SETSOCKOPT,
+    #[cfg(not(target_arch = "x86"))]
+    nr::SENDTO,
+    #[cfg(not(target_arch = "x86"))]
+    nr::RECVFROM,
+    #[cfg(not(target_arch = "x86"))]
+    nr::SENDMSG,
+    #[cfg(not(target_arch = "x86"))]
+    nr::RECVMSG,
+    #[cfg(target_arch = "arm")]
+    nr::SEND,
+    #[cfg(target_arch = "arm")]
+    nr::RECV,
+];
+
+/// What a syscall writes into memory, and which file descriptors it
+/// creates.
+#[derive(Clone, Copy)]
+enum Call {
+    /// The result is the number of bytes written to the buffer in argument
+    /// `buf`.
+    Buffer {
+        buf: usize,
+    },
+    /// The result is the number of bytes scattered across the `iovec`s in
+    /// arguments 1 and 2.
+    Vector,
+    Recvfrom,
+    Recvmsg,
+    /// A socket address and its length are written to arguments `addr` and
+    /// `addr + 1`, and the result is a new file descriptor if `fd`.
+    Sockaddr {
+        addr: usize,
+        fd: bool,
+    },
+    Socket,
+    Socketpair,
+    Ppoll,
+    EpollWait,
+    ClockGettime,
+    Write,
+    Other,
+}
+
+/// Read the environment variables, and start recording or replaying.
+///
+/// # Safety
+///
+/// This must be called from `mustang::auxv`'s startup function, after the
+/// vDSO is hidden.
+#[inline(never)]
+pub(crate) unsafe fn init(envp: *mut *mut c_char) {
+    let record = take_env(envp, RECORD_ENV);
+    let replay = take_env(envp, REPLAY_ENV);
+
+    #[cfg(feature = "deterministic")]
+    {
+        if record.is_some() || replay.is_some() {
+            fail(format_args!(
+                "{} and {} can't be used with the \"deterministic\" feature",
+                RECORD_ENV, REPLAY_ENV
+            ));
+        }
+    }
+
+    match (record, replay) {
+        // A parent process which was recording or replaying may have left
+        // us its filter, so make the trapped syscalls for real.
+        #[cfg(not(feature = "deterministic"))]
+        (None, None) => trap::install_handler(trap::passthrough),
+        #[cfg(feature = "deterministic")]
+        (None, None) => {}
+        (Some(_), Some(_)) => fail(format_args!(
+            "{} and {} can't both be set",
+            RECORD_ENV, REPLAY_ENV
+        )),
+        (Some(path), None) => start_recording(path),
+        (None, Some(path)) => start_replaying(path),
+    }
+}
+
+/// Find the value of `name` in the kernel-provided environment, and remove
+/// it from the environment by replacing its `=` with a NUL.
+unsafe fn take_env(envp: *mut *mut c_char, name: &str) -> Option<&'static str> {
+    if envp.is_null() {
+        return None;
+    }
+    let mut ptr = envp;
+    while !(*ptr).is_null() {
+        let var = CStr::from_ptr(*ptr).to_bytes();
+        if let Some(value) = var
+            .strip_prefix(name.as_bytes())
+            .and_then(|rest| rest.strip_prefix(b"="))
+        {
+            let value = match core::str::from_utf8(value) {
+                Ok(value) => value,
+                Err(_) => fail(format_args!("{} isn't valid UTF-8", name)),
+            };
+            (*ptr).add(name.len()).write(0);
+            return Some(value);
+        }
+        ptr = ptr.add(1);
+    }
+    None
+}
+
+unsafe fn start_recording(path: &str) {
+    let fd = match rustix::fs::open(
+        path,
+        OFlags::WRONLY | OFlags::CREATE | OFlags::TRUNC | OFlags::CLOEXEC,
+        FileMode::from_bits_truncate(0o644),
+    ) {
+        Ok(fd) => fd.into_raw_fd(),
+        Err(err) => fail(format_args!(
+            "can't create {} {:?}: {}",
+            RECORD_ENV, path, err
+        )),
+    };
+    FD.store(fd, Ordering::Relaxed);
+    let header = Header {
+        magic: MAGIC,
+        version: VERSION,
+        arch: trap::AUDIT_ARCH,
+    };
+    write_all((&header as *const Header).cast(), size_of::<Header>());
+
+    MODE.store(RECORD, Ordering::Relaxed);
+    trap::install(SYSCALLS, emulate);
+}
+
+unsafe fn start_replaying(path: &str) {
+    let fd = match rustix::fs::open(path, OFlags::RDONLY | OFlags::CLOEXEC, FileMode::empty()) {
+        Ok(fd) => fd,
+        Err(err) => fail(format_args!(
+            "can't open {} {:?}: {}",
+            REPLAY_ENV, path, err
+        )),
+    };
+    let len = match rustix::fs::fstat(&fd) {
+        Ok(stat) => stat.st_size as usize,
+        Err(err) => fail(format_args!(
+            "can't stat {} {:?}: {}",
+            REPLAY_ENV, path, err
+        )),
+    };
+    if len < size_of::<Header>() {
+        fail(format_args!("{} {:?} isn't a recording", REPLAY_ENV, path));
+    }
+    let log = match rustix::mm::mmap(null_mut(), len, ProtFlags::READ, MapFlags::PRIVATE, &fd, 0) {
+        Ok(log) => log.cast::<u8>(),
+        Err(err) => fail(format_args!("can't map {} {:?}: {}", REPLAY_ENV, path, err)),
+    };
+    let header = log.cast::<Header>().read();
+    if header.magic != MAGIC || header.version != VERSION {
+        fail(format_args!("{} {:?} isn't a recording", REPLAY_ENV, path));
+    }
+    if header.arch != trap::AUDIT_ARCH {
+        fail(format_args!(
+            "{} {:?} was recorded on another architecture",
+            REPLAY_ENV, path
+        ));
+    }
+    FD.store(fd.into_raw_fd(), Ordering::Relaxed);
+    LOG.store(log, Ordering::Relaxed);
+    LOG_LEN.store(len, Ordering::Relaxed);
+    OFFSET.store(size_of::<Header>(), Ordering::Relaxed);
+
+    MODE.store(REPLAY, Ordering::Relaxed);
+    trap::install(SYSCALLS, emulate);
+}
+
+unsafe fn emulate(nr: u32, raw_args: [usize; 6]) -> usize {
+    let (call, args) = classify(nr, raw_args);
+    match MODE.load(Ordering::Relaxed) {
+        RECORD => record(nr, raw_args, call, &args),
+        REPLAY => replay(nr, raw_args, call, &args),
+        _ => trap::passthrough(nr, raw_args),
+    }
+}
+
+/// Return what syscall `nr` writes into memory, and its arguments. On x86,
+/// the arguments of the socket syscalls made through `socketcall` are read
+/// from the array it's passed.
+unsafe fn classify(nr: u32, args: [usize; 6]) -> (Call, [usize; 6]) {
+    #[cfg(target_arch = "x86")]
+    if nr == nr::SOCKETCALL {
+        return socketcall(args[0], args[1] as *const usize);
+    }
+
+    let call = match nr {
+        nr::READ | nr::PREAD64 => Call::Buffer { buf: 1 },
+        nr::READV | nr::PREADV => Call::Vector,
+        nr::WRITE | nr::WRITEV => Call::Write,
+        nr::GETRANDOM => Call::Buffer { buf: 0 },
+        nr::CLOCK_GETTIME => Call::ClockGettime,
+        nr::PPOLL => Call::Ppoll,
+        #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+        nr::PPOLL_TIME32 => Call::Ppoll,
+        nr::EPOLL_PWAIT | nr::EPOLL_PWAIT2 => Call::EpollWait,
+        #[cfg(not(target_arch = "x86"))]
+        nr::SOCKET => Call::Socket,
+        #[cfg(not(target_arch = "x86"))]
+        nr::SOCKETPAIR => Call::Socketpair,
+        #[cfg(not(target_arch = "x86"))]
+        nr::ACCEPT | nr::ACCEPT4 => Call::Sockaddr { addr: 1, fd: true },
+        #[cfg(not(target_arch = "x86"))]
+        nr::GETSOCKNAME | nr::GETPEERNAME => Call::Sockaddr { addr: 1, fd: false },
+        #[cfg(not(target_arch = "x86"))]
+        nr::GETSOCKOPT => Call::Sockaddr { addr: 3, fd: false },
+        #[cfg(not(target_arch = "x86"))]
+        nr::RECVFROM => Call::Recvfrom,
+        #[cfg(not(target_arch = "x86"))]
+        nr::RECVMSG => Call::Recvmsg,
+        #[cfg(target_arch = "arm")]
+        nr::RECV => Call::Buffer { buf: 1 },
+        _ => Call::Other,
+    };
+    (call, args)
+}
+
+/// Classify a `socketcall` of `call` with the arguments at `args`.
+#[cfg(target_arch = "x86")]
+unsafe fn socketcall(call: usize, args: *const usize) -> (Call, [usize; 6]) {
+    // The number of arguments of each call, from `SYS_SOCKET` to
+    // `SYS_ACCEPT4`.
+    const ARGS: [usize; 19] = [0, 3, 3, 3, 2, 3, 3, 3, 4, 4, 4, 6, 6, 2, 5, 5, 3, 3, 4];
+    let call_args = core::array::from_fn(|i| {
+        if call < ARGS.len() && i < ARGS[call] {
+            args.add(i).read()
+        } else {
+            0
+        }
+    });
+    let call = match call {
+        1 => Call::Socket,
+        5 | 18 => Call::Sockaddr { addr: 1, fd: true },
+        6 | 7 => Call::Sockaddr { addr: 1, fd: false },
+        8 => Call::Socketpair,
+        10 => Call::Buffer { buf: 1 },
+        12 => Call::Recvfrom,
+        15 => Call::Sockaddr { addr: 3, fd: false },
+        17 => Call::Recvmsg,
+        _ => Call::Other,
+    };
+    (call, call_args)
+}
+
+/// Return whether `ret` is a raw syscall result which isn't an error.
+fn succeeded(ret: usize) -> bool {
+    ret <= -4096_isize as usize
+}
+
+/// Return the lengths of buffers which the kernel reads before the syscall
+/// replaces them.
+unsafe fn lengths(call: Call, args: &[usize; 6]) -> [usize; 2] {
+    let len = |addr: usize, len: usize| {
+        if addr != 0 && len != 0 {
+            (len as *const u32).read() as usize
+        } else {
+            0
+        }
+    };
+    match call {
+        Call::Sockaddr { addr, .. } => [len(args[addr], args[addr + 1]), 0],
+        Call::Recvfrom => [len(args[4], args[5]), 0],
+        Call::Recvmsg => {
+            let msg = &*(args[1] as *const libc::msghdr);
+            let name = if msg.msg_name.is_null() {
+                0
+            } else {
+                msg.msg_namelen as usize
+            };
+            let control = if msg.msg_control.is_null() {
+                0
+            } else {
+                msg.msg_controllen
+            };
+            [name, control]
+        }
+        _ => [0, 0],
+    }
+}
+
+/// Call `f` with each region of memory the syscall wrote, given its result
+/// and the buffer lengths from [`lengths`].
+unsafe fn regions(
+    call: Call,
+    args: &[usize; 6],
+    ret: usize,
+    lengths: [usize; 2],
+    f: &mut dyn FnMut(*mut u8, usize),
+) {
+    if !succeeded(ret) {
+        return;
+    }
+    let sockaddr = |addr: usize, len: usize, f: &mut dyn FnMut(*mut u8, usize)| {
+        if len != 0 {
+            if addr != 0 {
+                f(addr as *mut u8, lengths[0]);
+            }
+            f(len as *mut u8, size_of::<u32>());
+        }
+    };
+    match call {
+        Call::Buffer { buf } => f(args[buf] as *mut u8, ret),
+        Call::Vector => scatter(args[1] as *const libc::iovec, args[2], ret, f),
+        Call::Recvfrom => {
+            f(args[1] as *mut u8, ret);
+            sockaddr(args[4], args[5], f);
+        }
+        Call::Recvmsg => {
+            let msg = args[1] as *mut libc::msghdr;
+            if !(*msg).msg_name.is_null() {
+                f((*msg).msg_name.cast(), lengths[0]);
+            }
+            field(&mut (*msg).msg_namelen, f);
+            scatter((*msg).msg_iov, (*msg).msg_iovlen, ret, f);
+            if !(*msg).msg_control.is_null() {
+                f((*msg).msg_control.cast(), lengths[1]);
+            }
+            field(&mut (*msg).msg_controllen, f);
+            field(&mut (*msg).msg_flags, f);
+        }
+        Call::Sockaddr { addr, .. } => sockaddr(args[addr], args[addr + 1], f),
+        Call::Socketpair => f(args[3] as *mut u8, size_of::<[c_int; 2]>()),
+        Call::Ppoll => f(args[0] as *mut u8, args[1] * size_of::<libc::pollfd>()),
+        Call::EpollWait => f(args[1] as *mut u8, ret * size_of::<libc::epoll_event>()),
+        Call::ClockGettime => f(args[1] as *mut u8, size_of::<KernelTimespec>()),
+        Call::Socket | Call::Write | Call::Other => {}
+    }
+}
+
+fn field<T>(field: &mut T, f: &mut dyn FnMut(*mut u8, usize)) {
+    f((field as *mut T).cast(), size_of::<T>());
+}
+
+/// Call `f` with the regions of the first `len` bytes of `iovcnt` `iovec`s.
+unsafe fn scatter(
+    iov: *const libc::iovec,
+    iovcnt: usize,
+    mut len: usize,
+    f: &mut dyn FnMut(*mut u8, usize),
+) {
+    for i in 0..iovcnt {
+        if len == 0 {
+            break;
+        }
+        let iov = &*iov.add(i);
+        let n = iov.iov_len.min(len);
+        f(iov.iov_base.cast(), n);
+        len -= n;
+    }
+}
+
+/// The length of an entry with `len` bytes of data.
+fn entry_len(len: usize) -> usize {
+    size_of::<Entry>() + len.next_multiple_of(8)
+}
+
+unsafe fn record(nr: u32, raw_args: [usize; 6], call: Call, args: &[usize; 6]) -> usize {
+    let lengths = lengths(call, args);
+    let ret = trap::passthrough(nr, raw_args);
+    let mut len = 0;
+    regions(call, args, ret, lengths, &mut |_, n| len += n);
+
+    // Block signals while holding the lock, so that a signal handler on
+    // this thread can't try to take it again. `SIGSYS` stays unblocked, as
+    // it's fatal if it's blocked when a syscall is trapped, which [`stop`]
+    // relies on.
+    let mut all = core::mem::zeroed();
+    let mut old = core::mem::zeroed();
+    libc::sigfillset(&mut all);
+    libc::sigdelset(&mut all, libc::SIGSYS);
+    libc::pthread_sigmask(libc::SIG_SETMASK, &all, &mut old);
+    while LOCK.swap(1, Ordering::Acquire) != 0 {
+        let _ = futex::wait(&LOCK, Flags::PRIVATE, 1, None);
+    }
+
+    if THREAD.get() == 0 {
+        THREAD.set(THREADS.fetch_add(1, Ordering::Relaxed) + 1);
+    }
+    let entry = Entry {
+        thread: THREAD.get(),
+        nr,
+        ret: ret as u64,
+        len: len as u64,
+    };
+    write_all((&entry as *const Entry).cast(), size_of::<Entry>());
+    regions(call, args, ret, lengths, &mut |ptr, n| write_all(ptr, n));
+    let padding = [0_u8; 8];
+    write_all(padding.as_ptr(), entry_len(len) - size_of::<Entry>() - len);
+
+    LOCK.store(0, Ordering::Release);
+    let _ = futex::wake(&LOCK, Flags::PRIVATE, 1);
+    libc::pthread_sigmask(libc::SIG_SETMASK, &old, null_mut());
+    ret
+}
+
+/// Stop recording or replaying, so that the trapped syscalls are made for
+/// real, including the ones which report the failure, and fail.
+fn stop(args: core::fmt::Arguments<'_>) -> ! {
+    MODE.store(OFF, Ordering::Relaxed);
+    fail(args)
+}
+
+/// Write `len` bytes at `ptr` to the recording.
+unsafe fn write_all(mut ptr: *const u8, mut len: usize) {
+    let fd = FD.load(Ordering::Relaxed) as usize;
+    while len != 0 {
+        let ret = trap::passthrough(nr::WRITE, [fd, ptr as usize, len, 0, 0, 0]);
+        if !succeeded(ret) {
+            let err = Errno::from_raw_os_error(-(ret as isize) as i32);
+            if err == Errno::INTR {
+                continue;
+            }
+            stop(format_args!("can't write the recording: {}", err));
+        }
+        ptr = ptr.add(ret);
+        len -= ret;
+    }
+}
+
+unsafe fn replay(nr: u32, raw_args: [usize; 6], call: Call, args: &[usize; 6]) -> usize {
+    let lengths = lengths(call, args);
+    let (offset, entry) = next_entry();
+    if entry.nr != nr {
+        stop(format_args!(
+            "replay diverged: thread {} made syscall {}, but syscall {} was recorded",
+            entry.thread, nr, entry.nr
+        ));
+    }
+    let ret = entry.ret as usize;
+    let mut len = 0;
+    regions(call, args, ret, lengths, &mut |_, n| len += n);
+    if len as u64 != entry.len {
+        stop(format_args!(
+            "replay diverged: thread {}'s syscall {} wrote {} bytes, but {} were recorded",
+            entry.thread, nr, len, entry.len
+        ));
+    }
+    let mut data = LOG
+        .load(Ordering::Relaxed)
+        .add(offset + size_of::<Entry>())
+        .cast_const();
+    regions(call, args, ret, lengths, &mut |ptr, n| {
+        core::ptr::copy_nonoverlapping(data, ptr, n);
+        data = data.add(n);
+    });
+
+    if succeeded(ret) {
+        match call {
+            Call::Write if args[0] == 1 || args[0] == 2 => {
+                trap::passthrough(nr, raw_args);
+            }
+            Call::Socket | Call::Sockaddr { fd: true, .. } => placeholder(ret as c_int),
+            Call::Socketpair => {
+                let fds = (args[3] as *const [c_int; 2]).read();
+                placeholder(fds[0]);
+                placeholder(fds[1]);
+            }
+            _ => {}
+        }
+    }
+
+    OFFSET.store(offset + entry_len(len), Ordering::Relaxed);
+    TURN.fetch_add(1, Ordering::Release);
+    let _ = futex::wake(&TURN, Flags::PRIVATE, i32::MAX as u32);
+    ret
+}
+
+/// Wait for the calling thread's turn, and return the offset of its entry
+/// and the entry.
+unsafe fn next_entry() -> (usize, Entry) {
+    let log = LOG.load(Ordering::Relaxed);
+    let log_len = LOG_LEN.load(Ordering::Relaxed);
+    let mut stalls = 0;
+    loop {
+        let turn = TURN.load(Ordering::Acquire);
+        let offset = OFFSET.load(Ordering::Relaxed);
+        let mut next = None;
+        if offset + size_of::<Entry>() <= log_len {
+            let entry = log.add(offset).cast::<Entry>().read_unaligned();
+            if offset + entry_len(entry.len as usize) > log_len {
+                stop(format_args!("the recording is truncated"));
+            }
+            let thread = THREAD.get();
+            if thread == entry.thread {
+                return (offset, entry);
+            }
+            // Threads take numbers in the order they were numbered when
+            // recording.
+            if thread == 0
+                && THREADS
+                    .compare_exchange(
+                        entry.thread.wrapping_sub(1),
+                        entry.thread,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+            {
+                THREAD.set(entry.thread);
+                return (offset, entry);
+            }
+            next = Some(entry);
+        }
+        let Some(entry) = next else {
+            // The recording has ended, so block, as the thread was then.
+            let _ = futex::wait(&TURN, Flags::PRIVATE, turn, None);
+            continue;
+        };
+        if futex::wait(&TURN, Flags::PRIVATE, turn, Some(&STALL_CHECK)) == Err(Errno::TIMEDOUT)
+            && TURN.load(Ordering::Acquire) == turn
+            && stalled()
+        {
+            stalls += 1;
+            if stalls == STALL_CHECKS {
+                stop(format_args!(
+                    "replay stalled: thread {} is waiting for thread {}'s syscall {}, but all \
+                     threads are blocked",
+                    THREAD.get(),
+                    entry.thread,
+                    entry.nr
+                ));
+            }
+        } else {
+            stalls = 0;
+        }
+    }
+}
+
+/// Return whether every other thread in the process is blocked, according
+/// to `/proc/self/task`.
+unsafe fn stalled() -> bool {
+    // `read` is trapped, so read the files with `passthrough`.
+    let read = |fd: &OwnedFd, buf: &mut [u8]| {
+        let ret = trap::passthrough(
+            nr::READ,
+            [
+                fd.as_raw_fd() as usize,
+                buf.as_mut_ptr() as usize,
+                buf.len(),
+                0,
+                0,
+                0,
+            ],
+        );
+        if succeeded(ret) {
+            ret
+        } else {
+            0
+        }
+    };
+
+    let Ok(dir) = rustix::fs::open(
+        c"/proc/self/task",
+        OFlags::RDONLY | OFlags::DIRECTORY | OFlags::CLOEXEC,
+        FileMode::empty(),
+    ) else {
+        return false;
+    };
+    let tid = rustix::thread::gettid().as_raw_nonzero().get();
+    let mut buf = [MaybeUninit::<u8>::uninit(); 1024];
+    let mut entries = RawDir::new(&dir, &mut buf);
+    while let Some(Ok(entry)) = entries.next() {
+        let name = entry.file_name().to_bytes();
+        let Some(other) = parse_tid(name) else {
+            continue;
+        };
+        if other == tid {
+            continue;
+        }
+
+        let mut path = [0_u8; 32];
+        path[..name.len()].copy_from_slice(name);
+        path[name.len()..name.len() + 6].copy_from_slice(b"/stat\0");
+        let path = CStr::from_bytes_until_nul(&path).unwrap();
+        let Ok(stat) = rustix::fs::openat(
+            &dir,
+            path,
+            OFlags::RDONLY | OFlags::CLOEXEC,
+            FileMode::empty(),
+        ) else {
+            continue;
+        };
+        let mut stat_buf = [0_u8; 512];
+        let len = read(&stat, &mut stat_buf);
+        let stat = &stat_buf[..len];
+        // The state follows the command name, which is in parentheses.
+        let state = stat
+            .iter()
+            .rposition(|&b| b == b')')
+            .and_then(|end| stat.get(end + 2));
+        if state == Some(&b'R') {
+            return false;
+        }
+    }
+    true
+}
+
+/// Parse a thread id from a `/proc/self/task` entry's name.
+fn parse_tid(name: &[u8]) -> Option<i32> {
+    if name.is_empty() || name.len() > 10 {
+        return None;
+    }
+    core::str::from_utf8(name).ok()?.parse().ok()
+}
+
+/// Make `fd` a placeholder for a socket which was created when recording.
+/// It's a Unix-domain socket, so that `fcntl` and `ioctl` calls which work
+/// on sockets work on it.
+unsafe fn placeholder(fd: c_int) {
+    let socket = trap::passthrough(
+        nr::SOCKET,
+        [
+            libc::AF_UNIX as usize,
+            (libc::SOCK_STREAM | libc::SOCK_CLOEXEC) as usize,
+            0,
+            0,
+            0,
+            0,
+        ],
+    );
+    if !succeeded(socket) {
+        stop(format_args!("can't create a placeholder socket"));
+    }
+    if socket as c_int != fd {
+        trap::passthrough(
+            nr::DUP3,
+            [socket, fd as usize, libc::O_CLOEXEC as usize, 0, 0, 0],
+        );
+        trap::passthrough(nr::CLOSE, [socket, 0, 0, 0, 0, 0]);
+    }
+}
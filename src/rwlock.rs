@@ -0,0 +1,268 @@
+//! Process-shared reader-writer locks.
+//!
+//! Like mutexes, `pthread_rwlock_*` are bound to the implementations here,
+//! for C and Rust code alike, so that `PTHREAD_PROCESS_SHARED` locks can be
+//! placed in shared memory. See the mutex module for details.
+//!
+//! The futex word holds the number of readers, or `WRITE_LOCKED`, and a
+//! `WAITERS` bit. Readers are preferred, like glibc's default.
+
+use core::ffi::c_int;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicU32, Ordering};
+use rustix::io::Errno;
+use rustix::thread::futex;
+
+use crate::mutex::{futex_flags, futex_wait, ret, tid, timespec, Timeout};
+
+// Bind the standard names to the implementations here.
+link_args!(
+    "-Wl,--defsym=pthread_rwlockattr_init=__mustang_pthread_rwlockattr_init",
+    "-Wl,--defsym=pthread_rwlockattr_destroy=__mustang_pthread_rwlockattr_destroy",
+    "-Wl,--defsym=pthread_rwlockattr_setpshared=__mustang_pthread_rwlockattr_setpshared",
+    "-Wl,--defsym=pthread_rwlockattr_getpshared=__mustang_pthread_rwlockattr_getpshared",
+    "-Wl,--defsym=pthread_rwlock_init=__mustang_pthread_rwlock_init",
+    "-Wl,--defsym=pthread_rwlock_destroy=__mustang_pthread_rwlock_destroy",
+    "-Wl,--defsym=pthread_rwlock_rdlock=__mustang_pthread_rwlock_rdlock",
+    "-Wl,--defsym=pthread_rwlock_tryrdlock=__mustang_pthread_rwlock_tryrdlock",
+    "-Wl,--defsym=pthread_rwlock_timedrdlock=__mustang_pthread_rwlock_timedrdlock",
+    "-Wl,--defsym=pthread_rwlock_wrlock=__mustang_pthread_rwlock_wrlock",
+    "-Wl,--defsym=pthread_rwlock_trywrlock=__mustang_pthread_rwlock_trywrlock",
+    "-Wl,--defsym=pthread_rwlock_timedwrlock=__mustang_pthread_rwlock_timedwrlock",
+    "-Wl,--defsym=pthread_rwlock_unlock=__mustang_pthread_rwlock_unlock",
+);
+
+const LOCKS: u32 = 0x3fff_ffff;
+const WRITE_LOCKED: u32 = LOCKS;
+const WAITERS: u32 = 1 << 31;
+
+/// The bit of `pthread_rwlockattr_t`, and of a lock's `attr`, for
+/// `PTHREAD_PROCESS_SHARED`.
+const PSHARED: u32 = 1;
+
+/// Our layout of a `pthread_rwlock_t`. A zeroed one, which is what
+/// `PTHREAD_RWLOCK_INITIALIZER` is, is an unlocked private lock.
+#[repr(C)]
+struct RwLock {
+    state: AtomicU32,
+    /// The tid of the thread holding the write lock.
+    writer: AtomicU32,
+    attr: u32,
+}
+
+const _: () = assert!(size_of::<RwLock>() <= size_of::<libc::pthread_rwlock_t>());
+const _: () = assert!(size_of::<u32>() <= size_of::<libc::pthread_rwlockattr_t>());
+
+impl RwLock {
+    fn lock(&self, write: bool, timeout: Timeout<'_>) -> Result<(), Errno> {
+        let tid = tid();
+        if self.writer.load(Ordering::Relaxed) == tid {
+            return Err(Errno::DEADLK);
+        }
+        let flags = futex_flags(self.attr & PSHARED != 0);
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            let locks = state & LOCKS;
+            let next = if write {
+                (locks == 0).then_some(WRITE_LOCKED)
+            } else if locks == WRITE_LOCKED - 1 {
+                return Err(Errno::AGAIN);
+            } else {
+                (locks != WRITE_LOCKED).then_some(locks + 1)
+            };
+            if let Some(next) = next {
+                if self
+                    .state
+                    .compare_exchange(
+                        state,
+                        state & WAITERS | next,
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+                {
+                    if write {
+                        self.writer.store(tid, Ordering::Relaxed);
+                    }
+                    return Ok(());
+                }
+                continue;
+            }
+            if let Timeout::Try = timeout {
+                return Err(Errno::BUSY);
+            }
+            if state & WAITERS == 0
+                && self
+                    .state
+                    .compare_exchange(state, state | WAITERS, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_err()
+            {
+                continue;
+            }
+            match futex_wait(&self.state, flags, state | WAITERS, timeout) {
+                Ok(()) | Err(Errno::AGAIN) | Err(Errno::INTR) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn unlock(&self) -> Result<(), Errno> {
+        let flags = futex_flags(self.attr & PSHARED != 0);
+        let state = self.state.load(Ordering::Relaxed);
+        match state & LOCKS {
+            0 => Err(Errno::PERM),
+            WRITE_LOCKED => {
+                if self.writer.load(Ordering::Relaxed) != tid() {
+                    return Err(Errno::PERM);
+                }
+                self.writer.store(0, Ordering::Relaxed);
+                if self.state.swap(0, Ordering::Release) & WAITERS != 0 {
+                    let _ = futex::wake(&self.state, flags, i32::MAX as u32);
+                }
+                Ok(())
+            }
+            _ => {
+                // The last reader out wakes the waiters, unless someone
+                // else takes the lock first, in which case they will.
+                if self.state.fetch_sub(1, Ordering::Release) == 1 | WAITERS
+                    && self
+                        .state
+                        .compare_exchange(WAITERS, 0, Ordering::Relaxed, Ordering::Relaxed)
+                        .is_ok()
+                {
+                    let _ = futex::wake(&self.state, flags, i32::MAX as u32);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlockattr_init(
+    attr: *mut libc::pthread_rwlockattr_t,
+) -> c_int {
+    attr.cast::<u32>().write(0);
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlockattr_destroy(
+    _attr: *mut libc::pthread_rwlockattr_t,
+) -> c_int {
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlockattr_setpshared(
+    attr: *mut libc::pthread_rwlockattr_t,
+    pshared: c_int,
+) -> c_int {
+    match pshared {
+        libc::PTHREAD_PROCESS_PRIVATE => attr.cast::<u32>().write(0),
+        libc::PTHREAD_PROCESS_SHARED => attr.cast::<u32>().write(PSHARED),
+        _ => return libc::EINVAL,
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlockattr_getpshared(
+    attr: *const libc::pthread_rwlockattr_t,
+    pshared: *mut c_int,
+) -> c_int {
+    *pshared = if *attr.cast::<u32>() & PSHARED != 0 {
+        libc::PTHREAD_PROCESS_SHARED
+    } else {
+        libc::PTHREAD_PROCESS_PRIVATE
+    };
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlock_init(
+    rwlock: *mut libc::pthread_rwlock_t,
+    attr: *const libc::pthread_rwlockattr_t,
+) -> c_int {
+    let attr = if attr.is_null() {
+        0
+    } else {
+        *attr.cast::<u32>()
+    };
+    rwlock.cast::<RwLock>().write(RwLock {
+        state: AtomicU32::new(0),
+        writer: AtomicU32::new(0),
+        attr,
+    });
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlock_destroy(
+    rwlock: *mut libc::pthread_rwlock_t,
+) -> c_int {
+    if (*rwlock.cast::<RwLock>()).state.load(Ordering::Relaxed) & LOCKS != 0 {
+        return libc::EBUSY;
+    }
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlock_rdlock(rwlock: *mut libc::pthread_rwlock_t) -> c_int {
+    ret((*rwlock.cast::<RwLock>()).lock(false, Timeout::Never))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlock_tryrdlock(
+    rwlock: *mut libc::pthread_rwlock_t,
+) -> c_int {
+    ret((*rwlock.cast::<RwLock>()).lock(false, Timeout::Try))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlock_timedrdlock(
+    rwlock: *mut libc::pthread_rwlock_t,
+    abstime: *const libc::timespec,
+) -> c_int {
+    ret(timespec(abstime)
+        .and_then(|until| (*rwlock.cast::<RwLock>()).lock(false, Timeout::Until(&until))))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlock_wrlock(rwlock: *mut libc::pthread_rwlock_t) -> c_int {
+    ret((*rwlock.cast::<RwLock>()).lock(true, Timeout::Never))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlock_trywrlock(
+    rwlock: *mut libc::pthread_rwlock_t,
+) -> c_int {
+    ret((*rwlock.cast::<RwLock>()).lock(true, Timeout::Try))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlock_timedwrlock(
+    rwlock: *mut libc::pthread_rwlock_t,
+    abstime: *const libc::timespec,
+) -> c_int {
+    ret(timespec(abstime)
+        .and_then(|until| (*rwlock.cast::<RwLock>()).lock(true, Timeout::Until(&until))))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_rwlock_unlock(rwlock: *mut libc::pthread_rwlock_t) -> c_int {
+    ret((*rwlock.cast::<RwLock>()).unlock())
+}
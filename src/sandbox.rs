@@ -0,0 +1,519 @@
+//! Sandboxing with Landlock.
+//!
+//! [`Sandbox`] builds a Landlock ruleset which restricts filesystem access to
+//! a set of paths, and TCP `bind` and `connect` to a set of ports, and
+//! applies it to the calling thread and anything it creates afterward:
+//!
+//! ```no_run
+//! use mustang::sandbox::{AccessFs, AccessNet, Sandbox};
+//!
+//! Sandbox::new(AccessFs::all(), AccessNet::all())?
+//!     .allow_path("/usr", AccessFs::READ | AccessFs::EXECUTE)?
+//!     .allow_path("/tmp", AccessFs::READ | AccessFs::WRITE)?
+//!     .allow_tcp_connect(443)?
+//!     .restrict_self()?;
+//! # Ok::<(), rustix::io::Errno>(())
+//! ```
+//!
+//! Alternatively, the [`sandbox!`] macro declares a ruleset in the binary's
+//! `.mustang_sandbox` section, which is applied at startup, before
+//! `.init_array` functions at normal priorities, `#[ctor]` functions, and
+//! `main` run. This means no user code runs outside the sandbox.
+//!
+//! See <https://docs.kernel.org/userspace-api/landlock.html>.
+//!
+//! [`sandbox!`]: crate::sandbox!
+
+use crate::syscall::{nr, syscall2, syscall3, syscall4};
+use core::mem::size_of;
+use core::ops::{BitAnd, BitOr};
+use rustix::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use rustix::fs::{FileType, Mode, OFlags};
+use rustix::{io, path};
+
+/// A set of Landlock filesystem access rights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccessFs(u64);
+
+impl AccessFs {
+    /// Execute a file.
+    pub const EXECUTE: Self = Self(1 << 0);
+    /// Open a file for writing.
+    pub const WRITE_FILE: Self = Self(1 << 1);
+    /// Open a file for reading.
+    pub const READ_FILE: Self = Self(1 << 2);
+    /// Open a directory or list its contents.
+    pub const READ_DIR: Self = Self(1 << 3);
+    /// Remove an empty directory, or rename one.
+    pub const REMOVE_DIR: Self = Self(1 << 4);
+    /// Unlink or rename a file.
+    pub const REMOVE_FILE: Self = Self(1 << 5);
+    /// Create a character device.
+    pub const MAKE_CHAR: Self = Self(1 << 6);
+    /// Create a directory.
+    pub const MAKE_DIR: Self = Self(1 << 7);
+    /// Create a regular file.
+    pub const MAKE_REG: Self = Self(1 << 8);
+    /// Create a Unix domain socket.
+    pub const MAKE_SOCK: Self = Self(1 << 9);
+    /// Create a named pipe.
+    pub const MAKE_FIFO: Self = Self(1 << 10);
+    /// Create a block device.
+    pub const MAKE_BLOCK: Self = Self(1 << 11);
+    /// Create a symbolic link.
+    pub const MAKE_SYM: Self = Self(1 << 12);
+    /// Link or rename a file from or to a different directory. Since ABI 2.
+    pub const REFER: Self = Self(1 << 13);
+    /// Truncate a file. Since ABI 3.
+    pub const TRUNCATE: Self = Self(1 << 14);
+    /// Use `ioctl` on a character or block device. Since ABI 5.
+    pub const IOCTL_DEV: Self = Self(1 << 15);
+
+    /// Read files and list directories.
+    pub const READ: Self = Self(Self::READ_FILE.0 | Self::READ_DIR.0);
+
+    /// Write, create, remove, rename, and truncate files and directories.
+    pub const WRITE: Self = Self(
+        Self::WRITE_FILE.0
+            | Self::REMOVE_DIR.0
+            | Self::REMOVE_FILE.0
+            | Self::MAKE_CHAR.0
+            | Self::MAKE_DIR.0
+            | Self::MAKE_REG.0
+            | Self::MAKE_SOCK.0
+            | Self::MAKE_FIFO.0
+            | Self::MAKE_BLOCK.0
+            | Self::MAKE_SYM.0
+            | Self::REFER.0
+            | Self::TRUNCATE.0
+            | Self::IOCTL_DEV.0,
+    );
+
+    /// The rights which apply to files, as opposed to directories.
+    pub const FILE: Self = Self(
+        Self::EXECUTE.0
+            | Self::WRITE_FILE.0
+            | Self::READ_FILE.0
+            | Self::TRUNCATE.0
+            | Self::IOCTL_DEV.0,
+    );
+
+    /// No rights.
+    #[inline]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// All the rights known to this version of mustang.
+    #[inline]
+    pub const fn all() -> Self {
+        Self((1 << 16) - 1)
+    }
+
+    /// All the rights supported by the given Landlock ABI version.
+    pub const fn for_abi(abi: u32) -> Self {
+        let mut bits = (1 << 13) - 1;
+        if abi >= 2 {
+            bits |= Self::REFER.0;
+        }
+        if abi >= 3 {
+            bits |= Self::TRUNCATE.0;
+        }
+        if abi >= 5 {
+            bits |= Self::IOCTL_DEV.0;
+        }
+        Self(bits)
+    }
+
+    /// The raw `LANDLOCK_ACCESS_FS_*` bits.
+    #[inline]
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Test whether this is empty.
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// A set of Landlock network access rights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccessNet(u64);
+
+impl AccessNet {
+    /// Bind a TCP socket to a port. Since ABI 4.
+    pub const BIND_TCP: Self = Self(1 << 0);
+    /// Connect a TCP socket to a port. Since ABI 4.
+    pub const CONNECT_TCP: Self = Self(1 << 1);
+
+    /// No rights.
+    #[inline]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// All the rights known to this version of mustang.
+    #[inline]
+    pub const fn all() -> Self {
+        Self(Self::BIND_TCP.0 | Self::CONNECT_TCP.0)
+    }
+
+    /// All the rights supported by the given Landlock ABI version.
+    pub const fn for_abi(abi: u32) -> Self {
+        if abi >= 4 {
+            Self::all()
+        } else {
+            Self::empty()
+        }
+    }
+
+    /// The raw `LANDLOCK_ACCESS_NET_*` bits.
+    #[inline]
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Test whether this is empty.
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+macro_rules! bit_ops {
+    ($ty:ident) => {
+        impl BitOr for $ty {
+            type Output = Self;
+
+            #[inline]
+            fn bitor(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+        }
+
+        impl BitAnd for $ty {
+            type Output = Self;
+
+            #[inline]
+            fn bitand(self, other: Self) -> Self {
+                Self(self.0 & other.0)
+            }
+        }
+    };
+}
+
+bit_ops!(AccessFs);
+bit_ops!(AccessNet);
+
+const LANDLOCK_CREATE_RULESET_VERSION: usize = 1 << 0;
+const LANDLOCK_RULE_PATH_BENEATH: usize = 1;
+const LANDLOCK_RULE_NET_PORT: usize = 2;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+    handled_access_net: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+#[repr(C)]
+struct NetPortAttr {
+    allowed_access: u64,
+    port: u64,
+}
+
+/// Return the Landlock ABI version the kernel supports, or an error if
+/// Landlock is unsupported or disabled.
+pub fn abi_version() -> io::Result<u32> {
+    // SAFETY: A null attribute pointer with a zero size and the version flag
+    // is how the ABI version is queried.
+    unsafe {
+        syscall3(
+            nr::LANDLOCK_CREATE_RULESET,
+            0,
+            0,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+        .map(|version| version as u32)
+    }
+}
+
+/// A Landlock ruleset under construction.
+#[derive(Debug)]
+pub struct Sandbox {
+    fd: OwnedFd,
+    handled_fs: AccessFs,
+    handled_net: AccessNet,
+}
+
+impl Sandbox {
+    /// Create a ruleset which restricts the rights in `handled_fs` and
+    /// `handled_net` to what is explicitly allowed.
+    ///
+    /// Rights the kernel doesn't support are left unrestricted, so that
+    /// programs can use newer rights while still running on older kernels;
+    /// use [`Sandbox::handled_fs`] and [`Sandbox::handled_net`] to see what
+    /// will actually be restricted. This fails if Landlock isn't supported
+    /// at all.
+    pub fn new(handled_fs: AccessFs, handled_net: AccessNet) -> io::Result<Self> {
+        let abi = abi_version()?;
+        let handled_fs = handled_fs & AccessFs::for_abi(abi);
+        let handled_net = handled_net & AccessNet::for_abi(abi);
+
+        let attr = RulesetAttr {
+            handled_access_fs: handled_fs.0,
+            handled_access_net: handled_net.0,
+        };
+        // Kernels before ABI 4 only know about `handled_access_fs`.
+        let size = if abi >= 4 {
+            size_of::<RulesetAttr>()
+        } else {
+            size_of::<u64>()
+        };
+        // SAFETY: `attr` is a valid `landlock_ruleset_attr`, at least `size`
+        // bytes long.
+        unsafe {
+            let fd = syscall3(
+                nr::LANDLOCK_CREATE_RULESET,
+                &attr as *const RulesetAttr as usize,
+                size,
+                0,
+            )?;
+            Ok(Self {
+                fd: OwnedFd::from_raw_fd(fd as i32),
+                handled_fs,
+                handled_net,
+            })
+        }
+    }
+
+    /// The filesystem rights this ruleset restricts.
+    #[inline]
+    pub fn handled_fs(&self) -> AccessFs {
+        self.handled_fs
+    }
+
+    /// The network rights this ruleset restricts.
+    #[inline]
+    pub fn handled_net(&self) -> AccessNet {
+        self.handled_net
+    }
+
+    /// Allow the rights in `access` beneath `path`.
+    pub fn allow_path<P: path::Arg>(self, path: P, access: AccessFs) -> io::Result<Self> {
+        let fd = rustix::fs::open(path, OFlags::PATH | OFlags::CLOEXEC, Mode::empty())?;
+        self.allow_fd(fd, access)
+    }
+
+    /// Allow the rights in `access` beneath the file or directory `fd`.
+    ///
+    /// Rights which aren't restricted are ignored, and if `fd` isn't a
+    /// directory, rights which only apply to directories are ignored.
+    pub fn allow_fd<Fd: AsFd>(self, fd: Fd, access: AccessFs) -> io::Result<Self> {
+        let fd = fd.as_fd();
+        let mut access = access & self.handled_fs;
+        if FileType::from_raw_mode(rustix::fs::fstat(fd)?.st_mode) != FileType::Directory {
+            access = access & AccessFs::FILE;
+        }
+        if access.is_empty() {
+            return Ok(self);
+        }
+
+        let attr = PathBeneathAttr {
+            allowed_access: access.0,
+            parent_fd: fd.as_raw_fd(),
+        };
+        // SAFETY: `attr` is a valid `landlock_path_beneath_attr`.
+        unsafe {
+            syscall4(
+                nr::LANDLOCK_ADD_RULE,
+                self.fd.as_raw_fd() as usize,
+                LANDLOCK_RULE_PATH_BENEATH,
+                &attr as *const PathBeneathAttr as usize,
+                0,
+            )?;
+        }
+        Ok(self)
+    }
+
+    /// Allow the rights in `access` for TCP port `port`.
+    ///
+    /// Rights which aren't restricted are ignored.
+    pub fn allow_port(self, port: u16, access: AccessNet) -> io::Result<Self> {
+        let access = access & self.handled_net;
+        if access.is_empty() {
+            return Ok(self);
+        }
+
+        let attr = NetPortAttr {
+            allowed_access: access.0,
+            port: port.into(),
+        };
+        // SAFETY: `attr` is a valid `landlock_net_port_attr`.
+        unsafe {
+            syscall4(
+                nr::LANDLOCK_ADD_RULE,
+                self.fd.as_raw_fd() as usize,
+                LANDLOCK_RULE_NET_PORT,
+                &attr as *const NetPortAttr as usize,
+                0,
+            )?;
+        }
+        Ok(self)
+    }
+
+    /// Allow binding TCP sockets to port `port`.
+    #[inline]
+    pub fn allow_tcp_bind(self, port: u16) -> io::Result<Self> {
+        self.allow_port(port, AccessNet::BIND_TCP)
+    }
+
+    /// Allow connecting TCP sockets to port `port`.
+    #[inline]
+    pub fn allow_tcp_connect(self, port: u16) -> io::Result<Self> {
+        self.allow_port(port, AccessNet::CONNECT_TCP)
+    }
+
+    /// Enforce the ruleset on the calling thread, and any threads and
+    /// processes it creates after this.
+    ///
+    /// This also sets the thread's "no new privileges" flag, which Landlock
+    /// requires for unprivileged processes.
+    pub fn restrict_self(self) -> io::Result<()> {
+        rustix::thread::set_no_new_privs(true)?;
+        // SAFETY: `self.fd` is a Landlock ruleset fd.
+        unsafe {
+            syscall2(nr::LANDLOCK_RESTRICT_SELF, self.fd.as_raw_fd() as usize, 0)?;
+        }
+        Ok(())
+    }
+}
+
+/// A ruleset declared with the [`sandbox!`] macro.
+///
+/// Filesystem access is restricted to the paths listed, with all the rights
+/// the kernel supports being restricted. TCP binding and connecting are
+/// only restricted if `bind_tcp` or `connect_tcp`, respectively, is
+/// declared, in which case an empty list denies them entirely.
+///
+/// [`sandbox!`]: crate::sandbox!
+#[derive(Debug)]
+pub struct Policy {
+    read: &'static [&'static str],
+    write: &'static [&'static str],
+    execute: &'static [&'static str],
+    bind_tcp: Option<&'static [u16]>,
+    connect_tcp: Option<&'static [u16]>,
+}
+
+impl Policy {
+    /// A policy which denies all filesystem access.
+    pub const fn new() -> Self {
+        Self {
+            read: &[],
+            write: &[],
+            execute: &[],
+            bind_tcp: None,
+            connect_tcp: None,
+        }
+    }
+
+    /// Allow [`AccessFs::READ`] beneath `paths`.
+    pub const fn read(self, paths: &'static [&'static str]) -> Self {
+        Self {
+            read: paths,
+            ..self
+        }
+    }
+
+    /// Allow [`AccessFs::WRITE`] beneath `paths`.
+    pub const fn write(self, paths: &'static [&'static str]) -> Self {
+        Self {
+            write: paths,
+            ..self
+        }
+    }
+
+    /// Allow [`AccessFs::EXECUTE`] beneath `paths`.
+    pub const fn execute(self, paths: &'static [&'static str]) -> Self {
+        Self {
+            execute: paths,
+            ..self
+        }
+    }
+
+    /// Restrict TCP binding to `ports`.
+    pub const fn bind_tcp(self, ports: &'static [u16]) -> Self {
+        Self {
+            bind_tcp: Some(ports),
+            ..self
+        }
+    }
+
+    /// Restrict TCP connecting to `ports`.
+    pub const fn connect_tcp(self, ports: &'static [u16]) -> Self {
+        Self {
+            connect_tcp: Some(ports),
+            ..self
+        }
+    }
+
+    /// Build the ruleset and apply it to the calling thread.
+    ///
+    /// Unlike [`Sandbox::new`], this fails if the kernel doesn't support
+    /// network restrictions and the policy declares any.
+    pub fn apply(&self) -> io::Result<()> {
+        let mut handled_net = AccessNet::empty();
+        if self.bind_tcp.is_some() {
+            handled_net = handled_net | AccessNet::BIND_TCP;
+        }
+        if self.connect_tcp.is_some() {
+            handled_net = handled_net | AccessNet::CONNECT_TCP;
+        }
+
+        let mut sandbox = Sandbox::new(AccessFs::all(), handled_net)?;
+        if sandbox.handled_net() != handled_net {
+            return Err(io::Errno::OPNOTSUPP);
+        }
+        for (paths, access) in [
+            (self.read, AccessFs::READ),
+            (self.write, AccessFs::WRITE),
+            (self.execute, AccessFs::EXECUTE),
+        ] {
+            for path in paths {
+                sandbox = sandbox.allow_path(*path, access)?;
+            }
+        }
+        for port in self.bind_tcp.unwrap_or(&[]) {
+            sandbox = sandbox.allow_tcp_bind(*port)?;
+        }
+        for port in self.connect_tcp.unwrap_or(&[]) {
+            sandbox = sandbox.allow_tcp_connect(*port)?;
+        }
+        sandbox.restrict_self()
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Apply a policy declared with [`sandbox!`] at startup, aborting the
+/// process if it can't be applied.
+///
+/// [`sandbox!`]: crate::sandbox!
+#[doc(hidden)]
+pub fn __apply_declared(policy: &Policy) {
+    if let Err(err) = policy.apply() {
+        crate::startup::fail(format_args!("can't apply the declared sandbox: {}", err));
+    }
+}
@@ -0,0 +1,98 @@
+//! Installing a seccomp-BPF syscall filter at startup.
+//!
+//! With the "seccomp" feature, mustang reserves a `.mustang_seccomp` section
+//! in the binary to hold a seccomp-BPF program, and installs it at startup,
+//! before `main` and `.init_array` functions at normal priorities. The
+//! `mustang-seccomp` tool in the mustang repository computes an allowlist of
+//! the syscalls a binary makes and writes it into the section:
+//!
+//! ```console
+//! $ cargo build --release --target=x86_64-mustang-linux-gnu
+//! $ mustang-seccomp --patch target/x86_64-mustang-linux-gnu/release/program
+//! ```
+//!
+//! The section starts with a 16-byte header: the magic number `MUSTSECC`,
+//! then the number of instructions as a `u32` in the target's byte order,
+//! then 4 bytes of padding. It's followed by room for [`MAX_INSNS`]
+//! `struct sock_filter` instructions.
+//!
+//! A binary built with the feature which hasn't been patched aborts at
+//! startup, rather than run without its filter.
+
+use crate::startup::fail;
+use crate::syscall::{nr, syscall3};
+use core::ffi::{c_char, c_int};
+use core::ptr::{addr_of, read_volatile};
+
+/// The name of the section holding the filter.
+pub const SECTION: &str = ".mustang_seccomp";
+
+/// The maximum number of instructions in the filter.
+pub const MAX_INSNS: usize = 1024;
+
+const SECCOMP_SET_MODE_FILTER: usize = 1;
+
+#[repr(C, align(8))]
+struct Filter {
+    magic: [u8; 8],
+    len: u32,
+    _pad: u32,
+    insns: [[u8; 8]; MAX_INSNS],
+}
+
+/// `struct sock_fprog`.
+#[repr(C)]
+struct SockFprog {
+    len: u16,
+    filter: *const [u8; 8],
+}
+
+#[link_section = ".mustang_seccomp"]
+#[used]
+static FILTER: Filter = Filter {
+    magic: *b"MUSTSECC",
+    len: 0,
+    _pad: 0,
+    insns: [[0; 8]; MAX_INSNS],
+};
+
+/// Return the number of instructions in the filter written into this
+/// binary.
+pub fn filter_len() -> usize {
+    // SAFETY: `FILTER` is a valid static. The read is volatile because the
+    // contents are written into the binary after it's compiled.
+    unsafe { read_volatile(addr_of!(FILTER.len)) as usize }
+}
+
+// This runs after the other mustang startup functions, which may make
+// syscalls the program itself doesn't.
+#[link_section = ".init_array.00003"]
+#[used]
+static INIT_SECCOMP: unsafe extern "C" fn(c_int, *mut *mut c_char, *mut *mut c_char) = {
+    unsafe extern "C" fn function(_argc: c_int, _argv: *mut *mut c_char, _envp: *mut *mut c_char) {
+        let len = filter_len();
+        if len == 0 || len > MAX_INSNS {
+            fail(format_args!(
+                "the \"seccomp\" feature is enabled, but no filter has been written into \
+                 the binary; run `mustang-seccomp --patch` on it"
+            ));
+        }
+
+        let prog = SockFprog {
+            len: len as u16,
+            filter: addr_of!(FILTER.insns).cast(),
+        };
+        let result = rustix::thread::set_no_new_privs(true).and_then(|()| {
+            syscall3(
+                nr::SECCOMP,
+                SECCOMP_SET_MODE_FILTER,
+                0,
+                &prog as *const SockFprog as usize,
+            )
+        });
+        if let Err(err) = result {
+            fail(format_args!("can't install the seccomp filter: {}", err));
+        }
+    }
+    function
+};
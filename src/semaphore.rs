@@ -0,0 +1,353 @@
+//! POSIX semaphores, including named semaphores.
+//!
+//! Like mutexes, `sem_*` are bound to the implementations here, for C and
+//! Rust code alike. See the mutex module for details.
+//!
+//! Named semaphores are files in `/dev/shm` named `sem.` followed by the
+//! name, like glibc's, so programs using either can share them. A new one is
+//! initialized in a temporary file which is then linked into place, so that
+//! other processes never see it half-initialized.
+
+use core::ffi::{c_char, c_int, c_uint, CStr};
+use core::fmt::{self, Write};
+use core::mem::size_of;
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicU32, Ordering};
+use rustix::fd::OwnedFd;
+use rustix::fs::{linkat, open, unlink, AtFlags, Mode, OFlags, CWD};
+use rustix::io::{self, Errno};
+use rustix::mm::{mmap, munmap, MapFlags, ProtFlags};
+use rustix::thread::futex;
+
+use crate::cancel;
+use crate::mutex::{futex_flags, futex_wait, tid, timespec, Timeout};
+
+// Bind the standard names to the implementations here.
+link_args!(
+    "-Wl,--defsym=sem_init=__mustang_sem_init",
+    "-Wl,--defsym=sem_destroy=__mustang_sem_destroy",
+    "-Wl,--defsym=sem_wait=__mustang_sem_wait",
+    "-Wl,--defsym=sem_trywait=__mustang_sem_trywait",
+    "-Wl,--defsym=sem_timedwait=__mustang_sem_timedwait",
+    "-Wl,--defsym=sem_post=__mustang_sem_post",
+    "-Wl,--defsym=sem_getvalue=__mustang_sem_getvalue",
+    "-Wl,--defsym=sem_open=__mustang_sem_open",
+    "-Wl,--defsym=sem_close=__mustang_sem_close",
+    "-Wl,--defsym=sem_unlink=__mustang_sem_unlink",
+);
+
+const SEM_VALUE_MAX: u32 = i32::MAX as u32;
+const PREFIX: &[u8] = b"/dev/shm/sem.";
+
+/// Our layout of a `sem_t`.
+#[repr(C)]
+struct Semaphore {
+    value: AtomicU32,
+    /// How many threads are waiting for the value to become nonzero.
+    waiters: AtomicU32,
+    pshared: u32,
+}
+
+const _: () = assert!(size_of::<Semaphore>() <= size_of::<libc::sem_t>());
+
+impl Semaphore {
+    fn wait(&self, timeout: Timeout<'_>) -> io::Result<()> {
+        loop {
+            let value = self.value.load(Ordering::Relaxed);
+            if value != 0 {
+                if self
+                    .value
+                    .compare_exchange(value, value - 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return Ok(());
+                }
+                continue;
+            }
+            if let Timeout::Try = timeout {
+                return Err(Errno::AGAIN);
+            }
+            self.waiters.fetch_add(1, Ordering::Relaxed);
+            let result = futex_wait(&self.value, futex_flags(self.pshared != 0), 0, timeout);
+            self.waiters.fetch_sub(1, Ordering::Relaxed);
+            match result {
+                Ok(()) | Err(Errno::AGAIN) => {}
+                // Unlike mutexes, semaphore waits are interrupted by signals.
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn post(&self) -> io::Result<()> {
+        let mut value = self.value.load(Ordering::Relaxed);
+        loop {
+            if value == SEM_VALUE_MAX {
+                return Err(Errno::OVERFLOW);
+            }
+            match self.value.compare_exchange_weak(
+                value,
+                value + 1,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(current) => value = current,
+            }
+        }
+        if self.waiters.load(Ordering::Relaxed) != 0 {
+            let _ = futex::wake(&self.value, futex_flags(self.pshared != 0), 1);
+        }
+        Ok(())
+    }
+}
+
+/// Convert a `Result` into a C return value, setting `errno` on failure.
+pub(crate) unsafe fn result(result: io::Result<()>) -> c_int {
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            *libc::__errno_location() = err.raw_os_error();
+            -1
+        }
+    }
+}
+
+/// A path being formatted into a fixed-size buffer, NUL-terminated.
+pub(crate) struct Path {
+    buf: [u8; libc::PATH_MAX as usize],
+    len: usize,
+}
+
+impl Path {
+    pub(crate) fn new() -> Self {
+        Self {
+            buf: [0; libc::PATH_MAX as usize],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.len + bytes.len() >= self.buf.len() {
+            return Err(Errno::NAMETOOLONG);
+        }
+        self.buf[self.len..][..bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    pub(crate) fn as_c_str(&self) -> &CStr {
+        CStr::from_bytes_with_nul(&self.buf[..=self.len]).unwrap()
+    }
+}
+
+impl Write for Path {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// The path of the semaphore named `name`.
+unsafe fn path(name: *const c_char) -> io::Result<Path> {
+    let mut name = CStr::from_ptr(name).to_bytes();
+    while let [b'/', rest @ ..] = name {
+        name = rest;
+    }
+    if name.is_empty() || name.contains(&b'/') {
+        return Err(Errno::INVAL);
+    }
+    if name.len() + 4 > libc::NAME_MAX as usize {
+        return Err(Errno::NAMETOOLONG);
+    }
+    let mut path = Path::new();
+    path.push(PREFIX)?;
+    path.push(name)?;
+    Ok(path)
+}
+
+/// Map the semaphore in `fd`.
+fn map(fd: OwnedFd) -> io::Result<*mut libc::sem_t> {
+    let sem = unsafe {
+        mmap(
+            null_mut(),
+            size_of::<libc::sem_t>(),
+            ProtFlags::READ | ProtFlags::WRITE,
+            MapFlags::SHARED,
+            fd,
+            0,
+        )?
+    };
+    Ok(sem.cast())
+}
+
+/// Create a new semaphore file with the given mode and value, and link it
+/// at `path`.
+fn create(path: &Path, mode: Mode, value: u32) -> io::Result<OwnedFd> {
+    static TEMPS: AtomicU32 = AtomicU32::new(0);
+
+    let mut temp = Path::new();
+    let fd = loop {
+        temp.len = 0;
+        let n = TEMPS.fetch_add(1, Ordering::Relaxed);
+        write!(temp, "/dev/shm/.sem-{:x}-{:x}", tid(), n).map_err(|_| Errno::NAMETOOLONG)?;
+        match open(
+            temp.as_c_str(),
+            OFlags::RDWR | OFlags::CREATE | OFlags::EXCL | OFlags::CLOEXEC,
+            mode,
+        ) {
+            Err(Errno::EXIST) => continue,
+            fd => break fd?,
+        }
+    };
+
+    let mut bytes = [0_u8; size_of::<libc::sem_t>()];
+    unsafe {
+        bytes
+            .as_mut_ptr()
+            .cast::<Semaphore>()
+            .write_unaligned(Semaphore {
+                value: AtomicU32::new(value),
+                waiters: AtomicU32::new(0),
+                pshared: 1,
+            });
+    }
+    let written = rustix::io::write(&fd, &bytes).and_then(|len| {
+        if len == bytes.len() {
+            Ok(())
+        } else {
+            Err(Errno::NOSPC)
+        }
+    });
+    let linked =
+        written.and_then(|()| linkat(CWD, temp.as_c_str(), CWD, path.as_c_str(), AtFlags::empty()));
+    let _ = unlink(temp.as_c_str());
+    linked.map(|()| fd)
+}
+
+unsafe fn open_named(
+    name: *const c_char,
+    oflag: c_int,
+    mode: c_uint,
+    value: c_uint,
+) -> io::Result<*mut libc::sem_t> {
+    let path = path(name)?;
+    let create_new = oflag & libc::O_CREAT != 0;
+    let exclusive = create_new && oflag & libc::O_EXCL != 0;
+    if create_new && value > SEM_VALUE_MAX {
+        return Err(Errno::INVAL);
+    }
+    loop {
+        if !exclusive {
+            match open(
+                path.as_c_str(),
+                OFlags::RDWR | OFlags::CLOEXEC,
+                Mode::empty(),
+            ) {
+                Err(Errno::NOENT) if create_new => {}
+                fd => return map(fd?),
+            }
+        }
+        match create(&path, Mode::from_bits_truncate(mode), value) {
+            // Someone else created it first; open theirs.
+            Err(Errno::EXIST) if !exclusive => {}
+            fd => return map(fd?),
+        }
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sem_init(
+    sem: *mut libc::sem_t,
+    pshared: c_int,
+    value: c_uint,
+) -> c_int {
+    if value > SEM_VALUE_MAX {
+        return result(Err(Errno::INVAL));
+    }
+    sem.cast::<Semaphore>().write(Semaphore {
+        value: AtomicU32::new(value),
+        waiters: AtomicU32::new(0),
+        pshared: (pshared != 0) as u32,
+    });
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sem_destroy(_sem: *mut libc::sem_t) -> c_int {
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C-unwind" fn __mustang_sem_wait(sem: *mut libc::sem_t) -> c_int {
+    result(cancel::point(|| {
+        (*sem.cast::<Semaphore>()).wait(Timeout::Never)
+    }))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sem_trywait(sem: *mut libc::sem_t) -> c_int {
+    result((*sem.cast::<Semaphore>()).wait(Timeout::Try))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C-unwind" fn __mustang_sem_timedwait(
+    sem: *mut libc::sem_t,
+    abstime: *const libc::timespec,
+) -> c_int {
+    let sem = &*sem.cast::<Semaphore>();
+    // The timeout is only checked if the semaphore isn't available.
+    result(cancel::point(|| match sem.wait(Timeout::Try) {
+        Err(Errno::AGAIN) => timespec(abstime).and_then(|until| sem.wait(Timeout::Until(&until))),
+        other => other,
+    }))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sem_post(sem: *mut libc::sem_t) -> c_int {
+    result((*sem.cast::<Semaphore>()).post())
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sem_getvalue(sem: *mut libc::sem_t, value: *mut c_int) -> c_int {
+    *value = (*sem.cast::<Semaphore>()).value.load(Ordering::Relaxed) as c_int;
+    0
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sem_open(
+    name: *const c_char,
+    oflag: c_int,
+    mut args: ...
+) -> *mut libc::sem_t {
+    let (mode, value) = if oflag & libc::O_CREAT != 0 {
+        (args.arg::<c_uint>(), args.arg::<c_uint>())
+    } else {
+        (0, 0)
+    };
+    match open_named(name, oflag, mode, value) {
+        Ok(sem) => sem,
+        Err(err) => {
+            *libc::__errno_location() = err.raw_os_error();
+            libc::SEM_FAILED
+        }
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sem_close(sem: *mut libc::sem_t) -> c_int {
+    result(munmap(sem.cast(), size_of::<libc::sem_t>()))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sem_unlink(name: *const c_char) -> c_int {
+    result(path(name).and_then(|path| unlink(path.as_c_str())))
+}
@@ -0,0 +1,670 @@
+//! Signal handling.
+//!
+//! Handlers are registered per signal. The first registration for a signal
+//! installs a dispatcher with `SA_SIGINFO | SA_RESTART | SA_ONSTACK`, so that
+//! interrupted syscalls are restarted and stack overflows can still be
+//! reported, and the dispatcher runs each registered action in turn. If the
+//! signal previously had a handler, the dispatcher calls it afterwards.
+//!
+//! The safe registrations only do async-signal-safe things: set a flag
+//! ([`register_flag`]), or write the signal number to a pipe
+//! ([`register_pipe`], [`SelfPipe`]). [`register`] runs arbitrary code, and
+//! is `unsafe` because that code must be async-signal-safe. For async
+//! runtimes, [`SelfPipe`] and [`SignalFd`] turn signals into readable file
+//! descriptors.
+//!
+//! `SA_ONSTACK` handlers only run on an alternate stack if the receiving
+//! thread has one. With the "thread" feature, mustang's `pthread_create`
+//! gives each thread one as it starts, [`ALTSTACK_SIZE`] bytes above a guard
+//! page, and frees it when the thread exits; that covers threads spawned by
+//! `std::thread`, which then keep it rather than allocating their own.
+//! Registering a handler gives the calling thread, such as the main thread,
+//! one if it doesn't have one yet, and other threads can call
+//! [`ensure_altstack`] themselves.
+//!
+//! Like glibc, mustang reserves the first two real-time signals for the
+//! runtime, so applications' real-time signals run from [`SIGRTMIN`], 34, to
+//! [`SIGRTMAX`], 64. `libc::SIGRTMIN()` and `libc::SIGRTMAX()` agree. Signals
+//! can be sent with a payload with [`sigqueue`], and received synchronously,
+//! payload included, with [`sigwaitinfo`] and [`sigtimedwait`].
+//!
+//! The C functions behind these, `__libc_current_sigrtmin`,
+//! `__libc_current_sigrtmax`, `sigqueue`, `sigwaitinfo` and `sigtimedwait`,
+//! are also defined by c-scape, whose definitions take precedence over weak
+//! ones, so the implementations here are named `__mustang_sigqueue` and so
+//! on, and bound to the standard names with `--defsym`, as the mutex module
+//! does.
+
+use core::cell::UnsafeCell;
+use core::ffi::{c_int, c_void};
+use core::mem::{size_of, zeroed, MaybeUninit};
+use core::ptr::{null, null_mut};
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use core::time::Duration;
+use rustix::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use rustix::io::{self, Errno};
+use rustix::mm::{mmap_anonymous, mprotect, munmap, MapFlags, MprotectFlags, ProtFlags};
+
+use crate::syscall::{nr, syscall3, syscall4, KernelTimespec};
+
+// Bind the standard names to the implementations here; see above.
+link_args!(
+    "-Wl,--defsym=__libc_current_sigrtmin=__mustang___libc_current_sigrtmin",
+    "-Wl,--defsym=__libc_current_sigrtmax=__mustang___libc_current_sigrtmax",
+    "-Wl,--defsym=sigqueue=__mustang_sigqueue",
+    "-Wl,--defsym=sigwaitinfo=__mustang_sigwaitinfo",
+    "-Wl,--defsym=sigtimedwait=__mustang_sigtimedwait",
+);
+
+extern "C" {
+    fn __cxa_thread_atexit_impl(
+        dtor: unsafe extern "C" fn(*mut c_void),
+        obj: *mut c_void,
+        dso_symbol: *mut c_void,
+    ) -> c_int;
+}
+
+/// The highest signal number.
+pub const NSIG: c_int = 64;
+
+/// The lowest real-time signal available to applications.
+pub const SIGRTMIN: c_int = 34;
+
+/// The highest real-time signal available to applications.
+pub const SIGRTMAX: c_int = NSIG;
+
+/// The real-time signals reserved for the runtime, below [`SIGRTMIN`]. These
+/// can't be registered. The first notifies `SIGEV_THREAD` timers' helper
+/// thread, and the second cancels threads.
+pub const RESERVED: [c_int; 2] = [32, 33];
+
+/// Signals which can't be registered: `SIGKILL` and `SIGSTOP` can't be
+/// caught, and returning from a handler for a fault re-executes the faulting
+/// instruction.
+pub const FORBIDDEN: [c_int; 6] = [
+    libc::SIGKILL,
+    libc::SIGSTOP,
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGILL,
+    libc::SIGFPE,
+];
+
+/// The maximum number of registrations for each signal.
+pub const MAX_ACTIONS: usize = 8;
+
+/// The size of the alternate signal stacks [`ensure_altstack`] allocates.
+pub const ALTSTACK_SIZE: usize = 64 * 1024;
+
+/// A registration, for passing to [`unregister`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigId {
+    sig: c_int,
+    slot: usize,
+}
+
+impl SigId {
+    /// The signal this registration is for.
+    #[inline]
+    pub fn signal(&self) -> c_int {
+        self.sig
+    }
+}
+
+// Slot states. A slot being unregistered stays `FREEING` until no
+// dispatcher is running, so that it isn't reused while a dispatcher which
+// saw it `READY` may still be reading its kind and value.
+const FREE: u8 = 0;
+const BUSY: u8 = 1;
+const READY: u8 = 2;
+const FREEING: u8 = 3;
+
+// Slot kinds.
+const FLAG: u8 = 0;
+const PIPE: u8 = 1;
+const HANDLER: u8 = 2;
+
+struct Slot {
+    state: AtomicU8,
+    kind: AtomicU8,
+    value: AtomicUsize,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(FREE),
+            kind: AtomicU8::new(FLAG),
+            value: AtomicUsize::new(0),
+        }
+    }
+}
+
+struct Previous(UnsafeCell<[MaybeUninit<libc::sigaction>; NSIG as usize + 1]>);
+
+// SAFETY: An entry is written once, under `LOCK`, before the dispatcher for
+// its signal is installed, and only read by the dispatcher.
+unsafe impl Sync for Previous {}
+
+static ACTIONS: [[Slot; MAX_ACTIONS]; NSIG as usize + 1] =
+    [const { [const { Slot::new() }; MAX_ACTIONS] }; NSIG as usize + 1];
+static INSTALLED: [AtomicBool; NSIG as usize + 1] =
+    [const { AtomicBool::new(false) }; NSIG as usize + 1];
+static PREVIOUS: Previous = Previous(UnsafeCell::new(
+    [const { MaybeUninit::uninit() }; NSIG as usize + 1],
+));
+static LOCK: AtomicBool = AtomicBool::new(false);
+
+/// The number of dispatchers currently running actions, so that
+/// [`unregister`] can wait for them.
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+/// Set `flag` when `sig` is delivered.
+pub fn register_flag(sig: c_int, flag: &'static AtomicBool) -> io::Result<SigId> {
+    add(sig, FLAG, flag as *const AtomicBool as usize)
+}
+
+/// Write the signal number, as a byte, to `fd` when `sig` is delivered.
+///
+/// `fd` should be non-blocking, so that the handler doesn't block when the
+/// pipe is full, and it must stay open until the registration is removed.
+pub fn register_pipe(sig: c_int, fd: BorrowedFd<'static>) -> io::Result<SigId> {
+    add(sig, PIPE, fd.as_raw_fd() as usize)
+}
+
+/// Call `handler` when `sig` is delivered.
+///
+/// # Safety
+///
+/// `handler` runs in a signal handler, so it may only do async-signal-safe
+/// things. In particular, it mustn't allocate, take locks, or call
+/// [`unregister`].
+pub unsafe fn register(sig: c_int, handler: fn(&libc::siginfo_t)) -> io::Result<SigId> {
+    add(sig, HANDLER, handler as usize)
+}
+
+/// Remove a registration. Returns `false` if it was already removed.
+///
+/// When this returns, the action won't run again, and any running
+/// invocation of it has finished.
+pub fn unregister(id: SigId) -> bool {
+    let slot = &ACTIONS[id.sig as usize][id.slot];
+    if slot
+        .state
+        .compare_exchange(READY, FREEING, Ordering::SeqCst, Ordering::Relaxed)
+        .is_err()
+    {
+        return false;
+    }
+    // This pairs with the dispatcher incrementing `RUNNING` before it reads
+    // the slot's state, so either it sees `FREEING`, or we see it running.
+    while RUNNING.load(Ordering::SeqCst) != 0 {
+        core::hint::spin_loop();
+    }
+    slot.state.store(FREE, Ordering::Release);
+    true
+}
+
+fn add(sig: c_int, kind: u8, value: usize) -> io::Result<SigId> {
+    if !(1..=NSIG).contains(&sig) || FORBIDDEN.contains(&sig) || RESERVED.contains(&sig) {
+        return Err(Errno::INVAL);
+    }
+    install(sig)?;
+    ensure_altstack()?;
+
+    for (index, slot) in ACTIONS[sig as usize].iter().enumerate() {
+        if slot
+            .state
+            .compare_exchange(FREE, BUSY, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            slot.kind.store(kind, Ordering::Relaxed);
+            slot.value.store(value, Ordering::Relaxed);
+            slot.state.store(READY, Ordering::Release);
+            return Ok(SigId { sig, slot: index });
+        }
+    }
+    Err(Errno::NOSPC)
+}
+
+/// Install the dispatcher for `sig`, if it isn't installed already.
+fn install(sig: c_int) -> io::Result<()> {
+    if INSTALLED[sig as usize].load(Ordering::Acquire) {
+        return Ok(());
+    }
+
+    while LOCK
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+    let result = if INSTALLED[sig as usize].load(Ordering::Relaxed) {
+        Ok(())
+    } else {
+        // SAFETY: We hold `LOCK`, and the dispatcher for `sig` isn't
+        // installed, so nothing else is accessing this entry.
+        unsafe {
+            let previous = (*PREVIOUS.0.get())[sig as usize].as_mut_ptr();
+            let mut action: libc::sigaction = zeroed();
+            action.sa_sigaction = dispatch as *const () as usize;
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART | libc::SA_ONSTACK;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(sig, &action, previous) == 0 {
+                INSTALLED[sig as usize].store(true, Ordering::Release);
+                Ok(())
+            } else {
+                Err(errno())
+            }
+        }
+    };
+    LOCK.store(false, Ordering::Release);
+    result
+}
+
+unsafe extern "C" fn dispatch(sig: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let saved_errno = *libc::__errno_location();
+
+    RUNNING.fetch_add(1, Ordering::SeqCst);
+    for slot in &ACTIONS[sig as usize] {
+        if slot.state.load(Ordering::SeqCst) != READY {
+            continue;
+        }
+        let value = slot.value.load(Ordering::Relaxed);
+        match slot.kind.load(Ordering::Relaxed) {
+            FLAG => (*(value as *const AtomicBool)).store(true, Ordering::SeqCst),
+            PIPE => {
+                let fd = BorrowedFd::borrow_raw(value as RawFd);
+                let _ = rustix::io::write(fd, &[sig as u8]);
+            }
+            _ => {
+                let handler: fn(&libc::siginfo_t) = core::mem::transmute(value);
+                handler(&*info);
+            }
+        }
+    }
+    RUNNING.fetch_sub(1, Ordering::Release);
+
+    // Chain to the handler which was installed before ours, if any.
+    let previous = (*PREVIOUS.0.get())[sig as usize].assume_init_ref();
+    let handler = previous.sa_sigaction;
+    if handler != libc::SIG_DFL && handler != libc::SIG_IGN {
+        if previous.sa_flags & libc::SA_SIGINFO != 0 {
+            let handler: unsafe extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void) =
+                core::mem::transmute(handler);
+            handler(sig, info, context);
+        } else {
+            let handler: unsafe extern "C" fn(c_int) = core::mem::transmute(handler);
+            handler(sig);
+        }
+    }
+
+    *libc::__errno_location() = saved_errno;
+}
+
+/// Give the calling thread an alternate signal stack, if it doesn't have one
+/// already. The stack is freed when the thread exits.
+pub fn ensure_altstack() -> io::Result<()> {
+    if let Some(map) = install_altstack()? {
+        // SAFETY: `free_altstack` frees the stack when the thread exits.
+        unsafe {
+            __cxa_thread_atexit_impl(free_altstack, map, null_mut());
+        }
+    }
+    Ok(())
+}
+
+/// Give the calling thread an alternate signal stack, if it doesn't have one
+/// already, and return the new stack's mapping, which the caller is
+/// responsible for freeing, if any.
+pub(crate) fn install_altstack() -> io::Result<Option<*mut c_void>> {
+    // SAFETY: We pass valid `stack_t`s, and the stack we install stays
+    // mapped until the caller frees it.
+    unsafe {
+        let mut old: libc::stack_t = zeroed();
+        if libc::sigaltstack(null(), &mut old) != 0 {
+            return Err(errno());
+        }
+        if old.ss_flags & libc::SS_DISABLE == 0 {
+            return Ok(None);
+        }
+
+        // Put a guard page below the stack.
+        let page = rustix::param::page_size();
+        let map = mmap_anonymous(
+            null_mut(),
+            page + ALTSTACK_SIZE,
+            ProtFlags::READ | ProtFlags::WRITE,
+            MapFlags::PRIVATE,
+        )?;
+        let _ = mprotect(map, page, MprotectFlags::empty());
+
+        let new = libc::stack_t {
+            ss_sp: map.cast::<u8>().add(page).cast(),
+            ss_flags: 0,
+            ss_size: ALTSTACK_SIZE,
+        };
+        if libc::sigaltstack(&new, null_mut()) != 0 {
+            let err = errno();
+            let _ = munmap(map, page + ALTSTACK_SIZE);
+            return Err(err);
+        }
+        Ok(Some(map))
+    }
+}
+
+unsafe extern "C" fn free_altstack(map: *mut c_void) {
+    let disable = libc::stack_t {
+        ss_sp: null_mut(),
+        ss_flags: libc::SS_DISABLE,
+        ss_size: 0,
+    };
+    libc::sigaltstack(&disable, null_mut());
+    let _ = munmap(map, rustix::param::page_size() + ALTSTACK_SIZE);
+}
+
+/// A pipe which signals are written to, for waiting for signals with
+/// `poll`, `epoll`, or an async runtime.
+///
+/// The read end is non-blocking. Each delivery of a registered signal
+/// writes one byte; if the pipe is full, deliveries are dropped, but the
+/// pipe stays readable.
+#[derive(Debug)]
+pub struct SelfPipe {
+    read: OwnedFd,
+    write: OwnedFd,
+    ids: [Option<SigId>; NSIG as usize],
+}
+
+impl SelfPipe {
+    /// Create a pipe, and register it for `signals`. Repeated signals are
+    /// registered once.
+    pub fn new(signals: &[c_int]) -> io::Result<Self> {
+        use rustix::pipe::{pipe_with, PipeFlags};
+
+        let (read, write) = pipe_with(PipeFlags::CLOEXEC | PipeFlags::NONBLOCK)?;
+        let mut pipe = Self {
+            read,
+            write,
+            ids: [None; NSIG as usize],
+        };
+        for &sig in signals {
+            if !(1..=NSIG).contains(&sig) {
+                return Err(Errno::INVAL);
+            }
+            // Dropping `pipe` on failure unregisters the signals registered
+            // so far.
+            let id = &mut pipe.ids[sig as usize - 1];
+            if id.is_none() {
+                *id = Some(add(sig, PIPE, pipe.write.as_raw_fd() as usize)?);
+            }
+        }
+        Ok(pipe)
+    }
+
+    /// Read the next signal from the pipe, or return `None` if there isn't
+    /// one yet.
+    pub fn read(&self) -> io::Result<Option<c_int>> {
+        let mut buf = [0_u8; 1];
+        match rustix::io::read(&self.read, &mut buf) {
+            Ok(1) => Ok(Some(buf[0].into())),
+            Ok(_) => Err(Errno::PIPE),
+            Err(Errno::AGAIN) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Drop for SelfPipe {
+    fn drop(&mut self) {
+        // Unregister before `write` is closed, so that no handler writes to
+        // a closed, or reused, fd.
+        for id in self.ids.iter().flatten() {
+            unregister(*id);
+        }
+    }
+}
+
+impl AsFd for SelfPipe {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.read.as_fd()
+    }
+}
+
+/// A signalfd, for receiving signals by reading a file descriptor.
+///
+/// A signal is only reported through a signalfd if it's blocked, so that
+/// it stays pending, in the thread it's delivered to. Create the `SignalFd`
+/// in the main thread before spawning other threads, which inherit its
+/// signal mask, so that the signals are blocked in all threads.
+#[derive(Debug)]
+pub struct SignalFd(OwnedFd);
+
+impl SignalFd {
+    /// Block `signals` in the calling thread, and create a non-blocking
+    /// signalfd which reports them.
+    pub fn new(signals: &[c_int]) -> io::Result<Self> {
+        // SAFETY: We pass a valid signal set.
+        unsafe {
+            let mut set: libc::sigset_t = zeroed();
+            libc::sigemptyset(&mut set);
+            for sig in signals {
+                if !(1..=NSIG).contains(sig) {
+                    return Err(Errno::INVAL);
+                }
+                libc::sigaddset(&mut set, *sig);
+            }
+            match libc::pthread_sigmask(libc::SIG_BLOCK, &set, null_mut()) {
+                0 => {}
+                err => return Err(Errno::from_raw_os_error(err)),
+            }
+            let fd = syscall4(
+                nr::SIGNALFD4,
+                -1_isize as usize,
+                &set as *const libc::sigset_t as usize,
+                (NSIG / 8) as usize,
+                (libc::SFD_CLOEXEC | libc::SFD_NONBLOCK) as usize,
+            )?;
+            Ok(Self(OwnedFd::from_raw_fd(fd as RawFd)))
+        }
+    }
+
+    /// Read the next signal, or return `None` if there isn't one pending.
+    pub fn read(&self) -> io::Result<Option<libc::signalfd_siginfo>> {
+        // SAFETY: An all-zero `signalfd_siginfo` is valid, and any bytes
+        // the kernel writes to it are too.
+        unsafe {
+            let mut info: libc::signalfd_siginfo = zeroed();
+            let buf = core::slice::from_raw_parts_mut(
+                (&mut info as *mut libc::signalfd_siginfo).cast::<u8>(),
+                size_of::<libc::signalfd_siginfo>(),
+            );
+            match rustix::io::read(&self.0, buf) {
+                Ok(n) if n == size_of::<libc::signalfd_siginfo>() => Ok(Some(info)),
+                Ok(_) => Err(Errno::INVAL),
+                Err(Errno::AGAIN) => Ok(None),
+                Err(err) => Err(err),
+            }
+        }
+    }
+}
+
+impl AsFd for SignalFd {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+/// `si_code` for signals sent with `sigqueue`.
+const SI_QUEUE: c_int = -1;
+
+/// The start of a `siginfo_t` for a signal sent with `sigqueue`.
+#[repr(C)]
+struct QueueInfo {
+    signo: c_int,
+    errno: c_int,
+    code: c_int,
+    // In a union which is pointer-aligned.
+    rt: QueueRt,
+}
+
+#[repr(C)]
+struct QueueRt {
+    pid: c_int,
+    uid: libc::uid_t,
+    value: libc::sigval,
+}
+
+const _: () = assert!(size_of::<QueueInfo>() <= size_of::<libc::siginfo_t>());
+
+/// Send signal `sig` to process `pid`, with payload `value`, which the
+/// receiver finds in `si_value`.
+///
+/// Unlike standard signals, real-time signals sent this way are queued, so
+/// that each one is delivered, with its own payload.
+pub fn sigqueue(pid: c_int, sig: c_int, value: libc::sigval) -> io::Result<()> {
+    let info = queue_info(sig, value);
+    // SAFETY: We pass a valid `siginfo_t`.
+    unsafe {
+        syscall3(
+            nr::RT_SIGQUEUEINFO,
+            pid as usize,
+            sig as usize,
+            &info as *const libc::siginfo_t as usize,
+        )?;
+    }
+    Ok(())
+}
+
+/// Make the `siginfo_t` for sending signal `sig` from this process with
+/// `sigqueue`.
+pub(crate) fn queue_info(sig: c_int, value: libc::sigval) -> libc::siginfo_t {
+    // SAFETY: An all-zero `siginfo_t` is valid, and `QueueInfo` fits in it.
+    unsafe {
+        let mut info: libc::siginfo_t = zeroed();
+        (&mut info as *mut libc::siginfo_t)
+            .cast::<QueueInfo>()
+            .write(QueueInfo {
+                signo: sig,
+                errno: 0,
+                code: SI_QUEUE,
+                rt: QueueRt {
+                    pid: libc::getpid(),
+                    uid: libc::getuid(),
+                    value,
+                },
+            });
+        info
+    }
+}
+
+/// Wait for one of the signals in `set` to be pending, and accept it.
+///
+/// The signals in `set` should be blocked in all threads, so that they stay
+/// pending rather than being handled.
+pub fn sigwaitinfo(set: &libc::sigset_t) -> io::Result<libc::siginfo_t> {
+    wait(set, None)
+}
+
+/// Like [`sigwaitinfo`], but fail with `EAGAIN` if none of the signals is
+/// pending within `timeout`.
+pub fn sigtimedwait(set: &libc::sigset_t, timeout: Duration) -> io::Result<libc::siginfo_t> {
+    let timeout = KernelTimespec {
+        tv_sec: timeout.as_secs().try_into().unwrap_or(i64::MAX),
+        tv_nsec: timeout.subsec_nanos().into(),
+    };
+    wait(set, Some(&timeout))
+}
+
+fn wait(set: &libc::sigset_t, timeout: Option<&KernelTimespec>) -> io::Result<libc::siginfo_t> {
+    // SAFETY: An all-zero `siginfo_t` is valid, and we pass valid pointers.
+    unsafe {
+        let mut info: libc::siginfo_t = zeroed();
+        raw_wait(set, &mut info, timeout.map_or(null(), |t| t))?;
+        Ok(info)
+    }
+}
+
+pub(crate) unsafe fn raw_wait(
+    set: *const libc::sigset_t,
+    info: *mut libc::siginfo_t,
+    timeout: *const KernelTimespec,
+) -> io::Result<c_int> {
+    let sig = syscall4(
+        nr::RT_SIGTIMEDWAIT,
+        set as usize,
+        info as usize,
+        timeout as usize,
+        (NSIG / 8) as usize,
+    )?;
+    Ok(sig as c_int)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang___libc_current_sigrtmin() -> c_int {
+    SIGRTMIN
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang___libc_current_sigrtmax() -> c_int {
+    SIGRTMAX
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sigqueue(
+    pid: libc::pid_t,
+    sig: c_int,
+    value: libc::sigval,
+) -> c_int {
+    match sigqueue(pid, sig, value) {
+        Ok(()) => 0,
+        Err(err) => fail(err),
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sigwaitinfo(
+    set: *const libc::sigset_t,
+    info: *mut libc::siginfo_t,
+) -> c_int {
+    match raw_wait(set, info, null()) {
+        Ok(sig) => sig,
+        Err(err) => fail(err),
+    }
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_sigtimedwait(
+    set: *const libc::sigset_t,
+    info: *mut libc::siginfo_t,
+    timeout: *const libc::timespec,
+) -> c_int {
+    // `time_t` and `c_long` are 32-bit on some targets.
+    #[allow(clippy::useless_conversion)]
+    let timeout = timeout.as_ref().map(|timeout| KernelTimespec {
+        tv_sec: timeout.tv_sec.into(),
+        tv_nsec: timeout.tv_nsec.into(),
+    });
+    match raw_wait(set, info, timeout.as_ref().map_or(null(), |t| t)) {
+        Ok(sig) => sig,
+        Err(err) => fail(err),
+    }
+}
+
+/// Set `errno` to `err`, and return -1.
+unsafe fn fail(err: Errno) -> c_int {
+    *libc::__errno_location() = err.raw_os_error();
+    -1
+}
+
+fn errno() -> Errno {
+    // SAFETY: `__errno_location` always returns a valid pointer.
+    Errno::from_raw_os_error(unsafe { *libc::__errno_location() })
+}
@@ -0,0 +1,773 @@
+//! `posix_spawn` using `clone(CLONE_VM | CLONE_VFORK)`.
+//!
+//! `fork` has to copy the parent's page tables, so its cost grows with the
+//! parent's memory size, even though the child is about to exec. Here the
+//! child shares the parent's memory and runs on a small separate stack, and
+//! the parent is suspended until the child execs or exits, so spawning takes
+//! the same time regardless of the parent's size. `std::process::Command`
+//! uses `posix_spawn` when it can.
+//!
+//! Because the child shares the parent's memory, including its TLS, it makes
+//! only raw syscalls, which don't set `errno`. All signals are blocked in the
+//! parent across the `clone`, so that no handler runs in the child; the child
+//! resets caught signals to their default dispositions before restoring the
+//! signal mask. If the child fails before exec, it reports the error to the
+//! parent through the shared memory, and `posix_spawn` returns it.
+//!
+//! `pidfd_spawn` and `pidfd_spawnp` additionally pass `CLONE_PIDFD`, to
+//! return a pidfd for the child; see `mustang::process` for a Rust API.
+//!
+//! The `posix_spawnattr_t` and `posix_spawn_file_actions_t` layouts match
+//! glibc's, as described by the `libc` crate. c-scape defines `posix_spawn`
+//! too, with `fork`, and its definitions take precedence over weak ones, so
+//! the implementations here are named `__mustang_posix_spawn` and so on, and
+//! this module binds the standard names to them with `--defsym`, as the
+//! mutex module does.
+
+use crate::syscall::{clone_vfork, nr, syscall1, syscall2, syscall3, syscall4};
+use core::ffi::{c_char, c_int, c_short, c_void, CStr};
+use core::mem::{size_of, zeroed};
+use core::ptr::{self, null_mut};
+use libc::{mode_t, pid_t, posix_spawn_file_actions_t, posix_spawnattr_t, sched_param, sigset_t};
+use rustix::io::Errno;
+use rustix::mm::{mmap_anonymous, mprotect, munmap, MapFlags, MprotectFlags, ProtFlags};
+
+// Bind the standard names to the implementations here; see above.
+link_args!(
+    "-Wl,--defsym=posix_spawn=__mustang_posix_spawn",
+    "-Wl,--defsym=posix_spawnp=__mustang_posix_spawnp",
+    "-Wl,--defsym=pidfd_spawn=__mustang_pidfd_spawn",
+    "-Wl,--defsym=pidfd_spawnp=__mustang_pidfd_spawnp",
+    "-Wl,--defsym=posix_spawnattr_init=__mustang_posix_spawnattr_init",
+    "-Wl,--defsym=posix_spawnattr_destroy=__mustang_posix_spawnattr_destroy",
+    "-Wl,--defsym=posix_spawnattr_getflags=__mustang_posix_spawnattr_getflags",
+    "-Wl,--defsym=posix_spawnattr_setflags=__mustang_posix_spawnattr_setflags",
+    "-Wl,--defsym=posix_spawnattr_getpgroup=__mustang_posix_spawnattr_getpgroup",
+    "-Wl,--defsym=posix_spawnattr_setpgroup=__mustang_posix_spawnattr_setpgroup",
+    "-Wl,--defsym=posix_spawnattr_getsigmask=__mustang_posix_spawnattr_getsigmask",
+    "-Wl,--defsym=posix_spawnattr_setsigmask=__mustang_posix_spawnattr_setsigmask",
+    "-Wl,--defsym=posix_spawnattr_getsigdefault=__mustang_posix_spawnattr_getsigdefault",
+    "-Wl,--defsym=posix_spawnattr_setsigdefault=__mustang_posix_spawnattr_setsigdefault",
+    "-Wl,--defsym=posix_spawnattr_getschedpolicy=__mustang_posix_spawnattr_getschedpolicy",
+    "-Wl,--defsym=posix_spawnattr_setschedpolicy=__mustang_posix_spawnattr_setschedpolicy",
+    "-Wl,--defsym=posix_spawnattr_getschedparam=__mustang_posix_spawnattr_getschedparam",
+    "-Wl,--defsym=posix_spawnattr_setschedparam=__mustang_posix_spawnattr_setschedparam",
+    "-Wl,--defsym=posix_spawn_file_actions_init=__mustang_posix_spawn_file_actions_init",
+    "-Wl,--defsym=posix_spawn_file_actions_destroy=__mustang_posix_spawn_file_actions_destroy",
+    "-Wl,--defsym=posix_spawn_file_actions_addclose=__mustang_posix_spawn_file_actions_addclose",
+    "-Wl,--defsym=posix_spawn_file_actions_adddup2=__mustang_posix_spawn_file_actions_adddup2",
+    "-Wl,--defsym=posix_spawn_file_actions_addopen=__mustang_posix_spawn_file_actions_addopen",
+    "-Wl,--defsym=posix_spawn_file_actions_addchdir_np=__mustang_posix_spawn_file_actions_addchdir_np",
+    "-Wl,--defsym=posix_spawn_file_actions_addchdir=__mustang_posix_spawn_file_actions_addchdir",
+    "-Wl,--defsym=posix_spawn_file_actions_addfchdir_np=__mustang_posix_spawn_file_actions_addfchdir_np",
+    "-Wl,--defsym=posix_spawn_file_actions_addfchdir=__mustang_posix_spawn_file_actions_addfchdir",
+);
+
+extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+    fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void;
+    fn free(ptr: *mut c_void);
+    fn strdup(s: *const c_char) -> *mut c_char;
+    fn getenv(name: *const c_char) -> *mut c_char;
+    fn waitpid(pid: pid_t, status: *mut c_int, options: c_int) -> pid_t;
+}
+
+/// The size of the child's stack, including a guard page. The child needs
+/// room for a `PATH_MAX` buffer for `posix_spawnp`'s search.
+const STACK_SIZE: usize = 64 * 1024;
+
+/// The default search path for `posix_spawnp`, if `PATH` isn't set.
+const DEFAULT_PATH: &CStr = c"/bin:/usr/bin";
+
+/// The number of signals, and the size of the kernel's signal set.
+const NSIG: c_int = 64;
+const KERNEL_SIGSET_SIZE: usize = 8;
+
+// The `libc` crate declares most of these as `c_int`, but the flags are a
+// `c_short`.
+const RESETIDS: c_short = libc::POSIX_SPAWN_RESETIDS as c_short;
+const SETPGROUP: c_short = libc::POSIX_SPAWN_SETPGROUP as c_short;
+const SETSIGDEF: c_short = libc::POSIX_SPAWN_SETSIGDEF as c_short;
+const SETSIGMASK: c_short = libc::POSIX_SPAWN_SETSIGMASK as c_short;
+const SETSCHEDPARAM: c_short = libc::POSIX_SPAWN_SETSCHEDPARAM as c_short;
+const SETSCHEDULER: c_short = libc::POSIX_SPAWN_SETSCHEDULER as c_short;
+const SETSID: c_short = libc::POSIX_SPAWN_SETSID;
+const FLAGS: c_short =
+    RESETIDS | SETPGROUP | SETSIGDEF | SETSIGMASK | SETSCHEDPARAM | SETSCHEDULER | SETSID;
+
+/// Our view of `posix_spawnattr_t`.
+#[repr(C)]
+struct Attr {
+    flags: c_short,
+    pgroup: pid_t,
+    sigdefault: sigset_t,
+    sigmask: sigset_t,
+    schedparam: sched_param,
+    policy: c_int,
+    pad: [c_int; 16],
+}
+
+/// Our view of `posix_spawn_file_actions_t`.
+#[repr(C)]
+struct FileActions {
+    allocated: c_int,
+    used: c_int,
+    actions: *mut Action,
+    pad: [c_int; 16],
+}
+
+const _: () = assert!(size_of::<Attr>() == size_of::<posix_spawnattr_t>());
+const _: () = assert!(size_of::<FileActions>() == size_of::<posix_spawn_file_actions_t>());
+
+enum Action {
+    Close(c_int),
+    Dup2(c_int, c_int),
+    Open(c_int, *mut c_char, c_int, mode_t),
+    Chdir(*mut c_char),
+    Fchdir(c_int),
+}
+
+/// What the parent shares with the child.
+struct Child<'a> {
+    path: *const c_char,
+    search: Option<&'a CStr>,
+    argv: *const *mut c_char,
+    envp: *const *mut c_char,
+    actions: &'a [Action],
+    attr: Option<&'a Attr>,
+    mask: sigset_t,
+    err: c_int,
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawn(
+    pid: *mut pid_t,
+    path: *const c_char,
+    file_actions: *const posix_spawn_file_actions_t,
+    attrp: *const posix_spawnattr_t,
+    argv: *const *mut c_char,
+    envp: *const *mut c_char,
+) -> c_int {
+    spawn(
+        pid,
+        null_mut(),
+        path,
+        false,
+        file_actions,
+        attrp,
+        argv,
+        envp,
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnp(
+    pid: *mut pid_t,
+    file: *const c_char,
+    file_actions: *const posix_spawn_file_actions_t,
+    attrp: *const posix_spawnattr_t,
+    argv: *const *mut c_char,
+    envp: *const *mut c_char,
+) -> c_int {
+    spawn(pid, null_mut(), file, true, file_actions, attrp, argv, envp)
+}
+
+/// Like `posix_spawn`, but return a pidfd for the child, as in glibc 2.39.
+#[no_mangle]
+unsafe extern "C" fn __mustang_pidfd_spawn(
+    pidfd: *mut c_int,
+    path: *const c_char,
+    file_actions: *const posix_spawn_file_actions_t,
+    attrp: *const posix_spawnattr_t,
+    argv: *const *mut c_char,
+    envp: *const *mut c_char,
+) -> c_int {
+    spawn(
+        null_mut(),
+        pidfd,
+        path,
+        false,
+        file_actions,
+        attrp,
+        argv,
+        envp,
+    )
+}
+
+/// Like `posix_spawnp`, but return a pidfd for the child, as in glibc 2.39.
+#[no_mangle]
+unsafe extern "C" fn __mustang_pidfd_spawnp(
+    pidfd: *mut c_int,
+    file: *const c_char,
+    file_actions: *const posix_spawn_file_actions_t,
+    attrp: *const posix_spawnattr_t,
+    argv: *const *mut c_char,
+    envp: *const *mut c_char,
+) -> c_int {
+    spawn(
+        null_mut(),
+        pidfd,
+        file,
+        true,
+        file_actions,
+        attrp,
+        argv,
+        envp,
+    )
+}
+
+/// Spawn a child process, and store its pid in `pid` and a pidfd for it in
+/// `pidfd`, if they aren't null. If `search` is true and `path` doesn't
+/// contain a `/`, search for it in `PATH`. Returns 0, or an errno value.
+#[allow(clippy::too_many_arguments)]
+pub(crate) unsafe fn spawn(
+    pid: *mut pid_t,
+    pidfd: *mut c_int,
+    path: *const c_char,
+    search: bool,
+    file_actions: *const posix_spawn_file_actions_t,
+    attrp: *const posix_spawnattr_t,
+    argv: *const *mut c_char,
+    envp: *const *mut c_char,
+) -> c_int {
+    let search = if !search || CStr::from_ptr(path).to_bytes().contains(&b'/') {
+        None
+    } else {
+        let var = getenv(c"PATH".as_ptr());
+        Some(if var.is_null() {
+            DEFAULT_PATH
+        } else {
+            CStr::from_ptr(var)
+        })
+    };
+    let actions = match file_actions.cast::<FileActions>().as_ref() {
+        Some(file_actions) if file_actions.used != 0 => {
+            core::slice::from_raw_parts(file_actions.actions, file_actions.used as usize)
+        }
+        _ => &[],
+    };
+
+    let stack = match mmap_anonymous(
+        null_mut(),
+        STACK_SIZE,
+        ProtFlags::READ | ProtFlags::WRITE,
+        MapFlags::PRIVATE,
+    ) {
+        Ok(stack) => stack,
+        Err(err) => return err.raw_os_error(),
+    };
+    // Put a guard page at the bottom. If this fails, go ahead without one.
+    let _ = mprotect(stack, rustix::param::page_size(), MprotectFlags::empty());
+
+    let mut child = Child {
+        path,
+        search,
+        argv,
+        envp,
+        actions,
+        attr: attrp.cast::<Attr>().as_ref(),
+        mask: zeroed(),
+        err: 0,
+    };
+
+    // Block all signals, so that no handler runs in the child while it
+    // shares our memory.
+    let all = [!0_u8; KERNEL_SIGSET_SIZE];
+    sigprocmask(all.as_ptr().cast(), &mut child.mask);
+
+    let result = clone_vfork(
+        stack.cast::<u8>().add(STACK_SIZE),
+        child_main,
+        ptr::addr_of_mut!(child).cast(),
+        pidfd,
+    );
+
+    sigprocmask(ptr::addr_of!(child.mask), null_mut());
+    let _ = munmap(stack, STACK_SIZE);
+
+    match result {
+        Err(err) => err.raw_os_error(),
+        // The child has exited or exec'd by now. Read `err` through a
+        // volatile read, as the compiler doesn't know that the child wrote
+        // it.
+        Ok(child_pid) => match ptr::read_volatile(&child.err) {
+            0 => {
+                if !pid.is_null() {
+                    *pid = child_pid as pid_t;
+                }
+                0
+            }
+            err => {
+                let mut status = 0;
+                waitpid(child_pid as pid_t, &mut status, 0);
+                if !pidfd.is_null() {
+                    let _ = syscall1(nr::CLOSE, *pidfd as usize);
+                }
+                err
+            }
+        },
+    }
+}
+
+unsafe fn sigprocmask(set: *const sigset_t, old: *mut sigset_t) {
+    let _ = syscall4(
+        nr::RT_SIGPROCMASK,
+        libc::SIG_SETMASK as usize,
+        set as usize,
+        old as usize,
+        KERNEL_SIGSET_SIZE,
+    );
+}
+
+/// The child side of `spawn`.
+///
+/// This runs on the child's own stack, but shares the parent's memory, so it
+/// mustn't do anything that touches shared state, such as set `errno`, take
+/// locks, or allocate.
+unsafe extern "C" fn child_main(arg: *mut c_void) -> ! {
+    let child = &mut *arg.cast::<Child<'_>>();
+    let err = match prepare(child) {
+        Ok(()) => exec(child),
+        Err(err) => err,
+    };
+    ptr::write_volatile(&mut child.err, err.raw_os_error());
+    let _ = syscall1(nr::EXIT_GROUP, 127);
+    unreachable!()
+}
+
+/// Set up the child's process state according to the attributes and file
+/// actions.
+unsafe fn prepare(child: &Child<'_>) -> Result<(), Errno> {
+    let flags = child.attr.map_or(0, |attr| attr.flags);
+
+    // Reset signals which are caught in the parent, as the handlers aren't
+    // meant to run in the child, and signals in the attributes' default set.
+    for sig in 1..=NSIG {
+        if sig == libc::SIGKILL || sig == libc::SIGSTOP {
+            continue;
+        }
+        let default = match child.attr {
+            Some(attr) if flags & SETSIGDEF != 0 => is_member(&attr.sigdefault, sig),
+            _ => false,
+        };
+        // The kernel's `sigaction` layouts differ between architectures, but
+        // all start with the handler, and an all-zero one is `SIG_DFL`.
+        let mut old = [0_usize; 8];
+        let new = [0_usize; 8];
+        if !default {
+            if syscall4(
+                nr::RT_SIGACTION,
+                sig as usize,
+                0,
+                old.as_mut_ptr() as usize,
+                KERNEL_SIGSET_SIZE,
+            )
+            .is_err()
+            {
+                continue;
+            }
+            if old[0] == libc::SIG_DFL || old[0] == libc::SIG_IGN {
+                continue;
+            }
+        }
+        syscall4(
+            nr::RT_SIGACTION,
+            sig as usize,
+            new.as_ptr() as usize,
+            0,
+            KERNEL_SIGSET_SIZE,
+        )?;
+    }
+
+    if let Some(attr) = child.attr {
+        if flags & SETSID != 0 {
+            syscall1(nr::SETSID, 0)?;
+        }
+        if flags & SETPGROUP != 0 {
+            syscall2(nr::SETPGID, 0, attr.pgroup as usize)?;
+        }
+        if flags & SETSCHEDULER != 0 {
+            syscall3(
+                nr::SCHED_SETSCHEDULER,
+                0,
+                attr.policy as usize,
+                ptr::addr_of!(attr.schedparam) as usize,
+            )?;
+        } else if flags & SETSCHEDPARAM != 0 {
+            syscall2(
+                nr::SCHED_SETPARAM,
+                0,
+                ptr::addr_of!(attr.schedparam) as usize,
+            )?;
+        }
+        if flags & RESETIDS != 0 {
+            syscall1(nr::SETGID, syscall1(nr::GETGID, 0)?)?;
+            syscall1(nr::SETUID, syscall1(nr::GETUID, 0)?)?;
+        }
+    }
+
+    for action in child.actions {
+        match *action {
+            Action::Close(fd) => match syscall1(nr::CLOSE, fd as usize) {
+                // Closing an fd which isn't open isn't an error.
+                Ok(_) | Err(Errno::BADF) => {}
+                Err(err) => return Err(err),
+            },
+            Action::Dup2(fd, newfd) if fd == newfd => {
+                // The fd is inherited as-is, but without `FD_CLOEXEC`.
+                syscall3(nr::FCNTL, fd as usize, libc::F_SETFD as usize, 0)?;
+            }
+            Action::Dup2(fd, newfd) => {
+                syscall3(nr::DUP3, fd as usize, newfd as usize, 0)?;
+            }
+            Action::Open(fd, path, oflag, mode) => {
+                let opened = syscall4(
+                    nr::OPENAT,
+                    libc::AT_FDCWD as usize,
+                    path as usize,
+                    oflag as usize,
+                    mode as usize,
+                )?;
+                if opened != fd as usize {
+                    syscall3(nr::DUP3, opened, fd as usize, 0)?;
+                    syscall1(nr::CLOSE, opened)?;
+                }
+            }
+            Action::Chdir(path) => {
+                syscall1(nr::CHDIR, path as usize)?;
+            }
+            Action::Fchdir(fd) => {
+                syscall1(nr::FCHDIR, fd as usize)?;
+            }
+        }
+    }
+
+    let mask = match child.attr {
+        Some(attr) if flags & SETSIGMASK != 0 => &attr.sigmask,
+        _ => &child.mask,
+    };
+    sigprocmask(mask, null_mut());
+    Ok(())
+}
+
+/// Exec the program, searching the path if needed. This only returns if it
+/// fails.
+unsafe fn exec(child: &Child<'_>) -> Errno {
+    let Some(search) = child.search else {
+        return execve(child.path, child);
+    };
+
+    let file = CStr::from_ptr(child.path).to_bytes();
+    if file.is_empty() {
+        return Errno::NOENT;
+    }
+    let mut buf = [0_u8; libc::PATH_MAX as usize];
+    let mut err = Errno::NOENT;
+    for dir in search.to_bytes().split(|byte| *byte == b':') {
+        // An empty entry means the current directory.
+        let dir: &[u8] = if dir.is_empty() { b"." } else { dir };
+        let len = dir.len() + 1 + file.len();
+        if len >= buf.len() {
+            err = Errno::NAMETOOLONG;
+            continue;
+        }
+        buf[..dir.len()].copy_from_slice(dir);
+        buf[dir.len()] = b'/';
+        buf[dir.len() + 1..len].copy_from_slice(file);
+        buf[len] = 0;
+        match execve(buf.as_ptr().cast(), child) {
+            // Keep looking, but report `EACCES` if nothing else is found.
+            Errno::ACCESS => err = Errno::ACCESS,
+            Errno::NOENT | Errno::NOTDIR => {}
+            other => return other,
+        }
+    }
+    err
+}
+
+unsafe fn execve(path: *const c_char, child: &Child<'_>) -> Errno {
+    match syscall3(
+        nr::EXECVE,
+        path as usize,
+        child.argv as usize,
+        child.envp as usize,
+    ) {
+        Ok(_) => unreachable!(),
+        Err(err) => err,
+    }
+}
+
+fn is_member(set: &sigset_t, sig: c_int) -> bool {
+    let bit = (sig - 1) as usize;
+    // SAFETY: `sigset_t` is a plain array of bits.
+    let bytes: &[u8; size_of::<sigset_t>()] = unsafe { &*(set as *const sigset_t).cast() };
+    bytes[bit / 8] & (1 << (bit % 8)) != 0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_init(attr: *mut posix_spawnattr_t) -> c_int {
+    attr.write(zeroed());
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_destroy(_attr: *mut posix_spawnattr_t) -> c_int {
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_getflags(
+    attr: *const posix_spawnattr_t,
+    flags: *mut c_short,
+) -> c_int {
+    *flags = (*attr.cast::<Attr>()).flags;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_setflags(
+    attr: *mut posix_spawnattr_t,
+    flags: c_short,
+) -> c_int {
+    if flags & !FLAGS != 0 {
+        return libc::EINVAL;
+    }
+    (*attr.cast::<Attr>()).flags = flags;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_getpgroup(
+    attr: *const posix_spawnattr_t,
+    pgroup: *mut pid_t,
+) -> c_int {
+    *pgroup = (*attr.cast::<Attr>()).pgroup;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_setpgroup(
+    attr: *mut posix_spawnattr_t,
+    pgroup: pid_t,
+) -> c_int {
+    (*attr.cast::<Attr>()).pgroup = pgroup;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_getsigmask(
+    attr: *const posix_spawnattr_t,
+    mask: *mut sigset_t,
+) -> c_int {
+    *mask = (*attr.cast::<Attr>()).sigmask;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_setsigmask(
+    attr: *mut posix_spawnattr_t,
+    mask: *const sigset_t,
+) -> c_int {
+    (*attr.cast::<Attr>()).sigmask = *mask;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_getsigdefault(
+    attr: *const posix_spawnattr_t,
+    default: *mut sigset_t,
+) -> c_int {
+    *default = (*attr.cast::<Attr>()).sigdefault;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_setsigdefault(
+    attr: *mut posix_spawnattr_t,
+    default: *const sigset_t,
+) -> c_int {
+    (*attr.cast::<Attr>()).sigdefault = *default;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_getschedpolicy(
+    attr: *const posix_spawnattr_t,
+    policy: *mut c_int,
+) -> c_int {
+    *policy = (*attr.cast::<Attr>()).policy;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_setschedpolicy(
+    attr: *mut posix_spawnattr_t,
+    policy: c_int,
+) -> c_int {
+    (*attr.cast::<Attr>()).policy = policy;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_getschedparam(
+    attr: *const posix_spawnattr_t,
+    param: *mut sched_param,
+) -> c_int {
+    *param = (*attr.cast::<Attr>()).schedparam;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawnattr_setschedparam(
+    attr: *mut posix_spawnattr_t,
+    param: *const sched_param,
+) -> c_int {
+    (*attr.cast::<Attr>()).schedparam = *param;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawn_file_actions_init(
+    file_actions: *mut posix_spawn_file_actions_t,
+) -> c_int {
+    file_actions.write(zeroed());
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawn_file_actions_destroy(
+    file_actions: *mut posix_spawn_file_actions_t,
+) -> c_int {
+    let file_actions = &mut *file_actions.cast::<FileActions>();
+    for i in 0..file_actions.used as usize {
+        match *file_actions.actions.add(i) {
+            Action::Open(_, path, _, _) | Action::Chdir(path) => free(path.cast()),
+            _ => {}
+        }
+    }
+    free(file_actions.actions.cast());
+    *file_actions = zeroed();
+    0
+}
+
+/// Append `action` to `file_actions`.
+unsafe fn push(file_actions: *mut posix_spawn_file_actions_t, action: Action) -> c_int {
+    let file_actions = &mut *file_actions.cast::<FileActions>();
+    if file_actions.used == file_actions.allocated {
+        let allocated = (file_actions.allocated as usize * 2).max(8);
+        let actions = if file_actions.actions.is_null() {
+            malloc(allocated * size_of::<Action>())
+        } else {
+            realloc(file_actions.actions.cast(), allocated * size_of::<Action>())
+        };
+        if actions.is_null() {
+            return libc::ENOMEM;
+        }
+        file_actions.actions = actions.cast();
+        file_actions.allocated = allocated as c_int;
+    }
+    file_actions
+        .actions
+        .add(file_actions.used as usize)
+        .write(action);
+    file_actions.used += 1;
+    0
+}
+
+/// Copy a path for an action.
+unsafe fn copy(path: *const c_char) -> Result<*mut c_char, c_int> {
+    let copy = strdup(path);
+    if copy.is_null() {
+        Err(libc::ENOMEM)
+    } else {
+        Ok(copy)
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawn_file_actions_addclose(
+    file_actions: *mut posix_spawn_file_actions_t,
+    fd: c_int,
+) -> c_int {
+    if fd < 0 {
+        return libc::EBADF;
+    }
+    push(file_actions, Action::Close(fd))
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawn_file_actions_adddup2(
+    file_actions: *mut posix_spawn_file_actions_t,
+    fd: c_int,
+    newfd: c_int,
+) -> c_int {
+    if fd < 0 || newfd < 0 {
+        return libc::EBADF;
+    }
+    push(file_actions, Action::Dup2(fd, newfd))
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawn_file_actions_addopen(
+    file_actions: *mut posix_spawn_file_actions_t,
+    fd: c_int,
+    path: *const c_char,
+    oflag: c_int,
+    mode: mode_t,
+) -> c_int {
+    if fd < 0 {
+        return libc::EBADF;
+    }
+    let path = match copy(path) {
+        Ok(path) => path,
+        Err(err) => return err,
+    };
+    let err = push(file_actions, Action::Open(fd, path, oflag, mode));
+    if err != 0 {
+        free(path.cast());
+    }
+    err
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawn_file_actions_addchdir_np(
+    file_actions: *mut posix_spawn_file_actions_t,
+    path: *const c_char,
+) -> c_int {
+    let path = match copy(path) {
+        Ok(path) => path,
+        Err(err) => return err,
+    };
+    let err = push(file_actions, Action::Chdir(path));
+    if err != 0 {
+        free(path.cast());
+    }
+    err
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawn_file_actions_addchdir(
+    file_actions: *mut posix_spawn_file_actions_t,
+    path: *const c_char,
+) -> c_int {
+    __mustang_posix_spawn_file_actions_addchdir_np(file_actions, path)
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawn_file_actions_addfchdir_np(
+    file_actions: *mut posix_spawn_file_actions_t,
+    fd: c_int,
+) -> c_int {
+    if fd < 0 {
+        return libc::EBADF;
+    }
+    push(file_actions, Action::Fchdir(fd))
+}
+
+#[no_mangle]
+unsafe extern "C" fn __mustang_posix_spawn_file_actions_addfchdir(
+    file_actions: *mut posix_spawn_file_actions_t,
+    fd: c_int,
+) -> c_int {
+    __mustang_posix_spawn_file_actions_addfchdir_np(file_actions, fd)
+}
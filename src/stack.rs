@@ -0,0 +1,183 @@
+//! The main thread's stack bounds, and reporting overflows of it.
+//!
+//! The kernel grows the main thread's stack on demand, down from the top of
+//! the initial stack, until it reaches `RLIMIT_STACK`, or the kernel's guard
+//! gap above the mapping below it, whichever is higher. The top is just
+//! above the program path which `AT_EXECFN` points to. mustang computes the
+//! bounds from these at startup, like glibc does, finding the mapping below
+//! in `/proc/self/maps`, and `pthread_getattr_np` reports them for the main
+//! thread, to C and Rust code alike.
+//!
+//! Rust's std reports stack overflows from a `SIGSEGV` handler, which
+//! treats the page below the main thread's stack, as `pthread_getattr_np`
+//! reports it, as its guard. c-scape's `pthread_getattr_np` doesn't know how
+//! far the kernel will grow the stack, so std would miss overflows of it.
+//! c-scape's definitions take precedence over weak ones, so the
+//! implementation here is named `__mustang_pthread_getattr_np`, and this
+//! module binds the standard name to it with `--defsym`, as the mutex module
+//! does. Other threads' stacks are reported as origin describes them.
+
+use core::ffi::{c_char, c_int, c_void, CStr};
+use core::mem::zeroed;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use rustix::fs::{open, Mode, OFlags};
+
+/// The size of the gap the kernel keeps between a growing stack and the
+/// mapping below it, in pages. This is the default `stack_guard_gap`.
+const GUARD_GAP_PAGES: usize = 256;
+
+static LOWEST: AtomicUsize = AtomicUsize::new(0);
+static TOP: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "thread")]
+static MAIN_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+/// The bounds of the main thread's stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MainStack {
+    lowest: usize,
+    top: usize,
+}
+
+impl MainStack {
+    /// The lowest address the stack can grow down to.
+    #[inline]
+    pub fn addr(&self) -> *mut c_void {
+        self.lowest as *mut c_void
+    }
+
+    /// The size the stack can grow to.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.top - self.lowest
+    }
+
+    /// The size of the gap the kernel keeps below the stack.
+    #[inline]
+    pub fn guard_size(&self) -> usize {
+        GUARD_GAP_PAGES * rustix::param::page_size()
+    }
+}
+
+/// Return the bounds of the main thread's stack.
+pub fn main_stack() -> MainStack {
+    MainStack {
+        lowest: LOWEST.load(Ordering::Relaxed),
+        top: TOP.load(Ordering::Relaxed),
+    }
+}
+
+// This runs after `mustang::auxv` has initialized, and before constructors
+// at normal priorities, which may themselves overflow the stack.
+#[link_section = ".init_array.00002"]
+#[used]
+static INIT_STACK: unsafe extern "C" fn(c_int, *mut *mut c_char, *mut *mut c_char) = {
+    unsafe extern "C" fn function(_argc: c_int, _argv: *mut *mut c_char, envp: *mut *mut c_char) {
+        let page = rustix::param::page_size();
+        let top = top(envp).next_multiple_of(page);
+
+        let mut limit: libc::rlimit = zeroed();
+        libc::getrlimit(libc::RLIMIT_STACK, &mut limit);
+        let limited = if limit.rlim_cur != libc::RLIM_INFINITY && (limit.rlim_cur as usize) < top {
+            top - (limit.rlim_cur as usize & !(page - 1))
+        } else {
+            0
+        };
+        let lowest = limited.max(mapping_below(top).unwrap_or(0) + GUARD_GAP_PAGES * page);
+        LOWEST.store(lowest, Ordering::Relaxed);
+        TOP.store(top, Ordering::Relaxed);
+        #[cfg(feature = "thread")]
+        MAIN_THREAD.store(libc::pthread_self() as usize, Ordering::Relaxed);
+    }
+    function
+};
+
+/// Return the main thread's `pthread_t`.
+#[cfg(feature = "thread")]
+pub(crate) fn main_thread() -> libc::pthread_t {
+    MAIN_THREAD.load(Ordering::Relaxed) as libc::pthread_t
+}
+
+/// Return the end of the program path, or if there's no `AT_EXECFN`, of the
+/// last environment variable, which the kernel puts at the top of the stack.
+unsafe fn top(envp: *mut *mut c_char) -> usize {
+    if let Some(execfn) = crate::auxv::execfn() {
+        return execfn.as_ptr() as usize + execfn.to_bytes_with_nul().len();
+    }
+    let mut end = envp as usize;
+    let mut ptr = envp;
+    while !(*ptr).is_null() {
+        let var = CStr::from_ptr(*ptr);
+        end = end.max(var.as_ptr() as usize + var.to_bytes_with_nul().len());
+        ptr = ptr.add(1);
+    }
+    end
+}
+
+/// Return the end of the mapping below the one ending at `top`, from
+/// `/proc/self/maps`.
+fn mapping_below(top: usize) -> Option<usize> {
+    let fd = open(
+        "/proc/self/maps",
+        OFlags::RDONLY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )
+    .ok()?;
+    let mut buf = [0_u8; 1024];
+    let mut below = 0;
+    let mut end = 0;
+    // Each line starts with `start-end `, in hex. 0 is before the `-`, 1 is
+    // in `end`, and 2 is after it.
+    let mut field = 0;
+    loop {
+        let len = rustix::io::read(&fd, &mut buf).ok()?;
+        if len == 0 {
+            return None;
+        }
+        for &byte in &buf[..len] {
+            match (field, byte) {
+                (_, b'\n') => {
+                    if end == top {
+                        return Some(below);
+                    }
+                    below = end;
+                    end = 0;
+                    field = 0;
+                }
+                (0, b'-') => field = 1,
+                (1, b' ') => field = 2,
+                (1, _) => end = end << 4 | (byte as char).to_digit(16)? as usize,
+                _ => {}
+            }
+        }
+    }
+}
+
+// Bind the standard name to the implementation here; see above.
+#[cfg(feature = "thread")]
+link_args!("-Wl,--defsym=pthread_getattr_np=__mustang_pthread_getattr_np");
+
+/// `pthread_getattr_np`, with the main thread's stack bounds as computed
+/// here.
+#[cfg(feature = "thread")]
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_getattr_np(
+    thread: libc::pthread_t,
+    attr: *mut libc::pthread_attr_t,
+) -> c_int {
+    let (addr, size, guard_size) = if thread == main_thread() {
+        let stack = main_stack();
+        (stack.addr(), stack.size(), stack.guard_size())
+    } else {
+        origin::thread::stack(origin::thread::Thread::from_raw(thread as *mut c_void))
+    };
+    let err = libc::pthread_attr_init(attr);
+    if err != 0 {
+        return err;
+    }
+    let err = libc::pthread_attr_setstack(attr, addr, size);
+    if err != 0 {
+        return err;
+    }
+    libc::pthread_attr_setguardsize(attr, guard_size)
+}
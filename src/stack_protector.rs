@@ -0,0 +1,232 @@
+//! Support for `-Z stack-protector`.
+//!
+//! Code compiled with stack protectors compares a canary value saved in each
+//! protected frame against a guard value on return, and calls
+//! `__stack_chk_fail` if they differ. On x86_64 and x86, the guard is kept in
+//! the thread control block, at `%fs:0x28` and `%gs:0x14` respectively; on
+//! other architectures, it's the global `__stack_chk_guard`.
+//!
+//! The guard is seeded from the kernel's `AT_RANDOM` bytes by an
+//! `.init_array.00001` function, after `mustang::deterministic` may have
+//! replaced them. Origin lays out its thread control block like glibc's
+//! `tcbhead_t`, and gives new threads the creating thread's canary, so the
+//! TCB value set here is inherited by all threads.
+//!
+//! A protected frame which is live while the guard changes fails its check
+//! when it returns. The seeding function is written in assembly, so that it
+//! has no canary of its own even with `-Z stack-protector=all`, and it gets
+//! the new value from a Rust function which returns before the guard
+//! changes. The other frames which are live then are origin's: its entry
+//! frame, which never returns, and the frame which calls the `.init_array`
+//! functions, which does. That one has no arrays or address-taken locals, so
+//! it isn't protected unless origin itself is compiled with
+//! `-Z stack-protector=all`.
+//!
+//! These symbols are defined with weak linkage, so that if the libc
+//! implementation also defines them, its definitions are used.
+
+use core::ffi::{c_char, c_int};
+
+/// The stack protector guard value, on architectures which use a global.
+#[no_mangle]
+#[linkage = "weak"]
+static mut __stack_chk_guard: usize = 0;
+
+/// Called when a protected frame's canary has been overwritten.
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __stack_chk_fail() -> ! {
+    crate::startup::fail(format_args!("stack smashing detected"))
+}
+
+/// Called by protected code in shared objects; the same as
+/// `__stack_chk_fail`.
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __stack_chk_fail_local() -> ! {
+    __stack_chk_fail()
+}
+
+// Seed the guard. This runs after `mustang::auxv`'s startup function.
+#[link_section = ".init_array.00001"]
+#[used]
+static INIT_GUARD: unsafe extern "C" fn(c_int, *mut *mut c_char, *mut *mut c_char) =
+    __mustang_seed_stack_guard;
+
+extern "C" {
+    fn __mustang_seed_stack_guard(argc: c_int, argv: *mut *mut c_char, envp: *mut *mut c_char);
+}
+
+/// Return the guard value to seed, from the `AT_RANDOM` bytes, or the
+/// current value if there are none.
+#[inline(never)]
+extern "C" fn seed() -> usize {
+    let Some(random) = crate::auxv::random() else {
+        return guard();
+    };
+    // Like glibc, make the first byte of the guard zero, so that string
+    // functions can't read it out or write it back. All mustang targets are
+    // little-endian.
+    // SAFETY: There are 16 bytes, which is more than a `usize`.
+    usize::from_le(unsafe { random.as_ptr().cast::<usize>().read_unaligned() }) & !0xff
+}
+
+// `__mustang_seed_stack_guard` calls `seed`, and stores its result in the
+// guard.
+#[cfg(target_arch = "x86_64")]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_seed_stack_guard,\"ax\",@progbits",
+    ".hidden __mustang_seed_stack_guard",
+    ".p2align 4",
+    ".type __mustang_seed_stack_guard, @function",
+    "__mustang_seed_stack_guard:",
+    ".cfi_startproc",
+    "push rax",
+    ".cfi_adjust_cfa_offset 8",
+    "call {seed}",
+    "mov qword ptr fs:0x28, rax",
+    "pop rcx",
+    ".cfi_adjust_cfa_offset -8",
+    "ret",
+    ".cfi_endproc",
+    ".size __mustang_seed_stack_guard, .-__mustang_seed_stack_guard",
+    ".popsection",
+    seed = sym seed,
+);
+
+#[cfg(target_arch = "x86")]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_seed_stack_guard,\"ax\",@progbits",
+    ".hidden __mustang_seed_stack_guard",
+    ".p2align 4",
+    ".type __mustang_seed_stack_guard, @function",
+    "__mustang_seed_stack_guard:",
+    ".cfi_startproc",
+    "sub esp, 12",
+    ".cfi_adjust_cfa_offset 12",
+    "call {seed}",
+    "mov dword ptr gs:0x14, eax",
+    "add esp, 12",
+    ".cfi_adjust_cfa_offset -12",
+    "ret",
+    ".cfi_endproc",
+    ".size __mustang_seed_stack_guard, .-__mustang_seed_stack_guard",
+    ".popsection",
+    seed = sym seed,
+);
+
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_seed_stack_guard,\"ax\",@progbits",
+    ".hidden __mustang_seed_stack_guard",
+    ".p2align 2",
+    ".type __mustang_seed_stack_guard, @function",
+    "__mustang_seed_stack_guard:",
+    ".cfi_startproc",
+    "stp x29, x30, [sp, #-16]!",
+    ".cfi_def_cfa_offset 16",
+    ".cfi_offset x29, -16",
+    ".cfi_offset x30, -8",
+    "mov x29, sp",
+    "bl {seed}",
+    "adrp x1, {guard}",
+    "str x0, [x1, :lo12:{guard}]",
+    "ldp x29, x30, [sp], #16",
+    ".cfi_def_cfa_offset 0",
+    ".cfi_restore x29",
+    ".cfi_restore x30",
+    "ret",
+    ".cfi_endproc",
+    ".size __mustang_seed_stack_guard, .-__mustang_seed_stack_guard",
+    ".popsection",
+    seed = sym seed,
+    guard = sym __stack_chk_guard,
+);
+
+#[cfg(target_arch = "riscv64")]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_seed_stack_guard,\"ax\",@progbits",
+    ".hidden __mustang_seed_stack_guard",
+    ".p2align 2",
+    ".type __mustang_seed_stack_guard, @function",
+    "__mustang_seed_stack_guard:",
+    ".cfi_startproc",
+    "addi sp, sp, -16",
+    ".cfi_adjust_cfa_offset 16",
+    "sd ra, 8(sp)",
+    ".cfi_rel_offset ra, 8",
+    "call {seed}",
+    "lla t0, {guard}",
+    "sd a0, 0(t0)",
+    "ld ra, 8(sp)",
+    ".cfi_restore ra",
+    "addi sp, sp, 16",
+    ".cfi_adjust_cfa_offset -16",
+    "ret",
+    ".cfi_endproc",
+    ".size __mustang_seed_stack_guard, .-__mustang_seed_stack_guard",
+    ".popsection",
+    seed = sym seed,
+    guard = sym __stack_chk_guard,
+);
+
+// In ARM mode, `pc` reads as the address of the current instruction plus 8.
+#[cfg(target_arch = "arm")]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_seed_stack_guard,\"ax\",%progbits",
+    ".hidden __mustang_seed_stack_guard",
+    ".p2align 2",
+    ".type __mustang_seed_stack_guard, %function",
+    "__mustang_seed_stack_guard:",
+    ".cfi_startproc",
+    "push {{r4, lr}}",
+    ".cfi_adjust_cfa_offset 8",
+    ".cfi_rel_offset r4, 0",
+    ".cfi_rel_offset lr, 4",
+    "bl {seed}",
+    "ldr r1, 1f",
+    "2:",
+    "add r1, pc, r1",
+    "str r0, [r1]",
+    "pop {{r4, pc}}",
+    "1:",
+    ".word {guard} - (2b + 8)",
+    ".cfi_endproc",
+    ".size __mustang_seed_stack_guard, .-__mustang_seed_stack_guard",
+    ".popsection",
+    seed = sym seed,
+    guard = sym __stack_chk_guard,
+);
+
+/// Return the current thread's guard value.
+///
+/// This is mainly useful for testing.
+pub fn guard() -> usize {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: Origin's TCB has a canary at this offset.
+    unsafe {
+        let guard: usize;
+        core::arch::asm!(
+            "mov {}, qword ptr fs:0x28",
+            out(reg) guard,
+            options(nostack, preserves_flags, readonly)
+        );
+        guard
+    }
+    #[cfg(target_arch = "x86")]
+    // SAFETY: Origin's TCB has a canary at this offset.
+    unsafe {
+        let guard: usize;
+        core::arch::asm!(
+            "mov {}, dword ptr gs:0x14",
+            out(reg) guard,
+            options(nostack, preserves_flags, readonly)
+        );
+        guard
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    // SAFETY: The guard is only written during startup.
+    unsafe {
+        core::ptr::addr_of!(__stack_chk_guard).read()
+    }
+}
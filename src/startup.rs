@@ -0,0 +1,24 @@
+//! Utilities for code that runs during program startup.
+
+use core::fmt;
+
+/// Report a failure in processing startup state, such as a malformed
+/// environment variable, and abort.
+///
+/// This runs before `main`, and possibly before `std` is initialized, so it
+/// writes directly to stderr.
+pub(crate) fn fail(args: fmt::Arguments<'_>) -> ! {
+    struct Stderr;
+    impl fmt::Write for Stderr {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            // SAFETY: We don't close stderr.
+            rustix::io::write(unsafe { rustix::stdio::stderr() }, s.as_bytes())
+                .map(|_| ())
+                .map_err(|_| fmt::Error)
+        }
+    }
+    let _ = fmt::Write::write_fmt(&mut Stderr, format_args!("mustang: {}\n", args));
+
+    // SAFETY: `abort` is always safe to call.
+    unsafe { libc::abort() }
+}
@@ -0,0 +1,809 @@
+//! Raw syscalls, for the few syscalls rustix doesn't wrap.
+//!
+//! c-scape's `syscall` function only handles the syscalls it knows about, so
+//! we make these directly. None of the syscalls we need take more than five
+//! arguments, which keeps the 32-bit x86 sequence simple.
+
+#![allow(dead_code)]
+
+use core::arch::asm;
+use core::ffi::{c_int, c_void};
+use rustix::io;
+
+/// Convert a raw syscall return value into a `Result`.
+#[inline]
+pub(crate) fn check(ret: usize) -> io::Result<usize> {
+    // Values in `-4095..0` are negated errno values.
+    if ret > -4096_isize as usize {
+        Err(io::Errno::from_raw_os_error(-(ret as isize) as i32))
+    } else {
+        Ok(ret)
+    }
+}
+
+#[inline]
+pub(crate) unsafe fn syscall0(nr: u32) -> io::Result<usize> {
+    syscall5(nr, 0, 0, 0, 0, 0)
+}
+
+#[inline]
+pub(crate) unsafe fn syscall1(nr: u32, a0: usize) -> io::Result<usize> {
+    syscall5(nr, a0, 0, 0, 0, 0)
+}
+
+#[inline]
+pub(crate) unsafe fn syscall2(nr: u32, a0: usize, a1: usize) -> io::Result<usize> {
+    syscall5(nr, a0, a1, 0, 0, 0)
+}
+
+#[inline]
+pub(crate) unsafe fn syscall3(nr: u32, a0: usize, a1: usize, a2: usize) -> io::Result<usize> {
+    syscall5(nr, a0, a1, a2, 0, 0)
+}
+
+#[inline]
+pub(crate) unsafe fn syscall4(
+    nr: u32,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+) -> io::Result<usize> {
+    syscall5(nr, a0, a1, a2, a3, 0)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+pub(crate) unsafe fn syscall5(
+    nr: u32,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+) -> io::Result<usize> {
+    let ret: usize;
+    asm!(
+        "syscall",
+        inlateout("rax") nr as usize => ret,
+        in("rdi") a0,
+        in("rsi") a1,
+        in("rdx") a2,
+        in("r10") a3,
+        in("r8") a4,
+        lateout("rcx") _,
+        lateout("r11") _,
+        options(nostack, preserves_flags)
+    );
+    check(ret)
+}
+
+#[cfg(target_arch = "x86")]
+#[inline]
+pub(crate) unsafe fn syscall5(
+    nr: u32,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+) -> io::Result<usize> {
+    // LLVM reserves `esi`, so swap the fourth argument in and out of it. We
+    // use `int 0x80` rather than the vDSO's `__kernel_vsyscall`, as these
+    // syscalls aren't performance-sensitive.
+    let ret: usize;
+    asm!(
+        "xchg esi, {a3}",
+        "int 0x80",
+        "xchg esi, {a3}",
+        a3 = in(reg) a3,
+        inlateout("eax") nr as usize => ret,
+        in("ebx") a0,
+        in("ecx") a1,
+        in("edx") a2,
+        in("edi") a4,
+        options(preserves_flags)
+    );
+    check(ret)
+}
+
+#[cfg(target_arch = "aarch64")]
+#[inline]
+pub(crate) unsafe fn syscall5(
+    nr: u32,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+) -> io::Result<usize> {
+    let ret: usize;
+    asm!(
+        "svc 0",
+        in("x8") nr as usize,
+        inlateout("x0") a0 => ret,
+        in("x1") a1,
+        in("x2") a2,
+        in("x3") a3,
+        in("x4") a4,
+        options(nostack, preserves_flags)
+    );
+    check(ret)
+}
+
+#[cfg(target_arch = "riscv64")]
+#[inline]
+pub(crate) unsafe fn syscall5(
+    nr: u32,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+) -> io::Result<usize> {
+    let ret: usize;
+    asm!(
+        "ecall",
+        in("a7") nr as usize,
+        inlateout("a0") a0 => ret,
+        in("a1") a1,
+        in("a2") a2,
+        in("a3") a3,
+        in("a4") a4,
+        options(nostack, preserves_flags)
+    );
+    check(ret)
+}
+
+#[cfg(target_arch = "arm")]
+#[inline]
+pub(crate) unsafe fn syscall5(
+    nr: u32,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+) -> io::Result<usize> {
+    let ret: usize;
+    asm!(
+        "svc 0",
+        in("r7") nr as usize,
+        inlateout("r0") a0 => ret,
+        in("r1") a1,
+        in("r2") a2,
+        in("r3") a3,
+        in("r4") a4,
+        options(nostack, preserves_flags)
+    );
+    check(ret)
+}
+
+/// Start a child process which shares our memory, and run `func(arg)` in it
+/// on the stack ending at `stack`, which must be 16-byte aligned. The calling
+/// thread is suspended until the child execs or exits, and this returns the
+/// child's pid. If `pidfd` isn't null, a pidfd for the child is stored
+/// there.
+#[cfg(target_arch = "x86_64")]
+pub(crate) unsafe fn clone_vfork(
+    stack: *mut u8,
+    func: unsafe extern "C" fn(*mut c_void) -> !,
+    arg: *mut c_void,
+    pidfd: *mut c_int,
+) -> io::Result<usize> {
+    let flags = clone_flags(pidfd);
+    let ret: usize;
+    asm!(
+        "syscall",
+        "test rax, rax",
+        "jnz 2f",
+        // In the child, on the new stack.
+        "xor ebp, ebp",
+        "mov rdi, r13",
+        "call r12",
+        "ud2",
+        "2:",
+        inlateout("rax") nr::CLONE as usize => ret,
+        in("rdi") flags,
+        in("rsi") stack,
+        in("rdx") pidfd,
+        in("r10") 0,
+        in("r8") 0,
+        in("r12") func,
+        in("r13") arg,
+        lateout("rcx") _,
+        lateout("r11") _,
+    );
+    check(ret)
+}
+
+#[cfg(target_arch = "x86")]
+pub(crate) unsafe fn clone_vfork(
+    stack: *mut u8,
+    func: unsafe extern "C" fn(*mut c_void) -> !,
+    arg: *mut c_void,
+    pidfd: *mut c_int,
+) -> io::Result<usize> {
+    let flags = clone_flags(pidfd);
+    // There aren't enough registers, so pass `func` and `arg` to the child on
+    // its stack.
+    let stack = stack.sub(16).cast::<usize>();
+    stack.write(func as usize);
+    stack.add(1).write(arg as usize);
+    let ret: usize;
+    asm!(
+        "xchg esi, {tls}",
+        "int 0x80",
+        "test eax, eax",
+        "jnz 2f",
+        // In the child, on the new stack.
+        "xor ebp, ebp",
+        "mov eax, [esp]",
+        "mov edx, [esp + 4]",
+        "sub esp, 12",
+        "push edx",
+        "call eax",
+        "ud2",
+        "2:",
+        "xchg esi, {tls}",
+        tls = inout(reg) 0_usize => _,
+        inlateout("eax") nr::CLONE as usize => ret,
+        in("ebx") flags,
+        in("ecx") stack,
+        inlateout("edx") pidfd => _,
+        in("edi") 0,
+    );
+    check(ret)
+}
+
+#[cfg(target_arch = "aarch64")]
+pub(crate) unsafe fn clone_vfork(
+    stack: *mut u8,
+    func: unsafe extern "C" fn(*mut c_void) -> !,
+    arg: *mut c_void,
+    pidfd: *mut c_int,
+) -> io::Result<usize> {
+    let flags = clone_flags(pidfd);
+    let ret: usize;
+    asm!(
+        "svc 0",
+        "cbnz x0, 2f",
+        // In the child, on the new stack.
+        "mov x29, xzr",
+        "mov x30, xzr",
+        "mov x0, x10",
+        "blr x9",
+        "brk #0x1",
+        "2:",
+        in("x8") nr::CLONE as usize,
+        inlateout("x0") flags => ret,
+        in("x1") stack,
+        in("x2") pidfd,
+        in("x3") 0,
+        in("x4") 0,
+        in("x9") func,
+        in("x10") arg,
+    );
+    check(ret)
+}
+
+#[cfg(target_arch = "riscv64")]
+pub(crate) unsafe fn clone_vfork(
+    stack: *mut u8,
+    func: unsafe extern "C" fn(*mut c_void) -> !,
+    arg: *mut c_void,
+    pidfd: *mut c_int,
+) -> io::Result<usize> {
+    let flags = clone_flags(pidfd);
+    let ret: usize;
+    asm!(
+        "ecall",
+        "bnez a0, 2f",
+        // In the child, on the new stack.
+        "mv a0, a6",
+        "jalr a5",
+        "unimp",
+        "2:",
+        in("a7") nr::CLONE as usize,
+        inlateout("a0") flags => ret,
+        in("a1") stack,
+        in("a2") pidfd,
+        in("a3") 0,
+        in("a4") 0,
+        in("a5") func,
+        in("a6") arg,
+    );
+    check(ret)
+}
+
+#[cfg(target_arch = "arm")]
+pub(crate) unsafe fn clone_vfork(
+    stack: *mut u8,
+    func: unsafe extern "C" fn(*mut c_void) -> !,
+    arg: *mut c_void,
+    pidfd: *mut c_int,
+) -> io::Result<usize> {
+    let flags = clone_flags(pidfd);
+    let ret: usize;
+    asm!(
+        "svc 0",
+        "cmp r0, #0",
+        "bne 2f",
+        // In the child, on the new stack.
+        "mov r0, r8",
+        "blx r5",
+        ".inst 0xe7ffdefe",
+        "2:",
+        in("r7") nr::CLONE as usize,
+        inlateout("r0") flags => ret,
+        in("r1") stack,
+        in("r2") pidfd,
+        in("r3") 0,
+        in("r4") 0,
+        in("r5") func,
+        in("r8") arg,
+    );
+    check(ret)
+}
+
+fn clone_flags(pidfd: *mut c_int) -> usize {
+    const CLONE_VM: usize = 0x100;
+    const CLONE_PIDFD: usize = 0x1000;
+    const CLONE_VFORK: usize = 0x4000;
+    const SIGCHLD: usize = 17;
+
+    let flags = CLONE_VM | CLONE_VFORK | SIGCHLD;
+    if pidfd.is_null() {
+        flags
+    } else {
+        flags | CLONE_PIDFD
+    }
+}
+
+/// The kernel's `timespec`, which has a 64-bit `tv_sec` on all targets.
+#[repr(C)]
+pub(crate) struct KernelTimespec {
+    pub(crate) tv_sec: i64,
+    pub(crate) tv_nsec: i64,
+}
+
+/// The kernel's `itimerspec`, made of [`KernelTimespec`]s.
+#[repr(C)]
+pub(crate) struct KernelItimerspec {
+    pub(crate) it_interval: KernelTimespec,
+    pub(crate) it_value: KernelTimespec,
+}
+
+/// Syscall numbers. Syscalls added since Linux 5.1 have the same number on
+/// all architectures.
+pub(crate) mod nr {
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const SECCOMP: u32 = 317;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const SECCOMP: u32 = 354;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const SECCOMP: u32 = 277;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const SECCOMP: u32 = 383;
+
+    pub(crate) const LANDLOCK_CREATE_RULESET: u32 = 444;
+    pub(crate) const LANDLOCK_ADD_RULE: u32 = 445;
+    pub(crate) const LANDLOCK_RESTRICT_SELF: u32 = 446;
+    pub(crate) const PIDFD_SEND_SIGNAL: u32 = 424;
+    pub(crate) const PIDFD_OPEN: u32 = 434;
+    pub(crate) const EPOLL_PWAIT2: u32 = 441;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const WAITID: u32 = 247;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const WAITID: u32 = 284;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const WAITID: u32 = 95;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const WAITID: u32 = 280;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const SET_ROBUST_LIST: u32 = 273;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const SET_ROBUST_LIST: u32 = 311;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const SET_ROBUST_LIST: u32 = 99;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const SET_ROBUST_LIST: u32 = 338;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const SIGNALFD4: u32 = 289;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const SIGNALFD4: u32 = 327;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const SIGNALFD4: u32 = 74;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const SIGNALFD4: u32 = 355;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const RT_SIGQUEUEINFO: u32 = 129;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const RT_SIGQUEUEINFO: u32 = 178;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const RT_SIGQUEUEINFO: u32 = 138;
+
+    // On 32-bit targets, this is `rt_sigtimedwait_time64`, so that it takes
+    // a 64-bit `timespec` everywhere.
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const RT_SIGTIMEDWAIT: u32 = 128;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const RT_SIGTIMEDWAIT: u32 = 421;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const RT_SIGTIMEDWAIT: u32 = 137;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const RT_TGSIGQUEUEINFO: u32 = 297;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const RT_TGSIGQUEUEINFO: u32 = 335;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const RT_TGSIGQUEUEINFO: u32 = 240;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const RT_TGSIGQUEUEINFO: u32 = 363;
+
+    // `SCHED_SETSCHEDULER` is below, with the syscalls `posix_spawn` makes.
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const SCHED_GETSCHEDULER: u32 = 145;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const SCHED_GETSCHEDULER: u32 = 157;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const SCHED_GETSCHEDULER: u32 = 120;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const SCHED_GETPARAM: u32 = 143;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const SCHED_GETPARAM: u32 = 155;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const SCHED_GETPARAM: u32 = 121;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const SCHED_SETAFFINITY: u32 = 203;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const SCHED_SETAFFINITY: u32 = 241;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const SCHED_SETAFFINITY: u32 = 122;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const SCHED_GETAFFINITY: u32 = 204;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const SCHED_GETAFFINITY: u32 = 242;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const SCHED_GETAFFINITY: u32 = 123;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const SCHED_SETATTR: u32 = 314;
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const SCHED_GETATTR: u32 = 315;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const SCHED_SETATTR: u32 = 351;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const SCHED_GETATTR: u32 = 352;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const SCHED_SETATTR: u32 = 274;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const SCHED_GETATTR: u32 = 275;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const SCHED_SETATTR: u32 = 380;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const SCHED_GETATTR: u32 = 381;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const TIMER_CREATE: u32 = 222;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const TIMER_CREATE: u32 = 259;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const TIMER_CREATE: u32 = 107;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const TIMER_CREATE: u32 = 257;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const TIMER_GETOVERRUN: u32 = 225;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const TIMER_GETOVERRUN: u32 = 262;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const TIMER_GETOVERRUN: u32 = 109;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const TIMER_GETOVERRUN: u32 = 260;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const TIMER_DELETE: u32 = 226;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const TIMER_DELETE: u32 = 263;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const TIMER_DELETE: u32 = 111;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const TIMER_DELETE: u32 = 261;
+
+    // On 32-bit targets, these are the `_time64` variants.
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const TIMER_SETTIME: u32 = 223;
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const TIMER_GETTIME: u32 = 224;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const TIMER_SETTIME: u32 = 409;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const TIMER_GETTIME: u32 = 408;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const TIMER_SETTIME: u32 = 110;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const TIMER_GETTIME: u32 = 108;
+
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const GETRANDOM: u32 = 318;
+    #[cfg(target_arch = "x86")]
+    pub(crate) const GETRANDOM: u32 = 355;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const GETRANDOM: u32 = 278;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const GETRANDOM: u32 = 384;
+
+    // On 32-bit targets, these are the `_time64` variants.
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const CLOCK_GETTIME: u32 = 228;
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const CLOCK_NANOSLEEP: u32 = 230;
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) const PPOLL: u32 = 271;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const CLOCK_GETTIME: u32 = 403;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const CLOCK_NANOSLEEP: u32 = 407;
+    #[cfg(any(target_arch = "x86", target_arch = "arm"))]
+    pub(crate) const PPOLL: u32 = 414;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const CLOCK_GETTIME: u32 = 113;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const CLOCK_NANOSLEEP: u32 = 115;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) const PPOLL: u32 = 73;
+
+    // rustix uses the `ppoll` which takes a 32-bit `timespec` on 32-bit
+    // targets when the timeout fits.
+    #[cfg(target_arch = "x86")]
+    pub(crate) const PPOLL_TIME32: u32 = 309;
+    #[cfg(target_arch = "arm")]
+    pub(crate) const PPOLL_TIME32: u32 = 336;
+
+    // The syscalls `posix_spawn`'s child makes. It shares the parent's
+    // memory, so it can't use anything which sets `errno`.
+    #[cfg(target_arch = "arm")]
+    pub(crate) use self::arm::*;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    pub(crate) use self::generic::*;
+    #[cfg(target_arch = "x86")]
+    pub(crate) use self::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) use self::x86_64::*;
+
+    #[cfg(target_arch = "x86_64")]
+    mod x86_64 {
+        pub(crate) const CLOSE: u32 = 3;
+        pub(crate) const RT_SIGACTION: u32 = 13;
+        pub(crate) const RT_SIGPROCMASK: u32 = 14;
+        pub(crate) const CLONE: u32 = 56;
+        pub(crate) const EXECVE: u32 = 59;
+        pub(crate) const FCNTL: u32 = 72;
+        pub(crate) const CHDIR: u32 = 80;
+        pub(crate) const FCHDIR: u32 = 81;
+        pub(crate) const GETUID: u32 = 102;
+        pub(crate) const GETGID: u32 = 104;
+        pub(crate) const SETUID: u32 = 105;
+        pub(crate) const SETGID: u32 = 106;
+        pub(crate) const SETPGID: u32 = 109;
+        pub(crate) const SETSID: u32 = 112;
+        pub(crate) const SCHED_SETPARAM: u32 = 142;
+        pub(crate) const SCHED_SETSCHEDULER: u32 = 144;
+        pub(crate) const EXIT_GROUP: u32 = 231;
+        pub(crate) const OPENAT: u32 = 257;
+        pub(crate) const DUP3: u32 = 292;
+    }
+
+    #[cfg(target_arch = "x86")]
+    mod x86 {
+        pub(crate) const CLOSE: u32 = 6;
+        pub(crate) const EXECVE: u32 = 11;
+        pub(crate) const CHDIR: u32 = 12;
+        pub(crate) const SETPGID: u32 = 57;
+        pub(crate) const SETSID: u32 = 66;
+        pub(crate) const CLONE: u32 = 120;
+        pub(crate) const FCHDIR: u32 = 133;
+        pub(crate) const SCHED_SETPARAM: u32 = 154;
+        pub(crate) const SCHED_SETSCHEDULER: u32 = 156;
+        pub(crate) const RT_SIGACTION: u32 = 174;
+        pub(crate) const RT_SIGPROCMASK: u32 = 175;
+        pub(crate) const GETUID: u32 = 199;
+        pub(crate) const GETGID: u32 = 200;
+        pub(crate) const SETUID: u32 = 213;
+        pub(crate) const SETGID: u32 = 214;
+        pub(crate) const FCNTL: u32 = 221;
+        pub(crate) const EXIT_GROUP: u32 = 252;
+        pub(crate) const OPENAT: u32 = 295;
+        pub(crate) const DUP3: u32 = 330;
+    }
+
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    mod generic {
+        pub(crate) const DUP3: u32 = 24;
+        pub(crate) const FCNTL: u32 = 25;
+        pub(crate) const CHDIR: u32 = 49;
+        pub(crate) const FCHDIR: u32 = 50;
+        pub(crate) const OPENAT: u32 = 56;
+        pub(crate) const CLOSE: u32 = 57;
+        pub(crate) const EXIT_GROUP: u32 = 94;
+        pub(crate) const SCHED_SETPARAM: u32 = 118;
+        pub(crate) const SCHED_SETSCHEDULER: u32 = 119;
+        pub(crate) const RT_SIGACTION: u32 = 134;
+        pub(crate) const RT_SIGPROCMASK: u32 = 135;
+        pub(crate) const SETGID: u32 = 144;
+        pub(crate) const SETUID: u32 = 146;
+        pub(crate) const SETPGID: u32 = 154;
+        pub(crate) const SETSID: u32 = 157;
+        pub(crate) const GETUID: u32 = 174;
+        pub(crate) const GETGID: u32 = 176;
+        pub(crate) const CLONE: u32 = 220;
+        pub(crate) const EXECVE: u32 = 221;
+    }
+
+    #[cfg(target_arch = "arm")]
+    mod arm {
+        pub(crate) const CLOSE: u32 = 6;
+        pub(crate) const EXECVE: u32 = 11;
+        pub(crate) const CHDIR: u32 = 12;
+        pub(crate) const SETPGID: u32 = 57;
+        pub(crate) const SETSID: u32 = 66;
+        pub(crate) const CLONE: u32 = 120;
+        pub(crate) const FCHDIR: u32 = 133;
+        pub(crate) const SCHED_SETPARAM: u32 = 154;
+        pub(crate) const SCHED_SETSCHEDULER: u32 = 156;
+        pub(crate) const RT_SIGACTION: u32 = 174;
+        pub(crate) const RT_SIGPROCMASK: u32 = 175;
+        pub(crate) const GETUID: u32 = 199;
+        pub(crate) const GETGID: u32 = 200;
+        pub(crate) const SETUID: u32 = 213;
+        pub(crate) const SETGID: u32 = 214;
+        pub(crate) const FCNTL: u32 = 221;
+        pub(crate) const EXIT_GROUP: u32 = 248;
+        pub(crate) const OPENAT: u32 = 322;
+        pub(crate) const DUP3: u32 = 358;
+    }
+
+    // The syscalls `mustang::replay` records, and the cancellation points
+    // make. On x86, rustix makes the socket syscalls through `socketcall`,
+    // and `SOCKET` is only used to create placeholders.
+    #[cfg(all(
+        target_arch = "arm",
+        any(feature = "cancellation-points", feature = "record-replay")
+    ))]
+    pub(crate) use self::io_arm::*;
+    #[cfg(all(
+        any(target_arch = "aarch64", target_arch = "riscv64"),
+        any(feature = "cancellation-points", feature = "record-replay")
+    ))]
+    pub(crate) use self::io_generic::*;
+    #[cfg(all(
+        target_arch = "x86",
+        any(feature = "cancellation-points", feature = "record-replay")
+    ))]
+    pub(crate) use self::io_x86::*;
+    #[cfg(all(
+        target_arch = "x86_64",
+        any(feature = "cancellation-points", feature = "record-replay")
+    ))]
+    pub(crate) use self::io_x86_64::*;
+
+    #[cfg(target_arch = "x86_64")]
+    mod io_x86_64 {
+        pub(crate) const READ: u32 = 0;
+        pub(crate) const WRITE: u32 = 1;
+        pub(crate) const PREAD64: u32 = 17;
+        pub(crate) const READV: u32 = 19;
+        pub(crate) const WRITEV: u32 = 20;
+        pub(crate) const SOCKET: u32 = 41;
+        pub(crate) const CONNECT: u32 = 42;
+        pub(crate) const ACCEPT: u32 = 43;
+        pub(crate) const SENDTO: u32 = 44;
+        pub(crate) const RECVFROM: u32 = 45;
+        pub(crate) const SENDMSG: u32 = 46;
+        pub(crate) const RECVMSG: u32 = 47;
+        pub(crate) const SHUTDOWN: u32 = 48;
+        pub(crate) const BIND: u32 = 49;
+        pub(crate) const LISTEN: u32 = 50;
+        pub(crate) const GETSOCKNAME: u32 = 51;
+        pub(crate) const GETPEERNAME: u32 = 52;
+        pub(crate) const SOCKETPAIR: u32 = 53;
+        pub(crate) const SETSOCKOPT: u32 = 54;
+        pub(crate) const GETSOCKOPT: u32 = 55;
+        pub(crate) const WAIT4: u32 = 61;
+        pub(crate) const FSYNC: u32 = 74;
+        pub(crate) const FDATASYNC: u32 = 75;
+        pub(crate) const EPOLL_CTL: u32 = 233;
+        pub(crate) const EPOLL_PWAIT: u32 = 281;
+        pub(crate) const ACCEPT4: u32 = 288;
+        pub(crate) const PREADV: u32 = 295;
+    }
+
+    #[cfg(target_arch = "x86")]
+    mod io_x86 {
+        pub(crate) const READ: u32 = 3;
+        pub(crate) const WRITE: u32 = 4;
+        pub(crate) const SOCKETCALL: u32 = 102;
+        pub(crate) const WAIT4: u32 = 114;
+        pub(crate) const FSYNC: u32 = 118;
+        pub(crate) const READV: u32 = 145;
+        pub(crate) const WRITEV: u32 = 146;
+        pub(crate) const FDATASYNC: u32 = 148;
+        pub(crate) const PREAD64: u32 = 180;
+        pub(crate) const EPOLL_CTL: u32 = 255;
+        pub(crate) const EPOLL_PWAIT: u32 = 319;
+        pub(crate) const PREADV: u32 = 333;
+        pub(crate) const SOCKET: u32 = 359;
+    }
+
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    mod io_generic {
+        pub(crate) const EPOLL_CTL: u32 = 21;
+        pub(crate) const EPOLL_PWAIT: u32 = 22;
+        pub(crate) const READ: u32 = 63;
+        pub(crate) const WRITE: u32 = 64;
+        pub(crate) const READV: u32 = 65;
+        pub(crate) const WRITEV: u32 = 66;
+        pub(crate) const PREAD64: u32 = 67;
+        pub(crate) const PREADV: u32 = 69;
+        pub(crate) const FSYNC: u32 = 82;
+        pub(crate) const FDATASYNC: u32 = 83;
+        pub(crate) const SOCKET: u32 = 198;
+        pub(crate) const SOCKETPAIR: u32 = 199;
+        pub(crate) const BIND: u32 = 200;
+        pub(crate) const LISTEN: u32 = 201;
+        pub(crate) const ACCEPT: u32 = 202;
+        pub(crate) const CONNECT: u32 = 203;
+        pub(crate) const GETSOCKNAME: u32 = 204;
+        pub(crate) const GETPEERNAME: u32 = 205;
+        pub(crate) const SENDTO: u32 = 206;
+        pub(crate) const RECVFROM: u32 = 207;
+        pub(crate) const SETSOCKOPT: u32 = 208;
+        pub(crate) const GETSOCKOPT: u32 = 209;
+        pub(crate) const SHUTDOWN: u32 = 210;
+        pub(crate) const SENDMSG: u32 = 211;
+        pub(crate) const RECVMSG: u32 = 212;
+        pub(crate) const ACCEPT4: u32 = 242;
+        pub(crate) const WAIT4: u32 = 260;
+    }
+
+    #[cfg(target_arch = "arm")]
+    mod io_arm {
+        pub(crate) const READ: u32 = 3;
+        pub(crate) const WRITE: u32 = 4;
+        pub(crate) const WAIT4: u32 = 114;
+        pub(crate) const FSYNC: u32 = 118;
+        pub(crate) const READV: u32 = 145;
+        pub(crate) const WRITEV: u32 = 146;
+        pub(crate) const FDATASYNC: u32 = 148;
+        pub(crate) const PREAD64: u32 = 180;
+        pub(crate) const EPOLL_CTL: u32 = 251;
+        pub(crate) const SOCKET: u32 = 281;
+        pub(crate) const BIND: u32 = 282;
+        pub(crate) const CONNECT: u32 = 283;
+        pub(crate) const LISTEN: u32 = 284;
+        pub(crate) const ACCEPT: u32 = 285;
+        pub(crate) const GETSOCKNAME: u32 = 286;
+        pub(crate) const GETPEERNAME: u32 = 287;
+        pub(crate) const SOCKETPAIR: u32 = 288;
+        pub(crate) const SEND: u32 = 289;
+        pub(crate) const SENDTO: u32 = 290;
+        pub(crate) const RECV: u32 = 291;
+        pub(crate) const RECVFROM: u32 = 292;
+        pub(crate) const SHUTDOWN: u32 = 293;
+        pub(crate) const SETSOCKOPT: u32 = 294;
+        pub(crate) const GETSOCKOPT: u32 = 295;
+        pub(crate) const SENDMSG: u32 = 296;
+        pub(crate) const RECVMSG: u32 = 297;
+        pub(crate) const EPOLL_PWAIT: u32 = 346;
+        pub(crate) const PREADV: u32 = 361;
+        pub(crate) const ACCEPT4: u32 = 366;
+    }
+}
@@ -0,0 +1,512 @@
+//! Process-wide defaults for thread stack and guard sizes, and naming,
+//! affinity and scheduling of other threads.
+//!
+//! Threads created with `pthread_create` and a null or freshly initialized
+//! `pthread_attr_t` get the default stack size and guard size, whether
+//! they're created by C code, by Rust code with `libc::pthread_create`, or
+//! by `std::thread`. The defaults can be set in three ways, with later ones
+//! taking precedence:
+//!
+//!  - an option to `can_run_this!`, for example
+//!    `mustang::can_run_this!(thread_stack_size = 1 << 20)`,
+//!  - the `MUSTANG_THREAD_STACK_SIZE` and `MUSTANG_THREAD_GUARD_SIZE`
+//!    environment variables, read at startup, and
+//!  - at runtime, [`set_default_stack_size`] and [`set_default_guard_size`],
+//!    or `pthread_setattr_default_np` from C.
+//!
+//! The environment variables take a number of bytes with an optional `K`,
+//! `M` or `G` suffix. The guard size may also be `huge`, which selects
+//! [`HUGE_GUARD_SIZE`], so that runaway recursion faults on the guard rather
+//! than skipping over it into other memory. An invalid value aborts the
+//! program at startup.
+//!
+//! `std::thread::spawn` sets its own stack size, from
+//! `std::thread::Builder::stack_size` or the `RUST_MIN_STACK` environment
+//! variable. If a default stack size is set by the time the environment
+//! variables are read at startup, and `RUST_MIN_STACK` isn't set, mustang
+//! sets `RUST_MIN_STACK` to it, so that std uses it too. std only reads
+//! `RUST_MIN_STACK` once, so defaults set at runtime only affect its guard
+//! size.
+//!
+//! c-scape defines `pthread_attr_init` and `pthread_create` with origin's
+//! defaults, and its definitions take precedence over weak ones, so the
+//! implementations here are named `__mustang_pthread_attr_init` and so on,
+//! and this module binds the standard names to them with `--defsym`, as the
+//! mutex module does. `__mustang_pthread_create` creates threads with
+//! origin, as c-scape's does.
+//!
+//! `pthread_setname_np`, `pthread_getname_np`, `pthread_setaffinity_np`,
+//! `pthread_getaffinity_np`, `pthread_setschedparam` and
+//! `pthread_getschedparam` act on the kernel thread behind a `pthread_t`,
+//! which is a pointer to origin's thread, which records its kernel thread
+//! id. That works for every thread, including ones spawned by std; threads
+//! which have exited fail with `ESRCH`. Names are set with `PR_SET_NAME` for
+//! the calling thread, and by writing `/proc/self/task/<tid>/comm` for
+//! others, and are what `top` and `ps` show. c-scape defines some of these
+//! functions too, so they're bound to the standard names in the same way.
+//! `sched_setattr` and `sched_getattr` are also provided, for setting
+//! policies such as `SCHED_DEADLINE` which need more than a `sched_param`.
+
+use crate::mutex::ret;
+use crate::semaphore::{result, Path};
+use crate::syscall::{nr, syscall1, syscall2, syscall3, syscall4};
+use core::ffi::{c_char, c_int, c_void, CStr};
+use core::fmt::Write;
+use core::mem::{transmute, zeroed};
+use core::ptr::{null_mut, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use rustix::fd::OwnedFd;
+use rustix::fs::{open, Mode, OFlags};
+use rustix::io::{self, Errno};
+
+/// The name of the environment variable that sets the default stack size.
+pub const STACK_SIZE_ENV: &str = "MUSTANG_THREAD_STACK_SIZE";
+
+/// The name of the environment variable that sets the default guard size.
+pub const GUARD_SIZE_ENV: &str = "MUSTANG_THREAD_GUARD_SIZE";
+
+/// The guard size selected by `huge`.
+///
+/// This is large enough that a single stack frame is very unlikely to jump
+/// past it, while only reserving address space, not memory.
+#[cfg(target_pointer_width = "64")]
+pub const HUGE_GUARD_SIZE: usize = 256 << 20;
+
+/// The guard size selected by `huge`.
+///
+/// This is large enough that a single stack frame is very unlikely to jump
+/// past it, while only reserving address space, not memory.
+#[cfg(target_pointer_width = "32")]
+pub const HUGE_GUARD_SIZE: usize = 16 << 20;
+
+/// The value of `STACK_SIZE` and `GUARD_SIZE` when no default has been set,
+/// and the threading library's own default applies.
+const UNSET: usize = usize::MAX;
+
+static STACK_SIZE: AtomicUsize = AtomicUsize::new(UNSET);
+static GUARD_SIZE: AtomicUsize = AtomicUsize::new(UNSET);
+
+/// Set the default stack size for new threads.
+///
+/// This fails with `EINVAL` if `size` is less than `PTHREAD_STACK_MIN`.
+pub fn set_default_stack_size(size: usize) -> io::Result<()> {
+    if size < libc::PTHREAD_STACK_MIN {
+        return Err(Errno::INVAL);
+    }
+    STACK_SIZE.store(size, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Set the default guard size for new threads, rounded up to a multiple of
+/// the page size. Zero means no guard.
+///
+/// This fails with `EINVAL` if rounding up overflows.
+pub fn set_default_guard_size(size: usize) -> io::Result<()> {
+    let page_size = rustix::param::page_size();
+    let size = size
+        .checked_next_multiple_of(page_size)
+        .ok_or(Errno::INVAL)?;
+    GUARD_SIZE.store(size, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Return the default stack size for new threads.
+pub fn default_stack_size() -> usize {
+    match STACK_SIZE.load(Ordering::Relaxed) {
+        UNSET => origin::thread::default_stack_size(),
+        size => size,
+    }
+}
+
+/// Return the default guard size for new threads.
+pub fn default_guard_size() -> usize {
+    match GUARD_SIZE.load(Ordering::Relaxed) {
+        UNSET => origin::thread::default_guard_size(),
+        size => size,
+    }
+}
+
+/// Parse a size with an optional `K`, `M` or `G` suffix.
+fn parse_size(value: &str) -> Option<usize> {
+    let (digits, shift) = match value.as_bytes().last()? {
+        b'k' | b'K' => (&value[..value.len() - 1], 10),
+        b'm' | b'M' => (&value[..value.len() - 1], 20),
+        b'g' | b'G' => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+    let size = digits.parse::<usize>().ok()?;
+    size.checked_mul(1 << shift)
+}
+
+/// Options for `can_run_this!`.
+#[doc(hidden)]
+pub mod __can_run_this {
+    use crate::startup::fail;
+
+    pub fn thread_stack_size(size: usize) {
+        if super::set_default_stack_size(size).is_err() {
+            fail(format_args!(
+                "thread_stack_size {} is less than PTHREAD_STACK_MIN",
+                size
+            ));
+        }
+    }
+
+    pub fn thread_guard_size(size: usize) {
+        if super::set_default_guard_size(size).is_err() {
+            fail(format_args!("invalid thread_guard_size {}", size));
+        }
+    }
+}
+
+mod startup {
+    use super::{parse_size, GUARD_SIZE_ENV, HUGE_GUARD_SIZE, STACK_SIZE_ENV};
+    use crate::semaphore::Path;
+    use crate::startup::fail;
+    use core::ffi::{c_char, c_int, CStr};
+    use core::fmt::Write;
+    use core::sync::atomic::Ordering;
+
+    // This runs after `mustang::args` has installed a structured
+    // environment, if there is one, and after the `can_run_this!` options,
+    // which the environment variables override.
+    #[link_section = ".init_array.00002"]
+    #[used]
+    static INIT_THREAD_DEFAULTS: unsafe extern "C" fn(c_int, *mut *mut c_char, *mut *mut c_char) = {
+        unsafe extern "C" fn function(
+            _argc: c_int,
+            _argv: *mut *mut c_char,
+            envp: *mut *mut c_char,
+        ) {
+            if let Some(value) = find_env(envp, STACK_SIZE_ENV) {
+                let size = parse_size(value);
+                if size
+                    .and_then(|size| super::set_default_stack_size(size).ok())
+                    .is_none()
+                {
+                    fail(format_args!("invalid {} {:?}", STACK_SIZE_ENV, value));
+                }
+            }
+            if let Some(value) = find_env(envp, GUARD_SIZE_ENV) {
+                let size = if value == "huge" {
+                    Some(HUGE_GUARD_SIZE)
+                } else {
+                    parse_size(value)
+                };
+                if size
+                    .and_then(|size| super::set_default_guard_size(size).ok())
+                    .is_none()
+                {
+                    fail(format_args!("invalid {} {:?}", GUARD_SIZE_ENV, value));
+                }
+            }
+
+            // Give std the default stack size, unless `RUST_MIN_STACK`
+            // already sets one.
+            if super::STACK_SIZE.load(Ordering::Relaxed) != super::UNSET {
+                let mut value = Path::new();
+                if write!(value, "{}", super::default_stack_size()).is_err()
+                    || libc::setenv(c"RUST_MIN_STACK".as_ptr(), value.as_c_str().as_ptr(), 0) != 0
+                {
+                    fail(format_args!("can't set RUST_MIN_STACK"));
+                }
+            }
+        }
+        function
+    };
+
+    /// Find the value of the environment variable `name`.
+    unsafe fn find_env(envp: *mut *mut c_char, name: &str) -> Option<&'static str> {
+        #[cfg(feature = "memfd-args")]
+        if let Some(block) = crate::args::block() {
+            return block
+                .vars()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value);
+        }
+
+        if envp.is_null() {
+            return None;
+        }
+        let mut ptr = envp;
+        while !(*ptr).is_null() {
+            let var = CStr::from_ptr(*ptr).to_bytes();
+            if let Some(value) = var
+                .strip_prefix(name.as_bytes())
+                .and_then(|rest| rest.strip_prefix(b"="))
+            {
+                return match core::str::from_utf8(value) {
+                    Ok(value) => Some(value),
+                    Err(_) => fail(format_args!("{} isn't valid UTF-8", name)),
+                };
+            }
+            ptr = ptr.add(1);
+        }
+        None
+    }
+}
+
+// Bind the standard names to the implementations here; see above.
+link_args!(
+    "-Wl,--defsym=pthread_attr_init=__mustang_pthread_attr_init",
+    "-Wl,--defsym=pthread_create=__mustang_pthread_create",
+    "-Wl,--defsym=pthread_getattr_default_np=__mustang_pthread_getattr_default_np",
+    "-Wl,--defsym=pthread_setattr_default_np=__mustang_pthread_setattr_default_np",
+);
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_attr_init(attr: *mut libc::pthread_attr_t) -> c_int {
+    // Apart from the stack and guard sizes, c-scape's freshly initialized
+    // `pthread_attr_t` is all zeros.
+    attr.write(zeroed());
+    let err = libc::pthread_attr_setstacksize(attr, default_stack_size());
+    if err != 0 {
+        return err;
+    }
+    libc::pthread_attr_setguardsize(attr, default_guard_size())
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_create(
+    thread: *mut libc::pthread_t,
+    attr: *const libc::pthread_attr_t,
+    start: extern "C" fn(*mut c_void) -> *mut c_void,
+    arg: *mut c_void,
+) -> c_int {
+    let (stack_size, guard_size) = if attr.is_null() {
+        (default_stack_size(), default_guard_size())
+    } else {
+        let mut stack_size = 0;
+        let mut guard_size = 0;
+        libc::pthread_attr_getstacksize(attr, &mut stack_size);
+        libc::pthread_attr_getguardsize(attr, &mut guard_size);
+        (stack_size, guard_size)
+    };
+    let args = [NonNull::new(start as *mut c_void), NonNull::new(arg)];
+    match origin::thread::create(run, &args, stack_size, guard_size) {
+        Ok(new) => {
+            thread.write(new.to_raw() as libc::pthread_t);
+            0
+        }
+        Err(err) => err.raw_os_error(),
+    }
+}
+
+/// Run a thread created by `__mustang_pthread_create`, whose arguments are
+/// its start routine and the routine's argument.
+unsafe fn run(args: &mut [Option<NonNull<c_void>>]) -> Option<NonNull<c_void>> {
+    // Give the thread an alternate signal stack; see `mustang::signal`. If
+    // that fails, handlers just run on the thread's own stack.
+    let _ = crate::signal::ensure_altstack();
+
+    let start: extern "C" fn(*mut c_void) -> *mut c_void = transmute(args[0].unwrap());
+    let arg = args[1].map_or(null_mut(), NonNull::as_ptr);
+    NonNull::new(start(arg))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_getattr_default_np(
+    attr: *mut libc::pthread_attr_t,
+) -> c_int {
+    __mustang_pthread_attr_init(attr)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_setattr_default_np(
+    attr: *const libc::pthread_attr_t,
+) -> c_int {
+    let mut stack_size = 0;
+    let mut guard_size = 0;
+    libc::pthread_attr_getstacksize(attr, &mut stack_size);
+    libc::pthread_attr_getguardsize(attr, &mut guard_size);
+    if stack_size < libc::PTHREAD_STACK_MIN {
+        return libc::EINVAL;
+    }
+    match set_default_guard_size(guard_size) {
+        Ok(()) => {
+            STACK_SIZE.store(stack_size, Ordering::Relaxed);
+            0
+        }
+        Err(err) => err.raw_os_error(),
+    }
+}
+
+/// Return the kernel thread id of `thread`.
+fn thread_tid(thread: libc::pthread_t) -> io::Result<u32> {
+    // SAFETY: c-scape's `pthread_t` is a pointer to origin's thread, which
+    // stays valid until the thread is joined or, if it's detached, exits.
+    let id = unsafe { origin::thread::id(origin::thread::Thread::from_raw(thread as *mut c_void)) };
+    match id {
+        Some(id) => Ok(id.as_raw_nonzero().get() as u32),
+        None => Err(Errno::SRCH),
+    }
+}
+
+// Bind the standard names to the implementations here; see above.
+link_args!(
+    "-Wl,--defsym=pthread_setname_np=__mustang_pthread_setname_np",
+    "-Wl,--defsym=pthread_getname_np=__mustang_pthread_getname_np",
+    "-Wl,--defsym=pthread_setaffinity_np=__mustang_pthread_setaffinity_np",
+    "-Wl,--defsym=pthread_getaffinity_np=__mustang_pthread_getaffinity_np",
+    "-Wl,--defsym=pthread_setschedparam=__mustang_pthread_setschedparam",
+    "-Wl,--defsym=pthread_getschedparam=__mustang_pthread_getschedparam",
+);
+
+/// The size of a thread name, including the NUL.
+const TASK_COMM_LEN: usize = 16;
+
+/// Open `/proc/self/task/<tid>/comm`, which holds a thread's name.
+fn open_comm(tid: u32, flags: OFlags) -> io::Result<OwnedFd> {
+    let mut path = Path::new();
+    write!(path, "/proc/self/task/{}/comm", tid).map_err(|_| Errno::NAMETOOLONG)?;
+    open(path.as_c_str(), flags | OFlags::CLOEXEC, Mode::empty())
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_setname_np(
+    thread: libc::pthread_t,
+    name: *const c_char,
+) -> c_int {
+    let name = CStr::from_ptr(name);
+    if name.to_bytes().len() >= TASK_COMM_LEN {
+        return libc::ERANGE;
+    }
+    if thread == libc::pthread_self() {
+        return ret(rustix::thread::set_name(name));
+    }
+    ret(thread_tid(thread).and_then(|tid| {
+        let comm = open_comm(tid, OFlags::WRONLY)?;
+        rustix::io::write(&comm, name.to_bytes())?;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_getname_np(
+    thread: libc::pthread_t,
+    buf: *mut c_char,
+    len: usize,
+) -> c_int {
+    if len < TASK_COMM_LEN {
+        return libc::ERANGE;
+    }
+    let buf = core::slice::from_raw_parts_mut(buf.cast::<u8>(), TASK_COMM_LEN);
+    ret(thread_tid(thread).and_then(|tid| {
+        let comm = open_comm(tid, OFlags::RDONLY)?;
+        let len = rustix::io::read(&comm, &mut *buf)?;
+        // The name is followed by a newline.
+        let end = buf[..len]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .unwrap_or(len.min(TASK_COMM_LEN - 1));
+        buf[end] = 0;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_setaffinity_np(
+    thread: libc::pthread_t,
+    size: usize,
+    set: *const libc::cpu_set_t,
+) -> c_int {
+    ret(thread_tid(thread).and_then(|tid| {
+        syscall3(nr::SCHED_SETAFFINITY, tid as usize, size, set as usize)?;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_getaffinity_np(
+    thread: libc::pthread_t,
+    size: usize,
+    set: *mut libc::cpu_set_t,
+) -> c_int {
+    ret(thread_tid(thread).and_then(|tid| {
+        let len = syscall3(nr::SCHED_GETAFFINITY, tid as usize, size, set as usize)?;
+        // The kernel only writes the size of its own CPU mask.
+        set.cast::<u8>().add(len).write_bytes(0, size - len);
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_setschedparam(
+    thread: libc::pthread_t,
+    policy: c_int,
+    param: *const libc::sched_param,
+) -> c_int {
+    ret(thread_tid(thread).and_then(|tid| {
+        syscall3(
+            nr::SCHED_SETSCHEDULER,
+            tid as usize,
+            policy as usize,
+            param as usize,
+        )?;
+        Ok(())
+    }))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __mustang_pthread_getschedparam(
+    thread: libc::pthread_t,
+    policy: *mut c_int,
+    param: *mut libc::sched_param,
+) -> c_int {
+    ret(thread_tid(thread).and_then(|tid| {
+        let current = syscall1(nr::SCHED_GETSCHEDULER, tid as usize)?;
+        syscall2(nr::SCHED_GETPARAM, tid as usize, param as usize)?;
+        *policy = current as c_int;
+        Ok(())
+    }))
+}
+
+// c-scape doesn't have these.
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn sched_setattr(
+    pid: libc::pid_t,
+    attr: *mut libc::sched_attr,
+    flags: libc::c_uint,
+) -> c_int {
+    result(
+        syscall3(
+            nr::SCHED_SETATTR,
+            pid as usize,
+            attr as usize,
+            flags as usize,
+        )
+        .map(drop),
+    )
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn sched_getattr(
+    pid: libc::pid_t,
+    attr: *mut libc::sched_attr,
+    size: libc::c_uint,
+    flags: libc::c_uint,
+) -> c_int {
+    result(
+        syscall4(
+            nr::SCHED_GETATTR,
+            pid as usize,
+            attr as usize,
+            size as usize,
+            flags as usize,
+        )
+        .map(drop),
+    )
+}
@@ -0,0 +1,142 @@
+//! Diagnostics for how time is obtained.
+//!
+//! Mustang's `clock_gettime` is implemented by rustix, which calls into the
+//! vDSO when the kernel provides a suitable function, and makes a syscall
+//! otherwise. `gettimeofday` and `time` are implemented in terms of
+//! `clock_gettime(CLOCK_REALTIME)`, so they take the same path as it.
+//!
+//! With the "no-vdso" feature, the vDSO is hidden from rustix at startup, so
+//! that all of these make plain syscalls. This is useful in environments such
+//! as qemu-user, where the vDSO may misbehave. The "record-replay" feature
+//! implies it, so that it can trap the syscalls; see `mustang::replay`.
+
+use crate::syscall::KernelTimespec;
+use core::ffi::{c_int, c_void, CStr};
+use rustix::time::ClockId;
+
+/// How a clock is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimePath {
+    /// The clock is read by calling into the vDSO, without entering the
+    /// kernel.
+    ///
+    /// The kernel's vDSO code may still fall back to a syscall internally if
+    /// the current clocksource can't be read from userspace.
+    Vdso,
+
+    /// The clock is read with a `clock_gettime` syscall.
+    Syscall,
+}
+
+/// The version and name of the vDSO's `clock_gettime` function, which are
+/// the same as the ones rustix looks up. On 32-bit platforms, this is the
+/// variant with a 64-bit `time_t`.
+#[cfg(target_arch = "x86_64")]
+const CLOCK_GETTIME: (&CStr, &CStr) = (c"LINUX_2.6", c"__vdso_clock_gettime");
+#[cfg(any(target_arch = "x86", target_arch = "arm"))]
+const CLOCK_GETTIME: (&CStr, &CStr) = (c"LINUX_2.6", c"__vdso_clock_gettime64");
+#[cfg(target_arch = "aarch64")]
+const CLOCK_GETTIME: (&CStr, &CStr) = (c"LINUX_2.6.39", c"__kernel_clock_gettime");
+#[cfg(target_arch = "riscv64")]
+const CLOCK_GETTIME: (&CStr, &CStr) = (c"LINUX_4.15", c"__vdso_clock_gettime");
+
+/// The vDSO's `clock_gettime`, which uses the kernel's 64-bit `timespec`.
+type ClockGettime = unsafe extern "C" fn(c_int, *mut KernelTimespec) -> c_int;
+
+/// Resolve the vDSO's `clock_gettime` the way rustix does.
+fn vdso_clock_gettime() -> Option<ClockGettime> {
+    let (version, name) = CLOCK_GETTIME;
+    let ptr = crate::vdso::lookup(version, name);
+    // SAFETY: The vDSO's `clock_gettime` has this signature.
+    (!ptr.is_null()).then(|| unsafe { core::mem::transmute::<*const c_void, ClockGettime>(ptr) })
+}
+
+/// Test whether the vDSO is in use for `clock_gettime` at all.
+///
+/// This resolves the same vDSO symbol, with the same version, that rustix
+/// does, so it's false exactly when rustix makes syscalls for every clock:
+/// if the kernel didn't provide a vDSO, if it doesn't define that symbol, or
+/// if the "no-vdso" feature is enabled.
+pub fn vdso_available() -> bool {
+    vdso_clock_gettime().is_some()
+}
+
+/// The clocks the kernel's vDSO reads in userspace. It forwards the others,
+/// such as the CPU-time and alarm clocks, to the kernel with a syscall.
+pub const VDSO_CLOCKS: [c_int; 7] = [
+    libc::CLOCK_REALTIME,
+    libc::CLOCK_MONOTONIC,
+    libc::CLOCK_BOOTTIME,
+    libc::CLOCK_TAI,
+    libc::CLOCK_REALTIME_COARSE,
+    libc::CLOCK_MONOTONIC_COARSE,
+    libc::CLOCK_MONOTONIC_RAW,
+];
+
+/// Report how `clock_gettime` reads the clock `id`.
+///
+/// rustix calls the vDSO's `clock_gettime` for every clock. The vDSO reads
+/// the clocks in [`VDSO_CLOCKS`] itself, and makes a syscall for the others,
+/// so they're reported as [`TimePath::Syscall`]. For the clocks it reads,
+/// this calls the vDSO's function and observes the result, since rustix
+/// makes a syscall itself if the function fails.
+pub fn clock_gettime_path(id: ClockId) -> TimePath {
+    let Some(clock_gettime) = vdso_clock_gettime() else {
+        return TimePath::Syscall;
+    };
+    if !VDSO_CLOCKS.contains(&(id as c_int)) {
+        return TimePath::Syscall;
+    }
+
+    let mut ts = KernelTimespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid `timespec` to write to.
+    match unsafe { clock_gettime(id as c_int, &mut ts) } {
+        0 => TimePath::Vdso,
+        _ => TimePath::Syscall,
+    }
+}
+
+/// Report how `gettimeofday` reads the time.
+#[inline]
+pub fn gettimeofday_path() -> TimePath {
+    clock_gettime_path(ClockId::Realtime)
+}
+
+/// Report how `std::time::Instant::now` reads the time.
+#[inline]
+pub fn instant_path() -> TimePath {
+    clock_gettime_path(ClockId::Monotonic)
+}
+
+/// Report how `std::time::SystemTime::now` reads the time.
+#[inline]
+pub fn system_time_path() -> TimePath {
+    clock_gettime_path(ClockId::Realtime)
+}
+
+/// Hide the vDSO from rustix, so that it makes syscalls instead.
+///
+/// rustix looks up the vDSO lazily, the first time it needs it, so as long as
+/// nothing has read the time yet, replacing the `AT_SYSINFO_EHDR` value in
+/// the kernel-provided auxv with null and having rustix reread the auxv is
+/// enough.
+///
+/// # Safety
+///
+/// `envp` must be the kernel-provided environment pointer, and `auxv` the
+/// auxv that follows it.
+#[cfg(feature = "no-vdso")]
+pub(crate) unsafe fn disable_vdso(envp: *mut *mut core::ffi::c_char, auxv: *mut usize) {
+    let mut ptr = auxv;
+    while *ptr != crate::auxv::AT_NULL {
+        if *ptr == crate::auxv::AT_SYSINFO_EHDR {
+            *ptr.add(1) = 0;
+        }
+        ptr = ptr.add(2);
+    }
+
+    rustix::param::init(envp.cast());
+}
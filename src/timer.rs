@@ -0,0 +1,474 @@
+//! POSIX timers.
+//!
+//! `SIGEV_NONE`, `SIGEV_SIGNAL` and `SIGEV_THREAD_ID` timers are kernel
+//! timers, and a `timer_t` for one holds the kernel's timer id, shifted left
+//! with the low bit set. `SIGEV_THREAD` timers need userspace help: like
+//! glibc, we start a helper thread, with all signals blocked, the first time
+//! one is created. The kernel timer sends the first reserved real-time
+//! signal to the helper thread, with a pointer to our `Timer` as its value,
+//! and the helper thread starts a new detached thread to call the
+//! notification function for each expiration it receives. A `timer_t` for a
+//! `SIGEV_THREAD` timer is a pointer to its `Timer`.
+//!
+//! A signal for a timer can be pending when the timer is deleted, so
+//! `timer_delete` doesn't free the `Timer` itself. It queues the same signal
+//! to the helper thread, which frees the `Timer` when it gets there. Queued
+//! real-time signals are accepted in order, so by then any signal from the
+//! timer has been accepted.
+//!
+//! Without the "thread" feature, `SIGEV_THREAD` timers aren't supported, and
+//! `timer_create` fails with `ENOTSUP` for them.
+
+use core::ffi::c_int;
+use core::mem::zeroed;
+use rustix::io::{self, Errno};
+
+use crate::syscall::{
+    nr, syscall1, syscall2, syscall3, syscall4, KernelItimerspec, KernelTimespec,
+};
+#[cfg(feature = "thread")]
+use {
+    crate::signal::{queue_info, raw_wait, RESERVED},
+    core::ffi::c_void,
+    core::mem::size_of,
+    core::ptr::{null, null_mut},
+    core::sync::atomic::{AtomicBool, AtomicI32, Ordering},
+};
+
+/// The signal `SIGEV_THREAD` timers send to the helper thread.
+#[cfg(feature = "thread")]
+const SIGTIMER: c_int = RESERVED[0];
+
+/// `si_code` for signals sent by timers, and with `sigqueue`.
+#[cfg(feature = "thread")]
+const SI_TIMER: c_int = -2;
+#[cfg(feature = "thread")]
+const SI_QUEUE: c_int = -1;
+
+/// A `SIGEV_THREAD` timer.
+#[cfg(feature = "thread")]
+struct Timer {
+    /// The kernel's id for the timer.
+    id: c_int,
+    function: extern "C" fn(libc::sigval),
+    value: libc::sigval,
+    /// The stack size for notification threads, or 0 for the default.
+    stack_size: usize,
+}
+
+/// A `sigevent`, with the `SIGEV_THREAD` members of its union, which
+/// `libc::sigevent` doesn't expose.
+#[cfg(feature = "thread")]
+#[repr(C)]
+struct ThreadEvent {
+    value: libc::sigval,
+    signo: c_int,
+    notify: c_int,
+    function: extern "C" fn(libc::sigval),
+    attribute: *const libc::pthread_attr_t,
+}
+
+#[cfg(feature = "thread")]
+const _: () = assert!(size_of::<ThreadEvent>() <= size_of::<libc::sigevent>());
+
+/// A notification for a notification thread to deliver.
+#[cfg(feature = "thread")]
+struct Notification {
+    function: extern "C" fn(libc::sigval),
+    value: libc::sigval,
+}
+
+/// The helper thread's tid, valid in the process whose pid is in
+/// `HELPER_PID`. The helper thread doesn't survive `fork`, so a child starts
+/// its own.
+#[cfg(feature = "thread")]
+static HELPER_TID: AtomicI32 = AtomicI32::new(0);
+#[cfg(feature = "thread")]
+static HELPER_PID: AtomicI32 = AtomicI32::new(0);
+#[cfg(feature = "thread")]
+static HELPER_LOCK: AtomicBool = AtomicBool::new(false);
+
+enum Handle {
+    Kernel(c_int),
+    #[cfg(feature = "thread")]
+    Thread(*mut Timer),
+    /// Without the "thread" feature, every `timer_t` we return is a kernel
+    /// timer's.
+    #[cfg(not(feature = "thread"))]
+    Invalid,
+}
+
+fn encode(id: c_int) -> libc::timer_t {
+    (((id as usize) << 1) | 1) as libc::timer_t
+}
+
+fn decode(timer: libc::timer_t) -> Handle {
+    let bits = timer as usize;
+    if bits & 1 != 0 {
+        Handle::Kernel((bits >> 1) as c_int)
+    } else {
+        #[cfg(feature = "thread")]
+        {
+            Handle::Thread(timer.cast())
+        }
+        #[cfg(not(feature = "thread"))]
+        {
+            Handle::Invalid
+        }
+    }
+}
+
+/// The kernel's id for `timer`.
+unsafe fn kernel_id(timer: libc::timer_t) -> io::Result<c_int> {
+    match decode(timer) {
+        Handle::Kernel(id) => Ok(id),
+        #[cfg(feature = "thread")]
+        Handle::Thread(timer) if timer.is_null() => Err(Errno::INVAL),
+        #[cfg(feature = "thread")]
+        Handle::Thread(timer) => Ok((*timer).id),
+        #[cfg(not(feature = "thread"))]
+        Handle::Invalid => Err(Errno::INVAL),
+    }
+}
+
+/// Return the helper thread's tid, starting it if need be.
+#[cfg(feature = "thread")]
+fn helper_tid() -> io::Result<c_int> {
+    // SAFETY: `getpid` has no preconditions.
+    let pid = unsafe { libc::getpid() };
+    if HELPER_PID.load(Ordering::Acquire) == pid {
+        return Ok(HELPER_TID.load(Ordering::Relaxed));
+    }
+
+    while HELPER_LOCK
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+    let result = if HELPER_PID.load(Ordering::Relaxed) == pid {
+        Ok(HELPER_TID.load(Ordering::Relaxed))
+    } else {
+        HELPER_TID.store(0, Ordering::Relaxed);
+        // SAFETY: We pass valid signal sets, and `helper_main` is a valid
+        // thread start function.
+        unsafe {
+            // The helper thread inherits our signal mask, so block all
+            // signals while we start it.
+            let mut all: libc::sigset_t = zeroed();
+            let mut old: libc::sigset_t = zeroed();
+            libc::sigfillset(&mut all);
+            libc::pthread_sigmask(libc::SIG_SETMASK, &all, &mut old);
+            let mut thread: libc::pthread_t = zeroed();
+            let err = libc::pthread_create(&mut thread, null(), helper_main, null_mut());
+            libc::pthread_sigmask(libc::SIG_SETMASK, &old, null_mut());
+            if err == 0 {
+                libc::pthread_detach(thread);
+                loop {
+                    let tid = HELPER_TID.load(Ordering::Acquire);
+                    if tid != 0 {
+                        HELPER_PID.store(pid, Ordering::Release);
+                        break Ok(tid);
+                    }
+                    core::hint::spin_loop();
+                }
+            } else {
+                Err(Errno::AGAIN)
+            }
+        }
+    };
+    HELPER_LOCK.store(false, Ordering::Release);
+    result
+}
+
+#[cfg(feature = "thread")]
+extern "C" fn helper_main(_arg: *mut c_void) -> *mut c_void {
+    HELPER_TID.store(
+        rustix::thread::gettid().as_raw_nonzero().get(),
+        Ordering::Release,
+    );
+
+    // SAFETY: We pass valid pointers, and the `Timer`s we're sent are live
+    // until we free them here.
+    unsafe {
+        let mut set: libc::sigset_t = zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, SIGTIMER);
+        loop {
+            let mut info: libc::siginfo_t = zeroed();
+            if raw_wait(&set, &mut info, null()).is_err() {
+                continue;
+            }
+            let timer = info.si_value().sival_ptr.cast::<Timer>();
+            match info.si_code {
+                SI_TIMER => notify(&*timer),
+                SI_QUEUE => libc::free(timer.cast()),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Start a detached thread to call `timer`'s notification function.
+#[cfg(feature = "thread")]
+unsafe fn notify(timer: &Timer) {
+    let notification = libc::malloc(size_of::<Notification>()).cast::<Notification>();
+    if notification.is_null() {
+        return;
+    }
+    notification.write(Notification {
+        function: timer.function,
+        value: timer.value,
+    });
+
+    let mut attr: libc::pthread_attr_t = zeroed();
+    libc::pthread_attr_init(&mut attr);
+    libc::pthread_attr_setdetachstate(&mut attr, libc::PTHREAD_CREATE_DETACHED);
+    if timer.stack_size != 0 {
+        libc::pthread_attr_setstacksize(&mut attr, timer.stack_size);
+    }
+    let mut thread: libc::pthread_t = zeroed();
+    if libc::pthread_create(&mut thread, &attr, notify_main, notification.cast()) != 0 {
+        // The expiration is lost, as it would be if it were an overrun.
+        libc::free(notification.cast());
+    }
+    libc::pthread_attr_destroy(&mut attr);
+}
+
+#[cfg(feature = "thread")]
+extern "C" fn notify_main(arg: *mut c_void) -> *mut c_void {
+    // SAFETY: `notify` passes us a `Notification` it allocated.
+    let notification = unsafe {
+        let notification = arg.cast::<Notification>().read();
+        libc::free(arg);
+
+        // Undo the helper thread's signal mask.
+        let mut none: libc::sigset_t = zeroed();
+        libc::sigemptyset(&mut none);
+        libc::pthread_sigmask(libc::SIG_SETMASK, &none, null_mut());
+
+        notification
+    };
+    (notification.function)(notification.value);
+    null_mut()
+}
+
+unsafe fn create(
+    clock: libc::clockid_t,
+    event: *const libc::sigevent,
+    timer: *mut libc::timer_t,
+) -> io::Result<()> {
+    if event.is_null() || (*event).sigev_notify != libc::SIGEV_THREAD {
+        let mut id: c_int = 0;
+        syscall3(
+            nr::TIMER_CREATE,
+            clock as usize,
+            event as usize,
+            &mut id as *mut c_int as usize,
+        )?;
+        *timer = encode(id);
+        return Ok(());
+    }
+    create_thread(clock, event, timer)
+}
+
+/// Without the "thread" feature, there are no threads to notify with.
+#[cfg(not(feature = "thread"))]
+unsafe fn create_thread(
+    _clock: libc::clockid_t,
+    _event: *const libc::sigevent,
+    _timer: *mut libc::timer_t,
+) -> io::Result<()> {
+    Err(Errno::NOTSUP)
+}
+
+/// Create a `SIGEV_THREAD` timer.
+#[cfg(feature = "thread")]
+unsafe fn create_thread(
+    clock: libc::clockid_t,
+    event: *const libc::sigevent,
+    timer: *mut libc::timer_t,
+) -> io::Result<()> {
+    let event = &*event.cast::<ThreadEvent>();
+    let mut stack_size = 0;
+    if !event.attribute.is_null() {
+        libc::pthread_attr_getstacksize(event.attribute, &mut stack_size);
+    }
+    let tid = helper_tid()?;
+
+    let state = libc::malloc(size_of::<Timer>()).cast::<Timer>();
+    if state.is_null() {
+        return Err(Errno::AGAIN);
+    }
+    let mut kernel_event: libc::sigevent = zeroed();
+    kernel_event.sigev_value = libc::sigval {
+        sival_ptr: state.cast(),
+    };
+    kernel_event.sigev_signo = SIGTIMER;
+    kernel_event.sigev_notify = libc::SIGEV_THREAD_ID;
+    kernel_event.sigev_notify_thread_id = tid;
+    let mut id: c_int = 0;
+    if let Err(err) = syscall3(
+        nr::TIMER_CREATE,
+        clock as usize,
+        &kernel_event as *const libc::sigevent as usize,
+        &mut id as *mut c_int as usize,
+    ) {
+        libc::free(state.cast());
+        return Err(err);
+    }
+    state.write(Timer {
+        id,
+        function: event.function,
+        value: event.value,
+        stack_size,
+    });
+    *timer = state.cast();
+    Ok(())
+}
+
+unsafe fn delete(timer: libc::timer_t) -> io::Result<()> {
+    let id = kernel_id(timer)?;
+    syscall1(nr::TIMER_DELETE, id as usize)?;
+
+    // Have the helper thread free the `Timer`, once it has accepted any
+    // signal the timer sent before it was deleted.
+    #[cfg(feature = "thread")]
+    if let Handle::Thread(state) = decode(timer) {
+        let info = queue_info(
+            SIGTIMER,
+            libc::sigval {
+                sival_ptr: state.cast(),
+            },
+        );
+        syscall4(
+            nr::RT_TGSIGQUEUEINFO,
+            libc::getpid() as usize,
+            HELPER_TID.load(Ordering::Relaxed) as usize,
+            SIGTIMER as usize,
+            &info as *const libc::siginfo_t as usize,
+        )?;
+    }
+    Ok(())
+}
+
+// `time_t` and `c_long` are 32-bit on some targets.
+#[allow(clippy::useless_conversion)]
+fn to_kernel(ts: &libc::timespec) -> KernelTimespec {
+    KernelTimespec {
+        tv_sec: ts.tv_sec.into(),
+        tv_nsec: ts.tv_nsec.into(),
+    }
+}
+
+#[allow(clippy::useless_conversion)]
+fn from_kernel(ts: &KernelTimespec) -> io::Result<libc::timespec> {
+    Ok(libc::timespec {
+        tv_sec: ts.tv_sec.try_into().map_err(|_| Errno::OVERFLOW)?,
+        tv_nsec: ts.tv_nsec as _,
+    })
+}
+
+unsafe fn from_kernel_itimerspec(
+    kernel: &KernelItimerspec,
+    spec: *mut libc::itimerspec,
+) -> io::Result<()> {
+    if !spec.is_null() {
+        spec.write(libc::itimerspec {
+            it_interval: from_kernel(&kernel.it_interval)?,
+            it_value: from_kernel(&kernel.it_value)?,
+        });
+    }
+    Ok(())
+}
+
+unsafe fn settime(
+    timer: libc::timer_t,
+    flags: c_int,
+    new: *const libc::itimerspec,
+    old: *mut libc::itimerspec,
+) -> io::Result<()> {
+    let id = kernel_id(timer)?;
+    let new = new.as_ref().ok_or(Errno::INVAL)?;
+    let new = KernelItimerspec {
+        it_interval: to_kernel(&new.it_interval),
+        it_value: to_kernel(&new.it_value),
+    };
+    let mut kernel_old: KernelItimerspec = zeroed();
+    syscall4(
+        nr::TIMER_SETTIME,
+        id as usize,
+        flags as usize,
+        &new as *const KernelItimerspec as usize,
+        &mut kernel_old as *mut KernelItimerspec as usize,
+    )?;
+    from_kernel_itimerspec(&kernel_old, old)
+}
+
+unsafe fn gettime(timer: libc::timer_t, spec: *mut libc::itimerspec) -> io::Result<()> {
+    let id = kernel_id(timer)?;
+    if spec.is_null() {
+        return Err(Errno::FAULT);
+    }
+    let mut kernel: KernelItimerspec = zeroed();
+    syscall2(
+        nr::TIMER_GETTIME,
+        id as usize,
+        &mut kernel as *mut KernelItimerspec as usize,
+    )?;
+    from_kernel_itimerspec(&kernel, spec)
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn timer_create(
+    clock: libc::clockid_t,
+    event: *mut libc::sigevent,
+    timer: *mut libc::timer_t,
+) -> c_int {
+    result(create(clock, event, timer).map(|()| 0))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn timer_delete(timer: libc::timer_t) -> c_int {
+    result(delete(timer).map(|()| 0))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn timer_settime(
+    timer: libc::timer_t,
+    flags: c_int,
+    new: *const libc::itimerspec,
+    old: *mut libc::itimerspec,
+) -> c_int {
+    result(settime(timer, flags, new, old).map(|()| 0))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn timer_gettime(timer: libc::timer_t, spec: *mut libc::itimerspec) -> c_int {
+    result(gettime(timer, spec).map(|()| 0))
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn timer_getoverrun(timer: libc::timer_t) -> c_int {
+    result(
+        kernel_id(timer).and_then(|id| {
+            syscall1(nr::TIMER_GETOVERRUN, id as usize).map(|overrun| overrun as c_int)
+        }),
+    )
+}
+
+/// Convert a `Result` into a C return value, setting `errno` on failure.
+unsafe fn result(result: io::Result<c_int>) -> c_int {
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            *libc::__errno_location() = err.raw_os_error();
+            -1
+        }
+    }
+}
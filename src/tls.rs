@@ -0,0 +1,289 @@
+//! Dynamic thread-local storage models in static binaries.
+//!
+//! mustang programs are statically linked, so they have a single TLS module,
+//! the executable, whose module id is [`MODULE_ID`]. Rust code, and C code
+//! compiled without `-fPIC`, use the local-exec and initial-exec TLS models,
+//! which the linker resolves to fixed offsets from the thread pointer. C code
+//! compiled with `-fPIC` uses the general-dynamic and local-dynamic models
+//! instead, which call `__tls_get_addr` with a module id and an offset, or
+//! with TLS descriptors, call a resolver function stored in the descriptor.
+//! In an executable, linkers rewrite descriptor accesses into fixed offsets,
+//! and they rewrite `__tls_get_addr` calls on some architectures, but not
+//! all, and not in all cases.
+//!
+//! So mustang provides `__tls_get_addr`, and on x86, `___tls_get_addr`, which
+//! takes its argument in `eax`. It also provides `_dl_tlsdesc_return`, the
+//! resolver for descriptors of variables at fixed offsets from the thread
+//! pointer, which is where every variable in a static binary is; a
+//! descriptor's argument is then [`block_offset`] plus the variable's offset.
+//! That's what a descriptor the linker doesn't rewrite needs, such as one
+//! built at runtime. They find the executable's TLS block from its `PT_TLS`
+//! program header and the thread pointer, with the same layout the linker
+//! uses for local-exec accesses.
+
+use crate::vdso::Phdr;
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicIsize, Ordering};
+
+/// The module id of the executable, the only TLS module in a static binary.
+pub const MODULE_ID: usize = 1;
+
+/// The argument of `__tls_get_addr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct TlsIndex {
+    /// The module id, which must be [`MODULE_ID`].
+    pub module: usize,
+    /// The offset of the variable in the module's TLS block, less
+    /// [`DTV_OFFSET`].
+    pub offset: usize,
+}
+
+/// The bias which compilers subtract from offsets passed to
+/// `__tls_get_addr`, so that they can reach more of the TLS block with
+/// signed immediates.
+#[cfg(target_arch = "riscv64")]
+pub const DTV_OFFSET: usize = 0x800;
+
+/// The bias which compilers subtract from offsets passed to
+/// `__tls_get_addr`.
+#[cfg(not(target_arch = "riscv64"))]
+pub const DTV_OFFSET: usize = 0;
+
+const PT_TLS: u32 = 7;
+
+/// The value of `BLOCK_OFFSET` before it's computed.
+const UNKNOWN: isize = isize::MIN;
+
+static BLOCK_OFFSET: AtomicIsize = AtomicIsize::new(UNKNOWN);
+
+/// Return the calling thread's TLS block for the executable.
+pub fn block() -> *mut c_void {
+    thread_pointer()
+        .wrapping_offset(block_offset())
+        .cast::<c_void>()
+}
+
+/// Return the offset of the executable's TLS block from the thread pointer.
+///
+/// This is negative on x86 and x86_64, where the block is below the thread
+/// pointer.
+pub fn block_offset() -> isize {
+    let offset = BLOCK_OFFSET.load(Ordering::Relaxed);
+    if offset != UNKNOWN {
+        return offset;
+    }
+    // Racing threads compute the same value.
+    let offset = compute_block_offset();
+    BLOCK_OFFSET.store(offset, Ordering::Relaxed);
+    offset
+}
+
+/// Compute the block offset as the linker does, from the `PT_TLS` segment.
+/// This accounts for the segment's address not being aligned, which can
+/// happen when it's smaller than its alignment.
+fn compute_block_offset() -> isize {
+    let Some(tls) = tls_segment() else {
+        // There are no thread-local variables.
+        return 0;
+    };
+    let align = tls.p_align.max(1);
+    // Variant II: the block ends at the thread pointer.
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    let offset = -((tls.p_memsz
+        + (tls.p_vaddr.wrapping_neg().wrapping_sub(tls.p_memsz) & (align - 1)))
+        as isize);
+    // Variant I: the block follows a two-word TCB at the thread pointer.
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+    let offset = {
+        let tcb = 2 * core::mem::size_of::<usize>();
+        (tcb + (tls.p_vaddr.wrapping_sub(tcb) & (align - 1))) as isize
+    };
+    // RISC-V: the block starts at the thread pointer.
+    #[cfg(target_arch = "riscv64")]
+    let offset = (tls.p_vaddr & (align - 1)) as isize;
+    offset
+}
+
+/// Find the executable's `PT_TLS` program header.
+fn tls_segment() -> Option<&'static Phdr> {
+    let phdr = crate::auxv::get(crate::auxv::AT_PHDR)? as *const Phdr;
+    let phnum = crate::auxv::get(crate::auxv::AT_PHNUM)?;
+    // SAFETY: The kernel points `AT_PHDR` at the executable's program
+    // headers, which are mapped for the life of the process.
+    let phdrs = unsafe { core::slice::from_raw_parts(phdr, phnum) };
+    phdrs.iter().find(|phdr| phdr.p_type == PT_TLS)
+}
+
+/// Return the calling thread's thread pointer.
+fn thread_pointer() -> *mut u8 {
+    let tp: *mut u8;
+    // SAFETY: The TCB starts with a pointer to itself, as the ABI requires.
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        core::arch::asm!(
+            "mov {}, qword ptr fs:0",
+            out(reg) tp,
+            options(nostack, preserves_flags, readonly)
+        );
+    }
+    // SAFETY: The TCB starts with a pointer to itself, as the ABI requires.
+    #[cfg(target_arch = "x86")]
+    unsafe {
+        core::arch::asm!(
+            "mov {}, dword ptr gs:0",
+            out(reg) tp,
+            options(nostack, preserves_flags, readonly)
+        );
+    }
+    // SAFETY: Reading the thread pointer register has no side effects.
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        core::arch::asm!(
+            "mrs {}, tpidr_el0",
+            out(reg) tp,
+            options(nostack, preserves_flags, nomem)
+        );
+    }
+    // SAFETY: Reading the thread pointer register has no side effects.
+    #[cfg(target_arch = "riscv64")]
+    unsafe {
+        core::arch::asm!(
+            "mv {}, tp",
+            out(reg) tp,
+            options(nostack, preserves_flags, nomem)
+        );
+    }
+    // Older ARM cores don't have a thread pointer register, so the runtime
+    // provides a function which reads it in whichever way the core supports.
+    // SAFETY: `__aeabi_read_tp` has no preconditions.
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        extern "C" {
+            fn __aeabi_read_tp() -> *mut u8;
+        }
+        tp = __aeabi_read_tp();
+    }
+    tp
+}
+
+#[no_mangle]
+#[linkage = "weak"]
+unsafe extern "C" fn __tls_get_addr(index: *const TlsIndex) -> *mut c_void {
+    let index = &*index;
+    if index.module != MODULE_ID {
+        crate::startup::fail(format_args!(
+            "__tls_get_addr: unknown TLS module {}",
+            index.module
+        ));
+    }
+    block()
+        .cast::<u8>()
+        .wrapping_add(index.offset.wrapping_add(DTV_OFFSET))
+        .cast::<c_void>()
+}
+
+// On x86, GNU-style general-dynamic accesses call `___tls_get_addr` with
+// the argument in `eax`. This passes it on the stack, keeping the stack
+// 16-byte aligned at the call.
+#[cfg(target_arch = "x86")]
+core::arch::global_asm!(
+    ".pushsection .text.___tls_get_addr,\"ax\",@progbits",
+    ".weak ___tls_get_addr",
+    ".p2align 4",
+    ".type ___tls_get_addr, @function",
+    "___tls_get_addr:",
+    ".cfi_startproc",
+    "sub esp, 8",
+    ".cfi_adjust_cfa_offset 8",
+    "push eax",
+    ".cfi_adjust_cfa_offset 4",
+    "call {tls_get_addr}",
+    "add esp, 12",
+    ".cfi_adjust_cfa_offset -12",
+    "ret",
+    ".cfi_endproc",
+    ".size ___tls_get_addr, .-___tls_get_addr",
+    ".popsection",
+    tls_get_addr = sym __tls_get_addr,
+);
+
+// `_dl_tlsdesc_return` is called with the address of a descriptor, except
+// on ARM, where it's the address of the descriptor's argument, which comes
+// first there, and returns the argument. It preserves all other registers.
+// On RISC-V, the return address is in `t0`.
+
+#[cfg(target_arch = "x86_64")]
+core::arch::global_asm!(
+    ".pushsection .text._dl_tlsdesc_return,\"ax\",@progbits",
+    ".weak _dl_tlsdesc_return",
+    ".p2align 4",
+    ".type _dl_tlsdesc_return, @function",
+    "_dl_tlsdesc_return:",
+    ".cfi_startproc",
+    "mov rax, qword ptr [rax + 8]",
+    "ret",
+    ".cfi_endproc",
+    ".size _dl_tlsdesc_return, .-_dl_tlsdesc_return",
+    ".popsection",
+);
+
+#[cfg(target_arch = "x86")]
+core::arch::global_asm!(
+    ".pushsection .text._dl_tlsdesc_return,\"ax\",@progbits",
+    ".weak _dl_tlsdesc_return",
+    ".p2align 4",
+    ".type _dl_tlsdesc_return, @function",
+    "_dl_tlsdesc_return:",
+    ".cfi_startproc",
+    "mov eax, dword ptr [eax + 4]",
+    "ret",
+    ".cfi_endproc",
+    ".size _dl_tlsdesc_return, .-_dl_tlsdesc_return",
+    ".popsection",
+);
+
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(
+    ".pushsection .text._dl_tlsdesc_return,\"ax\",@progbits",
+    ".weak _dl_tlsdesc_return",
+    ".p2align 2",
+    ".type _dl_tlsdesc_return, @function",
+    "_dl_tlsdesc_return:",
+    ".cfi_startproc",
+    "ldr x0, [x0, #8]",
+    "ret",
+    ".cfi_endproc",
+    ".size _dl_tlsdesc_return, .-_dl_tlsdesc_return",
+    ".popsection",
+);
+
+#[cfg(target_arch = "arm")]
+core::arch::global_asm!(
+    ".pushsection .text._dl_tlsdesc_return,\"ax\",%progbits",
+    ".weak _dl_tlsdesc_return",
+    ".p2align 2",
+    ".type _dl_tlsdesc_return, %function",
+    "_dl_tlsdesc_return:",
+    ".cfi_startproc",
+    "ldr r0, [r0]",
+    "bx lr",
+    ".cfi_endproc",
+    ".size _dl_tlsdesc_return, .-_dl_tlsdesc_return",
+    ".popsection",
+);
+
+#[cfg(target_arch = "riscv64")]
+core::arch::global_asm!(
+    ".pushsection .text._dl_tlsdesc_return,\"ax\",@progbits",
+    ".weak _dl_tlsdesc_return",
+    ".p2align 2",
+    ".type _dl_tlsdesc_return, @function",
+    "_dl_tlsdesc_return:",
+    ".cfi_startproc",
+    "ld a0, 8(a0)",
+    "jr t0",
+    ".cfi_endproc",
+    ".size _dl_tlsdesc_return, .-_dl_tlsdesc_return",
+    ".popsection",
+);
@@ -0,0 +1,531 @@
+//! Emulating syscalls with seccomp's `SECCOMP_RET_TRAP`.
+//!
+//! [`install`] installs a seccomp-BPF filter which makes a set of syscalls
+//! raise `SIGSYS` instead of entering the kernel, and a `SIGSYS` handler
+//! which passes each one to an emulation function and stores its result in
+//! the interrupted thread's return register. The filter is inherited by
+//! threads created afterwards, so it's installed at startup, before there are
+//! other threads.
+//!
+//! Only syscalls made from the executable's own code are trapped. In a
+//! mustang program that's all of them, as long as the vDSO is hidden.
+//! Filters can't be removed and are kept across `execve`, so this keeps
+//! programs the process executes from being trapped, unless they're loaded
+//! at the same addresses, as another instance of the same program is. Such a
+//! program must install its own handler before it makes any of the trapped
+//! syscalls, which mustang programs do.
+//!
+//! Installing a filter requires setting the `no_new_privs` flag, which can't
+//! be cleared either, and is also kept across `execve`: programs the process
+//! executes don't gain privileges from setuid bits or file capabilities.
+//!
+//! A `SIGSYS` handler installed later by the program replaces ours, and then
+//! trapped syscalls go to it.
+//!
+//! Emulation functions can make the real syscalls with [`passthrough`],
+//! whose code the filter doesn't trap. The handler runs with `SIGSYS`
+//! unblocked, so signal handlers which interrupt it can make trapped
+//! syscalls too.
+
+use crate::startup::fail;
+use crate::syscall::{nr, syscall3};
+use crate::vdso::Phdr;
+use core::ffi::{c_int, c_uint, c_void};
+use core::mem::zeroed;
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The maximum number of syscalls [`install`] can trap.
+pub(crate) const MAX_SYSCALLS: usize = 32;
+
+/// A syscall emulation function. It takes the syscall number and arguments,
+/// and returns the raw result, which is a negated errno value on failure. It
+/// must be async-signal-safe and reentrant.
+pub(crate) type Emulate = unsafe fn(u32, [usize; 6]) -> usize;
+
+static EMULATE: AtomicUsize = AtomicUsize::new(0);
+
+const SECCOMP_SET_MODE_FILTER: usize = 1;
+const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+/// The `si_code` of a `SIGSYS` raised by a seccomp filter.
+const SYS_SECCOMP: c_int = 1;
+
+// BPF instructions.
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_JEQ_K: u16 = 0x15;
+const BPF_JGT_K: u16 = 0x25;
+const BPF_JGE_K: u16 = 0x35;
+const BPF_RET_K: u16 = 0x06;
+
+// Offsets in `struct seccomp_data`. All mustang targets are little-endian.
+const DATA_NR: u32 = 0;
+const DATA_ARCH: u32 = 4;
+const DATA_IP_LO: u32 = 8;
+const DATA_IP_HI: u32 = 12;
+
+/// The `AUDIT_ARCH_*` value for the target.
+#[cfg(target_arch = "x86_64")]
+pub(crate) const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "x86")]
+pub(crate) const AUDIT_ARCH: u32 = 0x4000_0003;
+#[cfg(target_arch = "aarch64")]
+pub(crate) const AUDIT_ARCH: u32 = 0xc000_00b7;
+#[cfg(target_arch = "riscv64")]
+pub(crate) const AUDIT_ARCH: u32 = 0xc000_00f3;
+#[cfg(target_arch = "arm")]
+pub(crate) const AUDIT_ARCH: u32 = 0x4000_0028;
+
+const PT_LOAD: u32 = 1;
+const PT_PHDR: u32 = 6;
+const PF_X: u32 = 1;
+
+/// `struct sock_filter`.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct SockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+/// `struct sock_fprog`.
+#[repr(C)]
+struct SockFprog {
+    len: u16,
+    filter: *const SockFilter,
+}
+
+/// The start of a `siginfo_t` for a `SIGSYS`.
+#[allow(dead_code)]
+#[repr(C)]
+struct SigsysInfo {
+    signo: c_int,
+    errno: c_int,
+    code: c_int,
+    call_addr: *mut c_void,
+    syscall: c_int,
+    arch: c_uint,
+}
+
+impl SockFilter {
+    const fn load(offset: u32) -> Self {
+        Self {
+            code: BPF_LD_W_ABS,
+            jt: 0,
+            jf: 0,
+            k: offset,
+        }
+    }
+
+    /// A conditional jump, at index `at`, to index `yes` or `no`.
+    const fn jump(code: u16, k: u32, at: usize, yes: usize, no: usize) -> Self {
+        Self {
+            code,
+            jt: (yes - at - 1) as u8,
+            jf: (no - at - 1) as u8,
+            k,
+        }
+    }
+
+    const fn ret(action: u32) -> Self {
+        Self {
+            code: BPF_RET_K,
+            jt: 0,
+            jf: 0,
+            k: action,
+        }
+    }
+}
+
+/// Trap `syscalls` made from the executable's code, and emulate them with
+/// `emulate`.
+///
+/// # Safety
+///
+/// This must be called at startup, before any other threads are created.
+#[inline(never)]
+pub(crate) unsafe fn install(syscalls: &[u32], emulate: Emulate) {
+    if syscalls.len() > MAX_SYSCALLS {
+        fail(format_args!("too many syscalls to trap"));
+    }
+    install_handler(emulate);
+
+    // Allow the syscall if it's from `passthrough`'s code or not from the
+    // executable's code, and then trap it if it's one of `syscalls`.
+    let stub = (
+        __mustang_passthrough as *const () as usize,
+        __mustang_passthrough_end as *const () as usize,
+    );
+    let check_nr = 22;
+    let allow = check_nr + 1 + syscalls.len();
+    let trap = allow + 1;
+    let mut insns = [SockFilter::ret(SECCOMP_RET_ALLOW); 24 + MAX_SYSCALLS];
+    insns[0] = SockFilter::load(DATA_ARCH);
+    insns[1] = SockFilter::jump(BPF_JEQ_K, AUDIT_ARCH, 1, 2, allow);
+    ip_in_range(&mut insns, 2, stub, allow, 12);
+    ip_in_range(&mut insns, 12, text_range(), check_nr, allow);
+    insns[check_nr] = SockFilter::load(DATA_NR);
+    for (i, nr) in syscalls.iter().enumerate() {
+        let at = check_nr + 1 + i;
+        insns[at] = SockFilter::jump(BPF_JEQ_K, *nr, at, trap, at + 1);
+    }
+    insns[allow] = SockFilter::ret(SECCOMP_RET_ALLOW);
+    insns[trap] = SockFilter::ret(SECCOMP_RET_TRAP);
+
+    let prog = SockFprog {
+        len: (trap + 1) as u16,
+        filter: insns.as_ptr(),
+    };
+    let result = rustix::thread::set_no_new_privs(true).and_then(|()| {
+        syscall3(
+            nr::SECCOMP,
+            SECCOMP_SET_MODE_FILTER,
+            0,
+            &prog as *const SockFprog as usize,
+        )
+    });
+    if let Err(err) = result {
+        fail(format_args!("can't install the seccomp filter: {}", err));
+    }
+}
+
+/// Install the `SIGSYS` handler, without a filter, so that syscalls trapped
+/// by a filter inherited from the parent process are emulated with
+/// `emulate`.
+///
+/// # Safety
+///
+/// This must be called at startup, before any other threads are created.
+pub(crate) unsafe fn install_handler(emulate: Emulate) {
+    if EMULATE.swap(emulate as usize, Ordering::Relaxed) != 0 {
+        fail(format_args!("syscalls are already being emulated"));
+    }
+
+    let mut action: libc::sigaction = zeroed();
+    action.sa_sigaction = handle as *const () as usize;
+    action.sa_flags = libc::SA_SIGINFO | libc::SA_NODEFER;
+    libc::sigemptyset(&mut action.sa_mask);
+    if libc::sigaction(libc::SIGSYS, &action, null_mut()) != 0 {
+        fail(format_args!("can't install the SIGSYS handler"));
+    }
+}
+
+/// Write the 10 instructions at `at` which jump to `inside` if the
+/// instruction pointer is in `range`, and to `outside` otherwise, comparing
+/// the 64-bit instruction pointer in 32-bit halves.
+fn ip_in_range(
+    insns: &mut [SockFilter],
+    at: usize,
+    (start, end): (usize, usize),
+    inside: usize,
+    outside: usize,
+) {
+    let (start_lo, start_hi) = (start as u64 as u32, (start as u64 >> 32) as u32);
+    let (end_lo, end_hi) = (end as u64 as u32, (end as u64 >> 32) as u32);
+    insns[at] = SockFilter::load(DATA_IP_HI);
+    insns[at + 1] = SockFilter::jump(BPF_JGT_K, start_hi, at + 1, at + 5, at + 2);
+    insns[at + 2] = SockFilter::jump(BPF_JEQ_K, start_hi, at + 2, at + 3, outside);
+    insns[at + 3] = SockFilter::load(DATA_IP_LO);
+    insns[at + 4] = SockFilter::jump(BPF_JGE_K, start_lo, at + 4, at + 5, outside);
+    insns[at + 5] = SockFilter::load(DATA_IP_HI);
+    insns[at + 6] = SockFilter::jump(BPF_JGT_K, end_hi, at + 6, outside, at + 7);
+    insns[at + 7] = SockFilter::jump(BPF_JEQ_K, end_hi, at + 7, at + 8, inside);
+    insns[at + 8] = SockFilter::load(DATA_IP_LO);
+    insns[at + 9] = SockFilter::jump(BPF_JGE_K, end_lo, at + 9, outside, inside);
+}
+
+/// Make syscall `nr` with `args`, without it being trapped, and return the
+/// raw result.
+#[cfg(feature = "record-replay")]
+pub(crate) unsafe fn passthrough(nr: u32, args: [usize; 6]) -> usize {
+    let call = [
+        nr as usize,
+        args[0],
+        args[1],
+        args[2],
+        args[3],
+        args[4],
+        args[5],
+    ];
+    __mustang_passthrough(call.as_ptr())
+}
+
+/// Return the range of addresses of the executable's code.
+unsafe fn text_range() -> (usize, usize) {
+    let (Some(phdr), Some(phnum)) = (
+        crate::auxv::get(crate::auxv::AT_PHDR),
+        crate::auxv::get(crate::auxv::AT_PHNUM),
+    ) else {
+        fail(format_args!("can't find the program headers"));
+    };
+    let phdrs = core::slice::from_raw_parts(phdr as *const Phdr, phnum);
+    // The load bias is zero unless the executable is position-independent.
+    let bias = phdrs
+        .iter()
+        .find(|header| header.p_type == PT_PHDR)
+        .map_or(0, |header| phdr.wrapping_sub(header.p_vaddr));
+    let mut start = usize::MAX;
+    let mut end = 0;
+    for header in phdrs
+        .iter()
+        .filter(|header| header.p_type == PT_LOAD && header.p_flags & PF_X != 0)
+    {
+        start = start.min(bias.wrapping_add(header.p_vaddr));
+        end = end.max(bias.wrapping_add(header.p_vaddr + header.p_memsz));
+    }
+    if start >= end {
+        fail(format_args!("can't find the executable's code"));
+    }
+    (start, end)
+}
+
+unsafe extern "C" fn handle(_sig: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let info = &*info.cast::<SigsysInfo>();
+    if info.code != SYS_SECCOMP || info.arch != AUDIT_ARCH {
+        // Someone sent us a `SIGSYS`.
+        return;
+    }
+    let emulate: Emulate = core::mem::transmute(EMULATE.load(Ordering::Relaxed));
+    let regs = &mut (*context.cast::<libc::ucontext_t>()).uc_mcontext;
+    let ret = emulate(info.syscall as u32, args(regs));
+    set_return(regs, ret);
+}
+
+#[cfg(target_arch = "x86_64")]
+fn args(regs: &libc::mcontext_t) -> [usize; 6] {
+    let reg = |reg: c_int| regs.gregs[reg as usize] as usize;
+    [
+        reg(libc::REG_RDI),
+        reg(libc::REG_RSI),
+        reg(libc::REG_RDX),
+        reg(libc::REG_R10),
+        reg(libc::REG_R8),
+        reg(libc::REG_R9),
+    ]
+}
+
+#[cfg(target_arch = "x86_64")]
+fn set_return(regs: &mut libc::mcontext_t, ret: usize) {
+    regs.gregs[libc::REG_RAX as usize] = ret as _;
+}
+
+#[cfg(target_arch = "x86")]
+fn args(regs: &libc::mcontext_t) -> [usize; 6] {
+    let reg = |reg: c_int| regs.gregs[reg as usize] as usize;
+    [
+        reg(libc::REG_EBX),
+        reg(libc::REG_ECX),
+        reg(libc::REG_EDX),
+        reg(libc::REG_ESI),
+        reg(libc::REG_EDI),
+        reg(libc::REG_EBP),
+    ]
+}
+
+#[cfg(target_arch = "x86")]
+fn set_return(regs: &mut libc::mcontext_t, ret: usize) {
+    regs.gregs[libc::REG_EAX as usize] = ret as _;
+}
+
+#[cfg(target_arch = "aarch64")]
+fn args(regs: &libc::mcontext_t) -> [usize; 6] {
+    core::array::from_fn(|i| regs.regs[i] as usize)
+}
+
+#[cfg(target_arch = "aarch64")]
+fn set_return(regs: &mut libc::mcontext_t, ret: usize) {
+    regs.regs[0] = ret as _;
+}
+
+// The arguments are in `a0` through `a5`, which are `x10` through `x15`.
+#[cfg(target_arch = "riscv64")]
+fn args(regs: &libc::mcontext_t) -> [usize; 6] {
+    core::array::from_fn(|i| regs.__gregs[10 + i] as usize)
+}
+
+#[cfg(target_arch = "riscv64")]
+fn set_return(regs: &mut libc::mcontext_t, ret: usize) {
+    regs.__gregs[10] = ret as _;
+}
+
+#[cfg(target_arch = "arm")]
+fn args(regs: &libc::mcontext_t) -> [usize; 6] {
+    [
+        regs.arm_r0 as usize,
+        regs.arm_r1 as usize,
+        regs.arm_r2 as usize,
+        regs.arm_r3 as usize,
+        regs.arm_r4 as usize,
+        regs.arm_r5 as usize,
+    ]
+}
+
+#[cfg(target_arch = "arm")]
+fn set_return(regs: &mut libc::mcontext_t, ret: usize) {
+    regs.arm_r0 = ret as _;
+}
+
+// `__mustang_passthrough` takes the address of an array of the syscall
+// number and the six arguments, and makes the syscall. The filter allows
+// syscalls from its code, up to `__mustang_passthrough_end`.
+extern "C" {
+    fn __mustang_passthrough(call: *const usize) -> usize;
+    fn __mustang_passthrough_end();
+}
+
+#[cfg(target_arch = "x86_64")]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_passthrough,\"ax\",@progbits",
+    ".hidden __mustang_passthrough",
+    ".hidden __mustang_passthrough_end",
+    ".p2align 4",
+    ".type __mustang_passthrough, @function",
+    "__mustang_passthrough:",
+    ".cfi_startproc",
+    "mov r11, rdi",
+    "mov rax, qword ptr [r11]",
+    "mov rdi, qword ptr [r11 + 8]",
+    "mov rsi, qword ptr [r11 + 16]",
+    "mov rdx, qword ptr [r11 + 24]",
+    "mov r10, qword ptr [r11 + 32]",
+    "mov r8, qword ptr [r11 + 40]",
+    "mov r9, qword ptr [r11 + 48]",
+    "syscall",
+    "ret",
+    ".cfi_endproc",
+    "__mustang_passthrough_end:",
+    ".size __mustang_passthrough, .-__mustang_passthrough",
+    ".popsection",
+);
+
+// `ebx`, `esi`, `edi` and `ebp` are callee-saved.
+#[cfg(target_arch = "x86")]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_passthrough,\"ax\",@progbits",
+    ".hidden __mustang_passthrough",
+    ".hidden __mustang_passthrough_end",
+    ".p2align 4",
+    ".type __mustang_passthrough, @function",
+    "__mustang_passthrough:",
+    ".cfi_startproc",
+    "push ebp",
+    ".cfi_adjust_cfa_offset 4",
+    ".cfi_rel_offset ebp, 0",
+    "push edi",
+    ".cfi_adjust_cfa_offset 4",
+    ".cfi_rel_offset edi, 0",
+    "push esi",
+    ".cfi_adjust_cfa_offset 4",
+    ".cfi_rel_offset esi, 0",
+    "push ebx",
+    ".cfi_adjust_cfa_offset 4",
+    ".cfi_rel_offset ebx, 0",
+    "mov eax, dword ptr [esp + 20]",
+    "mov ebx, dword ptr [eax + 4]",
+    "mov ecx, dword ptr [eax + 8]",
+    "mov edx, dword ptr [eax + 12]",
+    "mov esi, dword ptr [eax + 16]",
+    "mov edi, dword ptr [eax + 20]",
+    "mov ebp, dword ptr [eax + 24]",
+    "mov eax, dword ptr [eax]",
+    "int 0x80",
+    "pop ebx",
+    ".cfi_adjust_cfa_offset -4",
+    ".cfi_restore ebx",
+    "pop esi",
+    ".cfi_adjust_cfa_offset -4",
+    ".cfi_restore esi",
+    "pop edi",
+    ".cfi_adjust_cfa_offset -4",
+    ".cfi_restore edi",
+    "pop ebp",
+    ".cfi_adjust_cfa_offset -4",
+    ".cfi_restore ebp",
+    "ret",
+    ".cfi_endproc",
+    "__mustang_passthrough_end:",
+    ".size __mustang_passthrough, .-__mustang_passthrough",
+    ".popsection",
+);
+
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_passthrough,\"ax\",@progbits",
+    ".hidden __mustang_passthrough",
+    ".hidden __mustang_passthrough_end",
+    ".p2align 2",
+    ".type __mustang_passthrough, @function",
+    "__mustang_passthrough:",
+    ".cfi_startproc",
+    "mov x9, x0",
+    "ldp x8, x0, [x9]",
+    "ldp x1, x2, [x9, #16]",
+    "ldp x3, x4, [x9, #32]",
+    "ldr x5, [x9, #48]",
+    "svc #0",
+    "ret",
+    ".cfi_endproc",
+    "__mustang_passthrough_end:",
+    ".size __mustang_passthrough, .-__mustang_passthrough",
+    ".popsection",
+);
+
+#[cfg(target_arch = "riscv64")]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_passthrough,\"ax\",@progbits",
+    ".hidden __mustang_passthrough",
+    ".hidden __mustang_passthrough_end",
+    ".p2align 2",
+    ".type __mustang_passthrough, @function",
+    "__mustang_passthrough:",
+    ".cfi_startproc",
+    "mv t0, a0",
+    "ld a7, 0(t0)",
+    "ld a0, 8(t0)",
+    "ld a1, 16(t0)",
+    "ld a2, 24(t0)",
+    "ld a3, 32(t0)",
+    "ld a4, 40(t0)",
+    "ld a5, 48(t0)",
+    "ecall",
+    "ret",
+    ".cfi_endproc",
+    "__mustang_passthrough_end:",
+    ".size __mustang_passthrough, .-__mustang_passthrough",
+    ".popsection",
+);
+
+// `r4`, `r5` and `r7` are callee-saved.
+#[cfg(target_arch = "arm")]
+core::arch::global_asm!(
+    ".pushsection .text.__mustang_passthrough,\"ax\",%progbits",
+    ".hidden __mustang_passthrough",
+    ".hidden __mustang_passthrough_end",
+    ".p2align 2",
+    ".type __mustang_passthrough, %function",
+    "__mustang_passthrough:",
+    ".cfi_startproc",
+    "push {{r4, r5, r7, lr}}",
+    ".cfi_adjust_cfa_offset 16",
+    ".cfi_rel_offset r4, 0",
+    ".cfi_rel_offset r5, 4",
+    ".cfi_rel_offset r7, 8",
+    ".cfi_rel_offset lr, 12",
+    "mov ip, r0",
+    "ldr r7, [ip]",
+    "ldr r0, [ip, #4]",
+    "ldr r1, [ip, #8]",
+    "ldr r2, [ip, #12]",
+    "ldr r3, [ip, #16]",
+    "ldr r4, [ip, #20]",
+    "ldr r5, [ip, #24]",
+    "svc #0",
+    "pop {{r4, r5, r7, pc}}",
+    ".cfi_endproc",
+    "__mustang_passthrough_end:",
+    ".size __mustang_passthrough, .-__mustang_passthrough",
+    ".popsection",
+);
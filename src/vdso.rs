@@ -0,0 +1,246 @@
+//! A minimal vDSO symbol lookup.
+//!
+//! rustix does its own vDSO parsing for the functions it calls, but doesn't
+//! expose it, so this is just enough to resolve the same symbols it does,
+//! with the same versions.
+//!
+//! This follows the approach of the kernel's
+//! tools/testing/selftests/vDSO/parse_vdso.c.
+
+use crate::auxv;
+use core::ffi::{c_void, CStr};
+use core::ptr::null;
+
+// These are the ELF types for the target's native word size, with `usize`
+// for the fields that are 32 or 64 bits wide depending on the word size.
+
+#[allow(dead_code)]
+#[repr(C)]
+struct Ehdr {
+    e_ident: [u8; 16],
+    e_type: u16,
+    e_machine: u16,
+    e_version: u32,
+    e_entry: usize,
+    e_phoff: usize,
+    e_shoff: usize,
+    e_flags: u32,
+    e_ehsize: u16,
+    e_phentsize: u16,
+    e_phnum: u16,
+    e_shentsize: u16,
+    e_shnum: u16,
+    e_shstrndx: u16,
+}
+
+#[cfg(target_pointer_width = "64")]
+#[allow(dead_code)]
+#[repr(C)]
+pub(crate) struct Phdr {
+    pub(crate) p_type: u32,
+    pub(crate) p_flags: u32,
+    pub(crate) p_offset: usize,
+    pub(crate) p_vaddr: usize,
+    pub(crate) p_paddr: usize,
+    pub(crate) p_filesz: usize,
+    pub(crate) p_memsz: usize,
+    pub(crate) p_align: usize,
+}
+
+#[cfg(target_pointer_width = "32")]
+#[allow(dead_code)]
+#[repr(C)]
+pub(crate) struct Phdr {
+    pub(crate) p_type: u32,
+    pub(crate) p_offset: usize,
+    pub(crate) p_vaddr: usize,
+    pub(crate) p_paddr: usize,
+    pub(crate) p_filesz: usize,
+    pub(crate) p_memsz: usize,
+    pub(crate) p_flags: u32,
+    pub(crate) p_align: usize,
+}
+
+#[cfg(target_pointer_width = "64")]
+#[allow(dead_code)]
+#[repr(C)]
+struct Sym {
+    st_name: u32,
+    st_info: u8,
+    st_other: u8,
+    st_shndx: u16,
+    st_value: usize,
+    st_size: usize,
+}
+
+#[cfg(target_pointer_width = "32")]
+#[allow(dead_code)]
+#[repr(C)]
+struct Sym {
+    st_name: u32,
+    st_value: usize,
+    st_size: usize,
+    st_info: u8,
+    st_other: u8,
+    st_shndx: u16,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct Verdef {
+    vd_version: u16,
+    vd_flags: u16,
+    vd_ndx: u16,
+    vd_cnt: u16,
+    vd_hash: u32,
+    vd_aux: u32,
+    vd_next: u32,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct Verdaux {
+    vda_name: u32,
+    vda_next: u32,
+}
+
+#[repr(C)]
+struct Dyn {
+    d_tag: isize,
+    d_val: usize,
+}
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+
+const DT_NULL: isize = 0;
+const DT_HASH: isize = 4;
+const DT_STRTAB: isize = 5;
+const DT_SYMTAB: isize = 6;
+const DT_VERSYM: isize = 0x6fff_fff0;
+const DT_VERDEF: isize = 0x6fff_fffc;
+
+const STT_NOTYPE: u8 = 0;
+const STT_FUNC: u8 = 2;
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+const SHN_UNDEF: u16 = 0;
+const VER_FLG_BASE: u16 = 1;
+
+/// Look up `name` with version `version` in the vDSO, returning its address,
+/// or null if there's no vDSO or it doesn't define `name` with that version.
+pub(crate) fn lookup(version: &CStr, name: &CStr) -> *const c_void {
+    let Some(base) = auxv::sysinfo_ehdr() else {
+        return null();
+    };
+
+    // SAFETY: `AT_SYSINFO_EHDR` points to the vDSO image, which the kernel
+    // maps for the life of the process.
+    unsafe { lookup_in(base.cast::<u8>(), version, name) }
+}
+
+unsafe fn lookup_in(base: *const u8, version: &CStr, name: &CStr) -> *const c_void {
+    let ehdr = &*base.cast::<Ehdr>();
+    if ehdr.e_ident[..4] != *b"\x7fELF" {
+        return null();
+    }
+
+    // Find the load offset and the dynamic section.
+    let phdrs = base.add(ehdr.e_phoff).cast::<Phdr>();
+    let mut load_offset = None;
+    let mut dyn_ = null::<Dyn>();
+    for i in 0..usize::from(ehdr.e_phnum) {
+        let phdr = &*phdrs.add(i);
+        match phdr.p_type {
+            PT_LOAD if load_offset.is_none() => {
+                load_offset = Some(
+                    (base as usize)
+                        .wrapping_add(phdr.p_offset)
+                        .wrapping_sub(phdr.p_vaddr),
+                );
+            }
+            PT_DYNAMIC => dyn_ = base.add(phdr.p_offset).cast::<Dyn>(),
+            _ => {}
+        }
+    }
+    let (Some(load_offset), false) = (load_offset, dyn_.is_null()) else {
+        return null();
+    };
+
+    // Find the symbol table, string table, and hash table.
+    let mut symtab = null::<Sym>();
+    let mut strtab = null::<u8>();
+    let mut hash = null::<u32>();
+    let mut versym = null::<u16>();
+    let mut verdef = null::<Verdef>();
+    let mut i = 0;
+    loop {
+        let entry = &*dyn_.add(i);
+        let addr = load_offset.wrapping_add(entry.d_val);
+        match entry.d_tag {
+            DT_NULL => break,
+            DT_SYMTAB => symtab = addr as *const Sym,
+            DT_STRTAB => strtab = addr as *const u8,
+            DT_HASH => hash = addr as *const u32,
+            DT_VERSYM => versym = addr as *const u16,
+            DT_VERDEF => verdef = addr as *const Verdef,
+            _ => {}
+        }
+        i += 1;
+    }
+    if symtab.is_null() || strtab.is_null() || hash.is_null() {
+        return null();
+    }
+
+    // The second word of the SysV hash table is the number of symbols.
+    let nsyms = *hash.add(1) as usize;
+    for i in 0..nsyms {
+        let sym = &*symtab.add(i);
+        let type_ = sym.st_info & 0xf;
+        let bind = sym.st_info >> 4;
+        if (type_ != STT_FUNC && type_ != STT_NOTYPE)
+            || (bind != STB_GLOBAL && bind != STB_WEAK)
+            || sym.st_shndx == SHN_UNDEF
+        {
+            continue;
+        }
+        let sym_name = CStr::from_ptr(strtab.add(sym.st_name as usize).cast());
+        if sym_name != name {
+            continue;
+        }
+        // If the vDSO has symbol versions, the symbol's must match.
+        if !versym.is_null()
+            && !verdef.is_null()
+            && !match_version(verdef, strtab, *versym.add(i) & 0x7fff, version)
+        {
+            continue;
+        }
+        return load_offset.wrapping_add(sym.st_value) as *const c_void;
+    }
+
+    null()
+}
+
+/// Test whether version index `ndx` in the version definitions `verdef` is
+/// named `version`.
+unsafe fn match_version(
+    mut verdef: *const Verdef,
+    strtab: *const u8,
+    ndx: u16,
+    version: &CStr,
+) -> bool {
+    loop {
+        let def = &*verdef;
+        if def.vd_flags & VER_FLG_BASE == 0 && def.vd_ndx & 0x7fff == ndx {
+            let aux = &*verdef
+                .cast::<u8>()
+                .add(def.vd_aux as usize)
+                .cast::<Verdaux>();
+            return CStr::from_ptr(strtab.add(aux.vda_name as usize).cast()) == version;
+        }
+        if def.vd_next == 0 {
+            return false;
+        }
+        verdef = verdef.cast::<u8>().add(def.vd_next as usize).cast();
+    }
+}
@@ -0,0 +1,59 @@
+//! Test the `mustang::auxv` API against `getauxval`.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use mustang::auxv::{self, Entry};
+
+#[test]
+fn auxv_matches_getauxval() {
+    for key in [
+        auxv::AT_PAGESZ,
+        auxv::AT_HWCAP,
+        auxv::AT_HWCAP2,
+        auxv::AT_CLKTCK,
+        auxv::AT_SYSINFO_EHDR,
+        auxv::AT_MINSIGSTKSZ,
+    ] {
+        assert_eq!(
+            auxv::get(key).unwrap_or(0),
+            unsafe { libc::getauxval(key as _) } as usize,
+            "mismatch for key {}",
+            key
+        );
+    }
+}
+
+#[test]
+fn auxv_entries() {
+    assert!(auxv::entries().count() > 0);
+    assert_eq!(auxv::as_words().len(), auxv::raw_entries().count() * 2 + 2);
+
+    let page_size = auxv::entries()
+        .find_map(|entry| match entry {
+            Entry::Pagesz(n) => Some(n),
+            _ => None,
+        })
+        .unwrap();
+    assert!(page_size.is_power_of_two());
+
+    let random = auxv::random().unwrap();
+    assert_ne!(random, &[0; 16]);
+
+    let execfn = auxv::execfn().unwrap();
+    assert!(!execfn.to_bytes().is_empty());
+
+    // The tests aren't setuid.
+    assert!(!auxv::secure());
+}
+
+#[test]
+fn getauxval_missing() {
+    // No kernel reports key 1000.
+    unsafe {
+        *libc::__errno_location() = 0;
+        assert_eq!(libc::getauxval(1000), 0);
+        assert_eq!(*libc::__errno_location(), libc::ENOENT);
+    }
+}
@@ -0,0 +1,341 @@
+//! Test thread cancellation.
+//!
+//! `read` and `nanosleep` are only cancellation points with the
+//! "cancellation-points" feature.
+//!
+//! Cancelled threads' start routines use the `"C-unwind"` ABI, and declare
+//! the calls they're cancelled in as `"C-unwind"` too, so that cancellation
+//! can unwind through them.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use std::ffi::{c_int, c_void};
+use std::mem::transmute;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PTHREAD_CANCEL_ENABLE: c_int = 0;
+const PTHREAD_CANCEL_DISABLE: c_int = 1;
+const PTHREAD_CANCELED: *mut c_void = -1_isize as *mut c_void;
+
+/// `struct _pthread_cleanup_buffer`.
+#[repr(C)]
+struct CleanupBuffer {
+    routine: extern "C" fn(*mut c_void),
+    arg: *mut c_void,
+    canceltype: c_int,
+    prev: *mut CleanupBuffer,
+}
+
+extern "C" {
+    fn pthread_cancel(thread: libc::pthread_t) -> c_int;
+    fn pthread_setcancelstate(state: c_int, old: *mut c_int) -> c_int;
+    fn _pthread_cleanup_push(
+        buffer: *mut CleanupBuffer,
+        routine: extern "C" fn(*mut c_void),
+        arg: *mut c_void,
+    );
+    fn _pthread_cleanup_pop(buffer: *mut CleanupBuffer, execute: c_int);
+}
+
+extern "C-unwind" {
+    fn pthread_testcancel();
+    #[cfg(feature = "cancellation-points")]
+    fn read(fd: c_int, buf: *mut c_void, count: usize) -> isize;
+    #[cfg(feature = "cancellation-points")]
+    fn nanosleep(request: *const libc::timespec, remain: *mut libc::timespec) -> c_int;
+    fn pthread_cond_wait(
+        cond: *mut libc::pthread_cond_t,
+        mutex: *mut libc::pthread_mutex_t,
+    ) -> c_int;
+    fn sem_wait(sem: *mut libc::sem_t) -> c_int;
+}
+
+type Start = extern "C-unwind" fn(*mut c_void) -> *mut c_void;
+
+fn spawn(start: Start) -> libc::pthread_t {
+    let mut thread = 0;
+    unsafe {
+        let start = transmute::<Start, extern "C" fn(*mut c_void) -> *mut c_void>(start);
+        assert_eq!(
+            libc::pthread_create(&mut thread, std::ptr::null(), start, null_mut()),
+            0
+        );
+    }
+    thread
+}
+
+fn join(thread: libc::pthread_t) -> *mut c_void {
+    let mut result = null_mut();
+    assert_eq!(unsafe { libc::pthread_join(thread, &mut result) }, 0);
+    result
+}
+
+fn wait_for(flag: &AtomicBool) {
+    let start = Instant::now();
+    while !flag.load(Ordering::SeqCst) {
+        assert!(start.elapsed() < Duration::from_secs(10), "timed out");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Sets its flag when dropped.
+struct Guard(&'static AtomicBool);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+extern "C" fn set_flag(flag: *mut c_void) {
+    unsafe { &*flag.cast::<AtomicBool>() }.store(true, Ordering::SeqCst);
+}
+
+fn cleanup_buffer() -> CleanupBuffer {
+    CleanupBuffer {
+        routine: set_flag,
+        arg: null_mut(),
+        canceltype: 0,
+        prev: null_mut(),
+    }
+}
+
+#[cfg(feature = "cancellation-points")]
+fn sleep_long() {
+    let request = libc::timespec {
+        tv_sec: 10,
+        tv_nsec: 0,
+    };
+    unsafe { nanosleep(&request, null_mut()) };
+}
+
+/// Whether cancellation unwinds, running destructors.
+const UNWINDS: bool = cfg!(all(panic = "unwind", not(target_arch = "arm")));
+
+#[cfg(feature = "cancellation-points")]
+#[test]
+fn cancel_read() {
+    static READY: AtomicBool = AtomicBool::new(false);
+    static CLEANED: AtomicBool = AtomicBool::new(false);
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+    static mut FDS: [c_int; 2] = [-1; 2];
+
+    extern "C-unwind" fn start(_arg: *mut c_void) -> *mut c_void {
+        let _guard = Guard(&DROPPED);
+        let mut buffer = cleanup_buffer();
+        unsafe {
+            _pthread_cleanup_push(&mut buffer, set_flag, &CLEANED as *const _ as *mut _);
+            READY.store(true, Ordering::SeqCst);
+            let mut byte = 0_u8;
+            read(FDS[0], (&mut byte as *mut u8).cast(), 1);
+            _pthread_cleanup_pop(&mut buffer, 0);
+        }
+        null_mut()
+    }
+
+    unsafe { assert_eq!(libc::pipe(&raw mut FDS as *mut c_int), 0) };
+    let thread = spawn(start);
+    wait_for(&READY);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(unsafe { pthread_cancel(thread) }, 0);
+    assert_eq!(join(thread), PTHREAD_CANCELED);
+    assert!(CLEANED.load(Ordering::SeqCst));
+    assert_eq!(DROPPED.load(Ordering::SeqCst), UNWINDS);
+    unsafe {
+        libc::close(FDS[0]);
+        libc::close(FDS[1]);
+    }
+}
+
+#[cfg(feature = "cancellation-points")]
+#[test]
+fn cancel_nanosleep() {
+    static READY: AtomicBool = AtomicBool::new(false);
+    static CLEANED: AtomicBool = AtomicBool::new(false);
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+
+    extern "C-unwind" fn start(_arg: *mut c_void) -> *mut c_void {
+        let _guard = Guard(&DROPPED);
+        let mut buffer = cleanup_buffer();
+        unsafe {
+            _pthread_cleanup_push(&mut buffer, set_flag, &CLEANED as *const _ as *mut _);
+            READY.store(true, Ordering::SeqCst);
+            sleep_long();
+            _pthread_cleanup_pop(&mut buffer, 0);
+        }
+        null_mut()
+    }
+
+    let started = Instant::now();
+    let thread = spawn(start);
+    wait_for(&READY);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(unsafe { pthread_cancel(thread) }, 0);
+    assert_eq!(join(thread), PTHREAD_CANCELED);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(CLEANED.load(Ordering::SeqCst));
+    assert_eq!(DROPPED.load(Ordering::SeqCst), UNWINDS);
+}
+
+#[test]
+fn cancel_sem_wait() {
+    static READY: AtomicBool = AtomicBool::new(false);
+    static CLEANED: AtomicBool = AtomicBool::new(false);
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+    static mut SEM: libc::sem_t = unsafe { std::mem::zeroed() };
+
+    extern "C-unwind" fn start(_arg: *mut c_void) -> *mut c_void {
+        let _guard = Guard(&DROPPED);
+        let mut buffer = cleanup_buffer();
+        unsafe {
+            _pthread_cleanup_push(&mut buffer, set_flag, &CLEANED as *const _ as *mut _);
+            READY.store(true, Ordering::SeqCst);
+            sem_wait(&raw mut SEM);
+            _pthread_cleanup_pop(&mut buffer, 0);
+        }
+        null_mut()
+    }
+
+    unsafe { assert_eq!(libc::sem_init(&raw mut SEM, 0, 0), 0) };
+    let thread = spawn(start);
+    wait_for(&READY);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(unsafe { pthread_cancel(thread) }, 0);
+    assert_eq!(join(thread), PTHREAD_CANCELED);
+    assert!(CLEANED.load(Ordering::SeqCst));
+    assert_eq!(DROPPED.load(Ordering::SeqCst), UNWINDS);
+}
+
+#[test]
+fn cancel_cond_wait() {
+    static READY: AtomicBool = AtomicBool::new(false);
+    static HELD: AtomicBool = AtomicBool::new(false);
+    static mut MUTEX: libc::pthread_mutex_t = libc::PTHREAD_MUTEX_INITIALIZER;
+    static mut COND: libc::pthread_cond_t = libc::PTHREAD_COND_INITIALIZER;
+
+    // Unlocking an error-checking mutex fails if the thread doesn't hold it.
+    extern "C" fn unlock(_arg: *mut c_void) {
+        let result = unsafe { libc::pthread_mutex_unlock(&raw mut MUTEX) };
+        HELD.store(result == 0, Ordering::SeqCst);
+    }
+
+    extern "C-unwind" fn start(_arg: *mut c_void) -> *mut c_void {
+        let mut buffer = cleanup_buffer();
+        unsafe {
+            libc::pthread_mutex_lock(&raw mut MUTEX);
+            _pthread_cleanup_push(&mut buffer, unlock, null_mut());
+            READY.store(true, Ordering::SeqCst);
+            loop {
+                pthread_cond_wait(&raw mut COND, &raw mut MUTEX);
+            }
+        }
+    }
+
+    unsafe {
+        let mut attr = std::mem::zeroed();
+        assert_eq!(libc::pthread_mutexattr_init(&mut attr), 0);
+        assert_eq!(
+            libc::pthread_mutexattr_settype(&mut attr, libc::PTHREAD_MUTEX_ERRORCHECK),
+            0
+        );
+        assert_eq!(libc::pthread_mutex_init(&raw mut MUTEX, &attr), 0);
+    }
+    let thread = spawn(start);
+    wait_for(&READY);
+    unsafe {
+        // The mutex is free once the thread is waiting.
+        assert_eq!(libc::pthread_mutex_lock(&raw mut MUTEX), 0);
+        assert_eq!(pthread_cancel(thread), 0);
+        // The thread can't act on cancellation until it has the mutex.
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(libc::pthread_mutex_unlock(&raw mut MUTEX), 0);
+    }
+    assert_eq!(join(thread), PTHREAD_CANCELED);
+    // The cleanup handler ran with the mutex held, and released it.
+    assert!(HELD.load(Ordering::SeqCst));
+    unsafe {
+        assert_eq!(libc::pthread_mutex_lock(&raw mut MUTEX), 0);
+        assert_eq!(libc::pthread_mutex_unlock(&raw mut MUTEX), 0);
+    }
+}
+
+#[test]
+fn disabled() {
+    static READY: AtomicBool = AtomicBool::new(false);
+    static SENT: AtomicBool = AtomicBool::new(false);
+    static SURVIVED: AtomicBool = AtomicBool::new(false);
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+
+    extern "C-unwind" fn start(_arg: *mut c_void) -> *mut c_void {
+        let _guard = Guard(&DROPPED);
+        unsafe {
+            pthread_setcancelstate(PTHREAD_CANCEL_DISABLE, null_mut());
+            READY.store(true, Ordering::SeqCst);
+            wait_for(&SENT);
+
+            // Blocking calls aren't cancelled while cancellation is
+            // disabled.
+            thread::sleep(Duration::from_millis(50));
+            SURVIVED.store(true, Ordering::SeqCst);
+
+            let mut old = -1;
+            pthread_setcancelstate(PTHREAD_CANCEL_ENABLE, &mut old);
+            assert_eq!(old, PTHREAD_CANCEL_DISABLE);
+            pthread_testcancel();
+        }
+        null_mut()
+    }
+
+    let thread = spawn(start);
+    wait_for(&READY);
+    assert_eq!(unsafe { pthread_cancel(thread) }, 0);
+    SENT.store(true, Ordering::SeqCst);
+    assert_eq!(join(thread), PTHREAD_CANCELED);
+    assert!(SURVIVED.load(Ordering::SeqCst));
+    assert_eq!(DROPPED.load(Ordering::SeqCst), UNWINDS);
+}
+
+#[cfg(feature = "cancellation-points")]
+#[test]
+fn cancel_self() {
+    static CLEANED: AtomicBool = AtomicBool::new(false);
+
+    extern "C-unwind" fn start(_arg: *mut c_void) -> *mut c_void {
+        let mut buffer = cleanup_buffer();
+        unsafe {
+            _pthread_cleanup_push(&mut buffer, set_flag, &CLEANED as *const _ as *mut _);
+            // Cancellation takes effect at the next blocking call.
+            pthread_cancel(libc::pthread_self());
+            sleep_long();
+            _pthread_cleanup_pop(&mut buffer, 0);
+        }
+        null_mut()
+    }
+
+    assert_eq!(join(spawn(start)), PTHREAD_CANCELED);
+    assert!(CLEANED.load(Ordering::SeqCst));
+}
+
+#[test]
+fn cleanup_pop() {
+    static RAN: AtomicBool = AtomicBool::new(false);
+
+    let mut buffer = cleanup_buffer();
+    unsafe {
+        _pthread_cleanup_push(&mut buffer, set_flag, &RAN as *const _ as *mut _);
+        _pthread_cleanup_pop(&mut buffer, 1);
+    }
+    assert!(RAN.load(Ordering::SeqCst));
+}
+
+#[test]
+fn invalid() {
+    unsafe {
+        assert_eq!(pthread_setcancelstate(2, null_mut()), libc::EINVAL);
+    }
+}
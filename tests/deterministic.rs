@@ -0,0 +1,151 @@
+//! Test the "deterministic" feature's seeded randomness and virtual clock.
+//!
+//! Tests which set the seed run this binary again, running the `helper` test
+//! with `MUSTANG_DETERMINISTIC_HELPER` set, and compare what it prints.
+
+#![cfg(all(target_vendor = "mustang", feature = "deterministic"))]
+
+mustang::can_run_this!();
+
+use mustang::deterministic::{self, REALTIME_START, SEED_ENV, STEP};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::process::{Command, Output};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const HELPER_ENV: &str = "MUSTANG_DETERMINISTIC_HELPER";
+
+/// Run the helper with the given seed.
+fn run_helper(seed: &str) -> Output {
+    Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "helper", "--nocapture", "--test-threads=1"])
+        .env(HELPER_ENV, "1")
+        .env(SEED_ENV, seed)
+        .output()
+        .unwrap()
+}
+
+/// Run the helper, check that it succeeded, and return what it printed.
+fn helper_output(seed: &str) -> Vec<String> {
+    let output = run_helper(seed);
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("deterministic: "))
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn helper() {
+    if std::env::var_os(HELPER_ENV).is_none() {
+        return;
+    }
+    let mut bytes = [0_u8; 32];
+    let len = unsafe { libc::getrandom(bytes.as_mut_ptr().cast(), bytes.len(), 0) };
+    assert_eq!(len, bytes.len() as isize);
+    println!("deterministic: seed {:#x}", deterministic::seed());
+    println!("deterministic: getrandom {:02x?}", bytes);
+    println!("deterministic: at_random {:02x?}", mustang::auxv::random());
+    println!(
+        "deterministic: hash {:#x}",
+        RandomState::new().hash_one(1234)
+    );
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    println!("deterministic: now {:?}", now);
+}
+
+#[test]
+fn reproducible() {
+    let first = helper_output("42");
+    assert_eq!(first.len(), 5);
+    assert_eq!(first[0], "seed 0x2a");
+    assert_eq!(first, helper_output("42"));
+    assert_eq!(helper_output("0x2a"), first);
+
+    let other = helper_output("43");
+    for (line, other) in first.iter().zip(&other) {
+        if !line.starts_with("now ") {
+            assert_ne!(line, other);
+        }
+    }
+}
+
+#[test]
+fn invalid_seed() {
+    let output = run_helper("forty-two");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("invalid MUSTANG_SEED"), "{}", stderr);
+}
+
+#[test]
+fn virtual_clock() {
+    let a = Instant::now();
+    let b = Instant::now();
+    let delta = b - a;
+    assert!(delta >= STEP);
+    assert_eq!(delta.as_nanos() % STEP.as_nanos(), 0);
+
+    // The other tests sleep for a little while, and read the clock a few
+    // times.
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    assert!(now > REALTIME_START);
+    assert!(now < REALTIME_START + Duration::from_secs(60));
+}
+
+#[test]
+fn sleep_advances_clock() {
+    let before = deterministic::elapsed();
+    let a = Instant::now();
+    std::thread::sleep(Duration::from_millis(20));
+    let b = Instant::now();
+    assert!(b - a >= Duration::from_millis(20));
+    assert!(deterministic::elapsed() - before >= Duration::from_millis(20));
+}
+
+#[test]
+fn c_clocks() {
+    let limit = (REALTIME_START + Duration::from_secs(60)).as_secs();
+    let t = unsafe { libc::time(std::ptr::null_mut()) };
+    assert!((t as u64) < limit, "{}", t);
+    let mut tv = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    assert_eq!(
+        unsafe { libc::gettimeofday(&mut tv, std::ptr::null_mut()) },
+        0
+    );
+    assert!((tv.tv_sec as u64) < limit, "{:?}", tv.tv_sec);
+}
+
+#[test]
+fn child_unaffected() {
+    // Nothing is installed in the kernel, so the process keeps its
+    // privileges, and programs it runs read the real clock.
+    let fields = |path: String| {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .filter(|line| line.starts_with("NoNewPrivs:") || line.starts_with("Seccomp"))
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    let parent = fields(format!(
+        "/proc/{}/status",
+        std::os::unix::process::parent_id()
+    ));
+    assert!(!parent.is_empty());
+    assert_eq!(fields("/proc/self/status".to_owned()), parent);
+
+    let output = Command::new("date").arg("+%s").output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let secs: u64 = String::from_utf8(output.stdout)
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    assert!(secs > (REALTIME_START + Duration::from_secs(60)).as_secs());
+}
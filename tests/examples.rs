@@ -17,7 +17,20 @@ macro_rules! assert_eq_str {
     }};
 }
 
-fn test_example(name: &str, features: &str, stdout: &str, stderr: &str) {
+fn example_command(
+    name: &str,
+    features: &str,
+) -> (std::process::Command, &'static str, &'static str) {
+    cargo_example("run", name, features)
+}
+
+/// A `cargo <subcommand>` command for an example, with the target's
+/// architecture and environment names.
+fn cargo_example(
+    subcommand: &str,
+    name: &str,
+    features: &str,
+) -> (std::process::Command, &'static str, &'static str) {
     use std::process::Command;
 
     #[cfg(target_arch = "x86_64")]
@@ -41,7 +54,7 @@ fn test_example(name: &str, features: &str, stdout: &str, stderr: &str) {
     if which::which("rustup").is_ok() {
         command.arg("+nightly-2025-01-02");
     }
-    command.arg("run").arg("--quiet");
+    command.arg(subcommand).arg("--quiet");
     if !features.is_empty() {
         command
             .arg("--no-default-features")
@@ -57,20 +70,57 @@ fn test_example(name: &str, features: &str, stdout: &str, stderr: &str) {
         ))
         .arg("--example")
         .arg(name);
+    (command, arch, env)
+}
+
+fn test_example(name: &str, features: &str, stdout: &str, stderr: &str) {
+    let (mut command, arch, env) = example_command(name, features);
     let output = command.output().unwrap();
 
     assert_eq_str!(
-        stderr.as_bytes(),
-        &output.stderr,
-        "example {} had unexpected stderr, with {:?}",
+        stdout.as_bytes(),
+        &output.stdout,
+        "example {} had unexpected stdout, with {:?}",
+        name,
+        output
+    );
+    check_example(name, arch, env, stderr, &output);
+}
+
+/// Like `test_example`, but for examples whose output depends on the host,
+/// only check that each line of stdout is accepted by `line_ok`.
+fn test_example_lines(name: &str, features: &str, line_ok: fn(&str) -> bool, stderr: &str) {
+    let (mut command, arch, env) = example_command(name, features);
+    let output = command.output().unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        !stdout.is_empty(),
+        "example {} had no stdout, with {:?}",
         name,
         output
     );
+    for line in stdout.lines() {
+        assert!(
+            line_ok(line),
+            "example {} had unexpected stdout line {:?}, with {:?}",
+            name,
+            line,
+            output
+        );
+    }
+    check_example(name, arch, env, stderr, &output);
+}
+
+/// Check an example's stderr and exit status, and that it's linked
+/// statically.
+fn check_example(name: &str, arch: &str, env: &str, stderr: &str, output: &std::process::Output) {
+    use std::process::Command;
 
     assert_eq_str!(
-        stdout.as_bytes(),
-        &output.stdout,
-        "example {} had unexpected stdout, with {:?}",
+        stderr.as_bytes(),
+        &output.stderr,
+        "example {} had unexpected stderr, with {:?}",
         name,
         output
     );
@@ -125,4 +175,272 @@ fn test_examples() {
     test_example("test-workdir", "", "", "");
     test_example("test-simd", "", "", "");
     test_example("test-tls", "", "", "");
+    test_example_lines(
+        "cpu-features",
+        "",
+        |line| {
+            line.starts_with("AT_HWCAP") || line.ends_with(": true") || line.ends_with(": false")
+        },
+        "",
+    );
+}
+
+/// Run an example which is expected to abort with the message `stderr`.
+fn test_example_aborts(mut command: std::process::Command, name: &str, stderr: &str) {
+    let output = command.output().unwrap();
+
+    assert_eq_str!(
+        stderr.as_bytes(),
+        &output.stderr,
+        "example {} had unexpected stderr, with {:?}",
+        name,
+        output
+    );
+    assert_eq!(
+        std::os::unix::process::ExitStatusExt::signal(&output.status),
+        Some(libc::SIGABRT),
+        "example {} didn't abort, with {:?}",
+        name,
+        output
+    );
+}
+
+#[test]
+fn test_stack_protector() {
+    // Build in a separate target directory, so that the different flags don't
+    // invalidate the main build.
+    let (mut command, _arch, _env) = example_command("test-stack-protector", "");
+    command
+        .env("RUSTFLAGS", "-Z stack-protector=strong")
+        .env("CARGO_TARGET_DIR", "target/stack-protector");
+    test_example_aborts(
+        command,
+        "test-stack-protector",
+        "mustang: stack smashing detected\n",
+    );
+}
+
+#[test]
+fn test_stack_protector_all() {
+    // Protect every function in mustang, including its startup functions,
+    // whose frames must not be live while the guard is seeded. Origin's
+    // frames which call them aren't mustang's to protect, so this is set for
+    // this package only.
+    let (mut command, _arch, _env) = example_command("test-stack-protector", "");
+    command
+        .args(["-Z", "profile-rustflags", "--config"])
+        .arg("profile.dev.package.mustang.rustflags=[\"-Zstack-protector=all\"]")
+        .env("CARGO_TARGET_DIR", "target/stack-protector-all");
+    test_example_aborts(
+        command,
+        "test-stack-protector",
+        "mustang: stack smashing detected\n",
+    );
+}
+
+#[test]
+fn test_stack_overflow() {
+    let (mut command, _arch, _env) = example_command("test-stack-overflow", "");
+    let output = command.output().unwrap();
+
+    // Newer versions of std print the thread id after the name.
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("thread 'main'") && stderr.contains("has overflowed its stack\n"),
+        "example test-stack-overflow had unexpected stderr, with {:?}",
+        output
+    );
+    assert_eq!(
+        std::os::unix::process::ExitStatusExt::signal(&output.status),
+        Some(libc::SIGABRT),
+        "example test-stack-overflow didn't abort, with {:?}",
+        output
+    );
+}
+
+#[test]
+fn test_fortify() {
+    let (command, _arch, _env) = example_command("test-fortify", "");
+    test_example_aborts(
+        command,
+        "test-fortify",
+        "mustang: buffer overflow detected\n",
+    );
+}
+
+#[test]
+fn test_fortify_c() {
+    use std::process::Command;
+
+    let (mut command, arch, env) = example_command("test-fortify-c", "");
+    let target = format!("{}-mustang-linux-{}", arch, env);
+
+    // Build the example without the C code first, for c-scape's and
+    // mustang's rlibs, which mustang-headers reads the exported symbols of.
+    let status = cargo_example("build", "test-fortify-c", "")
+        .0
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "building test-fortify-c failed with {:?}",
+        status
+    );
+    let deps = std::env::current_dir()
+        .unwrap()
+        .join("target")
+        .join(&target)
+        .join("debug/deps");
+    let rlibs = std::fs::read_dir(&deps)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            (name.starts_with("libc_scape-") || name.starts_with("libmustang-"))
+                && name.ends_with(".rlib")
+        })
+        .collect::<Vec<_>>();
+
+    // Generate the headers, and compile the C code against them with
+    // `_FORTIFY_SOURCE`, with the C compiler configured for the target.
+    let dir = std::env::current_dir().unwrap().join("target/fortify-c");
+    let sysroot = dir.join("sysroot");
+    let status = Command::new("cargo")
+        .args([
+            "run",
+            "--quiet",
+            "--manifest-path",
+            "mustang-headers/Cargo.toml",
+            "--",
+        ])
+        .arg(&sysroot)
+        .args(&rlibs)
+        .status()
+        .unwrap();
+    assert!(status.success(), "mustang-headers failed with {:?}", status);
+    let cc = std::env::var(format!("CC_{}", target))
+        .or_else(|_| std::env::var(format!("CC_{}", target.replace('-', "_"))))
+        .unwrap_or_else(|_| "cc".to_owned());
+    let object = dir.join("test-fortify-c.o");
+    let status = Command::new("mustang-headers/mustang-cc")
+        .env("MUSTANG_CC", &cc)
+        .env("MUSTANG_SYSROOT", &sysroot)
+        .env("MUSTANG_TARGET", &target)
+        .args(["-c", "-O2", "-D_FORTIFY_SOURCE=2"])
+        .arg("examples/test-fortify-c.c")
+        .arg("-o")
+        .arg(&object)
+        .status()
+        .unwrap();
+    assert!(status.success(), "mustang-cc failed with {:?}", status);
+
+    // The headers' `strcpy` wrapper calls the checked function.
+    let nm = Command::new("nm").arg(&object).output().unwrap();
+    let symbols = String::from_utf8(nm.stdout).unwrap();
+    assert!(
+        symbols
+            .lines()
+            .any(|line| line.ends_with(" U __strcpy_chk")),
+        "test-fortify-c.o doesn't call __strcpy_chk:\n{}",
+        symbols
+    );
+
+    // Link it in, in a separate target directory, so that the different
+    // flags don't invalidate the main build.
+    command
+        .env("RUSTFLAGS", format!("-C link-arg={}", object.display()))
+        .env("CARGO_TARGET_DIR", "target/fortify-c");
+    test_example_aborts(
+        command,
+        "test-fortify-c",
+        "mustang: buffer overflow detected\n",
+    );
+}
+
+#[test]
+fn test_tls_dynamic() {
+    // The accesses use a dynamic model: they call `__tls_get_addr`, or on
+    // targets which default to TLS descriptors, have descriptor relocations.
+    test_tls_dynamic_with("tls-dynamic", &[], |symbols, relocs| {
+        symbols
+            .lines()
+            .any(|line| line.ends_with(" U __tls_get_addr") || line.ends_with(" U ___tls_get_addr"))
+            || relocs.contains("TLSDESC")
+    });
+}
+
+// GCC only supports TLS descriptors on RISC-V from version 14.
+#[cfg(not(target_arch = "riscv64"))]
+#[test]
+fn test_tls_descriptors() {
+    #[cfg(target_arch = "aarch64")]
+    let dialect = "-mtls-dialect=desc";
+    #[cfg(not(target_arch = "aarch64"))]
+    let dialect = "-mtls-dialect=gnu2";
+
+    test_tls_dynamic_with("tls-descriptors", &[dialect], |_symbols, relocs| {
+        relocs.contains("TLSDESC")
+    });
+}
+
+/// Compile the C code for the `test-tls-dynamic` example as
+/// position-independent code, with `flags`, check that `uses_model` accepts
+/// its undefined symbols and relocations, and link it into the example and
+/// run it, in `target/<dir>`.
+fn test_tls_dynamic_with(dir: &str, flags: &[&str], uses_model: fn(&str, &str) -> bool) {
+    use std::process::Command;
+
+    let (mut command, arch, env) = example_command("test-tls-dynamic", "");
+
+    // Compile it with the C compiler configured for the target.
+    let target = format!("{}-mustang-linux-{}", arch, env);
+    let cc = std::env::var(format!("CC_{}", target))
+        .or_else(|_| std::env::var(format!("CC_{}", target.replace('-', "_"))))
+        .unwrap_or_else(|_| "cc".to_owned());
+    let target_dir = std::env::current_dir().unwrap().join("target").join(dir);
+    std::fs::create_dir_all(&target_dir).unwrap();
+    let object = target_dir.join("test-tls-dynamic.o");
+    let status = Command::new(&cc)
+        .args(["-c", "-O2", "-fPIC", "-ftls-model=global-dynamic"])
+        .args(flags)
+        .arg("examples/test-tls-dynamic.c")
+        .arg("-o")
+        .arg(&object)
+        .status()
+        .unwrap();
+    assert!(status.success(), "{} failed with {:?}", cc, status);
+
+    let nm = Command::new("nm").arg(&object).output().unwrap();
+    let symbols = String::from_utf8(nm.stdout).unwrap();
+    let relocs = Command::new("readelf")
+        .arg("-r")
+        .arg(&object)
+        .output()
+        .unwrap();
+    let relocs = String::from_utf8(relocs.stdout).unwrap();
+    assert!(
+        uses_model(&symbols, &relocs),
+        "test-tls-dynamic.o doesn't use the expected TLS model:\n{}\n{}",
+        symbols,
+        relocs
+    );
+
+    // Link it in, in a separate target directory, so that the different
+    // flags don't invalidate the main build.
+    command
+        .env("RUSTFLAGS", format!("-C link-arg={}", object.display()))
+        .env("CARGO_TARGET_DIR", &target_dir);
+    let output = command.output().unwrap();
+
+    assert_eq_str!(
+        "".as_bytes(),
+        &output.stderr,
+        "example test-tls-dynamic had unexpected stderr, with {:?}",
+        output
+    );
+    assert!(
+        output.status.success(),
+        "example test-tls-dynamic failed with {:?}",
+        output
+    );
 }
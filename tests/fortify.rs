@@ -0,0 +1,96 @@
+//! Test the `_FORTIFY_SOURCE` checked functions' behavior when the buffers
+//! are big enough. See the `test-fortify` example for the failing case.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use std::ffi::{c_char, c_int, c_void, CStr};
+
+extern "C" {
+    fn __memcpy_chk(dst: *mut c_void, src: *const c_void, len: usize, dstlen: usize)
+        -> *mut c_void;
+    fn __stpcpy_chk(dst: *mut c_char, src: *const c_char, dstlen: usize) -> *mut c_char;
+    fn __strncpy_chk(dst: *mut c_char, src: *const c_char, n: usize, dstlen: usize) -> *mut c_char;
+    fn __strcat_chk(dst: *mut c_char, src: *const c_char, dstlen: usize) -> *mut c_char;
+    fn __strlcpy_chk(dst: *mut c_char, src: *const c_char, size: usize, dstlen: usize) -> usize;
+    fn __snprintf_chk(
+        s: *mut c_char,
+        maxlen: usize,
+        flag: c_int,
+        slen: usize,
+        fmt: *const c_char,
+        ...
+    ) -> c_int;
+    fn __read_chk(fd: c_int, buf: *mut c_void, count: usize, buflen: usize) -> isize;
+}
+
+#[test]
+fn mem_and_str() {
+    unsafe {
+        let mut buf = [0_u8; 8];
+        __memcpy_chk(
+            buf.as_mut_ptr().cast(),
+            b"abcd".as_ptr().cast(),
+            4,
+            buf.len(),
+        );
+        assert_eq!(&buf[..4], b"abcd");
+
+        let mut buf = [0 as c_char; 8];
+        let end = __stpcpy_chk(buf.as_mut_ptr(), c"abc".as_ptr(), buf.len());
+        assert_eq!(end, buf.as_mut_ptr().add(3));
+        __strcat_chk(buf.as_mut_ptr(), c"defg".as_ptr(), buf.len());
+        assert_eq!(CStr::from_ptr(buf.as_ptr()), c"abcdefg");
+
+        let mut buf = [1 as c_char; 8];
+        __strncpy_chk(buf.as_mut_ptr(), c"ab".as_ptr(), buf.len(), buf.len());
+        assert_eq!(buf, [b'a' as c_char, b'b' as c_char, 0, 0, 0, 0, 0, 0]);
+
+        let mut buf = [0 as c_char; 4];
+        assert_eq!(
+            __strlcpy_chk(
+                buf.as_mut_ptr(),
+                c"truncated".as_ptr(),
+                buf.len(),
+                buf.len()
+            ),
+            9
+        );
+        assert_eq!(CStr::from_ptr(buf.as_ptr()), c"tru");
+    }
+}
+
+#[test]
+fn snprintf() {
+    unsafe {
+        let mut buf = [0 as c_char; 16];
+        let n = __snprintf_chk(
+            buf.as_mut_ptr(),
+            buf.len(),
+            1,
+            buf.len(),
+            c"%d-%s".as_ptr(),
+            42 as c_int,
+            c"x".as_ptr(),
+        );
+        assert_eq!(n, 4);
+        assert_eq!(CStr::from_ptr(buf.as_ptr()), c"42-x");
+    }
+}
+
+#[test]
+fn read() {
+    let file = std::fs::File::open("/dev/zero").unwrap();
+    let mut buf = [1_u8; 8];
+    let n = unsafe {
+        __read_chk(
+            std::os::fd::AsRawFd::as_raw_fd(&file),
+            buf.as_mut_ptr().cast(),
+            4,
+            buf.len(),
+        )
+    };
+    assert_eq!(n, 4);
+    assert_eq!(buf, [0, 0, 0, 0, 1, 1, 1, 1]);
+}
@@ -0,0 +1,123 @@
+//! Test encoding and decoding of `mustang::args` structured argument blocks.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use mustang::args::{encode, encoded_len, Block, ParseError};
+
+#[test]
+fn round_trip() {
+    let args = ["prog", "héllo", "", "with spaces"];
+    let vars = [("A", "1"), ("B", "x=y"), ("EMPTY", "")];
+    let mut buf = vec![0; encoded_len(&args, &vars)];
+    encode(&mut buf, &args, &vars);
+
+    let block = Block::parse(&buf).unwrap();
+    assert_eq!(block.argc(), args.len());
+    assert_eq!(block.envc(), vars.len());
+    assert_eq!(block.args().collect::<Vec<_>>(), args);
+    assert_eq!(block.vars().collect::<Vec<_>>(), vars);
+}
+
+#[test]
+fn invalid_blocks() {
+    let args = ["prog", "arg"];
+    let vars = [("A", "1")];
+    let mut buf = vec![0; encoded_len(&args, &vars)];
+    encode(&mut buf, &args, &vars);
+    let len = buf.len();
+
+    assert_eq!(Block::parse(&buf[..4]).unwrap_err(), ParseError::BadMagic);
+    assert_eq!(
+        Block::parse(&buf[..len - 1]).unwrap_err(),
+        ParseError::BadLength
+    );
+
+    let mut trailing = buf.clone();
+    trailing.push(0);
+    assert_eq!(Block::parse(&trailing).unwrap_err(), ParseError::BadLength);
+
+    let mut bad_utf8 = buf.clone();
+    bad_utf8[len - 2] = 0xff;
+    assert_eq!(
+        Block::parse(&bad_utf8).unwrap_err(),
+        ParseError::InvalidUtf8
+    );
+
+    let mut missing_nul = buf.clone();
+    missing_nul[len - 1] = b'x';
+    assert_eq!(Block::parse(&missing_nul).unwrap_err(), ParseError::BadNul);
+
+    let mut missing_equals = buf.clone();
+    missing_equals[len - 3] = b'B';
+    assert_eq!(
+        Block::parse(&missing_equals).unwrap_err(),
+        ParseError::MissingEquals
+    );
+}
+
+#[cfg(feature = "memfd-args")]
+#[test]
+fn launch() {
+    use std::process::Command;
+
+    let dir = std::env::current_exe().unwrap();
+    let examples = dir.parent().unwrap().parent().unwrap().join("examples");
+    let launcher = examples.join("memfd-launch");
+    let program = examples.join("test-memfd-args");
+    // cargo builds the examples for the tests, with the tests' features.
+    assert!(launcher.exists(), "{} wasn't built", launcher.display());
+    assert!(program.exists(), "{} wasn't built", program.display());
+
+    let output = Command::new(&launcher)
+        .arg(&program)
+        .arg("a b")
+        .arg("ünïcödé")
+        .env("MUSTANG_TEST_VAR", "value")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{}\na b\nünïcödé\nMUSTANG_TEST_VAR=value\n",
+            program.display()
+        )
+    );
+}
+
+#[cfg(feature = "memfd-args")]
+#[test]
+fn rejected_fds() {
+    use mustang::args::FD_ENV;
+    use rustix::fd::AsRawFd;
+    use std::process::Command;
+
+    let dir = std::env::current_exe().unwrap();
+    let program = dir
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .join("examples/test-memfd-args");
+    assert!(program.exists(), "{} wasn't built", program.display());
+    let run = |fd: &str| {
+        let output = Command::new(&program).env(FD_ENV, fd).output().unwrap();
+        assert!(!output.status.success(), "{:?}", output);
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let stderr = run("-1");
+    assert!(stderr.contains("invalid MUSTANG_ARGS_FD"), "{}", stderr);
+
+    // The memfd is inherited, but it could be changed after it's validated.
+    let fd = rustix::fs::memfd_create("unsealed", rustix::fs::MemfdFlags::empty()).unwrap();
+    rustix::io::write(&fd, b"MUSTARG1\0\0\0\0\0\0\0\0").unwrap();
+    let stderr = run(&fd.as_raw_fd().to_string());
+    assert!(
+        stderr.contains("MUSTANG_ARGS_FD isn't sealed"),
+        "{}",
+        stderr
+    );
+}
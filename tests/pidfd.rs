@@ -0,0 +1,91 @@
+//! Test `mustang::process`'s pidfd API.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use mustang::process::{ChildExt, CommandExt, PidFd};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, Stdio};
+
+#[test]
+fn spawn_and_wait() {
+    let pidfd = PidFd::spawn(c"/bin/sh", &[c"sh", c"-c", c"exit 5"], None).unwrap();
+    let status = pidfd.wait().unwrap();
+    assert_eq!(status.code(), Some(5));
+
+    // Once reaped, the process is gone.
+    assert_eq!(pidfd.pid().unwrap_err().raw_os_error(), Some(libc::ESRCH));
+    assert_eq!(
+        pidfd.send_signal(libc::SIGTERM).unwrap_err().raw_os_error(),
+        Some(libc::ESRCH)
+    );
+}
+
+#[test]
+fn spawn_env() {
+    let pidfd = PidFd::spawn(
+        c"/bin/sh",
+        &[c"sh", c"-c", c"test \"$MUSTANG_PIDFD_TEST\" = yes"],
+        Some(&[c"MUSTANG_PIDFD_TEST=yes"]),
+    )
+    .unwrap();
+    assert_eq!(pidfd.wait().unwrap().code(), Some(0));
+}
+
+#[test]
+fn spawn_missing() {
+    let err = PidFd::spawn(c"/nonexistent", &[c"nonexistent"], None).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
+}
+
+#[test]
+fn signal_and_try_wait() {
+    let pidfd = PidFd::spawn(c"/bin/sleep", &[c"sleep", c"60"], None).unwrap();
+    assert!(pidfd.pid().unwrap() > 0);
+    assert!(pidfd.try_wait().unwrap().is_none());
+
+    pidfd.send_signal(libc::SIGKILL).unwrap();
+    let status = pidfd.wait().unwrap();
+    assert_eq!(status.signal(), Some(libc::SIGKILL));
+}
+
+#[test]
+fn child_ext() {
+    let (mut child, _) = Command::new("sleep").arg("60").spawn_pidfd().unwrap();
+    let pidfd = child.pidfd().unwrap();
+    assert_eq!(pidfd.pid().unwrap(), child.id());
+
+    child.send_signal(libc::SIGTERM).unwrap();
+    let status = child.wait().unwrap();
+    assert_eq!(status.signal(), Some(libc::SIGTERM));
+}
+
+#[test]
+fn child_ext_without_pidfd() {
+    // Without a pidfd from spawning, there's no race-free way to get one.
+    let mut child = Command::new("sleep").arg("60").spawn().unwrap();
+    assert!(child.pidfd().is_err());
+    assert!(child.send_signal(libc::SIGTERM).is_err());
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn command_ext() {
+    let (mut child, pidfd) = Command::new("sh")
+        .args(["-c", "exit 3"])
+        .stdout(Stdio::null())
+        .spawn_pidfd()
+        .unwrap();
+    assert_eq!(pidfd.pid().unwrap(), child.id());
+
+    // The pidfd becomes readable when the child exits.
+    let mut pollfd = libc::pollfd {
+        fd: std::os::fd::AsRawFd::as_raw_fd(&pidfd),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    assert_eq!(unsafe { libc::poll(&mut pollfd, 1, -1) }, 1);
+    assert_eq!(child.wait().unwrap().code(), Some(3));
+}
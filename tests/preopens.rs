@@ -0,0 +1,73 @@
+//! Test passing preopens to the `test-preopens` example.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+/// Run the example with a preopen named `/data` holding `hello.txt`, after
+/// letting `configure` adjust the command, and check its output.
+#[cfg(feature = "preopens")]
+fn run_example(configure: impl FnOnce(&mut std::process::Command)) {
+    use std::os::fd::AsRawFd;
+    use std::process::Command;
+
+    let dir = std::env::current_exe().unwrap();
+    let program = dir
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .join("examples/test-preopens");
+    // cargo builds the examples for the tests, with the tests' features.
+    assert!(program.exists(), "{} wasn't built", program.display());
+
+    let tmp = std::env::temp_dir().join(format!(
+        "mustang-preopens-{}-{:?}",
+        std::process::id(),
+        std::thread::current().id()
+    ));
+    std::fs::create_dir_all(&tmp).unwrap();
+    std::fs::write(tmp.join("hello.txt"), "hello, preopens\n").unwrap();
+
+    let data = std::fs::File::open(&tmp).unwrap();
+    let fd = data.as_raw_fd();
+    assert_ne!(unsafe { libc::fcntl(fd, libc::F_SETFD, 0) }, -1);
+
+    let mut command = Command::new(&program);
+    command.env("MUSTANG_PREOPENS", format!("{}=/data", fd));
+    configure(&mut command);
+    let output = command.output().unwrap();
+    std::fs::remove_dir_all(&tmp).unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "/data\nhello, preopens\n"
+    );
+}
+
+#[cfg(feature = "preopens")]
+#[test]
+fn preopens() {
+    run_example(|_| {});
+}
+
+#[cfg(feature = "preopens-enforce")]
+#[test]
+fn preopens_enforce_unlimited_stack() {
+    use std::os::unix::process::CommandExt;
+
+    // With an unlimited `RLIMIT_STACK`, finding the main thread's stack
+    // bounds reads `/proc/self/maps`, which Landlock would deny.
+    run_example(|command| unsafe {
+        command.pre_exec(|| {
+            let mut limit = std::mem::zeroed::<libc::rlimit>();
+            libc::getrlimit(libc::RLIMIT_STACK, &mut limit);
+            limit.rlim_cur = limit.rlim_max;
+            if libc::setrlimit(libc::RLIMIT_STACK, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    });
+}
@@ -0,0 +1,319 @@
+//! Test the robust, priority-inheritance and process-shared mutexes which
+//! mustang binds `pthread_mutex_*` and `pthread_cond_*` to. These tests call
+//! the standard names, as Rust code using the `libc` crate's bindings does.
+//!
+//! Mutexes shared between processes live in `MAP_SHARED` mappings which
+//! forked children inherit.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use std::ffi::c_int;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use libc::{pthread_cond_t, pthread_condattr_t, pthread_mutex_t, pthread_mutexattr_t, timespec};
+
+extern "C" {
+    fn pthread_mutexattr_init(attr: *mut pthread_mutexattr_t) -> c_int;
+    fn pthread_mutexattr_settype(attr: *mut pthread_mutexattr_t, type_: c_int) -> c_int;
+    fn pthread_mutexattr_setpshared(attr: *mut pthread_mutexattr_t, pshared: c_int) -> c_int;
+    fn pthread_mutexattr_setrobust(attr: *mut pthread_mutexattr_t, robust: c_int) -> c_int;
+    fn pthread_mutexattr_setprotocol(attr: *mut pthread_mutexattr_t, protocol: c_int) -> c_int;
+    fn pthread_mutex_init(mutex: *mut pthread_mutex_t, attr: *const pthread_mutexattr_t) -> c_int;
+    fn pthread_mutex_lock(mutex: *mut pthread_mutex_t) -> c_int;
+    fn pthread_mutex_trylock(mutex: *mut pthread_mutex_t) -> c_int;
+    fn pthread_mutex_timedlock(mutex: *mut pthread_mutex_t, abstime: *const timespec) -> c_int;
+    fn pthread_mutex_unlock(mutex: *mut pthread_mutex_t) -> c_int;
+    fn pthread_mutex_consistent(mutex: *mut pthread_mutex_t) -> c_int;
+    fn pthread_cond_init(cond: *mut pthread_cond_t, attr: *const pthread_condattr_t) -> c_int;
+    fn pthread_cond_wait(cond: *mut pthread_cond_t, mutex: *mut pthread_mutex_t) -> c_int;
+    fn pthread_cond_timedwait(
+        cond: *mut pthread_cond_t,
+        mutex: *mut pthread_mutex_t,
+        abstime: *const timespec,
+    ) -> c_int;
+    fn pthread_cond_signal(cond: *mut pthread_cond_t) -> c_int;
+}
+
+/// A mutex, and a counter it protects, in memory shared with children.
+struct Shared {
+    mutex: pthread_mutex_t,
+    counter: u64,
+    ready: AtomicBool,
+}
+
+/// Map a zeroed `Shared`, and initialize its mutex with the given
+/// attributes.
+fn shared(type_: c_int, robust: bool, pi: bool) -> &'static mut Shared {
+    unsafe {
+        let shared = libc::mmap(
+            null_mut(),
+            std::mem::size_of::<Shared>(),
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_ne!(shared, libc::MAP_FAILED);
+        let shared = &mut *shared.cast::<Shared>();
+
+        let mut attr = std::mem::zeroed();
+        assert_eq!(pthread_mutexattr_init(&mut attr), 0);
+        assert_eq!(pthread_mutexattr_settype(&mut attr, type_), 0);
+        assert_eq!(
+            pthread_mutexattr_setpshared(&mut attr, libc::PTHREAD_PROCESS_SHARED),
+            0
+        );
+        if robust {
+            assert_eq!(
+                pthread_mutexattr_setrobust(&mut attr, libc::PTHREAD_MUTEX_ROBUST),
+                0
+            );
+        }
+        if pi {
+            assert_eq!(
+                pthread_mutexattr_setprotocol(&mut attr, libc::PTHREAD_PRIO_INHERIT),
+                0
+            );
+        }
+        assert_eq!(pthread_mutex_init(&mut shared.mutex, &attr), 0);
+        shared
+    }
+}
+
+/// Run `f` in a forked child, and return its pid.
+fn fork(f: impl FnOnce() -> bool) -> libc::pid_t {
+    unsafe {
+        match libc::fork() {
+            0 => libc::_exit(if f() { 0 } else { 1 }),
+            -1 => panic!("fork failed"),
+            pid => pid,
+        }
+    }
+}
+
+fn wait(pid: libc::pid_t) {
+    let mut status = 0;
+    unsafe {
+        assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+    }
+    assert!(libc::WIFEXITED(status));
+    assert_eq!(libc::WEXITSTATUS(status), 0);
+}
+
+/// An absolute `CLOCK_REALTIME` time `ms` milliseconds from now.
+fn after(ms: i64) -> timespec {
+    let mut now = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut now) };
+    let nsec = now.tv_nsec as i64 + ms * 1_000_000;
+    timespec {
+        tv_sec: now.tv_sec + (nsec / 1_000_000_000) as libc::time_t,
+        tv_nsec: (nsec % 1_000_000_000) as _,
+    }
+}
+
+/// Fork a child which locks `mutex` and exits without unlocking it.
+fn die_holding(mutex: *mut pthread_mutex_t) {
+    wait(fork(|| unsafe { pthread_mutex_lock(mutex) == 0 }));
+}
+
+fn owner_dies(pi: bool) {
+    let shared = shared(libc::PTHREAD_MUTEX_NORMAL, true, pi);
+    let mutex = &raw mut shared.mutex;
+    die_holding(mutex);
+    unsafe {
+        assert_eq!(pthread_mutex_lock(mutex), libc::EOWNERDEAD);
+        assert_eq!(pthread_mutex_consistent(mutex), 0);
+        assert_eq!(pthread_mutex_unlock(mutex), 0);
+
+        // Once it's consistent, it's an ordinary mutex again.
+        assert_eq!(pthread_mutex_lock(mutex), 0);
+        assert_eq!(pthread_mutex_consistent(mutex), libc::EINVAL);
+        assert_eq!(pthread_mutex_unlock(mutex), 0);
+    }
+}
+
+#[test]
+fn robust_owner_dies() {
+    owner_dies(false);
+}
+
+#[test]
+fn robust_pi_owner_dies() {
+    owner_dies(true);
+}
+
+#[test]
+fn robust_not_recoverable() {
+    let shared = shared(libc::PTHREAD_MUTEX_NORMAL, true, false);
+    let mutex = &raw mut shared.mutex;
+    die_holding(mutex);
+    unsafe {
+        assert_eq!(pthread_mutex_lock(mutex), libc::EOWNERDEAD);
+        assert_eq!(pthread_mutex_unlock(mutex), 0);
+        assert_eq!(pthread_mutex_lock(mutex), libc::ENOTRECOVERABLE);
+        assert_eq!(pthread_mutex_trylock(mutex), libc::ENOTRECOVERABLE);
+    }
+}
+
+#[test]
+fn robust_waiter_woken() {
+    let shared = shared(libc::PTHREAD_MUTEX_NORMAL, true, false);
+    let mutex = &raw mut shared.mutex;
+    let ready = &shared.ready;
+    let pid = fork(|| unsafe {
+        if pthread_mutex_lock(mutex) != 0 {
+            return false;
+        }
+        ready.store(true, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(100));
+        true
+    });
+    while !ready.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(1));
+    }
+    unsafe {
+        // The kernel wakes us when the child exits.
+        assert_eq!(pthread_mutex_lock(mutex), libc::EOWNERDEAD);
+        assert_eq!(pthread_mutex_consistent(mutex), 0);
+        assert_eq!(pthread_mutex_unlock(mutex), 0);
+    }
+    wait(pid);
+}
+
+#[test]
+fn robust_thread_dies() {
+    let shared = shared(libc::PTHREAD_MUTEX_NORMAL, true, false);
+    let mutex = &raw mut shared.mutex as usize;
+    thread::spawn(move || unsafe {
+        assert_eq!(pthread_mutex_lock(mutex as *mut _), 0);
+    })
+    .join()
+    .unwrap();
+    unsafe {
+        assert_eq!(pthread_mutex_lock(mutex as *mut _), libc::EOWNERDEAD);
+        assert_eq!(pthread_mutex_consistent(mutex as *mut _), 0);
+        assert_eq!(pthread_mutex_unlock(mutex as *mut _), 0);
+    }
+}
+
+#[test]
+fn pi_contention() {
+    const N: u64 = 10_000;
+
+    let shared = shared(libc::PTHREAD_MUTEX_NORMAL, false, true);
+    let mutex = &raw mut shared.mutex;
+    let counter = &raw mut shared.counter;
+    let count = move || unsafe {
+        for _ in 0..N {
+            assert_eq!(pthread_mutex_lock(mutex), 0);
+            *counter += 1;
+            assert_eq!(pthread_mutex_unlock(mutex), 0);
+        }
+        true
+    };
+    let pids = [fork(count), fork(count)];
+    count();
+    for pid in pids {
+        wait(pid);
+    }
+    assert_eq!(shared.counter, 3 * N);
+}
+
+#[test]
+fn errorcheck() {
+    let shared = shared(libc::PTHREAD_MUTEX_ERRORCHECK, false, false);
+    let mutex = &raw mut shared.mutex;
+    unsafe {
+        assert_eq!(pthread_mutex_unlock(mutex), libc::EPERM);
+        assert_eq!(pthread_mutex_lock(mutex), 0);
+        assert_eq!(pthread_mutex_lock(mutex), libc::EDEADLK);
+        assert_eq!(pthread_mutex_unlock(mutex), 0);
+    }
+}
+
+#[test]
+fn recursive() {
+    let shared = shared(libc::PTHREAD_MUTEX_RECURSIVE, false, false);
+    let mutex = &raw mut shared.mutex;
+    unsafe {
+        assert_eq!(pthread_mutex_lock(mutex), 0);
+        assert_eq!(pthread_mutex_trylock(mutex), 0);
+        assert_eq!(pthread_mutex_unlock(mutex), 0);
+        assert_eq!(pthread_mutex_unlock(mutex), 0);
+        assert_eq!(pthread_mutex_unlock(mutex), libc::EPERM);
+    }
+}
+
+#[test]
+fn timedlock() {
+    for pi in [false, true] {
+        let shared = shared(libc::PTHREAD_MUTEX_NORMAL, false, pi);
+        let mutex = &raw mut shared.mutex as usize;
+        let locked = AtomicU32::new(0);
+        thread::scope(|s| {
+            s.spawn(|| unsafe {
+                assert_eq!(pthread_mutex_lock(mutex as *mut _), 0);
+                locked.store(1, Ordering::SeqCst);
+                while locked.load(Ordering::SeqCst) != 2 {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                assert_eq!(pthread_mutex_unlock(mutex as *mut _), 0);
+            });
+            while locked.load(Ordering::SeqCst) != 1 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            unsafe {
+                assert_eq!(pthread_mutex_trylock(mutex as *mut _), libc::EBUSY);
+                assert_eq!(
+                    pthread_mutex_timedlock(mutex as *mut _, &after(50)),
+                    libc::ETIMEDOUT
+                );
+            }
+            locked.store(2, Ordering::SeqCst);
+        });
+        unsafe {
+            assert_eq!(pthread_mutex_timedlock(mutex as *mut _, &after(1000)), 0);
+            assert_eq!(pthread_mutex_unlock(mutex as *mut _), 0);
+        }
+    }
+}
+
+#[test]
+fn condvar() {
+    let shared = shared(libc::PTHREAD_MUTEX_NORMAL, true, true);
+    let mutex = &raw mut shared.mutex as usize;
+    let counter = &raw mut shared.counter as usize;
+    let mut cond = unsafe { std::mem::zeroed() };
+    unsafe { assert_eq!(pthread_cond_init(&mut cond, std::ptr::null()), 0) };
+    let cond = &raw mut cond as usize;
+
+    unsafe {
+        assert_eq!(pthread_mutex_lock(mutex as *mut _), 0);
+        assert_eq!(
+            pthread_cond_timedwait(cond as *mut _, mutex as *mut _, &after(10)),
+            libc::ETIMEDOUT
+        );
+    }
+    thread::scope(|s| {
+        s.spawn(|| unsafe {
+            assert_eq!(pthread_mutex_lock(mutex as *mut _), 0);
+            *(counter as *mut u64) = 1;
+            assert_eq!(pthread_cond_signal(cond as *mut _), 0);
+            assert_eq!(pthread_mutex_unlock(mutex as *mut _), 0);
+        });
+        unsafe {
+            while *(counter as *mut u64) == 0 {
+                assert_eq!(pthread_cond_wait(cond as *mut _, mutex as *mut _), 0);
+            }
+            assert_eq!(pthread_mutex_unlock(mutex as *mut _), 0);
+        }
+    });
+}
@@ -0,0 +1,258 @@
+//! Test naming, affinity and scheduling of threads with `pthread_setname_np`
+//! and friends, which mustang binds to its own implementations.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use std::ffi::{c_char, c_int, c_uint, c_void, CStr};
+use std::mem::{size_of, zeroed, MaybeUninit};
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+
+use libc::{
+    cpu_set_t, pid_t, pthread_create, pthread_getaffinity_np, pthread_getname_np,
+    pthread_getschedparam, pthread_setaffinity_np, pthread_setname_np, pthread_setschedparam,
+    pthread_t, sched_attr, sched_param,
+};
+
+extern "C" {
+    fn sched_setattr(pid: pid_t, attr: *mut sched_attr, flags: c_uint) -> c_int;
+    fn sched_getattr(pid: pid_t, attr: *mut sched_attr, size: c_uint, flags: c_uint) -> c_int;
+}
+
+/// A thread created with `pthread_create`, which waits until it's
+/// told to exit.
+struct Thread {
+    thread: pthread_t,
+    tid: pid_t,
+    /// The thread's argument, which says when to exit.
+    state: Box<(Sender<pid_t>, AtomicBool)>,
+}
+
+impl Thread {
+    fn start() -> Self {
+        extern "C" fn start(arg: *mut c_void) -> *mut c_void {
+            // SAFETY: `Thread` keeps its `state` live until the thread is
+            // joined.
+            let (sender, exit) = unsafe { &*arg.cast::<(Sender<pid_t>, AtomicBool)>() };
+            sender.send(unsafe { libc::gettid() }).unwrap();
+            while !exit.load(Ordering::Acquire) {
+                std::thread::yield_now();
+            }
+            null_mut()
+        }
+
+        let (sender, receiver) = channel();
+        let state = Box::new((sender, AtomicBool::new(false)));
+        let mut thread = MaybeUninit::uninit();
+        unsafe {
+            assert_eq!(
+                pthread_create(
+                    thread.as_mut_ptr(),
+                    std::ptr::null(),
+                    start,
+                    &*state as *const _ as *mut c_void
+                ),
+                0
+            );
+        }
+        let tid = receiver.recv().unwrap();
+        Self {
+            thread: unsafe { thread.assume_init() },
+            tid,
+            state,
+        }
+    }
+
+    fn comm(&self) -> String {
+        std::fs::read_to_string(format!("/proc/self/task/{}/comm", self.tid)).unwrap()
+    }
+}
+
+impl Drop for Thread {
+    fn drop(&mut self) {
+        self.state.1.store(true, Ordering::Release);
+        unsafe {
+            assert_eq!(libc::pthread_join(self.thread, null_mut()), 0);
+        }
+    }
+}
+
+fn get_name(thread: pthread_t) -> String {
+    let mut buf = [0 as c_char; 16];
+    unsafe {
+        assert_eq!(pthread_getname_np(thread, buf.as_mut_ptr(), buf.len()), 0);
+        CStr::from_ptr(buf.as_ptr()).to_str().unwrap().to_owned()
+    }
+}
+
+#[test]
+fn name_self() {
+    std::thread::spawn(|| unsafe {
+        let this = libc::pthread_self();
+        assert_eq!(pthread_setname_np(this, c"self-named".as_ptr()), 0);
+        assert_eq!(
+            std::fs::read_to_string("/proc/thread-self/comm").unwrap(),
+            "self-named\n"
+        );
+        assert_eq!(get_name(this), "self-named");
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn name_other() {
+    let thread = Thread::start();
+    unsafe {
+        assert_eq!(
+            pthread_setname_np(thread.thread, c"other-named".as_ptr()),
+            0
+        );
+    }
+    assert_eq!(thread.comm(), "other-named\n");
+    assert_eq!(get_name(thread.thread), "other-named");
+}
+
+#[test]
+fn name_errors() {
+    let thread = Thread::start();
+    let mut buf = [0 as c_char; 15];
+    unsafe {
+        assert_eq!(
+            pthread_setname_np(thread.thread, c"sixteen-letters!".as_ptr()),
+            libc::ERANGE
+        );
+        assert_eq!(
+            pthread_getname_np(thread.thread, buf.as_mut_ptr(), buf.len()),
+            libc::ERANGE
+        );
+    }
+}
+
+#[test]
+fn name_std_thread() {
+    // Threads spawned by std are named the same way.
+    let (sender, receiver) = channel();
+    let (exit, wait) = channel::<()>();
+    let std_thread = std::thread::spawn(move || {
+        sender
+            .send(unsafe { (libc::pthread_self(), libc::gettid()) })
+            .unwrap();
+        wait.recv().ok();
+    });
+    let (std_pthread, tid) = receiver.recv().unwrap();
+    unsafe {
+        assert_eq!(pthread_setname_np(std_pthread, c"std-named".as_ptr()), 0);
+    }
+    assert_eq!(
+        std::fs::read_to_string(format!("/proc/self/task/{}/comm", tid)).unwrap(),
+        "std-named\n"
+    );
+    assert_eq!(get_name(std_pthread), "std-named");
+    drop(exit);
+    std_thread.join().unwrap();
+}
+
+#[test]
+fn affinity() {
+    let thread = Thread::start();
+    unsafe {
+        let mut all: cpu_set_t = zeroed();
+        assert_eq!(
+            pthread_getaffinity_np(thread.thread, size_of::<cpu_set_t>(), &mut all),
+            0
+        );
+        let cpu = (0..libc::CPU_SETSIZE as usize)
+            .find(|cpu| libc::CPU_ISSET(*cpu, &all))
+            .unwrap();
+
+        let mut one: cpu_set_t = zeroed();
+        libc::CPU_SET(cpu, &mut one);
+        assert_eq!(
+            pthread_setaffinity_np(thread.thread, size_of::<cpu_set_t>(), &one),
+            0
+        );
+        let mut got: cpu_set_t = zeroed();
+        assert_eq!(
+            pthread_getaffinity_np(thread.thread, size_of::<cpu_set_t>(), &mut got),
+            0
+        );
+        assert_eq!(libc::CPU_COUNT(&got), 1);
+        assert!(libc::CPU_ISSET(cpu, &got));
+
+        // The calling thread, once pinned, runs on that CPU.
+        std::thread::spawn(move || {
+            assert_eq!(
+                pthread_setaffinity_np(libc::pthread_self(), size_of::<cpu_set_t>(), &one),
+                0
+            );
+            assert_eq!(libc::sched_getcpu(), cpu as c_int);
+        })
+        .join()
+        .unwrap();
+    }
+}
+
+#[test]
+fn sched_param() {
+    let thread = Thread::start();
+    unsafe {
+        let mut policy = -1;
+        let mut param: sched_param = zeroed();
+        assert_eq!(
+            pthread_getschedparam(thread.thread, &mut policy, &mut param),
+            0
+        );
+        assert_eq!(policy, libc::SCHED_OTHER);
+        assert_eq!(param.sched_priority, 0);
+
+        assert_eq!(
+            pthread_setschedparam(thread.thread, libc::SCHED_BATCH, &param),
+            0
+        );
+        assert_eq!(
+            pthread_getschedparam(thread.thread, &mut policy, &mut param),
+            0
+        );
+        assert_eq!(policy, libc::SCHED_BATCH);
+
+        // Real-time policies need privileges.
+        param.sched_priority = 1;
+        let err = pthread_setschedparam(thread.thread, libc::SCHED_FIFO, &param);
+        assert!(err == 0 || err == libc::EPERM, "{}", err);
+
+        param.sched_priority = 0;
+        assert_eq!(
+            pthread_setschedparam(thread.thread, libc::SCHED_OTHER, &param),
+            0
+        );
+    }
+}
+
+#[test]
+fn sched_attr() {
+    std::thread::spawn(|| unsafe {
+        let mut attr: sched_attr = zeroed();
+        attr.size = size_of::<sched_attr>() as u32;
+        attr.sched_policy = libc::SCHED_OTHER as u32;
+        attr.sched_nice = 5;
+        assert_eq!(sched_setattr(0, &mut attr, 0), 0);
+
+        let mut got: sched_attr = zeroed();
+        assert_eq!(
+            sched_getattr(0, &mut got, size_of::<sched_attr>() as c_uint, 0),
+            0
+        );
+        assert_eq!(got.sched_policy, libc::SCHED_OTHER as u32);
+        assert_eq!(got.sched_nice, 5);
+
+        attr.size = 1;
+        assert_eq!(sched_setattr(0, &mut attr, 0), -1);
+        assert_eq!(*libc::__errno_location(), libc::E2BIG);
+    })
+    .join()
+    .unwrap();
+}
@@ -0,0 +1,200 @@
+//! Test recording and replaying syscall results with the "record-replay"
+//! feature.
+//!
+//! The tests run this binary again, running the `helper` test with
+//! `MUSTANG_RECORD_REPLAY_HELPER` set, naming what to do, and with
+//! `MUSTANG_RECORD` or `MUSTANG_REPLAY` set, and compare what it prints.
+
+#![cfg(all(target_vendor = "mustang", feature = "record-replay"))]
+
+mustang::can_run_this!();
+
+use mustang::replay::{self, Mode, RECORD_ENV, REPLAY_ENV};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+const HELPER_ENV: &str = "MUSTANG_RECORD_REPLAY_HELPER";
+
+/// Return a path for a recording, unique to this process and `name`.
+fn recording(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "mustang-record-replay-{}-{}",
+        std::process::id(),
+        name
+    ))
+}
+
+/// Run the helper, doing `what`, with `var` set to `path`.
+fn run_helper(what: &str, var: &str, path: &Path) -> Output {
+    Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "helper", "--nocapture", "--test-threads=1"])
+        .env(HELPER_ENV, what)
+        .env(var, path)
+        .output()
+        .unwrap()
+}
+
+/// Run the helper, check that it succeeded, and return what it printed,
+/// apart from the mode.
+fn helper_output(what: &str, var: &str, path: &Path) -> Vec<String> {
+    let output = run_helper(what, var, path);
+    assert!(output.status.success(), "{:?}", output);
+    printed(&output)
+}
+
+/// Return what the helper printed, apart from the mode.
+fn printed(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once("record-replay: "))
+        .map(|(_, line)| line.to_owned())
+        .filter(|line| !line.starts_with("mode "))
+        .collect()
+}
+
+#[test]
+fn helper() {
+    let Ok(what) = std::env::var(HELPER_ENV) else {
+        return;
+    };
+    println!("record-replay: mode {:?}", replay::mode());
+    // The variables are removed from the environment.
+    assert!(std::env::var_os(RECORD_ENV).is_none());
+    assert!(std::env::var_os(REPLAY_ENV).is_none());
+
+    if what == "mutex" {
+        contend();
+        return;
+    }
+    if what == "diverge" {
+        let _ = SystemTime::now();
+    }
+
+    let mut bytes = [0_u8; 16];
+    let len = unsafe { libc::getrandom(bytes.as_mut_ptr().cast(), bytes.len(), 0) };
+    assert_eq!(len, bytes.len() as isize);
+    println!("record-replay: getrandom {:02x?}", bytes);
+    println!(
+        "record-replay: hash {:#x}",
+        RandomState::new().hash_one(1234)
+    );
+    println!("record-replay: now {:?}", SystemTime::now());
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    println!("record-replay: port {}", addr.port());
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"ping").unwrap();
+        let mut buf = [0_u8; 4];
+        stream.read_exact(&mut buf).unwrap();
+        buf
+    });
+    let (mut stream, peer) = listener.accept().unwrap();
+    println!("record-replay: peer port {}", peer.port());
+    let mut buf = [0_u8; 4];
+    stream.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"ping");
+    stream.write_all(b"pong").unwrap();
+    assert_eq!(&client.join().unwrap(), b"pong");
+}
+
+/// Have threads contend on a `Mutex`, making a recorded syscall while
+/// holding it.
+fn contend() {
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let threads = (0..4)
+        .map(|_| {
+            let results = results.clone();
+            thread::spawn(move || {
+                for _ in 0..50 {
+                    let mut results = results.lock().unwrap();
+                    let mut bytes = [0_u8; 8];
+                    let len = unsafe { libc::getrandom(bytes.as_mut_ptr().cast(), bytes.len(), 0) };
+                    assert_eq!(len, bytes.len() as isize);
+                    results.push(u64::from_ne_bytes(bytes));
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let results = results.lock().unwrap();
+    println!(
+        "record-replay: mutex {:#x}",
+        results.iter().fold(0, |acc, x| acc ^ x.rotate_left(7))
+    );
+}
+
+#[test]
+fn not_recording() {
+    if std::env::var_os(HELPER_ENV).is_none() {
+        assert_eq!(replay::mode(), Mode::Off);
+    }
+}
+
+#[test]
+fn record_and_replay() {
+    let path = recording("tcp");
+    let recorded = helper_output("tcp", RECORD_ENV, &path);
+    assert_eq!(recorded.len(), 5);
+    assert_eq!(helper_output("tcp", REPLAY_ENV, &path), recorded);
+
+    // Another recording gets other results.
+    let other = recording("other");
+    let rerecorded = helper_output("tcp", RECORD_ENV, &other);
+    assert_ne!(rerecorded[0], recorded[0]);
+    assert_ne!(rerecorded[2], recorded[2]);
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(other).unwrap();
+}
+
+#[test]
+fn diverge() {
+    let path = recording("diverge");
+    helper_output("tcp", RECORD_ENV, &path);
+    let output = run_helper("diverge", REPLAY_ENV, &path);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("mustang: replay diverged"), "{}", stderr);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn mutex_contention() {
+    // The threads can take the lock in a different order when replaying,
+    // which makes the replay stall; it's reported rather than hanging.
+    let path = recording("mutex");
+    let recorded = helper_output("mutex", RECORD_ENV, &path);
+    assert_eq!(recorded.len(), 1);
+    for _ in 0..3 {
+        let output = run_helper("mutex", REPLAY_ENV, &path);
+        if output.status.success() {
+            assert_eq!(printed(&output), recorded);
+        } else {
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(stderr.contains("mustang: replay stalled"), "{}", stderr);
+        }
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn not_a_recording() {
+    let path = recording("garbage");
+    std::fs::write(&path, b"not a recording").unwrap();
+    let output = run_helper("tcp", REPLAY_ENV, &path);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("isn't a recording"), "{}", stderr);
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,114 @@
+//! Test `mustang::sandbox`.
+//!
+//! Landlock rulesets apply to the thread that applies them, so each test
+//! applies its ruleset in a new thread.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use mustang::sandbox::{abi_version, AccessFs, AccessNet, Sandbox};
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::process::Command;
+
+#[test]
+fn sandbox_fs() {
+    if abi_version().is_err() {
+        // Landlock isn't available.
+        return;
+    }
+
+    let tmp = std::env::temp_dir().join(format!("mustang-sandbox-{}", std::process::id()));
+    std::fs::create_dir_all(&tmp).unwrap();
+    std::fs::write(tmp.join("file"), "contents").unwrap();
+
+    let dir = tmp.clone();
+    std::thread::spawn(move || {
+        Sandbox::new(AccessFs::all(), AccessNet::empty())
+            .unwrap()
+            .allow_path(&dir, AccessFs::READ)
+            .unwrap()
+            .restrict_self()
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.join("file")).unwrap(),
+            "contents"
+        );
+        assert_eq!(
+            std::fs::write(dir.join("file"), "changed")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            std::fs::read_dir("/").map(|_| ()).unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+    })
+    .join()
+    .unwrap();
+
+    // The rest of the process is unaffected.
+    std::fs::read_dir("/").unwrap();
+    std::fs::remove_dir_all(&tmp).unwrap();
+}
+
+#[test]
+fn sandbox_net() {
+    if !abi_version().is_ok_and(|abi| abi >= 4) {
+        // Landlock network rules aren't available.
+        return;
+    }
+
+    let allowed = TcpListener::bind("127.0.0.1:0").unwrap();
+    let denied = TcpListener::bind("127.0.0.1:0").unwrap();
+    let allowed_addr = allowed.local_addr().unwrap();
+    let denied_addr = denied.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        let sandbox = Sandbox::new(AccessFs::empty(), AccessNet::all()).unwrap();
+        assert_eq!(sandbox.handled_net(), AccessNet::all());
+        sandbox
+            .allow_tcp_connect(allowed_addr.port())
+            .unwrap()
+            .restrict_self()
+            .unwrap();
+
+        TcpStream::connect(allowed_addr).unwrap();
+        assert_eq!(
+            TcpStream::connect(denied_addr).unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            TcpListener::bind("127.0.0.1:0").unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn sandbox_declared() {
+    if !abi_version().is_ok_and(|abi| abi >= 4) {
+        // The example declares a network rule, which needs ABI 4.
+        return;
+    }
+
+    let dir = std::env::current_exe().unwrap();
+    let program = dir
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .join("examples/test-sandbox");
+    if !program.exists() {
+        return;
+    }
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "sandboxed\n");
+}
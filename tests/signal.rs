@@ -0,0 +1,376 @@
+//! Test `mustang::signal`.
+//!
+//! Each test uses its own signal, as tests run concurrently.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use mustang::signal::{
+    ensure_altstack, register, register_flag, sigqueue, sigtimedwait, sigwaitinfo, unregister,
+    SelfPipe, SignalFd, ALTSTACK_SIZE, FORBIDDEN, NSIG, RESERVED, SIGRTMAX, SIGRTMIN,
+};
+use std::os::unix::thread::JoinHandleExt;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn flag() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+
+    let id = register_flag(libc::SIGUSR1, &FLAG).unwrap();
+    assert_eq!(id.signal(), libc::SIGUSR1);
+    unsafe { libc::raise(libc::SIGUSR1) };
+    assert!(FLAG.load(Ordering::SeqCst));
+
+    assert!(unregister(id));
+    assert!(!unregister(id));
+}
+
+#[test]
+fn self_pipe() {
+    let pipe = SelfPipe::new(&[libc::SIGUSR2]).unwrap();
+    assert_eq!(pipe.read().unwrap(), None);
+
+    unsafe { libc::raise(libc::SIGUSR2) };
+    unsafe { libc::raise(libc::SIGUSR2) };
+    assert_eq!(pipe.read().unwrap(), Some(libc::SIGUSR2));
+    assert_eq!(pipe.read().unwrap(), Some(libc::SIGUSR2));
+    assert_eq!(pipe.read().unwrap(), None);
+}
+
+#[test]
+fn self_pipe_repeated() {
+    // Each signal is registered once, however many times it's listed.
+    let sig = SIGRTMIN + 3;
+    let pipe = SelfPipe::new(&[sig; NSIG as usize + 1]).unwrap();
+    unsafe { libc::raise(sig) };
+    assert_eq!(pipe.read().unwrap(), Some(sig));
+    assert_eq!(pipe.read().unwrap(), None);
+}
+
+#[test]
+fn reregister_while_dispatching() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    static STOP: AtomicBool = AtomicBool::new(false);
+
+    fn handler(_info: &libc::siginfo_t) {
+        CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // Keep sending the signal to another thread while registrations of
+    // different kinds take turns in the same slots. A dispatcher must never
+    // run one registration's action with another's value.
+    let sig = SIGRTMIN + 4;
+    let id = register_flag(sig, &FLAG).unwrap();
+    let target = thread::spawn(|| {
+        while !STOP.load(Ordering::SeqCst) {
+            thread::yield_now();
+        }
+    });
+    let pthread = target.as_pthread_t();
+    let sender = thread::spawn(move || {
+        while !STOP.load(Ordering::SeqCst) {
+            unsafe { libc::pthread_kill(pthread, sig) };
+        }
+    });
+
+    for i in 0..10_000 {
+        let (a, b) = unsafe {
+            if i % 2 == 0 {
+                (register(sig, handler), register_flag(sig, &FLAG))
+            } else {
+                (register_flag(sig, &FLAG), register(sig, handler))
+            }
+        };
+        assert!(unregister(a.unwrap()));
+        assert!(unregister(b.unwrap()));
+    }
+
+    STOP.store(true, Ordering::SeqCst);
+    sender.join().unwrap();
+    target.join().unwrap();
+    assert!(unregister(id));
+}
+
+#[test]
+fn signalfd() {
+    // Use a thread of our own, so that blocking the signal doesn't affect
+    // other tests.
+    thread::spawn(|| {
+        let fd = SignalFd::new(&[libc::SIGWINCH]).unwrap();
+        assert!(fd.read().unwrap().is_none());
+
+        unsafe { libc::pthread_kill(libc::pthread_self(), libc::SIGWINCH) };
+        let info = fd.read().unwrap().unwrap();
+        assert_eq!(info.ssi_signo, libc::SIGWINCH as u32);
+        assert_eq!(info.ssi_pid, std::process::id());
+        assert!(fd.read().unwrap().is_none());
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn forbidden() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+
+    for sig in FORBIDDEN {
+        assert_eq!(
+            register_flag(sig, &FLAG).unwrap_err(),
+            rustix::io::Errno::INVAL
+        );
+    }
+    assert_eq!(
+        register_flag(0, &FLAG).unwrap_err(),
+        rustix::io::Errno::INVAL
+    );
+    assert_eq!(
+        register_flag(65, &FLAG).unwrap_err(),
+        rustix::io::Errno::INVAL
+    );
+}
+
+#[test]
+fn std_thread_delivery() {
+    static TID: AtomicI32 = AtomicI32::new(0);
+    static ON_STACK: AtomicBool = AtomicBool::new(false);
+
+    fn handler(_info: &libc::siginfo_t) {
+        TID.store(unsafe { libc::gettid() }, Ordering::SeqCst);
+
+        // The dispatcher runs on the thread's alternate signal stack.
+        let mut stack: libc::stack_t = unsafe { std::mem::zeroed() };
+        unsafe { libc::sigaltstack(std::ptr::null(), &mut stack) };
+        ON_STACK.store(stack.ss_flags & libc::SS_ONSTACK != 0, Ordering::SeqCst);
+    }
+
+    let id = unsafe { register(libc::SIGURG, handler) }.unwrap();
+
+    let (tid_sender, tid_receiver) = mpsc::channel();
+    let (done_sender, done_receiver) = mpsc::channel::<()>();
+    let thread = thread::spawn(move || {
+        tid_sender.send(unsafe { libc::gettid() }).unwrap();
+        done_receiver.recv().unwrap();
+    });
+    let tid = tid_receiver.recv().unwrap();
+
+    unsafe { libc::pthread_kill(thread.as_pthread_t(), libc::SIGURG) };
+    while TID.load(Ordering::SeqCst) == 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(TID.load(Ordering::SeqCst), tid);
+    assert!(ON_STACK.load(Ordering::SeqCst));
+
+    done_sender.send(()).unwrap();
+    thread.join().unwrap();
+    assert!(unregister(id));
+}
+
+#[test]
+fn altstack() {
+    thread::spawn(|| {
+        ensure_altstack().unwrap();
+
+        let mut stack: libc::stack_t = unsafe { std::mem::zeroed() };
+        unsafe { libc::sigaltstack(std::ptr::null(), &mut stack) };
+        assert_eq!(stack.ss_flags & libc::SS_DISABLE, 0);
+
+        // A second call keeps the existing stack.
+        ensure_altstack().unwrap();
+        let mut again: libc::stack_t = unsafe { std::mem::zeroed() };
+        unsafe { libc::sigaltstack(std::ptr::null(), &mut again) };
+        assert_eq!(again.ss_sp, stack.ss_sp);
+    })
+    .join()
+    .unwrap();
+}
+
+#[cfg(feature = "thread")]
+#[test]
+fn thread_altstack() {
+    /// Return the size of the calling thread's alternate signal stack.
+    fn altstack_size() -> usize {
+        let mut stack: libc::stack_t = unsafe { std::mem::zeroed() };
+        unsafe { libc::sigaltstack(std::ptr::null(), &mut stack) };
+        assert_eq!(stack.ss_flags & libc::SS_DISABLE, 0);
+        stack.ss_size
+    }
+
+    extern "C" fn start(_arg: *mut std::ffi::c_void) -> *mut std::ffi::c_void {
+        altstack_size() as *mut std::ffi::c_void
+    }
+
+    // Threads get mustang's alternate stack as they start, and std keeps it.
+    assert_eq!(thread::spawn(altstack_size).join().unwrap(), ALTSTACK_SIZE);
+
+    let mut thread = std::mem::MaybeUninit::uninit();
+    let mut size = std::ptr::null_mut();
+    unsafe {
+        assert_eq!(
+            libc::pthread_create(
+                thread.as_mut_ptr(),
+                std::ptr::null(),
+                start,
+                std::ptr::null_mut()
+            ),
+            0
+        );
+        assert_eq!(libc::pthread_join(thread.assume_init(), &mut size), 0);
+    }
+    assert_eq!(size as usize, ALTSTACK_SIZE);
+}
+
+#[test]
+fn restart() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+
+    let id = register_flag(libc::SIGPROF, &FLAG).unwrap();
+    let (read, write) = rustix::pipe::pipe().unwrap();
+
+    // Interrupt a blocking read; with `SA_RESTART` it resumes rather than
+    // failing with `EINTR`.
+    let reader = thread::spawn(move || {
+        let mut buf = [0_u8; 1];
+        rustix::io::read(&read, &mut buf).map(|n| (n, buf[0]))
+    });
+    thread::sleep(Duration::from_millis(50));
+    unsafe { libc::pthread_kill(reader.as_pthread_t(), libc::SIGPROF) };
+    while !FLAG.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(1));
+    }
+    rustix::io::write(&write, b"x").unwrap();
+    assert_eq!(reader.join().unwrap(), Ok((1, b'x')));
+
+    assert!(unregister(id));
+}
+
+#[test]
+fn rt_numbering() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+
+    assert_eq!(SIGRTMIN, 34);
+    assert_eq!(SIGRTMAX, 64);
+    assert_eq!(libc::SIGRTMIN(), SIGRTMIN);
+    assert_eq!(libc::SIGRTMAX(), SIGRTMAX);
+
+    for sig in RESERVED {
+        assert!(sig < SIGRTMIN);
+        assert_eq!(
+            register_flag(sig, &FLAG).unwrap_err(),
+            rustix::io::Errno::INVAL
+        );
+    }
+}
+
+#[test]
+fn sigqueue_handler() {
+    static VALUE: AtomicUsize = AtomicUsize::new(0);
+
+    fn handler(info: &libc::siginfo_t) {
+        if info.si_code == libc::SI_QUEUE {
+            VALUE.store(
+                unsafe { info.si_value() }.sival_ptr as usize,
+                Ordering::SeqCst,
+            );
+        }
+    }
+
+    let sig = SIGRTMIN + 2;
+    let id = unsafe { register(sig, handler) }.unwrap();
+    let value = libc::sigval {
+        sival_ptr: 0x1234 as *mut _,
+    };
+    sigqueue(std::process::id() as i32, sig, value).unwrap();
+    while VALUE.load(Ordering::SeqCst) == 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(VALUE.load(Ordering::SeqCst), 0x1234);
+    assert!(unregister(id));
+}
+
+/// Run `f` in a forked child, in which there are no other threads to take
+/// the signals it blocks.
+fn in_child(f: fn() -> bool) {
+    unsafe {
+        match libc::fork() {
+            0 => libc::_exit(if f() { 0 } else { 1 }),
+            -1 => panic!("fork failed"),
+            pid => {
+                let mut status = 0;
+                assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+                assert!(libc::WIFEXITED(status));
+                assert_eq!(libc::WEXITSTATUS(status), 0);
+            }
+        }
+    }
+}
+
+#[test]
+fn sigqueue_sigwaitinfo() {
+    in_child(|| unsafe {
+        let sig = SIGRTMIN + 1;
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, sig);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+
+        // Real-time signals are queued, in order, each with its payload.
+        let pid = libc::getpid();
+        for n in 1..=3 {
+            let value = libc::sigval {
+                sival_ptr: n as *mut _,
+            };
+            if sigqueue(pid, sig, value).is_err() {
+                return false;
+            }
+        }
+        for n in 1..=3 {
+            let info = match sigwaitinfo(&set) {
+                Ok(info) => info,
+                Err(_) => return false,
+            };
+            if info.si_signo != sig
+                || info.si_code != libc::SI_QUEUE
+                || info.si_pid() != pid
+                || info.si_value().sival_ptr as usize != n
+            {
+                return false;
+            }
+        }
+
+        sigtimedwait(&set, Duration::from_millis(10)).unwrap_err() == rustix::io::Errno::AGAIN
+    });
+}
+
+#[test]
+fn c_sigtimedwait() {
+    in_child(|| unsafe {
+        let sig = SIGRTMAX;
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, sig);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+
+        let timeout = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 10_000_000,
+        };
+        let mut info: libc::siginfo_t = std::mem::zeroed();
+        if libc::sigtimedwait(&set, &mut info, &timeout) != -1
+            || *libc::__errno_location() != libc::EAGAIN
+        {
+            return false;
+        }
+
+        let value = libc::sigval {
+            sival_ptr: 7 as *mut _,
+        };
+        libc::sigqueue(libc::getpid(), sig, value) == 0
+            && libc::sigtimedwait(&set, &mut info, &timeout) == sig
+            && info.si_value().sival_ptr as usize == 7
+    });
+}
@@ -0,0 +1,354 @@
+//! Test `posix_spawn`'s attributes and file actions, and that it's mustang's
+//! `CLONE_VM | CLONE_VFORK` implementation rather than c-scape's.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use libc::{c_char, c_int, pid_t, posix_spawn_file_actions_t, posix_spawnattr_t, sigset_t};
+use std::ffi::CStr;
+use std::mem::zeroed;
+use std::ptr::{null, null_mut};
+
+extern "C" {
+    static environ: *const *mut c_char;
+}
+
+/// Spawn `sh -c script`, and return its pid.
+unsafe fn spawn(
+    script: &CStr,
+    file_actions: *const posix_spawn_file_actions_t,
+    attr: *const posix_spawnattr_t,
+) -> Result<pid_t, c_int> {
+    let argv = [
+        c"sh".as_ptr() as *mut c_char,
+        c"-c".as_ptr() as *mut c_char,
+        script.as_ptr() as *mut c_char,
+        null_mut(),
+    ];
+    let mut pid = 0;
+    match libc::posix_spawn(
+        &mut pid,
+        c"/bin/sh".as_ptr(),
+        file_actions,
+        attr,
+        argv.as_ptr(),
+        environ,
+    ) {
+        0 => Ok(pid),
+        err => Err(err),
+    }
+}
+
+/// Wait for `pid` to exit, and return its exit code.
+unsafe fn wait(pid: pid_t) -> c_int {
+    let mut status = 0;
+    assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+    assert!(libc::WIFEXITED(status));
+    libc::WEXITSTATUS(status)
+}
+
+/// Run `sh -c script` with its stdout redirected to a pipe, and return what
+/// it writes.
+unsafe fn output(script: &CStr, attr: *const posix_spawnattr_t) -> String {
+    let mut fds = [0; 2];
+    assert_eq!(libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC), 0);
+    let mut file_actions: posix_spawn_file_actions_t = zeroed();
+    libc::posix_spawn_file_actions_init(&mut file_actions);
+    libc::posix_spawn_file_actions_adddup2(&mut file_actions, fds[1], 1);
+    let pid = spawn(script, &file_actions, attr).unwrap();
+    libc::posix_spawn_file_actions_destroy(&mut file_actions);
+    libc::close(fds[1]);
+
+    let mut out = Vec::new();
+    let mut buf = [0_u8; 256];
+    loop {
+        let n = libc::read(fds[0], buf.as_mut_ptr().cast(), buf.len());
+        assert!(n >= 0);
+        if n == 0 {
+            break;
+        }
+        out.extend_from_slice(&buf[..n as usize]);
+    }
+    libc::close(fds[0]);
+    assert_eq!(wait(pid), 0);
+    String::from_utf8(out).unwrap()
+}
+
+/// Return the value of `field` in the child's `/proc/self/status`.
+unsafe fn status_field(field: &str, attr: *const posix_spawnattr_t) -> u64 {
+    let out = output(c"exec cat /proc/self/status", attr);
+    let line = out
+        .lines()
+        .find_map(|line| line.strip_prefix(field))
+        .unwrap();
+    u64::from_str_radix(line.trim_start_matches(':').trim(), 16).unwrap()
+}
+
+unsafe fn sigset(sig: c_int) -> sigset_t {
+    let mut set = zeroed();
+    libc::sigemptyset(&mut set);
+    libc::sigaddset(&mut set, sig);
+    set
+}
+
+#[test]
+fn shares_memory() {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    // The `KCMP_VM` value from <linux/kcmp.h>.
+    const KCMP_VM: c_int = 1;
+
+    static TID: AtomicI32 = AtomicI32::new(0);
+
+    unsafe {
+        let dir = std::env::temp_dir().join("mustang-spawn-shares-memory");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        let fifo = std::ffi::CString::new(dir.join("fifo").to_str().unwrap()).unwrap();
+        assert_eq!(libc::mkfifo(fifo.as_ptr(), 0o600), 0);
+
+        // Spawn a child which blocks opening the FIFO, before its exec, in
+        // another thread, since the spawning thread is suspended until then.
+        let spawner = {
+            let fifo = fifo.clone();
+            std::thread::spawn(move || {
+                TID.store(libc::gettid(), Ordering::SeqCst);
+                let mut file_actions: posix_spawn_file_actions_t = zeroed();
+                libc::posix_spawn_file_actions_init(&mut file_actions);
+                libc::posix_spawn_file_actions_addopen(
+                    &mut file_actions,
+                    5,
+                    fifo.as_ptr(),
+                    libc::O_RDONLY,
+                    0,
+                );
+                let pid = spawn(c"exit 0", &file_actions, null()).unwrap();
+                libc::posix_spawn_file_actions_destroy(&mut file_actions);
+                assert_eq!(wait(pid), 0);
+            })
+        };
+
+        // Find the child, and check that it shares our memory.
+        let child = loop {
+            let tid = TID.load(Ordering::SeqCst);
+            if tid != 0 {
+                let children = format!("/proc/self/task/{}/children", tid);
+                let children = std::fs::read_to_string(children).unwrap();
+                if let Some(child) = children.split_whitespace().next() {
+                    break child.parse::<pid_t>().unwrap();
+                }
+            }
+            std::thread::yield_now();
+        };
+        let same = libc::syscall(libc::SYS_kcmp, libc::getpid(), child, KCMP_VM, 0, 0);
+        assert_eq!(same, 0, "the child doesn't share the parent's memory");
+
+        // Let the child exec.
+        let writer = libc::open(fifo.as_ptr(), libc::O_WRONLY);
+        assert!(writer >= 0);
+        spawner.join().unwrap();
+        libc::close(writer);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn exit_status() {
+    unsafe {
+        let pid = spawn(c"exit 7", null(), null()).unwrap();
+        assert_eq!(wait(pid), 7);
+    }
+}
+
+#[test]
+fn missing_program() {
+    unsafe {
+        let mut pid = 0;
+        let argv = [c"nonexistent".as_ptr() as *mut c_char, null_mut()];
+        let err = libc::posix_spawn(
+            &mut pid,
+            c"/nonexistent".as_ptr(),
+            null(),
+            null(),
+            argv.as_ptr(),
+            environ,
+        );
+        assert_eq!(err, libc::ENOENT);
+        let err = libc::posix_spawnp(
+            &mut pid,
+            c"mustang-nonexistent".as_ptr(),
+            null(),
+            null(),
+            argv.as_ptr(),
+            environ,
+        );
+        assert_eq!(err, libc::ENOENT);
+    }
+}
+
+#[test]
+fn spawnp() {
+    unsafe {
+        let mut pid = 0;
+        let argv = [
+            c"sh".as_ptr() as *mut c_char,
+            c"-c".as_ptr() as *mut c_char,
+            c"exit 3".as_ptr() as *mut c_char,
+            null_mut(),
+        ];
+        let err = libc::posix_spawnp(
+            &mut pid,
+            c"sh".as_ptr(),
+            null(),
+            null(),
+            argv.as_ptr(),
+            environ,
+        );
+        assert_eq!(err, 0);
+        assert_eq!(wait(pid), 3);
+    }
+}
+
+#[test]
+fn file_actions() {
+    unsafe {
+        let dir = std::env::temp_dir().join("mustang-spawn-file-actions");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        let dir_c = std::ffi::CString::new(dir.to_str().unwrap()).unwrap();
+
+        // Open a file in the child, write through it, and check that the
+        // `chdir` applied.
+        let mut file_actions: posix_spawn_file_actions_t = zeroed();
+        libc::posix_spawn_file_actions_init(&mut file_actions);
+        libc::posix_spawn_file_actions_addchdir_np(&mut file_actions, dir_c.as_ptr());
+        libc::posix_spawn_file_actions_addopen(
+            &mut file_actions,
+            5,
+            c"out".as_ptr(),
+            libc::O_WRONLY | libc::O_CREAT,
+            0o644,
+        );
+        libc::posix_spawn_file_actions_addclose(&mut file_actions, 0);
+        let pid = spawn(
+            c"pwd >&5; if read x; then echo open >&5; else echo closed >&5; fi",
+            &file_actions,
+            null(),
+        )
+        .unwrap();
+        assert_eq!(wait(pid), 0);
+        libc::posix_spawn_file_actions_destroy(&mut file_actions);
+
+        let out = std::fs::read_to_string(dir.join("out")).unwrap();
+        assert_eq!(out, format!("{}\nclosed\n", dir.display()));
+
+        // A failing file action makes `posix_spawn` fail.
+        libc::posix_spawn_file_actions_init(&mut file_actions);
+        libc::posix_spawn_file_actions_addopen(
+            &mut file_actions,
+            5,
+            c"/nonexistent/file".as_ptr(),
+            libc::O_RDONLY,
+            0,
+        );
+        assert_eq!(spawn(c"exit 0", &file_actions, null()), Err(libc::ENOENT));
+        libc::posix_spawn_file_actions_destroy(&mut file_actions);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn setsid() {
+    unsafe {
+        let mut attr: posix_spawnattr_t = zeroed();
+        libc::posix_spawnattr_init(&mut attr);
+        libc::posix_spawnattr_setflags(&mut attr, libc::POSIX_SPAWN_SETSID);
+        let out = output(c"echo $$; exec cat /proc/self/stat", &attr);
+        libc::posix_spawnattr_destroy(&mut attr);
+
+        // The fields after the command name are state, ppid, pgrp, and
+        // session.
+        let mut lines = out.lines();
+        let pid = lines.next().unwrap();
+        let stat = lines.next().unwrap();
+        let fields = stat.rsplit(')').next().unwrap().split_whitespace();
+        let fields = fields.collect::<Vec<_>>();
+        assert_eq!(fields[2], pid);
+        assert_eq!(fields[3], pid);
+    }
+}
+
+#[test]
+fn setpgroup() {
+    unsafe {
+        let mut attr: posix_spawnattr_t = zeroed();
+        libc::posix_spawnattr_init(&mut attr);
+        libc::posix_spawnattr_setflags(&mut attr, libc::POSIX_SPAWN_SETPGROUP as _);
+        libc::posix_spawnattr_setpgroup(&mut attr, 0);
+        let out = output(c"echo $$; exec cat /proc/self/stat", &attr);
+        libc::posix_spawnattr_destroy(&mut attr);
+
+        let mut lines = out.lines();
+        let pid = lines.next().unwrap();
+        let stat = lines.next().unwrap();
+        let fields = stat.rsplit(')').next().unwrap().split_whitespace();
+        let fields = fields.collect::<Vec<_>>();
+        assert_eq!(fields[2], pid);
+        assert_ne!(fields[3], pid);
+    }
+}
+
+#[test]
+fn sigmask() {
+    unsafe {
+        let mut attr: posix_spawnattr_t = zeroed();
+        libc::posix_spawnattr_init(&mut attr);
+        libc::posix_spawnattr_setflags(&mut attr, libc::POSIX_SPAWN_SETSIGMASK as _);
+        let set = sigset(libc::SIGUSR1);
+        libc::posix_spawnattr_setsigmask(&mut attr, &set);
+        let blocked = status_field("SigBlk", &attr);
+        libc::posix_spawnattr_destroy(&mut attr);
+        assert_eq!(blocked, 1 << (libc::SIGUSR1 - 1));
+
+        // Without the flag, the child inherits our mask, which doesn't
+        // block anything.
+        assert_eq!(status_field("SigBlk", null()), 0);
+    }
+}
+
+#[test]
+fn sigdefault() {
+    unsafe {
+        // Ignored signals stay ignored across exec, unless they're in the
+        // default set.
+        let old = libc::signal(libc::SIGUSR2, libc::SIG_IGN);
+        let bit = 1 << (libc::SIGUSR2 - 1);
+        assert_ne!(status_field("SigIgn", null()) & bit, 0);
+
+        let mut attr: posix_spawnattr_t = zeroed();
+        libc::posix_spawnattr_init(&mut attr);
+        libc::posix_spawnattr_setflags(&mut attr, libc::POSIX_SPAWN_SETSIGDEF as _);
+        let set = sigset(libc::SIGUSR2);
+        libc::posix_spawnattr_setsigdefault(&mut attr, &set);
+        assert_eq!(status_field("SigIgn", &attr) & bit, 0);
+        libc::posix_spawnattr_destroy(&mut attr);
+
+        libc::signal(libc::SIGUSR2, old);
+    }
+}
+
+#[test]
+fn invalid_flags() {
+    unsafe {
+        let mut attr: posix_spawnattr_t = zeroed();
+        libc::posix_spawnattr_init(&mut attr);
+        assert_eq!(
+            libc::posix_spawnattr_setflags(&mut attr, 0x4000),
+            libc::EINVAL
+        );
+        libc::posix_spawnattr_destroy(&mut attr);
+    }
+}
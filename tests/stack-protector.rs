@@ -0,0 +1,22 @@
+//! Test that the stack protector guard is seeded and shared by all threads.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use mustang::stack_protector::guard;
+
+#[test]
+fn guard_seeded() {
+    let guard = guard();
+    assert_ne!(guard, 0);
+    // The first byte is zero.
+    assert_eq!(guard & 0xff, 0);
+}
+
+#[test]
+fn guard_inherited() {
+    let main = guard();
+    let thread = std::thread::spawn(guard).join().unwrap();
+    assert_eq!(main, thread);
+}
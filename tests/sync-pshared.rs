@@ -0,0 +1,352 @@
+//! Test process-shared condvars, rwlocks, barriers and semaphores, which
+//! mustang binds the standard names to its own implementations of.
+//!
+//! Each test runs this binary again as helper processes, which run the
+//! `helper` test with `MUSTANG_PSHARED_HELPER` set, naming what to do and
+//! the memfd to map, which they inherit.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use std::ffi::{c_int, c_uint, CString};
+use std::mem::{size_of, zeroed};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use libc::{
+    pthread_barrier_t, pthread_barrierattr_t, pthread_cond_t, pthread_condattr_t, pthread_mutex_t,
+    pthread_mutexattr_t, pthread_rwlock_t, pthread_rwlockattr_t, sem_t,
+};
+
+extern "C" {
+    fn pthread_mutexattr_init(attr: *mut pthread_mutexattr_t) -> c_int;
+    fn pthread_mutexattr_setpshared(attr: *mut pthread_mutexattr_t, pshared: c_int) -> c_int;
+    fn pthread_mutex_init(mutex: *mut pthread_mutex_t, attr: *const pthread_mutexattr_t) -> c_int;
+    fn pthread_mutex_lock(mutex: *mut pthread_mutex_t) -> c_int;
+    fn pthread_mutex_unlock(mutex: *mut pthread_mutex_t) -> c_int;
+    fn pthread_condattr_init(attr: *mut pthread_condattr_t) -> c_int;
+    fn pthread_condattr_setpshared(attr: *mut pthread_condattr_t, pshared: c_int) -> c_int;
+    fn pthread_condattr_getpshared(attr: *const pthread_condattr_t, pshared: *mut c_int) -> c_int;
+    fn pthread_cond_init(cond: *mut pthread_cond_t, attr: *const pthread_condattr_t) -> c_int;
+    fn pthread_cond_wait(cond: *mut pthread_cond_t, mutex: *mut pthread_mutex_t) -> c_int;
+    fn pthread_cond_broadcast(cond: *mut pthread_cond_t) -> c_int;
+    fn pthread_rwlockattr_init(attr: *mut pthread_rwlockattr_t) -> c_int;
+    fn pthread_rwlockattr_setpshared(attr: *mut pthread_rwlockattr_t, pshared: c_int) -> c_int;
+    fn pthread_rwlock_init(
+        rwlock: *mut pthread_rwlock_t,
+        attr: *const pthread_rwlockattr_t,
+    ) -> c_int;
+    fn pthread_rwlock_rdlock(rwlock: *mut pthread_rwlock_t) -> c_int;
+    fn pthread_rwlock_wrlock(rwlock: *mut pthread_rwlock_t) -> c_int;
+    fn pthread_rwlock_trywrlock(rwlock: *mut pthread_rwlock_t) -> c_int;
+    fn pthread_rwlock_unlock(rwlock: *mut pthread_rwlock_t) -> c_int;
+    fn pthread_barrierattr_init(attr: *mut pthread_barrierattr_t) -> c_int;
+    fn pthread_barrierattr_setpshared(attr: *mut pthread_barrierattr_t, pshared: c_int) -> c_int;
+    fn pthread_barrier_init(
+        barrier: *mut pthread_barrier_t,
+        attr: *const pthread_barrierattr_t,
+        count: c_uint,
+    ) -> c_int;
+    fn pthread_barrier_wait(barrier: *mut pthread_barrier_t) -> c_int;
+    fn sem_init(sem: *mut sem_t, pshared: c_int, value: c_uint) -> c_int;
+    fn sem_wait(sem: *mut sem_t) -> c_int;
+    fn sem_trywait(sem: *mut sem_t) -> c_int;
+    fn sem_post(sem: *mut sem_t) -> c_int;
+    fn sem_getvalue(sem: *mut sem_t, value: *mut c_int) -> c_int;
+    fn sem_open(name: *const libc::c_char, oflag: c_int, ...) -> *mut sem_t;
+    fn sem_close(sem: *mut sem_t) -> c_int;
+    fn sem_unlink(name: *const libc::c_char) -> c_int;
+}
+
+/// How many helper processes each test starts.
+const HELPERS: u32 = 3;
+/// How many times each process goes around its loop.
+const ROUNDS: u64 = 1000;
+/// How many times each process waits on the barrier, twice.
+const BARRIER_ROUNDS: u32 = 100;
+
+/// The objects shared between the test and its helpers.
+#[repr(C)]
+struct Shared {
+    mutex: pthread_mutex_t,
+    cond: pthread_cond_t,
+    rwlock: pthread_rwlock_t,
+    barrier: pthread_barrier_t,
+    sem: sem_t,
+    counter: u64,
+    ready: u32,
+    go: u32,
+    arrived: AtomicU32,
+    serial: AtomicU32,
+}
+
+/// Map the `Shared` in `fd`.
+fn map(fd: c_int) -> &'static mut Shared {
+    unsafe {
+        let shared = libc::mmap(
+            std::ptr::null_mut(),
+            size_of::<Shared>(),
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        );
+        assert_ne!(shared, libc::MAP_FAILED);
+        &mut *shared.cast::<Shared>()
+    }
+}
+
+/// Create a memfd holding a `Shared` with process-shared objects, which
+/// helpers will inherit.
+fn create() -> (c_int, &'static mut Shared) {
+    unsafe {
+        let fd = libc::memfd_create(c"sync-pshared".as_ptr(), 0);
+        assert!(fd >= 0);
+        assert_eq!(libc::ftruncate(fd, size_of::<Shared>() as _), 0);
+        let shared = map(fd);
+
+        let mut attr = zeroed();
+        assert_eq!(pthread_mutexattr_init(&mut attr), 0);
+        assert_eq!(
+            pthread_mutexattr_setpshared(&mut attr, libc::PTHREAD_PROCESS_SHARED),
+            0
+        );
+        assert_eq!(pthread_mutex_init(&mut shared.mutex, &attr), 0);
+
+        let mut attr = zeroed();
+        assert_eq!(pthread_condattr_init(&mut attr), 0);
+        assert_eq!(
+            pthread_condattr_setpshared(&mut attr, libc::PTHREAD_PROCESS_SHARED),
+            0
+        );
+        let mut pshared = 0;
+        assert_eq!(pthread_condattr_getpshared(&attr, &mut pshared), 0);
+        assert_eq!(pshared, libc::PTHREAD_PROCESS_SHARED);
+        assert_eq!(pthread_cond_init(&mut shared.cond, &attr), 0);
+
+        let mut attr = zeroed();
+        assert_eq!(pthread_rwlockattr_init(&mut attr), 0);
+        assert_eq!(
+            pthread_rwlockattr_setpshared(&mut attr, libc::PTHREAD_PROCESS_SHARED),
+            0
+        );
+        assert_eq!(pthread_rwlock_init(&mut shared.rwlock, &attr), 0);
+
+        let mut attr = zeroed();
+        assert_eq!(pthread_barrierattr_init(&mut attr), 0);
+        assert_eq!(
+            pthread_barrierattr_setpshared(&mut attr, libc::PTHREAD_PROCESS_SHARED),
+            0
+        );
+        assert_eq!(
+            pthread_barrier_init(&mut shared.barrier, &attr, HELPERS + 1),
+            0
+        );
+
+        assert_eq!(sem_init(&mut shared.sem, 1, 0), 0);
+        (fd, shared)
+    }
+}
+
+/// Start `HELPERS` helpers to do `what`, with `arg`.
+fn start(what: &str, arg: &str) -> Vec<Child> {
+    (0..HELPERS)
+        .map(|_| {
+            Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "helper"])
+                .env("MUSTANG_PSHARED_HELPER", format!("{} {}", what, arg))
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap()
+        })
+        .collect()
+}
+
+fn finish(helpers: Vec<Child>) {
+    for helper in helpers {
+        let output = helper.wait_with_output().unwrap();
+        assert!(
+            output.status.success(),
+            "{:?}\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
+
+#[test]
+fn helper() {
+    let Ok(var) = std::env::var("MUSTANG_PSHARED_HELPER") else {
+        return;
+    };
+    let (what, arg) = var.split_once(' ').unwrap();
+    if what == "named" {
+        return named_helper(arg);
+    }
+    let shared = map(arg.parse().unwrap());
+    unsafe {
+        match what {
+            "condvar" => {
+                assert_eq!(pthread_mutex_lock(&mut shared.mutex), 0);
+                shared.ready += 1;
+                while shared.go == 0 {
+                    assert_eq!(pthread_cond_wait(&mut shared.cond, &mut shared.mutex), 0);
+                }
+                shared.counter += 1;
+                assert_eq!(pthread_mutex_unlock(&mut shared.mutex), 0);
+            }
+            "rwlock" => rwlock_rounds(shared),
+            "barrier" => barrier_rounds(shared),
+            "sem" => {
+                for _ in 0..ROUNDS {
+                    assert_eq!(sem_wait(&mut shared.sem), 0);
+                }
+            }
+            _ => panic!("unknown helper {}", what),
+        }
+    }
+}
+
+#[test]
+fn condvar() {
+    let (fd, shared) = create();
+    let helpers = start("condvar", &fd.to_string());
+    unsafe {
+        // Wait for all the helpers to be waiting, then release them.
+        loop {
+            assert_eq!(pthread_mutex_lock(&mut shared.mutex), 0);
+            if shared.ready == HELPERS {
+                break;
+            }
+            assert_eq!(pthread_mutex_unlock(&mut shared.mutex), 0);
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        shared.go = 1;
+        assert_eq!(pthread_cond_broadcast(&mut shared.cond), 0);
+        assert_eq!(pthread_mutex_unlock(&mut shared.mutex), 0);
+    }
+    finish(helpers);
+    assert_eq!(shared.counter, HELPERS as u64);
+}
+
+unsafe fn rwlock_rounds(shared: &mut Shared) {
+    for _ in 0..ROUNDS {
+        assert_eq!(pthread_rwlock_wrlock(&mut shared.rwlock), 0);
+        shared.counter += 1;
+        assert_eq!(pthread_rwlock_unlock(&mut shared.rwlock), 0);
+
+        assert_eq!(pthread_rwlock_rdlock(&mut shared.rwlock), 0);
+        assert_eq!(pthread_rwlock_trywrlock(&mut shared.rwlock), libc::EBUSY);
+        assert_eq!(pthread_rwlock_unlock(&mut shared.rwlock), 0);
+    }
+}
+
+#[test]
+fn rwlock() {
+    let (fd, shared) = create();
+    let helpers = start("rwlock", &fd.to_string());
+    unsafe { rwlock_rounds(shared) };
+    finish(helpers);
+    assert_eq!(shared.counter, (HELPERS as u64 + 1) * ROUNDS);
+}
+
+unsafe fn barrier_wait(shared: &mut Shared) {
+    match pthread_barrier_wait(&mut shared.barrier) {
+        libc::PTHREAD_BARRIER_SERIAL_THREAD => {
+            shared.serial.fetch_add(1, Ordering::SeqCst);
+        }
+        0 => {}
+        err => panic!("pthread_barrier_wait failed: {}", err),
+    }
+}
+
+unsafe fn barrier_rounds(shared: &mut Shared) {
+    for round in 1..=BARRIER_ROUNDS {
+        shared.arrived.fetch_add(1, Ordering::SeqCst);
+        barrier_wait(shared);
+        // Everyone has arrived in this round, and nobody goes on to the next
+        // until we've all checked.
+        assert_eq!(shared.arrived.load(Ordering::SeqCst), round * (HELPERS + 1));
+        barrier_wait(shared);
+    }
+}
+
+#[test]
+fn barrier() {
+    let (fd, shared) = create();
+    let helpers = start("barrier", &fd.to_string());
+    unsafe { barrier_rounds(shared) };
+    finish(helpers);
+    // Each wait had one serial thread.
+    assert_eq!(shared.serial.load(Ordering::SeqCst), 2 * BARRIER_ROUNDS);
+}
+
+#[test]
+fn semaphore() {
+    let (fd, shared) = create();
+    let helpers = start("sem", &fd.to_string());
+    unsafe {
+        for _ in 0..HELPERS as u64 * ROUNDS {
+            assert_eq!(sem_post(&mut shared.sem), 0);
+        }
+    }
+    finish(helpers);
+    unsafe {
+        let mut value = -1;
+        assert_eq!(sem_getvalue(&mut shared.sem, &mut value), 0);
+        assert_eq!(value, 0);
+        assert_eq!(sem_trywait(&mut shared.sem), -1);
+        assert_eq!(*libc::__errno_location(), libc::EAGAIN);
+    }
+}
+
+fn named_helper(name: &str) {
+    let name = CString::new(name).unwrap();
+    unsafe {
+        let sem = sem_open(name.as_ptr(), 0);
+        assert_ne!(sem, libc::SEM_FAILED);
+        assert_eq!(sem_post(sem), 0);
+        assert_eq!(sem_close(sem), 0);
+    }
+}
+
+#[test]
+fn named_semaphore() {
+    let name = format!("/mustang-sync-pshared-{}", std::process::id());
+    let c_name = CString::new(name.as_str()).unwrap();
+    unsafe {
+        let sem = sem_open(
+            c_name.as_ptr(),
+            libc::O_CREAT | libc::O_EXCL,
+            0o600 as c_uint,
+            0 as c_uint,
+        );
+        assert_ne!(sem, libc::SEM_FAILED);
+
+        // It's a file in /dev/shm, like glibc's.
+        assert!(std::path::Path::new(&format!("/dev/shm/sem.{}", &name[1..])).exists());
+        assert_eq!(
+            sem_open(
+                c_name.as_ptr(),
+                libc::O_CREAT | libc::O_EXCL,
+                0o600 as c_uint,
+                0 as c_uint
+            ),
+            libc::SEM_FAILED
+        );
+        assert_eq!(*libc::__errno_location(), libc::EEXIST);
+
+        finish(start("named", &name));
+        for _ in 0..HELPERS {
+            assert_eq!(sem_wait(sem), 0);
+        }
+        assert_eq!(sem_trywait(sem), -1);
+
+        assert_eq!(sem_close(sem), 0);
+        assert_eq!(sem_unlink(c_name.as_ptr()), 0);
+        assert_eq!(sem_open(c_name.as_ptr(), 0), libc::SEM_FAILED);
+        assert_eq!(*libc::__errno_location(), libc::ENOENT);
+        assert_eq!(sem_unlink(c_name.as_ptr()), -1);
+    }
+}
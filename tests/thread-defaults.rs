@@ -0,0 +1,244 @@
+//! Test the default thread stack and guard sizes, set with `can_run_this!`,
+//! environment variables, and `pthread_setattr_default_np`, and that
+//! `pthread_create` and `std::thread` use them.
+//!
+//! Tests which change the defaults, or set the environment variables, run
+//! this binary again, running the `helper` test with
+//! `MUSTANG_THREAD_DEFAULTS_HELPER` set, naming what to do.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!(thread_stack_size = 1 << 20, thread_guard_size = 64 << 10);
+
+use std::ffi::{c_int, c_void};
+use std::mem::MaybeUninit;
+use std::process::{Command, Output};
+
+use libc::pthread_attr_t;
+
+extern "C" {
+    fn pthread_getattr_default_np(attr: *mut pthread_attr_t) -> c_int;
+    fn pthread_setattr_default_np(attr: *const pthread_attr_t) -> c_int;
+}
+
+const HELPER_ENV: &str = "MUSTANG_THREAD_DEFAULTS_HELPER";
+
+/// Return the stack and guard sizes of the calling thread.
+fn own_sizes() -> (usize, usize) {
+    let mut attr = MaybeUninit::uninit();
+    unsafe {
+        assert_eq!(
+            libc::pthread_getattr_np(libc::pthread_self(), attr.as_mut_ptr()),
+            0
+        );
+        let sizes = sizes(attr.as_ptr());
+        libc::pthread_attr_destroy(attr.as_mut_ptr());
+        sizes
+    }
+}
+
+/// Return the stack and guard sizes in `attr`.
+unsafe fn sizes(attr: *const pthread_attr_t) -> (usize, usize) {
+    let mut stack_size = 0;
+    let mut guard_size = 0;
+    assert_eq!(libc::pthread_attr_getstacksize(attr, &mut stack_size), 0);
+    assert_eq!(libc::pthread_attr_getguardsize(attr, &mut guard_size), 0);
+    (stack_size, guard_size)
+}
+
+/// Run the helper, with the given environment variables.
+fn run_helper(what: &str, vars: &[(&str, &str)]) -> Output {
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command
+        .args(["--exact", "helper", "--nocapture", "--test-threads=1"])
+        .env(HELPER_ENV, what)
+        .env_remove(mustang::thread::STACK_SIZE_ENV)
+        .env_remove(mustang::thread::GUARD_SIZE_ENV)
+        .env_remove("RUST_MIN_STACK");
+    for (key, value) in vars {
+        command.env(key, value);
+    }
+    command.output().unwrap()
+}
+
+/// Run the helper, check that it succeeded, and return what it printed.
+fn helper_output(what: &str, vars: &[(&str, &str)]) -> String {
+    let output = run_helper(what, vars);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    stdout
+        .lines()
+        .find_map(|line| line.split_once("sizes: "))
+        .map(|(_, sizes)| sizes)
+        .unwrap()
+        .to_owned()
+}
+
+#[test]
+fn helper() {
+    let Ok(what) = std::env::var(HELPER_ENV) else {
+        return;
+    };
+    match what.as_str() {
+        "print" => {}
+        "set" => unsafe {
+            let mut attr = MaybeUninit::uninit();
+            assert_eq!(pthread_getattr_default_np(attr.as_mut_ptr()), 0);
+            assert_eq!(
+                libc::pthread_attr_setstacksize(attr.as_mut_ptr(), 3 << 20),
+                0
+            );
+            assert_eq!(
+                libc::pthread_attr_setguardsize(attr.as_mut_ptr(), 128 << 10),
+                0
+            );
+            assert_eq!(pthread_setattr_default_np(attr.as_ptr()), 0);
+            libc::pthread_attr_destroy(attr.as_mut_ptr());
+
+            // A freshly initialized attribute gets the new defaults.
+            let mut attr = MaybeUninit::uninit();
+            assert_eq!(libc::pthread_attr_init(attr.as_mut_ptr()), 0);
+            assert_eq!(sizes(attr.as_ptr()), (3 << 20, 128 << 10));
+            libc::pthread_attr_destroy(attr.as_mut_ptr());
+        },
+        "set-rust" => {
+            assert_eq!(
+                mustang::thread::set_default_stack_size(16),
+                Err(rustix::io::Errno::INVAL)
+            );
+            mustang::thread::set_default_stack_size(5 << 20).unwrap();
+            mustang::thread::set_default_guard_size(1).unwrap();
+        }
+        "std" => {
+            let (stack_size, guard_size) = std::thread::spawn(own_sizes).join().unwrap();
+            let min_stack = std::env::var("RUST_MIN_STACK").unwrap();
+            assert!(stack_size >= min_stack.parse().unwrap());
+            println!("std: {} {}", min_stack, guard_size);
+        }
+        _ => panic!("unknown helper {:?}", what),
+    }
+    println!(
+        "sizes: {} {}",
+        mustang::thread::default_stack_size(),
+        mustang::thread::default_guard_size()
+    );
+}
+
+#[test]
+fn can_run_this_options() {
+    if std::env::var_os(mustang::thread::STACK_SIZE_ENV).is_some()
+        || std::env::var_os(mustang::thread::GUARD_SIZE_ENV).is_some()
+    {
+        // The environment variables override the options.
+        return;
+    }
+
+    assert_eq!(mustang::thread::default_stack_size(), 1 << 20);
+    assert_eq!(mustang::thread::default_guard_size(), 64 << 10);
+    unsafe {
+        let mut attr = MaybeUninit::uninit();
+        assert_eq!(pthread_getattr_default_np(attr.as_mut_ptr()), 0);
+        assert_eq!(sizes(attr.as_ptr()), (1 << 20, 64 << 10));
+        libc::pthread_attr_destroy(attr.as_mut_ptr());
+    }
+    assert_eq!(helper_output("print", &[]), "1048576 65536");
+}
+
+#[test]
+fn create_with_defaults() {
+    extern "C" fn start(_arg: *mut c_void) -> *mut c_void {
+        own_sizes().0 as *mut c_void
+    }
+
+    let expected = mustang::thread::default_stack_size();
+    let mut thread = MaybeUninit::uninit();
+    let mut stack_size = std::ptr::null_mut();
+    unsafe {
+        assert_eq!(
+            libc::pthread_create(
+                thread.as_mut_ptr(),
+                std::ptr::null(),
+                start,
+                std::ptr::null_mut()
+            ),
+            0
+        );
+        assert_eq!(libc::pthread_join(thread.assume_init(), &mut stack_size), 0);
+    }
+    assert!(stack_size as usize >= expected);
+}
+
+#[test]
+fn std_thread() {
+    for (vars, expected) in [
+        (
+            &[
+                (mustang::thread::STACK_SIZE_ENV, "3M"),
+                (mustang::thread::GUARD_SIZE_ENV, "256k"),
+            ][..],
+            "std: 3145728 262144\n",
+        ),
+        // The option sets the stack size, but `RUST_MIN_STACK` overrides it.
+        (
+            &[
+                ("RUST_MIN_STACK", "4194304"),
+                (mustang::thread::GUARD_SIZE_ENV, "256k"),
+            ][..],
+            "std: 4194304 262144\n",
+        ),
+        (&[][..], "std: 1048576 65536\n"),
+    ] {
+        let output = run_helper("std", vars);
+        assert!(output.status.success(), "{:?}", output);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains(expected), "{:?}", stdout);
+    }
+}
+
+#[test]
+fn environment() {
+    assert_eq!(
+        helper_output(
+            "print",
+            &[
+                (mustang::thread::STACK_SIZE_ENV, "3M"),
+                (mustang::thread::GUARD_SIZE_ENV, "256k"),
+            ]
+        ),
+        "3145728 262144"
+    );
+    assert_eq!(
+        helper_output("print", &[(mustang::thread::GUARD_SIZE_ENV, "huge")]),
+        format!("1048576 {}", mustang::thread::HUGE_GUARD_SIZE)
+    );
+    assert_eq!(
+        helper_output("print", &[(mustang::thread::GUARD_SIZE_ENV, "0")]),
+        "1048576 0"
+    );
+}
+
+#[test]
+fn invalid_environment() {
+    for (key, value) in [
+        (mustang::thread::STACK_SIZE_ENV, "12"),
+        (mustang::thread::STACK_SIZE_ENV, "lots"),
+        (mustang::thread::GUARD_SIZE_ENV, "huuge"),
+        (mustang::thread::GUARD_SIZE_ENV, "99999999999G"),
+    ] {
+        let output = run_helper("print", &[(key, value)]);
+        assert!(!output.status.success(), "{:?}", output);
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            format!("mustang: invalid {} {:?}\n", key, value)
+        );
+    }
+}
+
+#[test]
+fn set_defaults() {
+    assert_eq!(helper_output("set", &[]), "3145728 131072");
+    assert_eq!(
+        helper_output("set-rust", &[]),
+        format!("5242880 {}", unsafe { libc::sysconf(libc::_SC_PAGESIZE) })
+    );
+}
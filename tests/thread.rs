@@ -45,6 +45,19 @@ fn test_named_thread() {
         .unwrap();
 }
 
+#[test]
+fn test_named_thread_kernel_name() {
+    Builder::new()
+        .name("ada lovelace".to_string())
+        .spawn(move || {
+            let comm = std::fs::read_to_string("/proc/thread-self/comm").unwrap();
+            assert_eq!(comm, "ada lovelace\n");
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
 #[test]
 #[cfg_attr(all(target_arch = "arm", not(feature = "unwinding")), ignore)]
 #[should_panic]
@@ -350,3 +363,69 @@ fn test_scoped_threads_nll() {
     let x = 42_u8;
     foo(&x);
 }
+
+const OVERFLOW_ENV: &str = "MUSTANG_TEST_OVERFLOW";
+
+#[inline(never)]
+#[allow(unconditional_recursion)]
+fn recurse(depth: u64) -> u64 {
+    let frame = std::hint::black_box([depth; 64]);
+    recurse(depth + 1) + frame[0]
+}
+
+#[test]
+fn overflow_helper() {
+    if std::env::var_os(OVERFLOW_ENV).is_some_and(|what| what == "spawned") {
+        Builder::new()
+            .name("overflower".to_owned())
+            .spawn(|| recurse(0))
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_stack_overflow() {
+    // Overflows of the main thread's stack are tested by the
+    // `test-stack-overflow` example, as the test harness runs tests on other
+    // threads.
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "overflow_helper"])
+        .env(OVERFLOW_ENV, "spawned")
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // Newer versions of std print the thread id after the name.
+    assert!(
+        stderr.contains("\nthread 'overflower'") && stderr.contains("has overflowed its stack\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[cfg(target_vendor = "mustang")]
+#[test]
+fn test_main_stack_bounds() {
+    let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+    let line = maps.lines().find(|line| line.ends_with("[stack]")).unwrap();
+    let (start, end) = line.split_once(' ').unwrap().0.split_once('-').unwrap();
+    let start = usize::from_str_radix(start, 16).unwrap();
+    let end = usize::from_str_radix(end, 16).unwrap();
+
+    let stack = mustang::stack::main_stack();
+    assert_eq!(stack.addr() as usize + stack.size(), end);
+    assert!(stack.addr() as usize <= start);
+
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let mut limit = unsafe { mem::zeroed::<libc::rlimit>() };
+    assert_eq!(
+        unsafe { libc::getrlimit(libc::RLIMIT_STACK, &mut limit) },
+        0
+    );
+    // The mapping below the stack can stop it short of the limit.
+    if limit.rlim_cur != libc::RLIM_INFINITY {
+        assert!(stack.size() <= limit.rlim_cur as usize & !(page - 1));
+    }
+}
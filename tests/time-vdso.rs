@@ -0,0 +1,59 @@
+//! Test that mustang reads the time through the vDSO where it can, and
+//! through syscalls with the "no-vdso" feature.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use mustang::time::{self, TimePath};
+use rustix::time::ClockId;
+
+#[cfg(not(feature = "no-vdso"))]
+#[test]
+fn vdso_clocks() {
+    if mustang::auxv::sysinfo_ehdr().is_none() {
+        // No vDSO was provided, as can happen under qemu-user.
+        assert!(!time::vdso_available());
+        return;
+    }
+
+    assert!(time::vdso_available());
+    assert_eq!(time::instant_path(), TimePath::Vdso);
+    assert_eq!(time::system_time_path(), TimePath::Vdso);
+    assert_eq!(time::gettimeofday_path(), TimePath::Vdso);
+    assert_eq!(
+        time::clock_gettime_path(ClockId::MonotonicCoarse),
+        TimePath::Vdso
+    );
+    assert_eq!(
+        time::clock_gettime_path(ClockId::MonotonicRaw),
+        TimePath::Vdso
+    );
+    // The vDSO forwards the CPU-time clocks to the kernel.
+    assert_eq!(
+        time::clock_gettime_path(ClockId::ProcessCPUTime),
+        TimePath::Syscall
+    );
+    assert_eq!(
+        time::clock_gettime_path(ClockId::ThreadCPUTime),
+        TimePath::Syscall
+    );
+}
+
+#[cfg(feature = "no-vdso")]
+#[test]
+fn syscall_clocks() {
+    assert!(!time::vdso_available());
+    assert!(mustang::auxv::sysinfo_ehdr().is_none());
+    assert_eq!(time::instant_path(), TimePath::Syscall);
+    assert_eq!(time::system_time_path(), TimePath::Syscall);
+    assert_eq!(time::gettimeofday_path(), TimePath::Syscall);
+}
+
+#[test]
+fn clocks_work() {
+    let a = std::time::Instant::now();
+    let b = std::time::Instant::now();
+    assert!(b >= a);
+    assert!(std::time::SystemTime::now() > std::time::UNIX_EPOCH);
+}
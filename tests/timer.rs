@@ -0,0 +1,251 @@
+//! Test POSIX timers.
+
+#![cfg(target_vendor = "mustang")]
+
+mustang::can_run_this!();
+
+use mustang::signal::{register_flag, unregister, SIGRTMIN};
+use std::mem::zeroed;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A `sigevent` with the `SIGEV_THREAD` members of its union.
+#[repr(C)]
+struct ThreadEvent {
+    value: libc::sigval,
+    signo: libc::c_int,
+    notify: libc::c_int,
+    function: extern "C" fn(libc::sigval),
+    attribute: *mut libc::pthread_attr_t,
+    pad: [usize; 8],
+}
+
+fn itimerspec(value: Duration, interval: Duration) -> libc::itimerspec {
+    let timespec = |d: Duration| libc::timespec {
+        tv_sec: d.as_secs() as _,
+        tv_nsec: d.subsec_nanos() as _,
+    };
+    libc::itimerspec {
+        it_interval: timespec(interval),
+        it_value: timespec(value),
+    }
+}
+
+fn thread_timer(function: extern "C" fn(libc::sigval), value: usize) -> libc::timer_t {
+    let mut event = ThreadEvent {
+        value: libc::sigval {
+            sival_ptr: value as *mut _,
+        },
+        signo: 0,
+        notify: libc::SIGEV_THREAD,
+        function,
+        attribute: null_mut(),
+        pad: [0; 8],
+    };
+    let mut timer: libc::timer_t = null_mut();
+    unsafe {
+        assert_eq!(
+            libc::timer_create(
+                libc::CLOCK_MONOTONIC,
+                (&mut event as *mut ThreadEvent).cast(),
+                &mut timer
+            ),
+            0
+        );
+    }
+    timer
+}
+
+fn wait_for(done: impl Fn() -> bool) {
+    let start = Instant::now();
+    while !done() {
+        assert!(start.elapsed() < Duration::from_secs(10), "timed out");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn sigev_signal() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+
+    let sig = SIGRTMIN + 5;
+    let id = register_flag(sig, &FLAG).unwrap();
+    unsafe {
+        let mut event: libc::sigevent = zeroed();
+        event.sigev_notify = libc::SIGEV_SIGNAL;
+        event.sigev_signo = sig;
+        let mut timer: libc::timer_t = null_mut();
+        assert_eq!(
+            libc::timer_create(libc::CLOCK_MONOTONIC, &mut event, &mut timer),
+            0
+        );
+        let spec = itimerspec(Duration::from_millis(10), Duration::ZERO);
+        assert_eq!(libc::timer_settime(timer, 0, &spec, null_mut()), 0);
+        wait_for(|| FLAG.load(Ordering::SeqCst));
+        assert_eq!(libc::timer_delete(timer), 0);
+    }
+    assert!(unregister(id));
+}
+
+#[test]
+fn sigev_thread_id_and_overrun() {
+    thread::spawn(|| unsafe {
+        let sig = SIGRTMIN + 6;
+        let mut set: libc::sigset_t = zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, sig);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, null_mut());
+
+        let mut event: libc::sigevent = zeroed();
+        event.sigev_notify = libc::SIGEV_THREAD_ID;
+        event.sigev_signo = sig;
+        event.sigev_value = libc::sigval {
+            sival_ptr: 42 as *mut _,
+        };
+        event.sigev_notify_thread_id = libc::gettid();
+        let mut timer: libc::timer_t = null_mut();
+        assert_eq!(
+            libc::timer_create(libc::CLOCK_MONOTONIC, &mut event, &mut timer),
+            0
+        );
+
+        // Expire every millisecond while the signal stays pending; the
+        // expirations after the first are counted as overruns.
+        let spec = itimerspec(Duration::from_millis(1), Duration::from_millis(1));
+        assert_eq!(libc::timer_settime(timer, 0, &spec, null_mut()), 0);
+        thread::sleep(Duration::from_millis(50));
+
+        let mut info: libc::siginfo_t = zeroed();
+        assert_eq!(libc::sigwaitinfo(&set, &mut info), sig);
+        assert_eq!(info.si_code, libc::SI_TIMER);
+        assert_eq!(info.si_value().sival_ptr as usize, 42);
+        assert!(libc::timer_getoverrun(timer) > 10);
+
+        assert_eq!(libc::timer_delete(timer), 0);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn gettime() {
+    static FLAG: AtomicBool = AtomicBool::new(false);
+
+    let sig = SIGRTMIN + 7;
+    let id = register_flag(sig, &FLAG).unwrap();
+    unsafe {
+        let mut event: libc::sigevent = zeroed();
+        event.sigev_notify = libc::SIGEV_SIGNAL;
+        event.sigev_signo = sig;
+        let mut timer: libc::timer_t = null_mut();
+        assert_eq!(
+            libc::timer_create(libc::CLOCK_MONOTONIC, &mut event, &mut timer),
+            0
+        );
+
+        let spec = itimerspec(Duration::from_secs(10), Duration::from_secs(1));
+        assert_eq!(libc::timer_settime(timer, 0, &spec, null_mut()), 0);
+        let mut current: libc::itimerspec = zeroed();
+        assert_eq!(libc::timer_gettime(timer, &mut current), 0);
+        assert!(current.it_value.tv_sec >= 9);
+        assert_eq!(current.it_interval.tv_sec, 1);
+
+        // Disarming returns the old setting.
+        let disarm = itimerspec(Duration::ZERO, Duration::ZERO);
+        let mut old: libc::itimerspec = zeroed();
+        assert_eq!(libc::timer_settime(timer, 0, &disarm, &mut old), 0);
+        assert_eq!(old.it_interval.tv_sec, 1);
+        assert_eq!(libc::timer_gettime(timer, &mut current), 0);
+        assert_eq!(current.it_value.tv_sec, 0);
+        assert_eq!(current.it_value.tv_nsec, 0);
+
+        assert_eq!(libc::timer_delete(timer), 0);
+    }
+    assert!(unregister(id));
+}
+
+#[test]
+fn sigev_thread() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    static VALUE: AtomicUsize = AtomicUsize::new(0);
+    static TID: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn notify(value: libc::sigval) {
+        VALUE.store(value.sival_ptr as usize, Ordering::SeqCst);
+        TID.store(unsafe { libc::gettid() }, Ordering::SeqCst);
+        COUNT.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let timer = thread_timer(notify, 7);
+    unsafe {
+        let spec = itimerspec(Duration::from_millis(5), Duration::from_millis(5));
+        assert_eq!(libc::timer_settime(timer, 0, &spec, null_mut()), 0);
+    }
+    wait_for(|| COUNT.load(Ordering::SeqCst) >= 3);
+    assert_eq!(VALUE.load(Ordering::SeqCst), 7);
+    assert_ne!(TID.load(Ordering::SeqCst), unsafe { libc::gettid() });
+
+    // After deletion, at most a notification which was already on its way
+    // is delivered.
+    assert_eq!(unsafe { libc::timer_delete(timer) }, 0);
+    let count = COUNT.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(50));
+    assert!(COUNT.load(Ordering::SeqCst) <= count + 1);
+}
+
+#[test]
+fn sigev_thread_delete_race() {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    static CORRUPT: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn notify(value: libc::sigval) {
+        if value.sival_ptr as usize >= 200 {
+            CORRUPT.store(true, Ordering::SeqCst);
+        }
+        COUNT.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // Delete timers while their signals are likely to be pending, and
+    // check that the notifications which do arrive are intact.
+    for i in 0..200 {
+        let timer = thread_timer(notify, i);
+        unsafe {
+            let spec = itimerspec(Duration::from_micros(100), Duration::from_micros(100));
+            assert_eq!(libc::timer_settime(timer, 0, &spec, null_mut()), 0);
+            thread::sleep(Duration::from_micros(i as u64 % 7 * 50));
+            assert_eq!(libc::timer_delete(timer), 0);
+        }
+    }
+
+    // The helper thread still works.
+    let before = COUNT.load(Ordering::SeqCst);
+    let timer = thread_timer(notify, 0);
+    unsafe {
+        let spec = itimerspec(Duration::from_millis(1), Duration::ZERO);
+        assert_eq!(libc::timer_settime(timer, 0, &spec, null_mut()), 0);
+    }
+    wait_for(|| COUNT.load(Ordering::SeqCst) > before);
+    assert_eq!(unsafe { libc::timer_delete(timer) }, 0);
+    assert!(!CORRUPT.load(Ordering::SeqCst));
+}
+
+#[test]
+fn invalid() {
+    unsafe {
+        assert_eq!(libc::timer_delete(null_mut()), -1);
+        assert_eq!(*libc::__errno_location(), libc::EINVAL);
+
+        let mut event: libc::sigevent = zeroed();
+        event.sigev_notify = libc::SIGEV_NONE;
+        let mut timer: libc::timer_t = null_mut();
+        assert_eq!(
+            libc::timer_create(libc::CLOCK_MONOTONIC, &mut event, &mut timer),
+            0
+        );
+        assert_eq!(libc::timer_delete(timer), 0);
+        assert_eq!(libc::timer_delete(timer), -1);
+        assert_eq!(*libc::__errno_location(), libc::EINVAL);
+    }
+}
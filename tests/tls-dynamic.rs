@@ -0,0 +1,165 @@
+//! Test the functions which general-dynamic, local-dynamic and TLS
+//! descriptor accesses call, by calling them as compiled code would.
+
+#![cfg(target_vendor = "mustang")]
+#![feature(thread_local)]
+
+mustang::can_run_this!();
+
+use std::cell::Cell;
+use std::ffi::c_void;
+
+use mustang::tls::{block, TlsIndex, DTV_OFFSET, MODULE_ID};
+
+extern "C" {
+    fn __tls_get_addr(index: *const TlsIndex) -> *mut c_void;
+}
+
+#[no_mangle]
+#[thread_local]
+static TLS_DYNAMIC_VAR: Cell<u64> = Cell::new(0x0123_4567_89ab_cdef);
+
+#[cfg(target_pointer_width = "32")]
+use libc::Elf32_Phdr as Phdr;
+#[cfg(target_pointer_width = "64")]
+use libc::Elf64_Phdr as Phdr;
+
+/// Return the executable's TLS initialization image.
+fn tls_image() -> &'static [u8] {
+    unsafe {
+        let phdrs = std::slice::from_raw_parts(
+            libc::getauxval(libc::AT_PHDR) as *const Phdr,
+            libc::getauxval(libc::AT_PHNUM) as usize,
+        );
+        let bias = phdrs
+            .iter()
+            .find(|phdr| phdr.p_type == libc::PT_PHDR)
+            .map_or(0, |phdr| {
+                (libc::getauxval(libc::AT_PHDR) as usize).wrapping_sub(phdr.p_vaddr as usize)
+            });
+        let tls = phdrs
+            .iter()
+            .find(|phdr| phdr.p_type == libc::PT_TLS)
+            .unwrap();
+        std::slice::from_raw_parts(
+            bias.wrapping_add(tls.p_vaddr as usize) as *const u8,
+            tls.p_filesz as usize,
+        )
+    }
+}
+
+/// Return the offset of `TLS_DYNAMIC_VAR` in the calling thread's block.
+fn offset_in_block() -> usize {
+    (TLS_DYNAMIC_VAR.as_ptr() as usize).wrapping_sub(block() as usize)
+}
+
+fn tls_get_addr(offset: usize) -> *mut c_void {
+    let index = TlsIndex {
+        module: MODULE_ID,
+        offset: offset.wrapping_sub(DTV_OFFSET),
+    };
+    unsafe { __tls_get_addr(&index) }
+}
+
+#[test]
+fn block_is_per_thread() {
+    let main = offset_in_block();
+    assert_eq!(tls_get_addr(0), block());
+    assert_eq!(tls_get_addr(main), TLS_DYNAMIC_VAR.as_ptr().cast());
+
+    let (thread, thread_block) = std::thread::spawn(|| {
+        assert_eq!(TLS_DYNAMIC_VAR.get(), 0x0123_4567_89ab_cdef);
+        let offset = offset_in_block();
+        assert_eq!(tls_get_addr(offset), TLS_DYNAMIC_VAR.as_ptr().cast());
+        (offset, block() as usize)
+    })
+    .join()
+    .unwrap();
+    assert_eq!(thread, main);
+    assert_ne!(thread_block, block() as usize);
+}
+
+#[test]
+fn block_layout() {
+    // The variable's offset in the block is its offset in the image.
+    let offset = offset_in_block();
+    let image = tls_image();
+    assert_eq!(
+        image[offset..][..8],
+        0x0123_4567_89ab_cdef_u64.to_ne_bytes()
+    );
+
+    std::thread::spawn(move || {
+        TLS_DYNAMIC_VAR.set(7);
+        let var = tls_get_addr(offset).cast::<u64>();
+        assert_eq!(var, TLS_DYNAMIC_VAR.as_ptr());
+        assert_eq!(unsafe { *var }, 7);
+    })
+    .join()
+    .unwrap();
+    assert_eq!(TLS_DYNAMIC_VAR.get(), 0x0123_4567_89ab_cdef);
+}
+
+#[test]
+fn tls_descriptor() {
+    use mustang::tls::block_offset;
+
+    extern "C" {
+        fn _dl_tlsdesc_return();
+    }
+
+    /// Call the resolver of a descriptor for `TLS_DYNAMIC_VAR`, and return
+    /// the variable's address.
+    fn resolve() -> *mut u64 {
+        let arg = (block_offset() as usize).wrapping_add(offset_in_block());
+        // On ARM, the argument comes first.
+        #[cfg(not(target_arch = "arm"))]
+        let desc: [usize; 2] = [_dl_tlsdesc_return as *const () as usize, arg];
+        #[cfg(target_arch = "arm")]
+        let desc: [usize; 2] = [arg, _dl_tlsdesc_return as *const () as usize];
+        let offset: usize;
+        unsafe {
+            #[cfg(target_arch = "x86_64")]
+            std::arch::asm!(
+                "call qword ptr [rax]",
+                inout("rax") desc.as_ptr() => offset,
+            );
+            #[cfg(target_arch = "x86")]
+            std::arch::asm!(
+                "call dword ptr [eax]",
+                inout("eax") desc.as_ptr() => offset,
+            );
+            #[cfg(target_arch = "aarch64")]
+            std::arch::asm!(
+                "ldr x1, [x0]",
+                "blr x1",
+                inout("x0") desc.as_ptr() => offset,
+                out("x1") _,
+                out("x30") _,
+            );
+            #[cfg(target_arch = "arm")]
+            std::arch::asm!(
+                "ldr r1, [r0, #4]",
+                "blx r1",
+                inout("r0") desc.as_ptr() => offset,
+                out("r1") _,
+                out("lr") _,
+            );
+            #[cfg(target_arch = "riscv64")]
+            std::arch::asm!(
+                "ld t0, 0(a0)",
+                "jalr t0, 0(t0)",
+                inout("a0") desc.as_ptr() => offset,
+                out("t0") _,
+            );
+        }
+        let tp = (block() as usize).wrapping_sub(block_offset() as usize);
+        tp.wrapping_add(offset) as *mut u64
+    }
+
+    assert_eq!(resolve(), TLS_DYNAMIC_VAR.as_ptr());
+    let thread = std::thread::spawn(|| resolve() == TLS_DYNAMIC_VAR.as_ptr())
+        .join()
+        .unwrap();
+    assert!(thread);
+}